@@ -9,7 +9,7 @@ if [ "${NO_STD}" = "1" ]; then
     FEATURES="rustc-internal-api"
     OP="build"
 else
-    FEATURES="rustc-internal-api,serde,rayon,raw,bumpalo"
+    FEATURES="rustc-internal-api,serde,rayon,raw,bumpalo,concurrent,defer-drop,epoch,persist,siphash,shadow,ffi,huge-pages,numa,metrics,unordered-hash,arrow,instrument"
     OP="test"
 fi
 if [ "${CHANNEL}" = "nightly" ]; then
@@ -36,6 +36,23 @@ fi
 "${CARGO}" -vv ${OP} --target="${TARGET}" --release
 "${CARGO}" -vv ${OP} --target="${TARGET}" --release --features "${FEATURES}"
 
+# Check that the `deterministic` feature gives the same iteration order on
+# every target
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features deterministic
+
+# Check that nothing depends on the iteration order of tables
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features random-order,raw
+
+# Check that aborting on panics during resizes keeps the tables working
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features abort-on-unwind
+
+# Check the scalar fallback of the SIMD group implementation against it
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features force-generic
+
+# Check that nothing depends on the probe sequence
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features linear-probing,raw
+"${CARGO}" -vv ${OP} --target="${TARGET}" --features double-hashing,raw
+
 if [ "${CHANNEL}" = "nightly" ] && [ "${NO_STD}" != 1 ]; then
     # Run benchmark on native targets, build them on non-native ones:
     NO_RUN=""
@@ -0,0 +1,507 @@
+use crate::map::{equivalent_key, make_hash, make_hasher, make_insert_hash, HashMap};
+use crate::raw::{Allocator, Global, RawTable};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::mem;
+use core::ops::Deref;
+
+/// A [`BuildHasher`] whose keys can be replaced with new random ones.
+///
+/// This is what lets an [`AdaptiveHashMap`] escape from a set of keys which
+/// collide under its current hasher.
+pub trait Reseed: BuildHasher {
+    /// Replaces the keys of the hasher with new random ones, so that hashes
+    /// computed afterwards are unrelated to those computed before.
+    fn reseed(&mut self);
+}
+
+#[cfg(feature = "ahash")]
+impl Reseed for crate::hash_map::RandomState {
+    #[inline]
+    fn reseed(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[cfg(feature = "siphash")]
+impl Reseed for std::collections::hash_map::RandomState {
+    #[inline]
+    fn reseed(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// The longest probe sequence, in groups, that an insertion may take before
+/// the table is rebuilt, unless configured otherwise.
+///
+/// Linear probing lets the probe sequences of neighbouring buckets run into
+/// each other, so a nearly full table has much longer ones even with a good
+/// hasher. Double hashing takes its step from the high half of the hash, so
+/// with a hasher which only fills the low 32 bits it steps one group at a time
+/// just like linear probing.
+const DEFAULT_MAX_PROBE_LENGTH: usize =
+    if cfg!(any(feature = "linear-probing", feature = "double-hashing")) {
+        128
+    } else {
+        32
+    };
+
+/// A [`HashMap`] which defends itself against keys chosen to collide.
+///
+/// Every insertion of a new key measures how many groups of buckets had to be
+/// probed to place it. With a decent hasher this stays very short even in a
+/// full table, so a long probe sequence means that many keys share their
+/// hash bits, as happens when an attacker picks keys against a known or fast
+/// non-random hasher. When an insertion probes more than
+/// [`max_probe_length`] groups, the map [reseeds] its hasher and rebuilds its
+/// table with the new hashes, which scatters the colliding keys again.
+///
+/// If the rebuilt table still contains a probe sequence longer than the
+/// limit, reseeding does not help against these keys, for example because
+/// their hashes are equal under every seed. The limit is then doubled, so
+/// that the cost of rebuilding stays proportional to the number of
+/// insertions.
+///
+/// The map dereferences to a [`HashMap`] for all read-only operations.
+/// Modifications have to go through the `AdaptiveHashMap` itself, so that
+/// every insertion is checked.
+///
+/// [`max_probe_length`]: AdaptiveHashMap::max_probe_length
+/// [reseeds]: Reseed::reseed
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::RandomState;
+/// use hashbrown::AdaptiveHashMap;
+///
+/// let mut map: AdaptiveHashMap<u32, u32, RandomState> = AdaptiveHashMap::new();
+/// for i in 0..1000 {
+///     map.insert(i, i * 2);
+/// }
+///
+/// // Read-only operations go through to the underlying `HashMap`.
+/// assert_eq!(map.get(&21), Some(&42));
+/// assert_eq!(map.len(), 1000);
+/// ```
+pub struct AdaptiveHashMap<K, V, S, A: Allocator + Clone = Global> {
+    map: HashMap<K, V, S, A>,
+    max_probe_length: usize,
+    reseeds: usize,
+}
+
+#[cfg(feature = "ahash")]
+impl<K, V> AdaptiveHashMap<K, V, crate::hash_map::RandomState> {
+    /// Creates an empty `AdaptiveHashMap` with a randomly keyed hasher.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::RandomState;
+    /// use hashbrown::AdaptiveHashMap;
+    ///
+    /// let map: AdaptiveHashMap<i32, i32, RandomState> = AdaptiveHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+
+    /// Creates an empty `AdaptiveHashMap` with a randomly keyed hasher, which
+    /// can hold at least `capacity` elements without reallocating.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::RandomState;
+    /// use hashbrown::AdaptiveHashMap;
+    ///
+    /// let map: AdaptiveHashMap<i32, i32, RandomState> = AdaptiveHashMap::with_capacity(10);
+    /// assert!(map.capacity() >= 10);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, S> AdaptiveHashMap<K, V, S> {
+    /// Creates an empty `AdaptiveHashMap` which uses `hash_builder` to hash
+    /// keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::RandomState;
+    /// use hashbrown::AdaptiveHashMap;
+    ///
+    /// let mut map = AdaptiveHashMap::with_hasher(RandomState::with_seed(1));
+    /// map.insert(1, 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from(HashMap::with_hasher(hash_builder))
+    }
+
+    /// Creates an empty `AdaptiveHashMap` which uses `hash_builder` to hash
+    /// keys and can hold at least `capacity` elements without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::from(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> AdaptiveHashMap<K, V, S, A> {
+    /// Returns the longest probe sequence, in groups of buckets, which an
+    /// insertion may take before the table is rebuilt with a new seed.
+    pub fn max_probe_length(&self) -> usize {
+        self.max_probe_length
+    }
+
+    /// Sets the longest probe sequence, in groups of buckets, which an
+    /// insertion may take before the table is rebuilt with a new seed.
+    ///
+    /// The default is generous enough to never trigger with a good hasher.
+    /// Lower limits detect collisions earlier but may also trigger on
+    /// unlucky, harmless keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_probe_length` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::RandomState;
+    /// use hashbrown::AdaptiveHashMap;
+    ///
+    /// let mut map: AdaptiveHashMap<i32, i32, RandomState> = AdaptiveHashMap::new();
+    /// map.set_max_probe_length(8);
+    /// assert_eq!(map.max_probe_length(), 8);
+    /// ```
+    pub fn set_max_probe_length(&mut self, max_probe_length: usize) {
+        assert!(max_probe_length > 0, "the probe length limit must not be 0");
+        self.max_probe_length = max_probe_length;
+    }
+
+    /// Returns how many times the table was rebuilt with a new seed.
+    pub fn reseeds(&self) -> usize {
+        self.reseeds
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` such that `f(&k, &mut v)`
+    /// returns `false`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.map.retain(f);
+    }
+
+    /// Returns the underlying `HashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_inner(self) -> HashMap<K, V, S, A> {
+        self.map
+    }
+}
+
+impl<K, V, S, A> AdaptiveHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: Reseed,
+    A: Allocator + Clone,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned, and
+    /// the table is rebuilt with a new seed if placing the key took too long
+    /// a probe sequence.
+    ///
+    /// If the map did have this key present, the value is updated, and the
+    /// old value is returned. The key is not updated, though.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::RandomState;
+    /// use hashbrown::AdaptiveHashMap;
+    ///
+    /// let mut map: AdaptiveHashMap<i32, &str, RandomState> = AdaptiveHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_insert_hash::<K, S>(&self.map.hash_builder, &k);
+        if let Some((_, item)) = self.map.table.get_mut(hash, equivalent_key(&k)) {
+            return Some(mem::replace(item, v));
+        }
+
+        let bucket =
+            self.map
+                .table
+                .insert(hash, (k, v), make_hasher::<K, V, S>(&self.map.hash_builder));
+        let index = unsafe { self.map.table.bucket_index(&bucket) };
+        if self.map.table.probe_length(hash, index) > self.max_probe_length {
+            self.rebuild();
+        }
+        None
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get_mut(k)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.remove(k)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.remove_entry(k)
+    }
+
+    /// Reseeds the hasher and moves all entries into a new table of the same
+    /// capacity, hashed with the new seed.
+    #[cold]
+    #[inline(never)]
+    fn rebuild(&mut self) {
+        self.map.hash_builder.reseed();
+        self.reseeds += 1;
+
+        let alloc = self.map.table.allocator().clone();
+        let capacity = self.map.table.capacity();
+        let old = mem::replace(
+            &mut self.map.table,
+            RawTable::with_capacity_in(capacity, alloc),
+        );
+        let mut longest = 0;
+        for item in old {
+            let hash = make_hash::<K, S>(&self.map.hash_builder, &item.0);
+            // SAFETY: The new table has room for all entries of the old one.
+            unsafe {
+                let bucket = self.map.table.insert_no_grow(hash, item);
+                let index = self.map.table.bucket_index(&bucket);
+                longest = longest.max(self.map.table.probe_length(hash, index));
+            }
+        }
+
+        if longest > self.max_probe_length {
+            self.max_probe_length = self.max_probe_length.saturating_mul(2);
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> From<HashMap<K, V, S, A>> for AdaptiveHashMap<K, V, S, A> {
+    /// Wraps a `HashMap`. Its existing entries are not checked until the next
+    /// insertion which triggers a rebuild.
+    fn from(map: HashMap<K, V, S, A>) -> Self {
+        Self {
+            map,
+            max_probe_length: DEFAULT_MAX_PROBE_LENGTH,
+            reseeds: 0,
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Deref for AdaptiveHashMap<K, V, S, A> {
+    type Target = HashMap<K, V, S, A>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+}
+
+impl<K, V, S, A> Extend<(K, V)> for AdaptiveHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: Reseed,
+    A: Allocator + Clone,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for AdaptiveHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: Reseed + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S: Default> Default for AdaptiveHashMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for AdaptiveHashMap<K, V, S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            max_probe_length: self.max_probe_length,
+            reseeds: self.reseeds,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for AdaptiveHashMap<K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_adaptive {
+    use super::{AdaptiveHashMap, Reseed};
+    use core::hash::{BuildHasher, Hasher};
+
+    /// A hasher which maps every key to the same hash until it is reseeded.
+    #[derive(Clone, Default)]
+    struct WeakHasher {
+        seed: u64,
+    }
+
+    impl BuildHasher for WeakHasher {
+        type Hasher = WeakHasherState;
+
+        fn build_hasher(&self) -> WeakHasherState {
+            WeakHasherState {
+                seed: self.seed,
+                hash: 0,
+            }
+        }
+    }
+
+    impl Reseed for WeakHasher {
+        fn reseed(&mut self) {
+            self.seed += 1;
+        }
+    }
+
+    struct WeakHasherState {
+        seed: u64,
+        hash: u64,
+    }
+
+    impl Hasher for WeakHasherState {
+        fn finish(&self) -> u64 {
+            if self.seed == 0 {
+                0
+            } else {
+                // The finalizer of SplitMix64.
+                let mut z = self.hash ^ self.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                z ^ (z >> 31)
+            }
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.hash = (self.hash << 8 | u64::from(byte)).rotate_left(5);
+            }
+        }
+    }
+
+    /// A hasher which maps every key to the same hash, whatever its seed.
+    #[derive(Default)]
+    struct ConstantHasher;
+
+    impl BuildHasher for ConstantHasher {
+        type Hasher = WeakHasherState;
+
+        fn build_hasher(&self) -> WeakHasherState {
+            WeakHasherState { seed: 0, hash: 0 }
+        }
+    }
+
+    impl Reseed for ConstantHasher {
+        fn reseed(&mut self) {}
+    }
+
+    #[test]
+    fn test_reseed_on_collisions() {
+        // Allow probing the same number of buckets whatever the group width.
+        let max_probe_length = 128 / crate::raw::Group::WIDTH;
+        let mut map = AdaptiveHashMap::with_hasher(WeakHasher::default());
+        map.set_max_probe_length(max_probe_length);
+        for i in 0..1000u32 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        assert_eq!(map.reseeds(), 1);
+        assert_eq!(map.max_probe_length(), max_probe_length);
+        assert_eq!(map.len(), 1000);
+        for i in 0..1000 {
+            assert_eq!(map[&i], i);
+        }
+        assert_eq!(map.insert(7, 0), Some(7));
+        assert_eq!(map.remove(&7), Some(0));
+        assert_eq!(map.len(), 999);
+    }
+
+    #[test]
+    fn test_limit_grows_when_reseeding_fails() {
+        let mut map = AdaptiveHashMap::with_hasher(ConstantHasher);
+        map.set_max_probe_length(2);
+        map.extend((0..200u32).map(|i| (i, i)));
+        assert!(map.reseeds() > 0);
+        // Each rebuild doubles the limit, so there are only a few of them.
+        assert!(map.reseeds() < 10);
+        assert!(map.max_probe_length() > 2);
+        for i in 0..200 {
+            assert_eq!(map[&i], i);
+        }
+    }
+
+    #[cfg(feature = "ahash")]
+    #[test]
+    fn test_no_reseed_with_good_hasher() {
+        let map: AdaptiveHashMap<u32, u32, crate::hash_map::RandomState> =
+            (0..100_000).map(|i| (i, i)).collect();
+        assert_eq!(map.reseeds(), 0);
+        assert_eq!(map.len(), 100_000);
+    }
+}
@@ -0,0 +1,219 @@
+//! A table of groups for hash aggregation.
+//!
+//! A hash aggregation maps every row of its input to the group of rows with
+//! the same key, and updates the accumulators of that group, for example a
+//! count or a sum. [`GroupTable`] does the mapping for batches of rows whose
+//! keys the caller has already hashed, numbering groups densely in the order
+//! their first row is seen, so that the accumulators can live in plain
+//! vectors indexed by group.
+//!
+//! # Examples
+//!
+//! ```
+//! use hashbrown::aggregate::GroupTable;
+//! use std::cell::RefCell;
+//!
+//! // SELECT key, SUM(value) GROUP BY key, with the identity as hash.
+//! let keys = [3_u64, 1, 3, 3, 2, 1];
+//! let values = [10, 20, 30, 40, 50, 60];
+//!
+//! // Both callbacks update the sums, so they share them through a `RefCell`.
+//! let sums = RefCell::new(Vec::new());
+//! let mut groups = GroupTable::new();
+//! groups.accumulate(
+//!     &keys,
+//!     &keys,
+//!     |_group, row| sums.borrow_mut().push(values[row]),
+//!     |group, row| sums.borrow_mut()[group] += values[row],
+//! );
+//! assert_eq!(groups.keys(), [3, 1, 2]);
+//! assert_eq!(sums.into_inner(), [80, 80, 50]);
+//! ```
+
+use crate::batch::BATCH;
+use crate::raw::RawTable;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Maps keys to dense group indices, for hash aggregation.
+///
+/// Every distinct key gets the next group index when it is first seen and
+/// keeps it for the life of the table, so the indices can address
+/// accumulators stored outside of the table. The table stores the hash of
+/// each group next to its index, and never hashes a key itself. See the
+/// [module documentation](self).
+pub struct GroupTable<K> {
+    // The hash and index of every group.
+    table: RawTable<(u64, usize)>,
+    // The key of every group, by index.
+    keys: Vec<K>,
+}
+
+impl<K> GroupTable<K> {
+    /// Creates an empty `GroupTable`.
+    pub fn new() -> Self {
+        Self {
+            table: RawTable::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Creates an empty `GroupTable` with room for at least `capacity`
+    /// groups.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: RawTable::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of groups.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns `true` if there are no groups.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the keys of the groups, indexed by group.
+    pub fn keys(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// Removes all groups, keeping the allocated memory for reuse. Group
+    /// indices start over from zero.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.keys.clear();
+    }
+
+    /// Consumes the table and returns the keys of the groups, indexed by
+    /// group.
+    pub fn into_keys(self) -> Vec<K> {
+        self.keys
+    }
+}
+
+impl<K: Eq> GroupTable<K> {
+    /// Returns the index of the group of `key`, which has the given hash, if
+    /// there is one.
+    pub fn find(&self, hash: u64, key: &K) -> Option<usize> {
+        let keys = &self.keys;
+        self.table
+            .get(hash, |&(h, group)| h == hash && keys[group] == *key)
+            .map(|&(_, group)| group)
+    }
+
+    /// Maps every row of a batch to its group, creating groups for the keys
+    /// not seen before.
+    ///
+    /// Row `i` has the key `keys[i]`, whose hash is `hashes[i]`. For every
+    /// row in order, `on_new(group, i)` is called if the row created a new
+    /// group, whose index is then the previous number of groups, and
+    /// `on_existing(group, i)` otherwise.
+    ///
+    /// The rows are processed a few at a time: room for all of them is
+    /// reserved first, so the table never grows in the middle of the few,
+    /// and the control bytes they probe are prefetched before any of them is
+    /// looked up.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hashes` and `keys` have different lengths.
+    pub fn accumulate<N, E>(
+        &mut self,
+        hashes: &[u64],
+        keys: &[K],
+        mut on_new: N,
+        mut on_existing: E,
+    ) where
+        K: Clone,
+        N: FnMut(usize, usize),
+        E: FnMut(usize, usize),
+    {
+        assert_eq!(hashes.len(), keys.len(), "one hash is needed per key");
+        for (chunk, (hashes, keys)) in hashes.chunks(BATCH).zip(keys.chunks(BATCH)).enumerate() {
+            self.table.reserve(hashes.len(), |&(hash, _)| hash);
+            for &hash in hashes {
+                self.table.prefetch(hash);
+            }
+            for (i, (&hash, key)) in hashes.iter().zip(keys).enumerate() {
+                let row = chunk * BATCH + i;
+                match self.find(hash, key) {
+                    Some(group) => on_existing(group, row),
+                    None => {
+                        let group = self.keys.len();
+                        // SAFETY: Room for every row of the chunk was
+                        // reserved above.
+                        unsafe {
+                            self.table.insert_no_grow(hash, (hash, group));
+                        }
+                        self.keys.push(key.clone());
+                        on_new(group, row);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<K> Default for GroupTable<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for GroupTable<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.keys).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_aggregate {
+    use super::GroupTable;
+    use core::cell::RefCell;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_accumulate() {
+        let mut groups = GroupTable::new();
+        let counts = RefCell::new(Vec::new());
+
+        // Several batches, with many keys per hash.
+        for batch in 0..4 {
+            let keys: Vec<u32> = (0..100).map(|i| (i * 7 + batch) % 50).collect();
+            let hashes: Vec<u64> = keys.iter().map(|&k| u64::from(k % 5)).collect();
+            groups.accumulate(
+                &hashes,
+                &keys,
+                |group, _| {
+                    let mut counts = counts.borrow_mut();
+                    assert_eq!(group, counts.len());
+                    counts.push(1);
+                },
+                |group, _| counts.borrow_mut()[group] += 1,
+            );
+        }
+
+        assert_eq!(groups.len(), 50);
+        assert!(counts.borrow().iter().all(|&count| count == 8));
+        for (group, key) in groups.keys().iter().enumerate() {
+            assert_eq!(groups.find(u64::from(key % 5), key), Some(group));
+        }
+        assert_eq!(groups.keys()[..3], [0, 7, 14]);
+        assert_eq!(groups.find(0, &51), None);
+
+        groups.clear();
+        assert!(groups.is_empty());
+        assert_eq!(groups.find(0, &0), None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_accumulate_length_mismatch() {
+        GroupTable::new().accumulate(&[1, 2], &[1], |_, _| {}, |_, _| {});
+    }
+}
@@ -0,0 +1,552 @@
+//! A hash map which copies its byte-string keys into an arena.
+//!
+//! [`ArenaMap`] is keyed by `[u8]` or `str` slices. Inserting a key copies its
+//! bytes to the end of one buffer owned by the map instead of storing an owned
+//! key per entry, so filling the map does not allocate once per key.
+
+use crate::map::{make_hash, DefaultHashBuilder};
+use crate::raw::{RawIter, RawTable};
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::ops::Index;
+use core::str;
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for [u8] {}
+    impl Sealed for str {}
+}
+
+/// Key types which an [`ArenaMap`] can copy into its arena.
+///
+/// This trait is sealed and implemented for `[u8]` and `str`.
+pub trait ArenaKey: private::Sealed + Hash + Eq {
+    /// Returns the bytes of the key.
+    #[doc(hidden)]
+    fn as_key_bytes(&self) -> &[u8];
+
+    /// Recovers a key from bytes returned by `as_key_bytes`.
+    #[doc(hidden)]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self;
+}
+
+impl ArenaKey for [u8] {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        bytes
+    }
+}
+
+impl ArenaKey for str {
+    #[inline]
+    fn as_key_bytes(&self) -> &[u8] {
+        self.as_bytes()
+    }
+
+    #[inline]
+    unsafe fn from_key_bytes(bytes: &[u8]) -> &Self {
+        str::from_utf8_unchecked(bytes)
+    }
+}
+
+/// A hash map keyed by `[u8]` or `str` slices which owns a copy of every key
+/// in an internal arena.
+///
+/// [`insert`] takes the key by reference and appends its bytes to a single
+/// buffer owned by the map, so building the map does not allocate once per
+/// key and callers never have to produce owned keys. The table itself only
+/// stores the position of each key in the arena next to its value. Lookups
+/// take a borrowed key as well and compare it against the arena in place.
+///
+/// Removing an entry does not free its bytes in the arena; they are only
+/// reclaimed by [`clear`]. The map is meant for tables which mostly grow,
+/// such as the symbol table of a parser or interner.
+///
+/// [`insert`]: ArenaMap::insert
+/// [`clear`]: ArenaMap::clear
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::ArenaMap;
+///
+/// let source = String::from("let x = y + x");
+/// let mut symbols: ArenaMap<str, usize> = ArenaMap::new();
+/// for word in source.split(' ') {
+///     let next = symbols.len();
+///     if !symbols.contains_key(word) {
+///         symbols.insert(word, next);
+///     }
+/// }
+/// drop(source);
+///
+/// assert_eq!(symbols.len(), 5);
+/// assert_eq!(symbols.get("x"), Some(&1));
+/// assert_eq!(symbols["+"], 4);
+/// ```
+pub struct ArenaMap<K: ?Sized + ArenaKey, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    arena: Vec<u8>,
+    table: RawTable<(usize, usize, V)>,
+    marker: PhantomData<fn(&K)>,
+}
+
+/// Returns the key stored at `start..start + len` in `arena`.
+#[inline]
+fn key_at<K: ?Sized + ArenaKey>(arena: &[u8], start: usize, len: usize) -> &K {
+    // SAFETY: the range was filled from a key of type `K`.
+    unsafe { K::from_key_bytes(&arena[start..start + len]) }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K: ?Sized + ArenaKey, V> ArenaMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `ArenaMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArenaMap;
+    ///
+    /// let mut map: ArenaMap<[u8], u32> = ArenaMap::new();
+    /// map.insert(b"key", 1);
+    /// assert_eq!(map.get(b"key"), Some(&1));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `ArenaMap` which can hold at least `capacity`
+    /// entries without reallocating its table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K: ?Sized + ArenaKey, V, S> ArenaMap<K, V, S> {
+    /// Creates an empty `ArenaMap` which uses `hash_builder` to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            arena: Vec::new(),
+            table: RawTable::new(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Creates an empty `ArenaMap` which uses `hash_builder` to hash keys
+    /// and can hold at least `capacity` entries without reallocating its
+    /// table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            arena: Vec::new(),
+            table: RawTable::with_capacity(capacity),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of entries in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the map contains no entries.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns the number of entries the map can hold without reallocating
+    /// its table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Returns the number of key bytes held in the arena, including the
+    /// bytes of removed keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArenaMap;
+    ///
+    /// let mut map: ArenaMap<str, ()> = ArenaMap::new();
+    /// map.insert("abc", ());
+    /// map.insert("de", ());
+    /// map.remove("abc");
+    /// assert_eq!(map.arena_len(), 5);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn arena_len(&self) -> usize {
+        self.arena.len()
+    }
+
+    /// Removes all entries and empties the arena, keeping the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.arena.clear();
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            // SAFETY: the iterator borrows the map.
+            inner: unsafe { self.table.iter() },
+            arena: &self.arena,
+            marker: PhantomData,
+        }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn keys(&self) -> impl Iterator<Item = &K> + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn values(&self) -> impl Iterator<Item = &V> + '_ {
+        self.iter().map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S> ArenaMap<K, V, S>
+where
+    K: ?Sized + ArenaKey,
+    S: BuildHasher,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn find(&self, hash: u64, k: &K) -> Option<crate::raw::Bucket<(usize, usize, V)>> {
+        let arena = &self.arena;
+        self.table
+            .find(hash, |&(start, len, _)| key_at::<K>(arena, start, len) == k)
+    }
+
+    /// Reserves capacity for at least `additional` more entries.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve(&mut self, additional: usize) {
+        let (arena, hash_builder) = (&self.arena, &self.hash_builder);
+        self.table.reserve(additional, |&(start, len, _)| {
+            make_hash::<K, S>(hash_builder, key_at::<K>(arena, start, len))
+        });
+    }
+
+    /// Returns `true` if the map contains the key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn contains_key(&self, k: &K) -> bool {
+        self.get(k).is_some()
+    }
+
+    /// Returns a reference to the value of the key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get(&self, k: &K) -> Option<&V> {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns the key as stored in the arena and a reference to its value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArenaMap;
+    ///
+    /// let mut map: ArenaMap<str, u32> = ArenaMap::new();
+    /// map.insert(&String::from("temporary"), 1);
+    /// let (key, value) = map.get_key_value("temporary").unwrap();
+    /// assert_eq!((key, *value), ("temporary", 1));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_key_value(&self, k: &K) -> Option<(&K, &V)> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<K, S>(&self.hash_builder, k);
+        match self.find(hash, k) {
+            Some(bucket) => {
+                // SAFETY: the bucket was just found in the table.
+                let (start, len, ref value) = *unsafe { bucket.as_ref() };
+                Some((key_at(&self.arena, start, len), value))
+            }
+            None => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value of the key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_mut(&mut self, k: &K) -> Option<&mut V> {
+        if self.table.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<K, S>(&self.hash_builder, k);
+        match self.find(hash, k) {
+            // SAFETY: the bucket was just found in the table.
+            Some(bucket) => Some(unsafe { &mut bucket.as_mut().2 }),
+            None => None,
+        }
+    }
+
+    /// Inserts a key-value pair into the map, copying the key into the arena
+    /// if it is not present yet.
+    ///
+    /// If the map already had the key, its value is replaced and the old
+    /// value returned, and the arena is left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArenaMap;
+    ///
+    /// let mut map: ArenaMap<[u8], u32> = ArenaMap::new();
+    /// assert_eq!(map.insert(b"a", 1), None);
+    /// assert_eq!(map.insert(b"a", 2), Some(1));
+    /// assert_eq!(map.arena_len(), 1);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: &K, v: V) -> Option<V> {
+        let hash = make_hash::<K, S>(&self.hash_builder, k);
+        if let Some(bucket) = self.find(hash, k) {
+            // SAFETY: the bucket was just found in the table.
+            return Some(core::mem::replace(unsafe { &mut bucket.as_mut().2 }, v));
+        }
+        let bytes = k.as_key_bytes();
+        let start = self.arena.len();
+        self.arena.extend_from_slice(bytes);
+        let (arena, hash_builder) = (&self.arena, &self.hash_builder);
+        self.table
+            .insert(hash, (start, bytes.len(), v), |&(start, len, _)| {
+                make_hash::<K, S>(hash_builder, key_at::<K>(arena, start, len))
+            });
+        None
+    }
+
+    /// Removes a key from the map, returning its value if it was present.
+    ///
+    /// The bytes of the key stay in the arena until the map is cleared.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove(&mut self, k: &K) -> Option<V> {
+        let hash = make_hash::<K, S>(&self.hash_builder, k);
+        let arena = &self.arena;
+        match self
+            .table
+            .remove_entry(hash, |&(start, len, _)| key_at::<K>(arena, start, len) == k)
+        {
+            Some((_, _, v)) => Some(v),
+            None => None,
+        }
+    }
+}
+
+impl<K: ?Sized + ArenaKey, V: Clone, S: Clone> Clone for ArenaMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            hash_builder: self.hash_builder.clone(),
+            arena: self.arena.clone(),
+            table: self.table.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K: ?Sized + ArenaKey, V, S: Default> Default for ArenaMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: ?Sized + ArenaKey + Debug, V: Debug, S> Debug for ArenaMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> Index<&K> for ArenaMap<K, V, S>
+where
+    K: ?Sized + ArenaKey,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value of the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &K) -> &V {
+        self.get(key).expect("key not found")
+    }
+}
+
+impl<'a, K, V, S> Extend<(&'a K, V)> for ArenaMap<K, V, S>
+where
+    K: ?Sized + ArenaKey,
+    S: BuildHasher,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn extend<T: IntoIterator<Item = (&'a K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S> FromIterator<(&'a K, V)> for ArenaMap<K, V, S>
+where
+    K: ?Sized + ArenaKey,
+    S: BuildHasher + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (&'a K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K: ?Sized + ArenaKey, V, S> IntoIterator for &'a ArenaMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an [`ArenaMap`].
+///
+/// This `struct` is created by the [`iter`](ArenaMap::iter) method.
+pub struct Iter<'a, K: ?Sized, V> {
+    inner: RawIter<(usize, usize, V)>,
+    arena: &'a [u8],
+    marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K: ?Sized + ArenaKey, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let bucket = self.inner.next()?;
+        // SAFETY: the iterator borrows the map it came from.
+        let (start, len, ref value) = *unsafe { bucket.as_ref() };
+        Some((key_at(self.arena, start, len), value))
+    }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K: ?Sized + ArenaKey, V> ExactSizeIterator for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<K: ?Sized + ArenaKey, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: ?Sized, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            arena: self.arena,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K: ?Sized + ArenaKey + Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_arena_map {
+    use super::ArenaMap;
+    use alloc::format;
+    use alloc::string::String;
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut map: ArenaMap<str, usize> = ArenaMap::new();
+        for i in 0..1000 {
+            let key = format!("symbol{}", i);
+            assert_eq!(map.insert(&key, i), None);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get("symbol500"), Some(&500));
+        assert_eq!(map.get("symbol1000"), None);
+        *map.get_mut("symbol7").unwrap() += 1;
+        assert_eq!(map["symbol7"], 8);
+
+        let mut keys: usize = 0;
+        for (k, v) in &map {
+            assert!(k.starts_with("symbol"));
+            keys += *v;
+        }
+        assert_eq!(keys, (0..1000).sum::<usize>() + 1);
+    }
+
+    #[test]
+    fn test_remove_and_clear() {
+        let mut map: ArenaMap<[u8], u32> = ArenaMap::new();
+        map.insert(b"one", 1);
+        map.insert(b"two", 2);
+        assert_eq!(map.remove(b"one"), Some(1));
+        assert_eq!(map.remove(b"one"), None);
+        assert_eq!(map.arena_len(), 6);
+        assert_eq!(map.get(b"two"), Some(&2));
+
+        map.insert(b"one", 3);
+        assert_eq!(map.arena_len(), 9);
+        assert_eq!(map.get(b"one"), Some(&3));
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.arena_len(), 0);
+    }
+
+    #[test]
+    fn test_clone_and_collect() {
+        let words = [String::from("a"), String::from("bb"), String::from("a")];
+        let map: ArenaMap<str, usize> = words.iter().map(|w| (w.as_str(), w.len())).collect();
+        let copy = map.clone();
+        drop(map);
+        assert_eq!(copy.len(), 2);
+        assert_eq!(copy.get("bb"), Some(&2));
+        assert_eq!(copy.arena_len(), 3);
+    }
+}
@@ -0,0 +1,1074 @@
+use crate::map::{make_hash, make_insert_hash, DefaultHashBuilder};
+use crate::map::{Iter, IterMut, Keys, Values, ValuesMut};
+use crate::raw::{
+    bucket_mask_to_capacity, h2, is_full, special_is_empty, Group, ProbeSeq, RawIter, DELETED,
+    EMPTY,
+};
+use crate::scopeguard::guard;
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem::{self, MaybeUninit};
+use core::ptr::{self, NonNull};
+
+/// Control bytes of an [`ArrayHashMap`].
+///
+/// The trailing `Group::WIDTH` bytes mirror the start of the table so that
+/// unaligned group loads never need to wrap around, exactly like the control
+/// bytes of a `RawTable`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct CtrlBytes<const N: usize> {
+    _align: [Group; 0],
+    bytes: [u8; N],
+    tail: [u8; Group::WIDTH],
+}
+
+/// A fixed-capacity hash map which stores its control bytes and buckets inline
+/// instead of in a heap allocation.
+///
+/// `ArrayHashMap` uses the same SIMD group probing as [`HashMap`], but its
+/// number of buckets is fixed at compile time by `N`, which must be a power of
+/// two. Like `HashMap` it keeps some buckets empty so that lookups always
+/// terminate: it can hold at most [`capacity`] elements, which is `N - 1` for
+/// tables with fewer than 8 buckets and 7/8 of `N` otherwise.
+///
+/// Since an `ArrayHashMap` never allocates, inserting into a full map fails
+/// instead of growing it, see [`insert`] for details. This makes it suitable
+/// for `no_std` targets without a global allocator.
+///
+/// [`HashMap`]: crate::HashMap
+/// [`capacity`]: ArrayHashMap::capacity
+/// [`insert`]: ArrayHashMap::insert
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::ArrayHashMap;
+///
+/// let mut map: ArrayHashMap<&str, i32, 8> = ArrayHashMap::new();
+/// assert_eq!(map.capacity(), 7);
+///
+/// map.insert("a", 1).unwrap();
+/// map.insert("b", 2).unwrap();
+/// assert_eq!(map.get("a"), Some(&1));
+///
+/// for i in 0..5 {
+///     map.insert(["c", "d", "e", "f", "g"][i], i as i32).unwrap();
+/// }
+///
+/// // The map is full, so inserting a new key hands the pair back.
+/// assert_eq!(map.insert("h", 8), Err(("h", 8)));
+/// // Replacing the value of an existing key still works.
+/// assert_eq!(map.insert("a", 10), Ok(Some(1)));
+/// ```
+pub struct ArrayHashMap<K, V, const N: usize, S = DefaultHashBuilder> {
+    hash_builder: S,
+    ctrl: CtrlBytes<N>,
+    // Bucket `i` is stored in `data[N - 1 - i]`, which matches the layout that
+    // `RawIter` expects: buckets are addressed backwards from the end of the
+    // data array.
+    data: [MaybeUninit<(K, V)>; N],
+    // Number of elements that can be inserted before we run out of EMPTY
+    // buckets and need to reclaim DELETED ones.
+    growth_left: usize,
+    items: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V, const N: usize> ArrayHashMap<K, V, N, DefaultHashBuilder> {
+    /// Creates an empty `ArrayHashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    /// let mut map: ArrayHashMap<&str, i32, 16> = ArrayHashMap::new();
+    /// assert_eq!(map.len(), 0);
+    /// assert_eq!(map.capacity(), 14);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, const N: usize, S> ArrayHashMap<K, V, N, S> {
+    const BUCKET_MASK: usize = {
+        assert!(
+            N.is_power_of_two(),
+            "the number of buckets of an ArrayHashMap must be a power of two"
+        );
+        N - 1
+    };
+    const CAPACITY: usize = bucket_mask_to_capacity(Self::BUCKET_MASK);
+    const DATA_NEEDS_DROP: bool = mem::needs_drop::<(K, V)>();
+
+    /// Creates an empty `ArrayHashMap` which will use the given hash builder to
+    /// hash keys.
+    ///
+    /// This function is `const`, so it can be used to initialize `static`s.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    /// use hashbrown::hash_map::DefaultHashBuilder;
+    ///
+    /// let s = DefaultHashBuilder::default();
+    /// let mut map: ArrayHashMap<i32, i32, 4, _> = ArrayHashMap::with_hasher(s);
+    /// map.insert(1, 2).unwrap();
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            ctrl: CtrlBytes {
+                _align: [],
+                bytes: [EMPTY; N],
+                tail: [EMPTY; Group::WIDTH],
+            },
+            // SAFETY: An uninitialized `[MaybeUninit<_>; N]` is valid.
+            data: unsafe { MaybeUninit::uninit().assume_init() },
+            growth_left: Self::CAPACITY,
+            items: 0,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the maximum number of elements the map can hold.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    /// let map: ArrayHashMap<i32, i32, 4> = ArrayHashMap::new();
+    /// assert_eq!(map.capacity(), 3);
+    /// let map: ArrayHashMap<i32, i32, 64> = ArrayHashMap::new();
+    /// assert_eq!(map.capacity(), 56);
+    /// ```
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        Self::CAPACITY
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut a: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// assert_eq!(a.len(), 0);
+    /// a.insert(1, "a").unwrap();
+    /// assert_eq!(a.len(), 1);
+    /// ```
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    /// Returns `true` if the map contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut a: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// assert!(a.is_empty());
+    /// a.insert(1, "a").unwrap();
+    /// assert!(!a.is_empty());
+    /// ```
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Returns `true` if the map holds [`capacity`] elements, in which case
+    /// new keys can no longer be inserted.
+    ///
+    /// [`capacity`]: ArrayHashMap::capacity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut a: ArrayHashMap<i32, &str, 2> = ArrayHashMap::new();
+    /// assert!(!a.is_full());
+    /// a.insert(1, "a").unwrap();
+    /// assert!(a.is_full());
+    /// ```
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.items == Self::CAPACITY
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<&str, i32, 8> = ArrayHashMap::new();
+    /// map.insert("a", 1).unwrap();
+    /// map.insert("b", 2).unwrap();
+    /// map.insert("c", 3).unwrap();
+    ///
+    /// let mut vec: Vec<(&str, i32)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    /// vec.sort_unstable();
+    /// assert_eq!(vec, [("a", 1), ("b", 2), ("c", 3)]);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        // Here we tie the lifetime of self to the iter.
+        unsafe {
+            Iter {
+                inner: self.raw_iter(),
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order,
+    /// with mutable references to the values.
+    /// The iterator element type is `(&'a K, &'a mut V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<&str, i32, 8> = ArrayHashMap::new();
+    /// map.insert("a", 1).unwrap();
+    /// map.insert("b", 2).unwrap();
+    ///
+    /// for (_, val) in map.iter_mut() {
+    ///     *val *= 2;
+    /// }
+    ///
+    /// assert_eq!(map.get("a"), Some(&2));
+    /// assert_eq!(map.get("b"), Some(&4));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        // Here we tie the lifetime of self to the iter.
+        unsafe {
+            IterMut {
+                inner: self.raw_iter_mut(),
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    /// The iterator element type is `&'a K`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    /// The iterator element type is `&'a V`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values mutably in arbitrary order.
+    /// The iterator element type is `&'a mut V`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` such that `f(&k, &mut v)` returns `false`.
+    /// The elements are visited in unsorted (and unspecified) order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, i32, 16> = ArrayHashMap::new();
+    /// for x in 0..8 {
+    ///     map.insert(x, x * 10).unwrap();
+    /// }
+    ///
+    /// map.retain(|&k, _| k % 2 == 0);
+    /// assert_eq!(map.len(), 4);
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        unsafe {
+            for index in 0..N {
+                if !is_full(self.ctrl(index)) {
+                    continue;
+                }
+                let (key, value) = self.bucket_mut(index);
+                if !f(key, value) {
+                    self.erase(index);
+                    self.bucket_ptr(index).drop_in_place();
+                }
+            }
+        }
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut a: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// a.insert(1, "a").unwrap();
+    /// a.clear();
+    /// assert!(a.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        // Ensure that the table is reset even if one of the drops panic
+        let mut self_ = guard(self, |self_| self_.clear_no_drop());
+        unsafe {
+            self_.drop_elements();
+        }
+    }
+
+    /// Marks all buckets as empty without dropping their contents.
+    #[inline]
+    fn clear_no_drop(&mut self) {
+        self.ctrl.bytes = [EMPTY; N];
+        self.ctrl.tail = [EMPTY; Group::WIDTH];
+        self.items = 0;
+        self.growth_left = Self::CAPACITY;
+    }
+
+    unsafe fn drop_elements(&mut self) {
+        if Self::DATA_NEEDS_DROP && !self.is_empty() {
+            for item in self.raw_iter_mut() {
+                item.as_ptr().drop_in_place();
+            }
+        }
+    }
+
+    /// Returns an iterator over every full bucket. It is up to the caller to
+    /// ensure that the map outlives the `RawIter`, and that it is only used to
+    /// read the elements.
+    #[inline]
+    pub(crate) unsafe fn raw_iter(&self) -> RawIter<(K, V)> {
+        let data_end = self.data.as_ptr().add(N) as *mut (K, V);
+        RawIter::new(
+            self.ctrl_ptr(),
+            NonNull::new_unchecked(data_end),
+            N,
+            self.items,
+        )
+    }
+
+    /// Returns an iterator over every full bucket. It is up to the caller to
+    /// ensure that the map outlives the `RawIter`.
+    #[inline]
+    pub(crate) unsafe fn raw_iter_mut(&mut self) -> RawIter<(K, V)> {
+        let data_end = self.data.as_mut_ptr().add(N).cast::<(K, V)>();
+        RawIter::new(
+            self.ctrl_ptr(),
+            NonNull::new_unchecked(data_end),
+            N,
+            self.items,
+        )
+    }
+
+    #[inline]
+    fn ctrl_ptr(&self) -> *const u8 {
+        (&self.ctrl as *const CtrlBytes<N>).cast()
+    }
+
+    /// Returns the control byte of the bucket at `index`, which may be one of
+    /// the trailing mirrored bytes.
+    #[inline]
+    unsafe fn ctrl(&self, index: usize) -> u8 {
+        debug_assert!(index < N + Group::WIDTH);
+        *self.ctrl_ptr().add(index)
+    }
+
+    /// Sets a control byte, and possibly also the replicated control byte at
+    /// the end of the array. See `RawTableInner::set_ctrl` for details.
+    #[inline]
+    unsafe fn set_ctrl(&mut self, index: usize, ctrl: u8) {
+        let index2 = ((index.wrapping_sub(Group::WIDTH)) & Self::BUCKET_MASK) + Group::WIDTH;
+        let base = (&mut self.ctrl as *mut CtrlBytes<N>).cast::<u8>();
+        *base.add(index) = ctrl;
+        *base.add(index2) = ctrl;
+    }
+
+    #[inline]
+    unsafe fn bucket(&self, index: usize) -> &(K, V) {
+        debug_assert!(index < N);
+        self.data
+            .get_unchecked(Self::BUCKET_MASK - index)
+            .assume_init_ref()
+    }
+
+    #[inline]
+    unsafe fn bucket_mut(&mut self, index: usize) -> &mut (K, V) {
+        debug_assert!(index < N);
+        self.data
+            .get_unchecked_mut(Self::BUCKET_MASK - index)
+            .assume_init_mut()
+    }
+
+    #[inline]
+    unsafe fn bucket_ptr(&mut self, index: usize) -> *mut (K, V) {
+        debug_assert!(index < N);
+        self.data
+            .get_unchecked_mut(Self::BUCKET_MASK - index)
+            .as_mut_ptr()
+    }
+
+    #[inline]
+    fn probe_seq(hash: u64) -> ProbeSeq {
+        ProbeSeq::new(hash, Self::BUCKET_MASK)
+    }
+
+    /// Searches for an element in the table, returning its bucket index.
+    #[inline]
+    fn find(&self, hash: u64, mut eq: impl FnMut(&(K, V)) -> bool) -> Option<usize> {
+        let h2_hash = h2(hash);
+        let mut probe_seq = Self::probe_seq(hash);
+
+        loop {
+            let group = unsafe { Group::load(self.ctrl_ptr().add(probe_seq.pos)) };
+
+            for bit in group.match_byte(h2_hash) {
+                let index = (probe_seq.pos + bit) & Self::BUCKET_MASK;
+
+                if eq(unsafe { self.bucket(index) }) {
+                    return Some(index);
+                }
+            }
+
+            if group.match_empty().any_bit_set() {
+                return None;
+            }
+
+            probe_seq.move_next(Self::BUCKET_MASK);
+        }
+    }
+
+    /// Searches for an empty or deleted bucket which is suitable for inserting
+    /// a new element.
+    ///
+    /// There must be at least 1 empty bucket in the table.
+    #[inline]
+    fn find_insert_slot(&self, hash: u64) -> usize {
+        let mut probe_seq = Self::probe_seq(hash);
+        loop {
+            unsafe {
+                let group = Group::load(self.ctrl_ptr().add(probe_seq.pos));
+                if let Some(bit) = group.match_empty_or_deleted().lowest_set_bit() {
+                    let result = (probe_seq.pos + bit) & Self::BUCKET_MASK;
+
+                    // In tables smaller than the group width the trailing
+                    // EMPTY control bytes may match and point to a full
+                    // bucket once masked. See `RawTableInner::find_insert_slot`.
+                    if is_full(self.ctrl(result)) {
+                        debug_assert!(Self::BUCKET_MASK < Group::WIDTH);
+                        debug_assert_ne!(probe_seq.pos, 0);
+                        return Group::load_aligned(self.ctrl_ptr())
+                            .match_empty_or_deleted()
+                            .lowest_set_bit_nonzero();
+                    }
+
+                    return result;
+                }
+            }
+            probe_seq.move_next(Self::BUCKET_MASK);
+        }
+    }
+
+    #[inline]
+    fn is_in_same_group(i: usize, new_i: usize, hash: u64) -> bool {
+        let probe_seq_pos = Self::probe_seq(hash).pos;
+        let probe_index =
+            |pos: usize| (pos.wrapping_sub(probe_seq_pos) & Self::BUCKET_MASK) / Group::WIDTH;
+        probe_index(i) == probe_index(new_i)
+    }
+
+    /// Marks the bucket at `index` as no longer full, without dropping its
+    /// contents. See `RawTableInner::erase` for details.
+    #[inline]
+    unsafe fn erase(&mut self, index: usize) {
+        debug_assert!(is_full(self.ctrl(index)));
+        let index_before = index.wrapping_sub(Group::WIDTH) & Self::BUCKET_MASK;
+        let empty_before = Group::load(self.ctrl_ptr().add(index_before)).match_empty();
+        let empty_after = Group::load(self.ctrl_ptr().add(index)).match_empty();
+
+        let ctrl = if empty_before.leading_zeros() + empty_after.trailing_zeros() >= Group::WIDTH {
+            DELETED
+        } else {
+            self.growth_left += 1;
+            EMPTY
+        };
+        self.set_ctrl(index, ctrl);
+        self.items -= 1;
+    }
+}
+
+impl<K, V, const N: usize, S> ArrayHashMap<K, V, N, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        // Avoid `Option::map` because it bloats LLVM IR.
+        match self.get_key_value(k) {
+            Some((_, v)) => Some(v),
+            None => None,
+        }
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// assert_eq!(map.get_key_value(&1), Some((&1, &"a")));
+    /// assert_eq!(map.get_key_value(&2), None);
+    /// ```
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        match self.find(hash, |x| k.equivalent(&x.0)) {
+            Some(index) => {
+                let (key, value) = unsafe { self.bucket(index) };
+                Some((key, value))
+            }
+            None => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// if let Some(x) = map.get_mut(&1) {
+    ///     *x = "b";
+    /// }
+    /// assert_eq!(map.get(&1), Some(&"b"));
+    /// ```
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        match self.find(hash, |x| k.equivalent(&x.0)) {
+            Some(index) => Some(unsafe { &mut self.bucket_mut(index).1 }),
+            None => None,
+        }
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// assert!(map.contains_key(&1));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_key_value(k).is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, `Ok(None)` is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned as `Ok(Some(old))`. The key is not updated, though.
+    ///
+    /// # Errors
+    ///
+    /// If the key is not present and the map is already [full], the key-value
+    /// pair is handed back as `Err((k, v))` and the map is left unchanged.
+    ///
+    /// [full]: ArrayHashMap::is_full
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 4> = ArrayHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), Ok(None));
+    /// assert_eq!(map.insert(37, "b"), Ok(Some("a")));
+    /// assert_eq!(map.insert(38, "c"), Ok(None));
+    /// assert_eq!(map.insert(39, "d"), Ok(None));
+    /// assert_eq!(map.insert(40, "e"), Err((40, "e")));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Result<Option<V>, (K, V)> {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+        if let Some(index) = self.find(hash, |x| k.equivalent(&x.0)) {
+            let item = unsafe { &mut self.bucket_mut(index).1 };
+            return Ok(Some(mem::replace(item, v)));
+        }
+
+        if self.is_full() {
+            return Err((k, v));
+        }
+
+        unsafe {
+            let mut index = self.find_insert_slot(hash);
+            let mut old_ctrl = self.ctrl(index);
+
+            // We are out of EMPTY buckets but some DELETED ones can be
+            // reclaimed since the map isn't full.
+            if self.growth_left == 0 && special_is_empty(old_ctrl) {
+                self.rehash_in_place();
+                index = self.find_insert_slot(hash);
+                old_ctrl = self.ctrl(index);
+            }
+
+            self.growth_left -= usize::from(special_is_empty(old_ctrl));
+            self.set_ctrl(index, h2(hash));
+            self.bucket_ptr(index).write((k, v));
+            self.items += 1;
+        }
+        Ok(None)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// assert_eq!(map.remove(&1), Some("a"));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        // Avoid `Option::map` because it bloats LLVM IR.
+        match self.remove_entry(k) {
+            Some((_, v)) => Some(v),
+            None => None,
+        }
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ArrayHashMap;
+    ///
+    /// let mut map: ArrayHashMap<i32, &str, 8> = ArrayHashMap::new();
+    /// map.insert(1, "a").unwrap();
+    /// assert_eq!(map.remove_entry(&1), Some((1, "a")));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        match self.find(hash, |x| k.equivalent(&x.0)) {
+            Some(index) => unsafe {
+                self.erase(index);
+                Some(self.bucket_ptr(index).read())
+            },
+            None => None,
+        }
+    }
+
+    /// Rehashes the contents of the table in place to turn all DELETED
+    /// buckets back into EMPTY ones. See `RawTableInner::rehash_in_place`.
+    ///
+    /// If the hasher panics then the elements which have not been rehashed yet
+    /// are dropped.
+    #[cold]
+    #[inline(never)]
+    unsafe fn rehash_in_place(&mut self) {
+        // Bulk convert all full control bytes to DELETED, and all DELETED
+        // control bytes to EMPTY.
+        let base = (&mut self.ctrl as *mut CtrlBytes<N>).cast::<u8>();
+        for i in (0..N).step_by(Group::WIDTH) {
+            let group = Group::load_aligned(base.add(i));
+            let group = group.convert_special_to_empty_and_full_to_deleted();
+            group.store_aligned(base.add(i));
+        }
+        if N < Group::WIDTH {
+            base.copy_to(base.add(Group::WIDTH), N);
+        } else {
+            base.copy_to(base.add(N), Group::WIDTH);
+        }
+
+        let mut guard = guard(self, |self_| {
+            for i in 0..N {
+                if self_.ctrl(i) == DELETED {
+                    self_.set_ctrl(i, EMPTY);
+                    if Self::DATA_NEEDS_DROP {
+                        self_.bucket_ptr(i).drop_in_place();
+                    }
+                    self_.items -= 1;
+                }
+            }
+            self_.growth_left = Self::CAPACITY - self_.items;
+        });
+
+        // At this point, DELETED elements are elements that we haven't
+        // rehashed yet. Find them and re-insert them at their ideal
+        // position.
+        'outer: for i in 0..N {
+            if guard.ctrl(i) != DELETED {
+                continue;
+            }
+
+            'inner: loop {
+                let hash = make_insert_hash::<K, S>(&guard.hash_builder, &guard.bucket(i).0);
+                let new_i = guard.find_insert_slot(hash);
+
+                if Self::is_in_same_group(i, new_i, hash) {
+                    guard.set_ctrl(i, h2(hash));
+                    continue 'outer;
+                }
+
+                let prev_ctrl = guard.ctrl(new_i);
+                guard.set_ctrl(new_i, h2(hash));
+                if prev_ctrl == EMPTY {
+                    guard.set_ctrl(i, EMPTY);
+                    ptr::copy_nonoverlapping(guard.bucket_ptr(i), guard.bucket_ptr(new_i), 1);
+                    continue 'outer;
+                } else {
+                    debug_assert_eq!(prev_ctrl, DELETED);
+                    ptr::swap_nonoverlapping(guard.bucket_ptr(i), guard.bucket_ptr(new_i), 1);
+                    continue 'inner;
+                }
+            }
+        }
+
+        guard.growth_left = Self::CAPACITY - guard.items;
+        mem::forget(guard);
+    }
+}
+
+impl<K, V, const N: usize, S> Drop for ArrayHashMap<K, V, N, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn drop(&mut self) {
+        unsafe {
+            self.drop_elements();
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, const N: usize, S: Clone> Clone for ArrayHashMap<K, V, N, S> {
+    fn clone(&self) -> Self {
+        let mut new = Self::with_hasher(self.hash_builder.clone());
+        unsafe {
+            // Only mark a bucket as full once its element has been cloned, so
+            // that `new` drops exactly the cloned elements if a clone panics.
+            for i in 0..N {
+                let ctrl = self.ctrl(i);
+                if is_full(ctrl) {
+                    let (k, v) = self.bucket(i);
+                    new.bucket_ptr(i).write((k.clone(), v.clone()));
+                    new.set_ctrl(i, ctrl);
+                    new.items += 1;
+                }
+            }
+        }
+        // Copy the DELETED markers as well to keep probe sequences intact.
+        new.ctrl = self.ctrl;
+        new.growth_left = self.growth_left;
+        new
+    }
+}
+
+impl<K, V, const N: usize, S> Debug for ArrayHashMap<K, V, N, S>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, const N: usize, S: Default> Default for ArrayHashMap<K, V, N, S> {
+    /// Creates an empty `ArrayHashMap<K, V, N, S>`, with the `Default` value
+    /// for the hasher.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(Default::default())
+    }
+}
+
+impl<K, V, const N: usize, S> PartialEq for ArrayHashMap<K, V, N, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        if self.len() != other.len() {
+            return false;
+        }
+
+        self.iter()
+            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+    }
+}
+
+impl<K, V, const N: usize, S> Eq for ArrayHashMap<K, V, N, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, Q, V, const N: usize, S> core::ops::Index<&Q> for ArrayHashMap<K, V, N, S>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `ArrayHashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<'a, K, V, const N: usize, S> IntoIterator for &'a ArrayHashMap<K, V, N, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, const N: usize, S> IntoIterator for &'a mut ArrayHashMap<K, V, N, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test_array_map {
+    use super::ArrayHashMap;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!(ArrayHashMap::<u32, u32, 1>::new().capacity(), 0);
+        assert_eq!(ArrayHashMap::<u32, u32, 2>::new().capacity(), 1);
+        assert_eq!(ArrayHashMap::<u32, u32, 8>::new().capacity(), 7);
+        assert_eq!(ArrayHashMap::<u32, u32, 32>::new().capacity(), 28);
+    }
+
+    #[test]
+    fn test_insert_until_full() {
+        fn check<const N: usize>() {
+            let mut map = ArrayHashMap::<u32, u32, N>::new();
+            let cap = map.capacity() as u32;
+            for i in 0..cap {
+                assert_eq!(map.insert(i, i * 2), Ok(None));
+            }
+            assert!(map.is_full());
+            assert_eq!(map.insert(cap, 0), Err((cap, 0)));
+            for i in 0..cap {
+                assert_eq!(map.get(&i), Some(&(i * 2)));
+            }
+            assert_eq!(map.get(&cap), None);
+            assert_eq!(map.iter().count(), cap as usize);
+        }
+        check::<1>();
+        check::<4>();
+        check::<8>();
+        check::<16>();
+        check::<128>();
+    }
+
+    #[test]
+    fn test_remove_reinsert_churn() {
+        // Removing and inserting different keys forever must keep working
+        // even though erasing may leave DELETED buckets behind.
+        let mut map = ArrayHashMap::<u64, u64, 32>::new();
+        let cap = map.capacity() as u64;
+        for i in 0..cap {
+            map.insert(i, i).unwrap();
+        }
+        for i in cap..cap * 50 {
+            assert_eq!(map.remove(&(i - cap)), Some(i - cap));
+            assert_eq!(map.insert(i, i), Ok(None));
+            assert_eq!(map.len(), cap as usize);
+        }
+        let mut keys: Vec<u64> = map.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (cap * 49..cap * 50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_retain_and_clear() {
+        let mut map = ArrayHashMap::<i32, i32, 64>::new();
+        for i in 0..50 {
+            map.insert(i, i * 10).unwrap();
+        }
+        map.retain(|&k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        assert_eq!(map.len(), 17);
+        for (k, v) in &map {
+            assert_eq!(k % 3, 0);
+            assert_eq!(*v, k * 10 + 1);
+        }
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.get(&0), None);
+    }
+
+    #[test]
+    fn test_drops() {
+        static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Clone)]
+        struct Droppable;
+        impl Drop for Droppable {
+            fn drop(&mut self) {
+                DROPS.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        {
+            let mut map = ArrayHashMap::<i32, Droppable, 16>::new();
+            for i in 0..10 {
+                assert!(map.insert(i, Droppable).is_ok());
+            }
+            drop(map.remove(&0));
+            assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+            map.retain(|&k, _| k < 5);
+            assert_eq!(DROPS.load(Ordering::SeqCst), 6);
+            let cloned = map.clone();
+            assert_eq!(cloned.len(), 4);
+            drop(cloned);
+            assert_eq!(DROPS.load(Ordering::SeqCst), 10);
+        }
+        assert_eq!(DROPS.load(Ordering::SeqCst), 14);
+    }
+
+    #[test]
+    fn test_clone_eq() {
+        let mut map = ArrayHashMap::<i32, i32, 16>::new();
+        for i in 0..12 {
+            map.insert(i, i).unwrap();
+        }
+        for i in 0..6 {
+            map.remove(&i);
+        }
+        let cloned = map.clone();
+        assert_eq!(map, cloned);
+        for i in 6..12 {
+            assert_eq!(cloned[&i], i);
+        }
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let mut map = ArrayHashMap::<(), (), 4>::new();
+        assert_eq!(map.insert((), ()), Ok(None));
+        assert_eq!(map.insert((), ()), Ok(Some(())));
+        assert_eq!(map.len(), 1);
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,463 @@
+//! Group ids for columns in the Arrow memory layout.
+//!
+//! Hash aggregations and joins over columnar data start by mapping every row
+//! of a column to a dense group id: the first distinct value gets 0, the next
+//! one 1, and so on. [`PrimitiveGroups`] and [`BinaryGroups`] do this a batch
+//! at a time, hashing the rows of a batch together with
+//! [`HashBatch::hash_batch`] and prefetching the control bytes they probe
+//! before inserting any of them.
+//!
+//! The methods take the buffers of an array as laid out by the [Arrow]
+//! specification rather than the array types of a particular Arrow crate:
+//! the values of a primitive array, the offsets and value bytes of a binary
+//! or string array, and an optional validity bitmap. With `arrow-rs`, these
+//! are `PrimitiveArray::values`, `GenericByteArray::value_offsets` and
+//! `GenericByteArray::value_data`, and the buffer and offset of the array's
+//! `NullBuffer`. Null rows all belong to a single group.
+//!
+//! [Arrow]: https://arrow.apache.org/docs/format/Columnar.html
+//! [`HashBatch::hash_batch`]: crate::hash_map::HashBatch::hash_batch
+//!
+//! # Examples
+//!
+//! ```
+//! use hashbrown::arrow::{BinaryGroups, PrimitiveGroups, Validity};
+//!
+//! let mut groups = PrimitiveGroups::new();
+//! let mut ids = Vec::new();
+//! // The third row is null.
+//! let validity = [0b1111_1011];
+//! groups.push_batch(&[7_i64, 3, 0, 7, 3], Some(Validity::new(&validity, 0)), &mut ids);
+//! assert_eq!(ids, [0, 1, 2, 0, 1]);
+//! assert_eq!(groups.null_group(), Some(2));
+//!
+//! let mut groups = BinaryGroups::new();
+//! let mut ids = Vec::new();
+//! groups.push_batch(&[0_i32, 3, 6, 9], b"fooBARfoo", None, &mut ids);
+//! assert_eq!(ids, [0, 1, 0]);
+//! assert_eq!(groups.group(1), Some(&b"BAR"[..]));
+//! ```
+
+use crate::batch::BATCH;
+use crate::hash_map::{DefaultHashBuilder, HashBatch};
+use crate::map::{equivalent_key, make_hash, make_hasher};
+use crate::raw::RawTable;
+use crate::HashMap;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+
+/// A validity bitmap in the Arrow layout: row `i` is valid if bit
+/// `offset + i` of `bitmap` is set, counting from the least significant bit
+/// of the first byte.
+#[derive(Clone, Copy, Debug)]
+pub struct Validity<'a> {
+    bitmap: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Validity<'a> {
+    /// Creates a validity bitmap whose first row is at bit `offset`.
+    pub fn new(bitmap: &'a [u8], offset: usize) -> Self {
+        Self { bitmap, offset }
+    }
+
+    /// Returns whether row `row` is valid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bitmap is too short to have a bit for the row.
+    #[inline]
+    pub fn is_valid(&self, row: usize) -> bool {
+        let bit = self.offset + row;
+        self.bitmap[bit / 8] & (1 << (bit % 8)) != 0
+    }
+}
+
+/// An offset of the Arrow binary and string layouts, `i32` or `i64`.
+///
+/// This trait is sealed and cannot be implemented outside this crate.
+pub trait Offset: Copy + private::Sealed {
+    /// Converts the offset to an index into the value bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offset is negative.
+    fn to_usize(self) -> usize;
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for i32 {}
+    impl Sealed for i64 {}
+}
+
+impl Offset for i32 {
+    #[inline]
+    fn to_usize(self) -> usize {
+        usize::try_from(self).expect("negative offset")
+    }
+}
+
+impl Offset for i64 {
+    #[inline]
+    fn to_usize(self) -> usize {
+        usize::try_from(self).expect("offset out of range")
+    }
+}
+
+/// Assigns dense group ids to the values of primitive columns.
+///
+/// Every distinct value gets the next unused id when it is first seen, and
+/// keeps it across batches. See the [module documentation](self).
+pub struct PrimitiveGroups<T, S = DefaultHashBuilder> {
+    map: HashMap<T, usize, S>,
+    null_group: Option<usize>,
+    len: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<T> PrimitiveGroups<T> {
+    /// Creates an empty `PrimitiveGroups` with the default hasher.
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<T> Default for PrimitiveGroups<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S> PrimitiveGroups<T, S> {
+    /// Creates an empty `PrimitiveGroups` which hashes values with
+    /// `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_hasher(hash_builder),
+            null_group: None,
+            len: 0,
+        }
+    }
+
+    /// Returns the number of groups, including the null group.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no row has been grouped yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the id of the group of null rows, if a null row has been
+    /// grouped.
+    pub fn null_group(&self) -> Option<usize> {
+        self.null_group
+    }
+
+    /// Returns an iterator over the values of the groups and their ids, in
+    /// arbitrary order. The null group is not included.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, usize)> + '_ {
+        self.map.iter().map(|(value, &id)| (value, id))
+    }
+}
+
+impl<T, S> PrimitiveGroups<T, S>
+where
+    T: Copy + Eq + Hash,
+    S: HashBatch<T>,
+{
+    /// Appends the group id of every row of `values` to `group_ids`,
+    /// creating a group for each value not seen before.
+    ///
+    /// If `validity` is given, rows whose bit is unset are null and all get
+    /// the id of the null group, whatever their value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `validity` is too short to have a bit for every row.
+    pub fn push_batch(
+        &mut self,
+        values: &[T],
+        validity: Option<Validity<'_>>,
+        group_ids: &mut Vec<usize>,
+    ) {
+        group_ids.reserve(values.len());
+        let mut hashes = [0; BATCH];
+        for (chunk, values) in values.chunks(BATCH).enumerate() {
+            let hashes = &mut hashes[..values.len()];
+            self.map.hash_builder.hash_batch(values, hashes);
+            for &hash in hashes.iter() {
+                self.map.table.prefetch(hash);
+            }
+            for (i, (&value, &hash)) in values.iter().zip(hashes.iter()).enumerate() {
+                let id = match validity {
+                    Some(validity) if !validity.is_valid(chunk * BATCH + i) => self.null_id(),
+                    _ => self.value_id(hash, value),
+                };
+                group_ids.push(id);
+            }
+        }
+    }
+
+    fn null_id(&mut self) -> usize {
+        let len = &mut self.len;
+        *self.null_group.get_or_insert_with(|| {
+            *len += 1;
+            *len - 1
+        })
+    }
+
+    fn value_id(&mut self, hash: u64, value: T) -> usize {
+        if let Some(bucket) = self.map.table.find(hash, equivalent_key(&value)) {
+            // SAFETY: The bucket was just found in the table.
+            return unsafe { bucket.as_ref().1 };
+        }
+        let id = self.len;
+        self.map.table.insert(
+            hash,
+            (value, id),
+            make_hasher::<T, usize, S>(&self.map.hash_builder),
+        );
+        self.len += 1;
+        id
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for PrimitiveGroups<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PrimitiveGroups")
+            .field("groups", &self.map)
+            .field("null_group", &self.null_group)
+            .finish()
+    }
+}
+
+/// Assigns dense group ids to the values of binary and string columns.
+///
+/// The bytes of every distinct value are copied once into a buffer owned by
+/// the groups, and each entry of the table holds only the hash and id of a
+/// group, so growing the table never hashes a value again. See the
+/// [module documentation](self).
+pub struct BinaryGroups<S = DefaultHashBuilder> {
+    hash_builder: S,
+    // The hash and id of every group except the null group.
+    table: RawTable<(u64, usize)>,
+    // The bytes of group `id` are `bytes[offsets[id]..offsets[id + 1]]`. The
+    // null group has no bytes.
+    offsets: Vec<usize>,
+    bytes: Vec<u8>,
+    null_group: Option<usize>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl BinaryGroups {
+    /// Creates an empty `BinaryGroups` with the default hasher.
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl Default for BinaryGroups {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> BinaryGroups<S> {
+    /// Creates an empty `BinaryGroups` which hashes values with
+    /// `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::new(),
+            offsets: alloc::vec![0],
+            bytes: Vec::new(),
+            null_group: None,
+        }
+    }
+
+    /// Returns the number of groups, including the null group.
+    pub fn len(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Returns `true` if no row has been grouped yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the id of the group of null rows, if a null row has been
+    /// grouped.
+    pub fn null_group(&self) -> Option<usize> {
+        self.null_group
+    }
+
+    /// Returns the bytes of the group with the given id, or `None` if there
+    /// is no such group or it is the null group.
+    pub fn group(&self, id: usize) -> Option<&[u8]> {
+        if id >= self.len() || Some(id) == self.null_group {
+            return None;
+        }
+        Some(&self.bytes[self.offsets[id]..self.offsets[id + 1]])
+    }
+}
+
+impl<S: BuildHasher> BinaryGroups<S> {
+    /// Appends the group id of every row of a binary column to `group_ids`,
+    /// creating a group for each value not seen before.
+    ///
+    /// Row `i` holds the bytes `values[offsets[i]..offsets[i + 1]]`, so
+    /// `offsets` has one more entry than there are rows. If `validity` is
+    /// given, rows whose bit is unset are null and all get the id of the null
+    /// group, whatever their bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an offset is negative or out of bounds of `values`, if the
+    /// offsets decrease, or if `validity` is too short to have a bit for
+    /// every row.
+    pub fn push_batch<O: Offset>(
+        &mut self,
+        offsets: &[O],
+        values: &[u8],
+        validity: Option<Validity<'_>>,
+        group_ids: &mut Vec<usize>,
+    ) {
+        let rows = offsets.len().saturating_sub(1);
+        group_ids.reserve(rows);
+        let mut hashes = [0; BATCH];
+        for start in (0..rows).step_by(BATCH) {
+            let end = usize::min(start + BATCH, rows);
+            let row = |i: usize| &values[offsets[i].to_usize()..offsets[i + 1].to_usize()];
+
+            let hashes = &mut hashes[..end - start];
+            for (i, hash) in (start..end).zip(hashes.iter_mut()) {
+                *hash = make_hash::<[u8], S>(&self.hash_builder, row(i));
+                self.table.prefetch(*hash);
+            }
+            for (i, &hash) in (start..end).zip(hashes.iter()) {
+                let id = match validity {
+                    Some(validity) if !validity.is_valid(i) => self.null_id(),
+                    _ => self.value_id(hash, row(i)),
+                };
+                group_ids.push(id);
+            }
+        }
+    }
+
+    fn null_id(&mut self) -> usize {
+        if let Some(id) = self.null_group {
+            return id;
+        }
+        let id = self.len();
+        self.offsets.push(self.bytes.len());
+        self.null_group = Some(id);
+        id
+    }
+
+    fn value_id(&mut self, hash: u64, value: &[u8]) -> usize {
+        let (offsets, bytes) = (&self.offsets, &self.bytes);
+        let found = self.table.find(hash, |&(group_hash, id)| {
+            group_hash == hash && &bytes[offsets[id]..offsets[id + 1]] == value
+        });
+        if let Some(bucket) = found {
+            // SAFETY: The bucket was just found in the table.
+            return unsafe { bucket.as_ref().1 };
+        }
+        let id = self.len();
+        self.table.insert(hash, (hash, id), |&(hash, _)| hash);
+        self.bytes.extend_from_slice(value);
+        self.offsets.push(self.bytes.len());
+        id
+    }
+}
+
+impl<S> fmt::Debug for BinaryGroups<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BinaryGroups")
+            .field("len", &self.len())
+            .field("null_group", &self.null_group)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test_arrow {
+    use super::{BinaryGroups, PrimitiveGroups, Validity};
+    use crate::hash_map::IntegerHashBuilder;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_primitive_groups() {
+        let mut groups = PrimitiveGroups::with_hasher(IntegerHashBuilder::default());
+        let mut ids = Vec::new();
+        let values: Vec<u32> = (0..100).map(|i| i % 7).collect();
+        groups.push_batch(&values, None, &mut ids);
+        assert_eq!(groups.len(), 7);
+        assert!(ids.iter().zip(&values).all(|(&id, &v)| id == v as usize));
+
+        // Ids are kept across batches, and nulls share one group.
+        let validity = [0b0101_0101; 4];
+        let mut more = Vec::new();
+        groups.push_batch(
+            &[6, 7, 6, 7, 8, 9, 8, 9],
+            Some(Validity::new(&validity, 1)),
+            &mut more,
+        );
+        assert_eq!(more, [7, 8, 7, 8, 7, 9, 7, 9]);
+        assert_eq!(groups.null_group(), Some(7));
+        assert_eq!(groups.len(), 10);
+
+        let mut seen: Vec<_> = groups.iter().map(|(&v, id)| (v, id)).collect();
+        seen.sort_unstable();
+        assert_eq!(seen.last(), Some(&(9, 9)));
+        assert_eq!(seen.len(), 9);
+    }
+
+    #[test]
+    fn test_binary_groups() {
+        let mut groups = BinaryGroups::new();
+        let mut ids = Vec::new();
+        let words: Vec<String> = (0..100_u32).map(|i| (i % 13).to_string()).collect();
+        let mut offsets = std::vec![0_i64];
+        let mut values = Vec::new();
+        for word in &words {
+            values.extend_from_slice(word.as_bytes());
+            offsets.push(values.len() as i64);
+        }
+        groups.push_batch(&offsets, &values, None, &mut ids);
+        assert_eq!(groups.len(), 13);
+        for (word, &id) in words.iter().zip(&ids) {
+            assert_eq!(groups.group(id), Some(word.as_bytes()));
+        }
+
+        // Empty values are a group of their own, distinct from nulls.
+        let mut more = Vec::new();
+        groups.push_batch(
+            &[0_i32, 0, 0, 1],
+            b"7",
+            Some(Validity::new(&[0b101], 0)),
+            &mut more,
+        );
+        assert_eq!(more, [13, 14, ids[7]]);
+        assert_eq!(groups.group(13), Some(&[][..]));
+        assert_eq!(groups.null_group(), Some(14));
+        assert_eq!(groups.group(14), None);
+        assert_eq!(groups.group(15), None);
+
+        groups.push_batch::<i32>(&[], &[], None, &mut more);
+        assert_eq!(more.len(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_binary_groups_negative_offset() {
+        BinaryGroups::new().push_batch(&[-1_i32, 0], b"", None, &mut Vec::new());
+    }
+}
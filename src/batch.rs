@@ -0,0 +1,490 @@
+use crate::hasher::{
+    hash_integer, hash_integer32, widen_hash32, IntegerHash32Builder, IntegerHashBuilder,
+};
+use crate::map::{equivalent_key, make_hash, make_hasher, Entry, HashMap};
+use crate::raw::Allocator;
+use crate::set::HashSet;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+/// Number of keys which the bulk methods of `HashMap` hash at once.
+pub(crate) const BATCH: usize = 16;
+
+/// A [`BuildHasher`] which can hash many keys at once.
+///
+/// Hashing a slice of fixed-width keys in one call lets the hasher process
+/// several keys at a time, for example with SIMD instructions, instead of
+/// building a [`Hasher`] for every key. The bulk methods of [`HashMap`], like
+/// [`get_batch`] and [`extend_batched`], hash their keys through this trait.
+///
+/// The provided method hashes one key after the other, so implementing the
+/// trait with no methods is always correct. Implementations which override it
+/// must produce exactly the same hashes as the [`BuildHasher`] would, since
+/// the other methods of the map hash keys one at a time.
+///
+/// [`Hasher`]: core::hash::Hasher
+/// [`get_batch`]: HashMap::get_batch
+/// [`extend_batched`]: HashMap::extend_batched
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::{HashBatch, IntegerHashBuilder};
+/// use std::hash::{BuildHasher, Hash, Hasher};
+///
+/// let hash_builder = IntegerHashBuilder::default();
+/// let mut hashes = [0; 3];
+/// hash_builder.hash_batch(&[1u64, 2, 3], &mut hashes);
+///
+/// let mut hasher = hash_builder.build_hasher();
+/// 2u64.hash(&mut hasher);
+/// assert_eq!(hashes[1], hasher.finish());
+/// ```
+pub trait HashBatch<K: Hash>: BuildHasher + Sized {
+    /// Writes the hash of every key of `keys` to the same position of
+    /// `hashes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `hashes` have different lengths.
+    fn hash_batch(&self, keys: &[K], hashes: &mut [u64]) {
+        assert_eq!(keys.len(), hashes.len(), "one hash is needed per key");
+        for (hash, key) in hashes.iter_mut().zip(keys) {
+            *hash = make_hash::<K, Self>(self, key);
+        }
+    }
+}
+
+macro_rules! impl_integer_hash_batch {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl HashBatch<$t> for IntegerHashBuilder {
+                #[inline]
+                fn hash_batch(&self, keys: &[$t], hashes: &mut [u64]) {
+                    assert_eq!(keys.len(), hashes.len(), "one hash is needed per key");
+                    // A straight loop without any hasher state, which the
+                    // compiler can unroll and vectorize.
+                    for (hash, &key) in hashes.iter_mut().zip(keys) {
+                        *hash = hash_integer(key as $u as u64);
+                    }
+                }
+            }
+        )*
+    };
+}
+
+// Signed integers are hashed as their unsigned counterparts, so they must not
+// be sign-extended.
+impl_integer_hash_batch!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize
+);
+
+impl<K: Hash> HashBatch<K> for crate::hash_map::FixedState {}
+
+impl HashBatch<u128> for IntegerHashBuilder {}
+impl HashBatch<i128> for IntegerHashBuilder {}
+
+macro_rules! impl_integer_hash32_batch {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl HashBatch<$t> for IntegerHash32Builder {
+                #[inline]
+                fn hash_batch(&self, keys: &[$t], hashes: &mut [u64]) {
+                    assert_eq!(keys.len(), hashes.len(), "one hash is needed per key");
+                    for (hash, &key) in hashes.iter_mut().zip(keys) {
+                        *hash = widen_hash32(hash_integer32(key as $u as u32));
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_hash32_batch!(u8 => u8, u16 => u16, u32 => u32, i8 => u8, i16 => u16, i32 => u32);
+
+// Wider keys take more than one mixing step, like in the hasher itself.
+impl HashBatch<u64> for IntegerHash32Builder {}
+impl HashBatch<i64> for IntegerHash32Builder {}
+impl HashBatch<usize> for IntegerHash32Builder {}
+impl HashBatch<isize> for IntegerHash32Builder {}
+
+#[cfg(all(
+    feature = "ahash",
+    not(feature = "siphash"),
+    not(feature = "deterministic")
+))]
+impl<K: Hash> HashBatch<K> for crate::hash_map::DefaultHashBuilder {}
+
+#[cfg(feature = "ahash")]
+impl<K: Hash> HashBatch<K> for crate::hash_map::RandomState {}
+
+#[cfg(feature = "siphash")]
+impl<K: Hash> HashBatch<K> for std::collections::hash_map::RandomState {}
+
+impl<K, V, S, A> HashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: HashBatch<K>,
+    A: Allocator + Clone,
+{
+    /// Looks up many keys at once, returning the value of each key, or
+    /// `None` for keys which are not in the map.
+    ///
+    /// The keys are hashed in batches with [`HashBatch::hash_batch`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<u64, &str, IntegerHashBuilder> = [(1, "a"), (2, "b")].into_iter().collect();
+    /// assert_eq!(map.get_batch(&[2, 3, 1]), [Some(&"b"), None, Some(&"a")]);
+    /// ```
+    pub fn get_batch(&self, keys: &[K]) -> Vec<Option<&V>> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut hashes = [0; BATCH];
+        for keys in keys.chunks(BATCH) {
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(keys, hashes);
+            values.extend(
+                keys.iter()
+                    .zip(hashes.iter())
+                    .map(|(k, &hash)| self.table.get(hash, equivalent_key(k)).map(|(_, v)| v)),
+            );
+        }
+        values
+    }
+
+    /// Checks whether each of many keys is in the map, returning one `bool`
+    /// per key.
+    ///
+    /// The keys are hashed in batches with [`HashBatch::hash_batch`], and the
+    /// control bytes each key of a batch probes first are prefetched before
+    /// any key of the batch is looked up, so that the lookups don't wait for
+    /// each other's memory loads.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<u64, &str, IntegerHashBuilder> = [(1, "a"), (2, "b")].into_iter().collect();
+    /// assert_eq!(map.contains_many(&[2, 3, 1]), [true, false, true]);
+    /// ```
+    pub fn contains_many(&self, keys: &[K]) -> Vec<bool> {
+        let mut found = Vec::with_capacity(keys.len());
+        let mut hashes = [0; BATCH];
+        for keys in keys.chunks(BATCH) {
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(keys, hashes);
+            for &hash in hashes.iter() {
+                self.table.prefetch(hash);
+            }
+            found.extend(
+                keys.iter()
+                    .zip(hashes.iter())
+                    .map(|(k, &hash)| self.table.find(hash, equivalent_key(k)).is_some()),
+            );
+        }
+        found
+    }
+
+    /// Inserts all key-value pairs of the iterator, replacing the values of
+    /// keys which are already in the map, like [`extend`].
+    ///
+    /// The keys are hashed in batches with [`HashBatch::hash_batch`].
+    ///
+    /// [`extend`]: HashMap::extend
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<u32, u32, IntegerHashBuilder> = HashMap::default();
+    /// map.insert(1, 0);
+    /// map.extend_batched((0..100).map(|i| (i, i * 2)));
+    /// assert_eq!(map.len(), 100);
+    /// assert_eq!(map[&1], 2);
+    /// ```
+    pub fn extend_batched<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        // Reserve like `extend` does.
+        let reserve = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            (iter.size_hint().0 + 1) / 2
+        };
+        self.reserve(reserve);
+
+        let mut keys = Vec::with_capacity(BATCH);
+        let mut values = Vec::with_capacity(BATCH);
+        let mut hashes = [0; BATCH];
+        loop {
+            for (k, v) in iter.by_ref().take(BATCH) {
+                keys.push(k);
+                values.push(v);
+            }
+            if keys.is_empty() {
+                return;
+            }
+
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(&keys, hashes);
+            for ((k, v), &hash) in keys.drain(..).zip(values.drain(..)).zip(hashes.iter()) {
+                if let Some((_, item)) = self.table.get_mut(hash, equivalent_key(&k)) {
+                    *item = v;
+                } else {
+                    self.table
+                        .insert(hash, (k, v), make_hasher::<K, V, S>(&self.hash_builder));
+                }
+            }
+        }
+    }
+
+    /// Inserts every key of `keys` which is not in the map yet, with the
+    /// value returned by `default` for it, and returns how many keys were
+    /// inserted. Keys which are already in the map keep their values.
+    ///
+    /// The keys are hashed in batches with [`HashBatch::hash_batch`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut counts: HashMap<u64, usize, IntegerHashBuilder> = HashMap::default();
+    /// counts.insert(1, 5);
+    /// assert_eq!(counts.get_or_insert_batch(&[1, 2, 3, 2], |_| 0), 2);
+    /// assert_eq!(counts[&1], 5);
+    /// assert_eq!(counts[&2], 0);
+    /// ```
+    pub fn get_or_insert_batch<F>(&mut self, keys: &[K], mut default: F) -> usize
+    where
+        K: Clone,
+        F: FnMut(&K) -> V,
+    {
+        let len = self.len();
+        let mut hashes = [0; BATCH];
+        for keys in keys.chunks(BATCH) {
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(keys, hashes);
+            for (k, &hash) in keys.iter().zip(hashes.iter()) {
+                if self.table.find(hash, equivalent_key(k)).is_none() {
+                    let value = default(k);
+                    self.table.insert(
+                        hash,
+                        (k.clone(), value),
+                        make_hasher::<K, V, S>(&self.hash_builder),
+                    );
+                }
+            }
+        }
+        self.len() - len
+    }
+
+    /// Calls `f` with the [`Entry`] of every key of `keys`, in order.
+    ///
+    /// The keys are hashed in batches with [`HashBatch::hash_batch`], and the
+    /// control bytes each key of a batch probes first are prefetched before
+    /// the entry of any key of the batch is resolved, so that an update-heavy
+    /// loop does not wait for one cache miss per key. Room for a whole batch
+    /// is reserved up front, so inserting into vacant entries never resizes
+    /// the table in the middle of a batch.
+    ///
+    /// Only one entry can borrow the map at a time, so instead of returning
+    /// the entries this method hands them to `f` one after the other.
+    ///
+    /// [`Entry`]: crate::hash_map::Entry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut counts: HashMap<u64, usize, IntegerHashBuilder> = HashMap::default();
+    /// counts.entry_many([3, 1, 3, 3, 2], |entry| *entry.or_insert(0) += 1);
+    /// assert_eq!(counts[&3], 3);
+    /// assert_eq!(counts[&1], 1);
+    /// assert_eq!(counts.len(), 3);
+    /// ```
+    pub fn entry_many<I, F>(&mut self, keys: I, mut f: F)
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut(Entry<'_, K, V, S, A>),
+    {
+        let mut keys = keys.into_iter();
+        let mut batch = Vec::with_capacity(BATCH);
+        let mut hashes = [0; BATCH];
+        loop {
+            batch.extend(keys.by_ref().take(BATCH));
+            if batch.is_empty() {
+                return;
+            }
+
+            let hashes = &mut hashes[..batch.len()];
+            self.hash_builder.hash_batch(&batch, hashes);
+            self.reserve(batch.len());
+            for &hash in hashes.iter() {
+                self.table.prefetch(hash);
+            }
+            for (k, &hash) in batch.drain(..).zip(hashes.iter()) {
+                f(self.entry_hashed(hash, k));
+            }
+        }
+    }
+}
+
+impl<T, S, A> HashSet<T, S, A>
+where
+    T: Eq + Hash,
+    S: HashBatch<T>,
+    A: Allocator + Clone,
+{
+    /// Checks whether each of many values is in the set, returning one `bool`
+    /// per value.
+    ///
+    /// This works like [`HashMap::contains_many`], hashing the values in
+    /// batches and prefetching the control bytes they probe before looking
+    /// them up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::HashSet;
+    ///
+    /// let set: HashSet<u32, IntegerHashBuilder> = [1, 2, 3].into_iter().collect();
+    /// assert_eq!(set.contains_many(&[0, 1, 4, 3]), [false, true, false, true]);
+    /// ```
+    pub fn contains_many(&self, values: &[T]) -> Vec<bool> {
+        self.map.contains_many(values)
+    }
+}
+
+#[cfg(test)]
+mod test_batch {
+    use super::HashBatch;
+    use crate::hash_map::{IntegerHash32Builder, IntegerHashBuilder};
+    use crate::map::make_hash;
+    use crate::{HashMap, HashSet};
+    use std::vec::Vec;
+
+    fn check_hashes<K: core::hash::Hash>(keys: &[K])
+    where
+        IntegerHashBuilder: HashBatch<K>,
+        IntegerHash32Builder: HashBatch<K>,
+    {
+        check_hashes_with(&IntegerHashBuilder::default(), keys);
+        check_hashes_with(&IntegerHash32Builder::default(), keys);
+    }
+
+    fn check_hashes_with<S: HashBatch<K>, K: core::hash::Hash>(hash_builder: &S, keys: &[K]) {
+        let mut hashes = std::vec![0; keys.len()];
+        hash_builder.hash_batch(keys, &mut hashes);
+        for (key, &hash) in keys.iter().zip(&hashes) {
+            assert_eq!(hash, make_hash::<K, _>(hash_builder, key));
+        }
+    }
+
+    #[test]
+    fn test_hash_batch_matches_hasher() {
+        check_hashes(&[0u8, 1, 255]);
+        check_hashes(&[0u32, 1, u32::MAX]);
+        check_hashes(&[0u64, 1, u64::MAX]);
+        check_hashes(&[0usize, 1, usize::MAX]);
+        check_hashes(&[0i8, -1, i8::MIN]);
+        check_hashes(&[0i32, -1, i32::MIN, i32::MAX]);
+        check_hashes(&[0i64, -1, i64::MIN]);
+        check_hashes(&[0isize, -1, isize::MIN]);
+        check_hashes_with(&IntegerHashBuilder::default(), &[0u128, 1, u128::MAX]);
+        check_hashes_with(&IntegerHashBuilder::default(), &[0i128, -1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hash_batch_length_mismatch() {
+        IntegerHashBuilder::default().hash_batch(&[1u64, 2], &mut [0; 1]);
+    }
+
+    #[test]
+    fn test_get_batch() {
+        let map: HashMap<u64, u64, IntegerHashBuilder> = (0..100).map(|i| (i * 2, i)).collect();
+        let keys: Vec<u64> = (0..50).collect();
+        let values = map.get_batch(&keys);
+        assert_eq!(values.len(), 50);
+        for (&key, value) in keys.iter().zip(values) {
+            assert_eq!(value, map.get(&key));
+        }
+        assert!(map.get_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_contains_many() {
+        let map: HashMap<u64, u64, IntegerHashBuilder> = (0..100).map(|i| (i * 3, i)).collect();
+        let keys: Vec<u64> = (0..300).collect();
+        let found = map.contains_many(&keys);
+        assert_eq!(found.len(), 300);
+        for (&key, found) in keys.iter().zip(found) {
+            assert_eq!(found, key % 3 == 0);
+        }
+        assert!(map.contains_many(&[]).is_empty());
+
+        let empty: HashSet<u64, IntegerHashBuilder> = HashSet::default();
+        assert_eq!(empty.contains_many(&[1, 2]), [false, false]);
+        let set: HashSet<u64, IntegerHashBuilder> = keys.iter().copied().collect();
+        assert!(set.contains_many(&keys).into_iter().all(|found| found));
+    }
+
+    #[test]
+    fn test_extend_batched() {
+        let mut map: HashMap<i32, i32, IntegerHashBuilder> = HashMap::default();
+        map.insert(-5, 0);
+        map.extend_batched((-40..40).map(|i| (i, i * 3)));
+        assert_eq!(map.len(), 80);
+        for i in -40..40 {
+            assert_eq!(map[&i], i * 3);
+        }
+        // Duplicate keys within a batch keep the last value.
+        map.extend_batched(vec![(1, 10), (1, 11)]);
+        assert_eq!(map[&1], 11);
+    }
+
+    #[test]
+    fn test_get_or_insert_batch() {
+        let mut map: HashMap<u32, u32, IntegerHashBuilder> = HashMap::default();
+        map.insert(3, 100);
+        let keys: Vec<u32> = (0..40).chain(0..40).collect();
+        assert_eq!(map.get_or_insert_batch(&keys, |&k| k + 1), 39);
+        assert_eq!(map.len(), 40);
+        assert_eq!(map[&3], 100);
+        assert_eq!(map[&39], 40);
+        assert_eq!(map.get_or_insert_batch(&keys, |_| unreachable!()), 0);
+    }
+
+    #[test]
+    fn test_entry_many() {
+        use crate::hash_map::Entry;
+
+        let mut map: HashMap<u32, u32, IntegerHashBuilder> = HashMap::default();
+        map.insert(5, 100);
+        let mut vacant = 0;
+        map.entry_many((0..50).chain(0..50), |entry| match entry {
+            Entry::Occupied(mut entry) => *entry.get_mut() += 1,
+            Entry::Vacant(entry) => {
+                vacant += 1;
+                entry.insert(0);
+            }
+        });
+        assert_eq!(vacant, 49);
+        assert_eq!(map.len(), 50);
+        assert_eq!(map[&5], 102);
+        assert_eq!(map[&49], 1);
+    }
+}
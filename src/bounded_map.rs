@@ -0,0 +1,454 @@
+use crate::map::{equivalent_key, make_hash, make_hasher, make_insert_hash, HashMap};
+use crate::raw::{Allocator, Global, RawTable};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::mem;
+use core::ops::Deref;
+
+/// The longest probe sequence, in groups, that an element may need before the
+/// table grows, unless configured otherwise.
+const DEFAULT_MAX_PROBE_LENGTH: usize = 8;
+
+/// A [`HashMap`] which grows its table early to keep every probe sequence
+/// short.
+///
+/// The map keeps track of the longest probe sequence, in groups of buckets,
+/// that a lookup of any of its keys takes. When an insertion takes it over
+/// [`max_probe_length`], the table is rebuilt with twice as many buckets,
+/// which spreads the keys over more groups, even if the load factor would
+/// still allow more insertions. This bounds the work done by lookups of
+/// present keys at the cost of memory. Unlike [`AdaptiveHashMap`], the hasher
+/// is kept, so this works with any [`BuildHasher`].
+///
+/// Even with a good hasher, a table close to its maximum load factor has a
+/// few probe sequences of 10 to 20 groups, so a low limit mostly makes the
+/// table grow before it is full.
+///
+/// Growing does not shorten the probe sequences of keys whose hashes are
+/// equal. If doubling the table does not shorten the longest probe sequence,
+/// the limit is doubled instead so that the table does not keep growing; the
+/// current limit is always at least [`longest_probe_length`].
+///
+/// The map dereferences to a [`HashMap`] for all read-only operations.
+/// Modifications have to go through the `BoundedHashMap` itself, so that
+/// every insertion is checked.
+///
+/// [`max_probe_length`]: BoundedHashMap::max_probe_length
+/// [`longest_probe_length`]: BoundedHashMap::longest_probe_length
+/// [`AdaptiveHashMap`]: crate::AdaptiveHashMap
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::BoundedHashMap;
+///
+/// let mut map = BoundedHashMap::new();
+/// map.set_max_probe_length(2);
+/// for i in 0..1000 {
+///     map.insert(i, i * 2);
+/// }
+/// assert!(map.longest_probe_length() <= 2);
+///
+/// // Read-only operations go through to the underlying `HashMap`.
+/// assert_eq!(map.get(&21), Some(&42));
+/// assert_eq!(map.len(), 1000);
+/// ```
+pub struct BoundedHashMap<K, V, S = crate::DefaultHashBuilder, A: Allocator + Clone = Global> {
+    map: HashMap<K, V, S, A>,
+    max_probe_length: usize,
+    longest_probe_length: usize,
+    forced_grows: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> BoundedHashMap<K, V> {
+    /// Creates an empty `BoundedHashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::BoundedHashMap;
+    ///
+    /// let map: BoundedHashMap<i32, i32> = BoundedHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+
+    /// Creates an empty `BoundedHashMap` which can hold at least `capacity`
+    /// elements without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, S> BoundedHashMap<K, V, S> {
+    /// Creates an empty `BoundedHashMap` which uses `hash_builder` to hash
+    /// keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from(HashMap::with_hasher(hash_builder))
+    }
+
+    /// Creates an empty `BoundedHashMap` which uses `hash_builder` to hash
+    /// keys and can hold at least `capacity` elements without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::from(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> BoundedHashMap<K, V, S, A> {
+    /// Returns the longest probe sequence, in groups of buckets, which an
+    /// element may need before the table grows.
+    pub fn max_probe_length(&self) -> usize {
+        self.max_probe_length
+    }
+
+    /// Sets the longest probe sequence, in groups of buckets, which an
+    /// element may need before the table grows.
+    ///
+    /// The new limit applies from the next insertion. Lower limits make
+    /// lookups cheaper but may leave the table much emptier than its load
+    /// factor allows.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_probe_length` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::BoundedHashMap;
+    ///
+    /// let mut map: BoundedHashMap<i32, i32> = BoundedHashMap::new();
+    /// map.set_max_probe_length(1);
+    /// assert_eq!(map.max_probe_length(), 1);
+    /// ```
+    pub fn set_max_probe_length(&mut self, max_probe_length: usize) {
+        assert!(max_probe_length > 0, "the probe length limit must not be 0");
+        self.max_probe_length = max_probe_length;
+    }
+
+    /// Returns the longest probe sequence, in groups of buckets, which a
+    /// lookup of any key inserted since the table was last rebuilt takes.
+    ///
+    /// Removals do not lower this value, so it may overestimate the longest
+    /// probe sequence of the keys still in the map.
+    pub fn longest_probe_length(&self) -> usize {
+        self.longest_probe_length
+    }
+
+    /// Returns how many times the table grew because of a long probe
+    /// sequence rather than because it was full.
+    pub fn forced_grows(&self) -> usize {
+        self.forced_grows
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.longest_probe_length = 0;
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// In other words, remove all pairs `(k, v)` such that `f(&k, &mut v)`
+    /// returns `false`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.map.retain(f);
+    }
+
+    /// Returns the underlying `HashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_inner(self) -> HashMap<K, V, S, A> {
+        self.map
+    }
+}
+
+impl<K, V, S, A> BoundedHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned, and
+    /// the table grows if placing the key took too long a probe sequence.
+    ///
+    /// If the map did have this key present, the value is updated, and the
+    /// old value is returned. The key is not updated, though.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::BoundedHashMap;
+    ///
+    /// let mut map = BoundedHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_insert_hash::<K, S>(&self.map.hash_builder, &k);
+        if let Some((_, item)) = self.map.table.get_mut(hash, equivalent_key(&k)) {
+            return Some(mem::replace(item, v));
+        }
+
+        let buckets = self.map.table.buckets();
+        let bucket =
+            self.map
+                .table
+                .insert(hash, (k, v), make_hasher::<K, V, S>(&self.map.hash_builder));
+        if self.map.table.buckets() != buckets {
+            // The table grew because it was full, which moved every element.
+            self.longest_probe_length = self.map.stats().max_probe_length;
+        } else {
+            let index = unsafe { self.map.table.bucket_index(&bucket) };
+            let probe_length = self.map.table.probe_length(hash, index);
+            self.longest_probe_length = self.longest_probe_length.max(probe_length);
+        }
+        if self.longest_probe_length > self.max_probe_length {
+            self.grow();
+        }
+        None
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get_mut(k)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.remove(k)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.remove_entry(k)
+    }
+
+    /// Moves all entries into new tables with twice as many buckets until the
+    /// longest probe sequence fits within the limit, or the limit is raised
+    /// when growing stops shortening it.
+    #[cold]
+    #[inline(never)]
+    fn grow(&mut self) {
+        loop {
+            self.forced_grows += 1;
+            let previous = self.longest_probe_length;
+            self.rebuild(self.map.table.capacity().max(1) * 2);
+            if self.longest_probe_length <= self.max_probe_length {
+                return;
+            }
+            if self.longest_probe_length >= previous {
+                while self.longest_probe_length > self.max_probe_length {
+                    self.max_probe_length = self.max_probe_length.saturating_mul(2);
+                }
+                return;
+            }
+        }
+    }
+
+    /// Moves all entries into a new table which can hold `capacity` elements
+    /// and measures its longest probe sequence.
+    fn rebuild(&mut self, capacity: usize) {
+        let alloc = self.map.table.allocator().clone();
+        let old = mem::replace(
+            &mut self.map.table,
+            RawTable::with_capacity_in(capacity, alloc),
+        );
+        let mut longest = 0;
+        for item in old {
+            let hash = make_hash::<K, S>(&self.map.hash_builder, &item.0);
+            // SAFETY: The new table has room for all entries of the old one.
+            unsafe {
+                let bucket = self.map.table.insert_no_grow(hash, item);
+                let index = self.map.table.bucket_index(&bucket);
+                longest = longest.max(self.map.table.probe_length(hash, index));
+            }
+        }
+        self.longest_probe_length = longest;
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> From<HashMap<K, V, S, A>> for BoundedHashMap<K, V, S, A> {
+    /// Wraps a `HashMap`. Its existing entries are not checked until the
+    /// table is next rebuilt.
+    fn from(map: HashMap<K, V, S, A>) -> Self {
+        Self {
+            map,
+            max_probe_length: DEFAULT_MAX_PROBE_LENGTH,
+            longest_probe_length: 0,
+            forced_grows: 0,
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Deref for BoundedHashMap<K, V, S, A> {
+    type Target = HashMap<K, V, S, A>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+}
+
+impl<K, V, S, A> Extend<(K, V)> for BoundedHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for BoundedHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::with_hasher(S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<K, V, S: Default> Default for BoundedHashMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for BoundedHashMap<K, V, S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            max_probe_length: self.max_probe_length,
+            longest_probe_length: self.longest_probe_length,
+            forced_grows: self.forced_grows,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for BoundedHashMap<K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_bounded {
+    use super::BoundedHashMap;
+    use crate::hasher::IntegerHashBuilder;
+    use core::hash::{BuildHasherDefault, Hasher};
+
+    /// A hasher which leaves the low bits of small keys empty, so that they
+    /// share home buckets in a small table but not in a large one.
+    #[derive(Default)]
+    struct ShiftHasher(u64);
+
+    impl Hasher for ShiftHasher {
+        fn finish(&self) -> u64 {
+            self.0 << 8
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = self.0 << 8 | u64::from(byte);
+            }
+        }
+
+        fn write_u32(&mut self, i: u32) {
+            self.0 = u64::from(i);
+        }
+    }
+
+    #[test]
+    fn test_grow_on_long_probes() {
+        // Start with enough buckets for the keys to have several home
+        // buckets, so that every doubling shortens the probe sequences.
+        let mut map: BoundedHashMap<u32, u32, BuildHasherDefault<ShiftHasher>> =
+            BoundedHashMap::with_capacity_and_hasher(1000, Default::default());
+        map.set_max_probe_length(2);
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i), None);
+            assert!(map.longest_probe_length() <= map.max_probe_length());
+        }
+        assert!(map.forced_grows() > 0);
+        assert_eq!(map.max_probe_length(), 2);
+        assert!(map.stats().max_probe_length <= 2);
+        assert!((0..1000).all(|i| map[&i] == i));
+        assert_eq!(map.remove(&7), Some(7));
+        assert_eq!(map.len(), 999);
+    }
+
+    #[test]
+    fn test_limit_grows_with_equal_hashes() {
+        #[derive(Default)]
+        struct ConstantHasher;
+        impl Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+        let mut map: BoundedHashMap<u32, u32, BuildHasherDefault<ConstantHasher>> =
+            BoundedHashMap::default();
+        map.set_max_probe_length(1);
+        map.extend((0..200).map(|i| (i, i)));
+        assert!(map.max_probe_length() > 1);
+        // Each forced grow at least doubles the limit, so there are only a
+        // few of them.
+        assert!(map.forced_grows() < 10);
+        assert!(map.longest_probe_length() <= map.max_probe_length());
+        assert!((0..200).all(|i| map[&i] == i));
+    }
+
+    #[test]
+    fn test_good_hasher() {
+        let map: BoundedHashMap<u64, u64, IntegerHashBuilder> =
+            (0..100_000).map(|i| (i, i)).collect();
+        assert_eq!(map.max_probe_length(), 8);
+        assert!(map.stats().max_probe_length <= 8);
+        // At most one doubling more than the load factor needs.
+        assert!(map.table.buckets() <= 262_144);
+        assert_eq!(map.len(), 100_000);
+    }
+}
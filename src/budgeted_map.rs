@@ -0,0 +1,361 @@
+use crate::map::{equivalent_key, make_hash, make_hasher, DefaultHashBuilder, HashMap, Iter};
+use crate::raw::Bucket;
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+/// A hash map which keeps its approximate memory usage under a budget by
+/// evicting entries.
+///
+/// The memory usage of the map is the size of its table's allocation plus
+/// the sum of the sizes of its entries, as reported by the `size_of` function
+/// given when the map is created. `size_of` should count the heap memory an
+/// entry owns, such as the buffer of a `String`, since the key and value
+/// themselves are stored in the table.
+///
+/// Whenever an insertion takes the memory usage over the budget, other
+/// entries are removed, in arbitrary order, until it fits again, and passed
+/// to the `on_evict` callback. The entry just inserted is never evicted, so
+/// an entry which does not fit in the budget on its own is still stored.
+/// Removing entries does not shrink the table, so the budget should leave
+/// room for the table needed by the expected number of entries.
+///
+/// Values cannot be modified in place, since that could change their size
+/// behind the map's back; use [`update`] instead.
+///
+/// [`update`]: BudgetedMap::update
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::BudgetedMap;
+///
+/// let mut evicted = Vec::new();
+/// let mut cache = BudgetedMap::new(
+///     4096,
+///     |_: &u32, v: &String| v.capacity(),
+///     |k, _| evicted.push(k),
+/// );
+/// for i in 0..100 {
+///     cache.insert(i, "x".repeat(100));
+/// }
+/// assert!(cache.memory_usage() <= 4096);
+/// assert!(cache.contains_key(&99));
+/// drop(cache);
+/// assert!(!evicted.is_empty());
+/// ```
+pub struct BudgetedMap<K, V, F, E, S = DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+    size_of: F,
+    on_evict: E,
+    budget: usize,
+    // The sum of the sizes of the entries reported by `size_of`.
+    entry_bytes: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V, F, E> BudgetedMap<K, V, F, E, DefaultHashBuilder>
+where
+    F: Fn(&K, &V) -> usize,
+    E: FnMut(K, V),
+{
+    /// Creates an empty `BudgetedMap` which keeps its memory usage under
+    /// `budget` bytes, measuring entries with `size_of` and passing the
+    /// entries it evicts to `on_evict`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new(budget: usize, size_of: F, on_evict: E) -> Self {
+        Self::with_hasher(budget, size_of, on_evict, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, F, E, S> BudgetedMap<K, V, F, E, S>
+where
+    F: Fn(&K, &V) -> usize,
+    E: FnMut(K, V),
+{
+    /// Creates an empty `BudgetedMap` which keeps its memory usage under
+    /// `budget` bytes and uses the given hash builder to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(budget: usize, size_of: F, on_evict: E, hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_hasher(hash_builder),
+            size_of,
+            on_evict,
+            budget,
+            entry_bytes: 0,
+        }
+    }
+
+    /// Returns a reference to the underlying map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn as_map(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Returns the underlying map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_map(self) -> HashMap<K, V, S> {
+        self.map
+    }
+
+    /// Returns the memory budget of the map, in bytes.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Returns the approximate memory usage of the map, in bytes: the size of
+    /// the table's allocation plus the sizes of the entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::BudgetedMap;
+    ///
+    /// let mut map = BudgetedMap::new(1 << 20, |_: &i32, v: &Vec<u8>| v.len(), |_, _| {});
+    /// assert_eq!(map.memory_usage(), 0);
+    /// map.insert(1, vec![0; 1000]);
+    /// assert!(map.memory_usage() > 1000);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn memory_usage(&self) -> usize {
+        self.map.table.allocation_info().1.size() + self.entry_bytes
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Clears the map, removing all key-value pairs without passing them to
+    /// the eviction callback. Keeps the allocated memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.entry_bytes = 0;
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter()
+    }
+
+    /// Evicts entries other than the one in `keep` while the memory usage is
+    /// over the budget.
+    fn enforce_budget(&mut self, keep: Option<&Bucket<(K, V)>>) {
+        if self.memory_usage() <= self.budget {
+            return;
+        }
+        // SAFETY: Each bucket is removed right after it is yielded, and the
+        // iterator does not outlive the table.
+        unsafe {
+            for bucket in self.map.table.iter() {
+                if keep.map_or(false, |keep| keep.as_ptr() == bucket.as_ptr()) {
+                    continue;
+                }
+                let (k, v) = self.map.table.remove(bucket);
+                self.entry_bytes -= (self.size_of)(&k, &v);
+                (self.on_evict)(k, v);
+                if self.memory_usage() <= self.budget {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, F, E, S> BudgetedMap<K, V, F, E, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    F: Fn(&K, &V) -> usize,
+    E: FnMut(K, V),
+{
+    /// Changes the memory budget of the map, evicting entries if the map uses
+    /// more than `budget` bytes.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.enforce_budget(None);
+    }
+
+    /// Inserts a key-value pair into the map, then evicts other entries while
+    /// the memory usage is over the budget.
+    ///
+    /// If the map already had this key present, its value is replaced and
+    /// the old value is returned instead of being passed to the eviction
+    /// callback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::BudgetedMap;
+    ///
+    /// let mut evicted = 0;
+    /// let mut map = BudgetedMap::new(1000, |_: &i32, v: &Vec<u8>| v.len(), |_, _| evicted += 1);
+    /// assert_eq!(map.insert(1, vec![0; 10]), None);
+    /// assert_eq!(map.insert(1, vec![1; 10]), Some(vec![0; 10]));
+    ///
+    /// // The new entry does not fit next to the first one.
+    /// map.insert(2, vec![0; 990]);
+    /// assert!(!map.contains_key(&1));
+    /// drop(map);
+    /// assert_eq!(evicted, 1);
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_hash::<K, S>(&self.map.hash_builder, &k);
+        let (bucket, old) = match self.map.table.find(hash, equivalent_key(&k)) {
+            Some(bucket) => {
+                // SAFETY: The bucket was just found in the table.
+                let (key, value) = unsafe { bucket.as_mut() };
+                self.entry_bytes -= (self.size_of)(key, value);
+                self.entry_bytes += (self.size_of)(key, &v);
+                let old = mem::replace(value, v);
+                (bucket, Some(old))
+            }
+            None => {
+                self.entry_bytes += (self.size_of)(&k, &v);
+                let bucket = self.map.table.insert(
+                    hash,
+                    (k, v),
+                    make_hasher::<K, V, S>(&self.map.hash_builder),
+                );
+                (bucket, None)
+            }
+        };
+        self.enforce_budget(Some(&bucket));
+        old
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get(k)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.contains_key(k)
+    }
+
+    /// Calls `f` on the value of the key, if it is present, and accounts for
+    /// the change of its size, evicting other entries if the map goes over
+    /// its budget. Returns whether the key was present.
+    pub fn update<Q, U>(&mut self, k: &Q, f: U) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        U: FnOnce(&mut V),
+    {
+        let hash = make_hash::<Q, S>(&self.map.hash_builder, k);
+        let bucket = match self.map.table.find(hash, equivalent_key(k)) {
+            Some(bucket) => bucket,
+            None => return false,
+        };
+        // SAFETY: The bucket was just found in the table.
+        let (key, value) = unsafe { bucket.as_mut() };
+        self.entry_bytes -= (self.size_of)(key, value);
+        f(value);
+        self.entry_bytes += (self.size_of)(key, value);
+        self.enforce_budget(Some(&bucket));
+        true
+    }
+
+    /// Removes a key from the map, returning its value if the key was
+    /// present. The value is not passed to the eviction callback.
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let (k, v) = self.map.remove_entry(k)?;
+        self.entry_bytes -= (self.size_of)(&k, &v);
+        Some(v)
+    }
+}
+
+impl<K: Debug, V: Debug, F, E, S> Debug for BudgetedMap<K, V, F, E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_budgeted_map {
+    use super::BudgetedMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_stays_under_budget() {
+        let mut evicted = Vec::new();
+        let mut map = BudgetedMap::new(
+            10_000,
+            |_: &u32, v: &Vec<u8>| v.len(),
+            |k, v: Vec<u8>| evicted.push((k, v.len())),
+        );
+        for i in 0..1000 {
+            map.insert(i, std::vec![0; 50]);
+            assert!(map.memory_usage() <= 10_000);
+            assert!(map.contains_key(&i));
+        }
+        let len = map.len();
+        // Only the entries can be evicted, the table keeps its size.
+        let budget = map.memory_usage() - len * 50 + 1_000;
+        map.set_budget(budget);
+        assert!(map.memory_usage() <= budget);
+        assert_eq!(map.len(), 20);
+        let len = map.len();
+        drop(map);
+        assert_eq!(evicted.len() + len, 1000);
+        assert!(evicted.iter().all(|&(_, n)| n == 50));
+    }
+
+    #[test]
+    fn test_update_and_remove() {
+        let mut evictions = 0;
+        let mut map =
+            BudgetedMap::new(2_000, |_: &u32, v: &Vec<u8>| v.len(), |_, _| evictions += 1);
+        map.insert(1, std::vec![0; 100]);
+        map.insert(2, std::vec![0; 100]);
+        let usage = map.memory_usage();
+        assert!(map.update(&1, |v| v.extend_from_slice(&[0; 100])));
+        assert_eq!(map.memory_usage(), usage + 100);
+        assert!(!map.update(&3, |_| unreachable!()));
+        // Growing the value of 2 past the budget evicts 1 but keeps 2.
+        assert!(map.update(&2, |v| v.resize(1_900, 0)));
+        assert!(!map.contains_key(&1));
+        assert_eq!(map.get(&2).map(Vec::len), Some(1_900));
+        assert_eq!(map.remove(&2).map(|v| v.len()), Some(1_900));
+        assert_eq!(map.memory_usage(), usage - 200);
+        assert_eq!(map.remove(&2), None);
+        drop(map);
+        assert_eq!(evictions, 1);
+    }
+
+    #[test]
+    fn test_oversized_entry_is_kept() {
+        let mut map = BudgetedMap::new(100, |_: &u32, v: &Vec<u8>| v.len(), |_, _| {});
+        map.insert(1, std::vec![0; 10]);
+        map.insert(2, std::vec![0; 1000]);
+        assert_eq!(map.len(), 1);
+        assert!(map.contains_key(&2));
+        assert!(map.memory_usage() > map.budget());
+        let table_bytes = map.memory_usage() - 1000;
+        map.clear();
+        assert_eq!(map.memory_usage(), table_bytes);
+    }
+}
@@ -0,0 +1,527 @@
+use crate::hash_map::DefaultHashBuilder;
+use crate::map::HashMap;
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// A hash map whose readers never wait for its writer.
+///
+/// Readers look entries up in an immutable snapshot of the map, protected by
+/// epoch-based reclamation: pinning a snapshot is lock-free and never blocks,
+/// however busy the writer is. A writer works on a private copy of the map and
+/// atomically publishes it as the new snapshot when done, after which the old
+/// snapshot is freed as soon as the last reader which could see it has
+/// unpinned it.
+///
+/// Writers are serialized by a lock, and every write copies the whole map, so
+/// this type suits read-mostly data such as routing or configuration tables
+/// which are updated in batches. Use [`ShardedHashMap`] for write-heavy
+/// workloads.
+///
+/// [`ShardedHashMap`]: super::ShardedHashMap
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::concurrent::EpochMap;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let routes = Arc::new(EpochMap::new());
+/// routes.update(|map| {
+///     map.insert("/", 1);
+///     map.insert("/about", 2);
+/// });
+///
+/// let reader = {
+///     let routes = Arc::clone(&routes);
+///     thread::spawn(move || routes.get("/about"))
+/// };
+///
+/// routes.update(|map| {
+///     map.insert("/contact", 3);
+/// });
+///
+/// assert_eq!(reader.join().unwrap(), Some(2));
+/// assert_eq!(routes.pin().len(), 3);
+/// ```
+pub struct EpochMap<K, V, S = DefaultHashBuilder> {
+    current: Atomic<HashMap<K, V, S>>,
+    writer: Mutex<()>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> EpochMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `EpochMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    /// let map: EpochMap<&str, i32> = EpochMap::new();
+    /// assert!(map.pin().is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::from_map(HashMap::default())
+    }
+}
+
+impl<K, V, S> EpochMap<K, V, S> {
+    /// Creates an empty `EpochMap` which will use the given hash builder to
+    /// hash keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    /// use hashbrown::hash_map::DefaultHashBuilder;
+    ///
+    /// let s = DefaultHashBuilder::default();
+    /// let map: EpochMap<i32, i32> = EpochMap::with_hasher(s);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from_map(HashMap::with_hasher(hash_builder))
+    }
+
+    /// Creates an `EpochMap` whose first snapshot is the given map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a")].into();
+    /// let map = EpochMap::from_map(map);
+    /// assert_eq!(map.get(&1), Some("a"));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn from_map(map: HashMap<K, V, S>) -> Self {
+        Self {
+            current: Atomic::new(map),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Pins the current snapshot of the map so that it can be read.
+    ///
+    /// The snapshot is not affected by writes published after it was pinned,
+    /// and keeps its memory alive until it is dropped: avoid holding on to it
+    /// for long periods of time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// map.update(|m| {
+    ///     m.insert(1, "a");
+    /// });
+    ///
+    /// let snapshot = map.pin();
+    /// map.update(|m| {
+    ///     m.insert(2, "b");
+    /// });
+    /// assert_eq!(snapshot.len(), 1);
+    /// assert_eq!(map.pin().len(), 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn pin(&self) -> Snapshot<'_, K, V, S> {
+        let guard = epoch::pin();
+        let map = self.current.load(Ordering::Acquire, &guard).as_raw();
+        Snapshot {
+            _guard: guard,
+            map,
+            marker: PhantomData,
+        }
+    }
+
+    /// Consumes the `EpochMap`, returning its current snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// map.update(|m| {
+    ///     m.insert(1, "a");
+    /// });
+    /// assert_eq!(map.into_inner().len(), 1);
+    /// ```
+    pub fn into_inner(mut self) -> HashMap<K, V, S> {
+        let current = mem::replace(&mut self.current, Atomic::null());
+        // SAFETY: We own the map, so no reader can hold a snapshot anymore.
+        unsafe { *current.into_owned().into_box() }
+    }
+}
+
+impl<K, V, S> EpochMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns a clone of the value corresponding to the key in the current
+    /// snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// map.update(|m| {
+    ///     m.insert(1, "a");
+    /// });
+    /// assert_eq!(map.get(&1), Some("a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        V: Clone,
+    {
+        self.pin().get(k).cloned()
+    }
+
+    /// Returns `true` if the current snapshot contains a value for the
+    /// specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// map.update(|m| {
+    ///     m.insert(1, "a");
+    /// });
+    /// assert!(map.contains_key(&1));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.pin().contains_key(k)
+    }
+}
+
+impl<K, V, S> EpochMap<K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    /// Locks the map for writing and returns a copy of its current snapshot
+    /// which can be modified freely.
+    ///
+    /// The modifications are published atomically when the returned guard is
+    /// dropped, unless the thread is panicking or the guard was [discarded].
+    /// Only one writer can exist at a time, but readers are never blocked.
+    ///
+    /// [discarded]: WriteGuard::discard
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// let mut writer = map.write();
+    /// writer.insert(1, "a");
+    /// writer.insert(2, "b");
+    ///
+    /// // Not visible until the guard is dropped.
+    /// assert!(map.pin().is_empty());
+    /// drop(writer);
+    /// assert_eq!(map.pin().len(), 2);
+    /// ```
+    pub fn write(&self) -> WriteGuard<'_, K, V, S> {
+        let lock = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let next = (*self.pin()).clone();
+        WriteGuard {
+            owner: self,
+            next: Some(next),
+            _lock: lock,
+        }
+    }
+
+    /// Applies `f` to a copy of the current snapshot and publishes the result.
+    ///
+    /// This is a shorthand for [`write`] when all modifications can be done
+    /// in one closure.
+    ///
+    /// [`write`]: EpochMap::write
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// let len = map.update(|m| {
+    ///     m.insert(1, "a");
+    ///     m.len()
+    /// });
+    /// assert_eq!(len, 1);
+    /// ```
+    pub fn update<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut HashMap<K, V, S>) -> R,
+    {
+        f(&mut self.write())
+    }
+
+    fn publish(&self, next: HashMap<K, V, S>) {
+        let guard = epoch::pin();
+        let old = self
+            .current
+            .swap(Owned::new(next), Ordering::AcqRel, &guard);
+        // SAFETY: `old` is no longer reachable through `self.current`, so it
+        // can be freed once every reader pinned before the swap is gone. The
+        // bounds on `K`, `V` and `S` allow this to happen on any thread and
+        // after `self` is dropped.
+        unsafe {
+            guard.defer_destroy(old);
+        }
+    }
+}
+
+impl<K, V, S> Drop for EpochMap<K, V, S> {
+    fn drop(&mut self) {
+        // SAFETY: We have exclusive access, so there are no readers left.
+        unsafe {
+            let current = self.current.load(Ordering::Relaxed, epoch::unprotected());
+            if !current.is_null() {
+                drop(current.into_owned());
+            }
+        }
+    }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> Default for EpochMap<K, V, DefaultHashBuilder> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for EpochMap<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        Self::from_map(map)
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for EpochMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (*self.pin()).fmt(f)
+    }
+}
+
+/// A pinned, immutable snapshot of an [`EpochMap`].
+///
+/// This `struct` is created by [`EpochMap::pin`] and dereferences to the
+/// [`HashMap`] it protects.
+pub struct Snapshot<'a, K, V, S> {
+    _guard: Guard,
+    map: *const HashMap<K, V, S>,
+    marker: PhantomData<&'a HashMap<K, V, S>>,
+}
+
+impl<K, V, S> Deref for Snapshot<'_, K, V, S> {
+    type Target = HashMap<K, V, S>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn deref(&self) -> &HashMap<K, V, S> {
+        // SAFETY: The pointer was loaded while `self.guard` was pinned, and
+        // snapshots are never null nor modified after being published.
+        unsafe { &*self.map }
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for Snapshot<'_, K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Exclusive write access to a copy of an [`EpochMap`].
+///
+/// This `struct` is created by [`EpochMap::write`] and dereferences to the
+/// [`HashMap`] being modified, which is published when the guard is dropped.
+pub struct WriteGuard<'a, K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    owner: &'a EpochMap<K, V, S>,
+    next: Option<HashMap<K, V, S>>,
+    _lock: MutexGuard<'a, ()>,
+}
+
+impl<K, V, S> WriteGuard<'_, K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    /// Releases the write lock without publishing any of the modifications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::EpochMap;
+    ///
+    /// let map = EpochMap::new();
+    /// let mut writer = map.write();
+    /// writer.insert(1, "a");
+    /// writer.discard();
+    /// assert!(map.pin().is_empty());
+    /// ```
+    pub fn discard(mut self) {
+        self.next = None;
+    }
+}
+
+impl<K, V, S> Deref for WriteGuard<'_, K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    type Target = HashMap<K, V, S>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn deref(&self) -> &HashMap<K, V, S> {
+        // `next` is only taken in `drop` and `discard`.
+        self.next.as_ref().unwrap()
+    }
+}
+
+impl<K, V, S> DerefMut for WriteGuard<'_, K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn deref_mut(&mut self) -> &mut HashMap<K, V, S> {
+        self.next.as_mut().unwrap()
+    }
+}
+
+impl<K, V, S> Drop for WriteGuard<'_, K, V, S>
+where
+    K: Clone + Send + 'static,
+    V: Clone + Send + 'static,
+    S: Clone + Send + 'static,
+{
+    fn drop(&mut self) {
+        if let Some(next) = self.next.take() {
+            if !std::thread::panicking() {
+                self.owner.publish(next);
+            }
+        }
+    }
+}
+
+impl<K, V, S> Debug for WriteGuard<'_, K, V, S>
+where
+    K: Clone + Send + 'static + Debug,
+    V: Clone + Send + 'static + Debug,
+    S: Clone + Send + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_epoch {
+    use super::EpochMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_snapshot_isolation() {
+        let map = EpochMap::new();
+        map.update(|m| {
+            m.insert(1, 10);
+        });
+        let before = map.pin();
+        {
+            let mut writer = map.write();
+            writer.insert(2, 20);
+            *writer.get_mut(&1).unwrap() += 1;
+        }
+        assert_eq!(before.get(&1), Some(&10));
+        assert_eq!(before.len(), 1);
+        assert_eq!(map.get(&1), Some(11));
+        assert_eq!(map.pin().len(), 2);
+    }
+
+    #[test]
+    fn test_panicking_writer_does_not_publish() {
+        let map = EpochMap::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.update(|m| {
+                m.insert(1, 1);
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(map.pin().is_empty());
+        // The lock is usable again despite being poisoned.
+        map.update(|m| {
+            m.insert(2, 2);
+        });
+        assert_eq!(map.into_inner().len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_readers() {
+        let map = Arc::new(EpochMap::new());
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let map = Arc::clone(&map);
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    while !done.load(Ordering::Relaxed) {
+                        // Every published snapshot holds keys `0..len`.
+                        let snapshot = map.pin();
+                        let len = snapshot.len() as u32;
+                        assert!((0..len).all(|k| snapshot.get(&k) == Some(&k)));
+                    }
+                })
+            })
+            .collect();
+
+        for i in 0..200 {
+            map.update(|m| {
+                m.insert(i, i);
+            });
+        }
+        done.store(true, Ordering::Relaxed);
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(map.pin().len(), 200);
+    }
+}
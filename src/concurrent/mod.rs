@@ -0,0 +1,1082 @@
+//! A sharded hash map which can be shared between threads.
+//!
+//! [`ShardedHashMap`] splits its entries over a number of independent
+//! [`RawTable`]s, each protected by its own [`RwLock`]. Operations on keys
+//! which live in different shards never contend with each other, which makes
+//! it a drop-in improvement over wrapping a whole [`HashMap`] in a single
+//! `RwLock`.
+//!
+//! Since entries live behind locks, the map never hands out references which
+//! outlive a method call: lookups either clone the value or pass a reference
+//! to a closure.
+//!
+//! With the `epoch` feature, [`EpochMap`] offers reads which never wait for
+//! writers instead, at the cost of copying the map on every write.
+//!
+//! [`HashMap`]: crate::HashMap
+
+use crate::hash_map::DefaultHashBuilder;
+use crate::map::{equivalent_key, make_hash, make_hasher, make_insert_hash, shard_index};
+use crate::raw::{Allocator, Bucket, Global, RawIntoIter, RawTable};
+use crate::Equivalent;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[cfg(feature = "epoch")]
+mod epoch;
+#[cfg(feature = "epoch")]
+pub use self::epoch::{EpochMap, Snapshot, WriteGuard};
+
+/// A hash map split into independently locked shards.
+///
+/// The shard of a key is picked by the top bits of its hash multiplied by an
+/// odd constant, which depend on every bit of the hash, so that entries stay
+/// evenly spread across shards even with a hasher which only fills 32 bits,
+/// and within each shard too.
+///
+/// All methods take `&self`, so the map can be shared between threads, for
+/// example in an `Arc` or a `static`.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::concurrent::ShardedHashMap;
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let map = Arc::new(ShardedHashMap::new());
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|t| {
+///         let map = Arc::clone(&map);
+///         thread::spawn(move || {
+///             for i in 0..100 {
+///                 map.insert(t * 100 + i, i);
+///             }
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+///
+/// assert_eq!(map.len(), 400);
+/// assert_eq!(map.get(&205), Some(5));
+/// ```
+pub struct ShardedHashMap<K, V, S = DefaultHashBuilder, A: Allocator + Clone = Global> {
+    hash_builder: S,
+    shards: Box<[Shard<K, V, A>]>,
+    // Number of hash bits used to select a shard.
+    shard_bits: u32,
+}
+
+/// A single independently locked table of a [`ShardedHashMap`].
+type Shard<K, V, A> = RwLock<RawTable<(K, V), A>>;
+
+/// Returns the default number of shards: a few per available CPU so that
+/// threads rarely contend on the same lock.
+fn default_shard_amount() -> usize {
+    let parallelism = std::thread::available_parallelism().map_or(1, usize::from);
+    (parallelism * 4).next_power_of_two()
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> ShardedHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `ShardedHashMap` with a default number of shards.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    /// let map: ShardedHashMap<&str, i32> = ShardedHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `ShardedHashMap` with at least the given number of
+    /// shards. The number of shards is rounded up to a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    /// let map: ShardedHashMap<&str, i32> = ShardedHashMap::with_shard_amount(6);
+    /// assert_eq!(map.shard_amount(), 8);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_shard_amount(shards: usize) -> Self {
+        Self::with_shard_amount_and_hasher(shards, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ShardedHashMap<K, V, S> {
+    /// Creates an empty `ShardedHashMap` with a default number of shards which
+    /// will use the given hash builder to hash keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    /// use hashbrown::hash_map::DefaultHashBuilder;
+    ///
+    /// let s = DefaultHashBuilder::default();
+    /// let map = ShardedHashMap::with_hasher(s);
+    /// map.insert(1, 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_shard_amount_and_hasher(default_shard_amount(), hash_builder)
+    }
+
+    /// Creates an empty `ShardedHashMap` with at least the given number of
+    /// shards which will use the given hash builder to hash keys. The number
+    /// of shards is rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of shards, rounded up to a power of two, does not
+    /// fit in a `usize` or needs more than 32 hash bits to index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    /// use hashbrown::hash_map::DefaultHashBuilder;
+    ///
+    /// let s = DefaultHashBuilder::default();
+    /// let map = ShardedHashMap::with_shard_amount_and_hasher(16, s);
+    /// map.insert(1, 2);
+    /// assert_eq!(map.shard_amount(), 16);
+    /// ```
+    pub fn with_shard_amount_and_hasher(shards: usize, hash_builder: S) -> Self {
+        Self::with_shard_amount_and_hasher_in(shards, hash_builder, |_| Global)
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> ShardedHashMap<K, V, S, A> {
+    /// Creates an empty `ShardedHashMap` with at least the given number of
+    /// shards which will use the given hash builder to hash keys, and
+    /// `make_alloc(i)` to allocate the table of shard `i`. The number of
+    /// shards is rounded up to a power of two.
+    ///
+    /// Every shard is a separate table which only ever allocates through its
+    /// own allocator, so this can place each shard's memory close to the
+    /// threads which use it, for example on a particular NUMA node. Use
+    /// [`shard_of`](ShardedHashMap::shard_of) to find the shard of a key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of shards, rounded up to a power of two, does not
+    /// fit in a `usize` or needs more than 32 hash bits to index.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(feature = "huge-pages")]
+    /// # {
+    /// use hashbrown::concurrent::ShardedHashMap;
+    /// use hashbrown::hash_map::DefaultHashBuilder;
+    /// use hashbrown::huge_pages::HugePageAlloc;
+    ///
+    /// let s = DefaultHashBuilder::default();
+    /// let map = ShardedHashMap::with_shard_amount_and_hasher_in(4, s, |_| HugePageAlloc::new());
+    /// map.insert(1, 2);
+    /// assert_eq!(map.get(&1), Some(2));
+    /// # }
+    /// ```
+    pub fn with_shard_amount_and_hasher_in<F>(
+        shards: usize,
+        hash_builder: S,
+        mut make_alloc: F,
+    ) -> Self
+    where
+        F: FnMut(usize) -> A,
+    {
+        let shards = shards.max(1).next_power_of_two();
+        let shard_bits = shards.trailing_zeros();
+        assert!(shard_bits <= 32, "too many shards");
+        Self {
+            hash_builder,
+            shards: (0..shards)
+                .map(|i| RwLock::new(RawTable::new_in(make_alloc(i))))
+                .collect(),
+            shard_bits,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of shards of the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn shard_amount(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// Since other threads may modify the map concurrently, the result is only
+    /// a snapshot: the shards are counted one after the other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// assert_eq!(map.len(), 0);
+    /// map.insert(1, "a");
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// assert!(map.is_empty());
+    /// map.insert(1, "a");
+    /// assert!(!map.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| read(shard).is_empty())
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory of every shard for reuse.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// map.clear();
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn clear(&self) {
+        for shard in self.shards.iter() {
+            write(shard).clear();
+        }
+    }
+
+    /// Calls `f` on every key-value pair of the map, in arbitrary order.
+    ///
+    /// Each shard is read-locked while its entries are visited, so `f` must not
+    /// write to the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut sum = 0;
+    /// map.for_each(|_, v| sum += v);
+    /// assert_eq!(sum, 3);
+    /// ```
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        for shard in self.shards.iter() {
+            let table = read(shard);
+            // Here we tie the lifetime of the iterator to the lock guard.
+            unsafe {
+                for item in table.iter() {
+                    let (key, value) = item.as_ref();
+                    f(key, value);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on every key-value pair of the map, with mutable references
+    /// to the values, in arbitrary order.
+    ///
+    /// Each shard is write-locked while its entries are visited, so `f` must
+    /// not access the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// map.for_each_mut(|_, v| *v *= 10);
+    /// assert_eq!(map.get("b"), Some(20));
+    /// ```
+    pub fn for_each_mut<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        for shard in self.shards.iter() {
+            let table = write(shard);
+            // Here we tie the lifetime of the iterator to the lock guard.
+            unsafe {
+                for item in table.iter() {
+                    let (key, value) = item.as_mut();
+                    f(key, value);
+                }
+            }
+        }
+    }
+
+    /// Retains only the elements specified by the predicate.
+    ///
+    /// Each shard is write-locked while its entries are visited, so `f` must
+    /// not access the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// for i in 0..8 {
+    ///     map.insert(i, i * 10);
+    /// }
+    /// map.retain(|&k, _| k % 2 == 0);
+    /// assert_eq!(map.len(), 4);
+    /// ```
+    pub fn retain<F>(&self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for shard in self.shards.iter() {
+            let mut table = write(shard);
+            // Here we only use the iterator as a temporary, preventing use-after-free
+            unsafe {
+                for item in table.iter() {
+                    let (key, value) = item.as_mut();
+                    if !f(key, value) {
+                        table.erase(item);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn shard(&self, hash: u64) -> &Shard<K, V, A> {
+        let index = shard_index(hash, self.shard_bits);
+        // SAFETY: `index` is below `1 << shard_bits`, the number of shards.
+        unsafe { self.shards.get_unchecked(index) }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> ShardedHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns the index of the shard which holds `k`, between zero and
+    /// [`shard_amount`](ShardedHashMap::shard_amount).
+    ///
+    /// This depends only on the hash of the key, so threads which own a range
+    /// of shards can route keys to the owning thread without touching the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map: ShardedHashMap<u32, u32> = ShardedHashMap::with_shard_amount(8);
+    /// let shard = map.shard_of(&7);
+    /// assert!(shard < 8);
+    /// assert_eq!(map.shard_of(&7), shard);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn shard_of<Q>(&self, k: &Q) -> usize
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        shard_index(make_hash::<Q, S>(&self.hash_builder, k), self.shard_bits)
+    }
+
+    /// Returns a clone of the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some("a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        V: Clone,
+    {
+        self.read(k, |_, v| v.clone())
+    }
+
+    /// Calls `f` with the key-value pair corresponding to the key and returns
+    /// its result, or `None` if the key is not present.
+    ///
+    /// The shard of the key is read-locked while `f` runs, so `f` must not
+    /// write to the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, String::from("abc"));
+    /// assert_eq!(map.read(&1, |_, v| v.len()), Some(3));
+    /// assert_eq!(map.read(&2, |_, v| v.len()), None);
+    /// ```
+    #[inline]
+    pub fn read<Q, F, R>(&self, k: &Q, f: F) -> Option<R>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        F: FnOnce(&K, &V) -> R,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let table = read(self.shard(hash));
+        match table.get(hash, equivalent_key(k)) {
+            Some((key, value)) => Some(f(key, value)),
+            None => None,
+        }
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// assert!(map.contains_key(&1));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.read(k, |_, _| ()).is_some()
+    }
+
+    /// Calls `f` with a mutable reference to the value corresponding to the
+    /// key and returns its result, or `None` if the key is not present.
+    ///
+    /// The shard of the key is write-locked while `f` runs, so `f` must not
+    /// access the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert("a", 1);
+    /// assert_eq!(map.update("a", |v| { *v += 1; *v }), Some(2));
+    /// assert_eq!(map.update("b", |v| { *v += 1; *v }), None);
+    /// ```
+    #[inline]
+    pub fn update<Q, F, R>(&self, k: &Q, f: F) -> Option<R>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        F: FnOnce(&mut V) -> R,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let mut table = write(self.shard(hash));
+        match table.get_mut(hash, equivalent_key(k)) {
+            Some((_, value)) => Some(f(value)),
+            None => None,
+        }
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned. The key is not updated, though.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map.get(&37), Some("b"));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&self, k: K, v: V) -> Option<V> {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+        let mut table = write(self.shard(hash));
+        match table.get_mut(hash, equivalent_key(&k)) {
+            Some((_, item)) => Some(mem::replace(item, v)),
+            None => {
+                table.insert(hash, (k, v), make_hasher::<K, V, S>(&self.hash_builder));
+                None
+            }
+        }
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some("a"));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        // Avoid `Option::map` because it bloats LLVM IR.
+        match self.remove_entry(k) {
+            Some((_, v)) => Some(v),
+            None => None,
+        }
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove_entry(&1), Some((1, "a")));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry<Q>(&self, k: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        write(self.shard(hash)).remove_entry(hash, equivalent_key(k))
+    }
+
+    /// Calls `f` with the [`Entry`] of the given key and returns its result.
+    ///
+    /// The shard of the key is write-locked while `f` runs, so `f` must not
+    /// access the map or it may deadlock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let words = ShardedHashMap::new();
+    /// for word in ["a", "b", "a", "c", "a"] {
+    ///     words.entry(word, |entry| *entry.or_insert(0) += 1);
+    /// }
+    /// assert_eq!(words.get("a"), Some(3));
+    ///
+    /// let removed = words.entry("b", |entry| match entry {
+    ///     hashbrown::concurrent::Entry::Occupied(o) => Some(o.remove()),
+    ///     hashbrown::concurrent::Entry::Vacant(_) => None,
+    /// });
+    /// assert_eq!(removed, Some(1));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn entry<F, R>(&self, key: K, f: F) -> R
+    where
+        F: FnOnce(Entry<'_, K, V, S, A>) -> R,
+    {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &key);
+        let mut table = write(self.shard(hash));
+        let entry = match table.find(hash, equivalent_key(&key)) {
+            Some(elem) => Entry::Occupied(OccupiedEntry {
+                elem,
+                table: &mut table,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                hash,
+                key,
+                table: &mut table,
+                hash_builder: &self.hash_builder,
+            }),
+        };
+        f(entry)
+    }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> Default for ShardedHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `ShardedHashMap` with a default number of shards.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Debug for ShardedHashMap<K, V, S, A>
+where
+    K: Debug,
+    V: Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each(|k, v| {
+            map.entry(k, v);
+        });
+        map.finish()
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> IntoIterator for ShardedHashMap<K, V, S, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    /// Creates a consuming iterator, that is, one that moves each key-value
+    /// pair out of the map in arbitrary order. The map cannot be used after
+    /// calling this.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::concurrent::ShardedHashMap;
+    ///
+    /// let map = ShardedHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut vec: Vec<(&str, i32)> = map.into_iter().collect();
+    /// vec.sort_unstable();
+    /// assert_eq!(vec, [("a", 1), ("b", 2)]);
+    /// ```
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        let shards: Vec<_> = self.shards.into_vec();
+        IntoIter {
+            shards: shards.into_iter(),
+            current: None,
+        }
+    }
+}
+
+/// An owning iterator over the entries of a [`ShardedHashMap`].
+///
+/// This `struct` is created by the [`into_iter`] method on [`ShardedHashMap`]
+/// (provided by the `IntoIterator` trait).
+///
+/// [`into_iter`]: ShardedHashMap::into_iter
+pub struct IntoIter<K, V, A: Allocator + Clone = Global> {
+    shards: alloc::vec::IntoIter<Shard<K, V, A>>,
+    current: Option<RawIntoIter<(K, V), A>>,
+}
+
+impl<K, V, A: Allocator + Clone> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(ref mut current) = self.current {
+                if let Some(item) = current.next() {
+                    return Some(item);
+                }
+            }
+            let shard = self.shards.next()?;
+            let table = shard.into_inner().unwrap_or_else(PoisonError::into_inner);
+            self.current = Some(table.into_iter());
+        }
+    }
+}
+
+impl<K, V, A: Allocator + Clone> core::iter::FusedIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A: Allocator + Clone> Debug for IntoIter<K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoIter").finish_non_exhaustive()
+    }
+}
+
+/// A view into a single entry of a [`ShardedHashMap`], which may either be
+/// vacant or occupied.
+///
+/// This `enum` is passed to the closure given to [`ShardedHashMap::entry`].
+pub enum Entry<'a, K, V, S, A: Allocator + Clone = Global> {
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K, V, A>),
+
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K, V, S, A>),
+}
+
+/// A view into an occupied entry in a [`ShardedHashMap`].
+/// It is part of the [`Entry`] enum.
+pub struct OccupiedEntry<'a, K, V, A: Allocator + Clone = Global> {
+    elem: Bucket<(K, V)>,
+    table: &'a mut RawTable<(K, V), A>,
+}
+
+/// A view into a vacant entry in a [`ShardedHashMap`].
+/// It is part of the [`Entry`] enum.
+pub struct VacantEntry<'a, K, V, S, A: Allocator + Clone = Global> {
+    hash: u64,
+    key: K,
+    table: &'a mut RawTable<(K, V), A>,
+    hash_builder: &'a S,
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> Entry<'a, K, V, S, A> {
+    /// Returns a reference to this entry's key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn key(&self) -> &K {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key(),
+        }
+    }
+
+    /// Provides in-place mutable access to an occupied entry before any
+    /// potential inserts into the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> Entry<'a, K, V, S, A>
+where
+    K: Hash,
+    S: BuildHasher,
+{
+    /// Ensures a value is in the entry by inserting the default if empty, and
+    /// returns a mutable reference to the value in the entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the result of the default
+    /// function if empty, and returns a mutable reference to the value in the
+    /// entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting the default value if
+    /// empty, and returns a mutable reference to the value in the entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+}
+
+impl<'a, K, V, A: Allocator + Clone> OccupiedEntry<'a, K, V, A> {
+    /// Gets a reference to the key in the entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn key(&self) -> &K {
+        unsafe { &self.elem.as_ref().0 }
+    }
+
+    /// Gets a reference to the value in the entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get(&self) -> &V {
+        unsafe { &self.elem.as_ref().1 }
+    }
+
+    /// Gets a mutable reference to the value in the entry.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_mut(&mut self) -> &mut V {
+        unsafe { &mut self.elem.as_mut().1 }
+    }
+
+    /// Converts the entry into a mutable reference to the value in the entry
+    /// with a lifetime bound to the shard lock.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_mut(self) -> &'a mut V {
+        unsafe { &mut self.elem.as_mut().1 }
+    }
+
+    /// Sets the value of the entry, and returns the entry's old value.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Takes the value out of the entry, and returns it.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Takes the ownership of the key and value from the map.
+    ///
+    /// The key passed to [`ShardedHashMap::entry`] is dropped.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry(self) -> (K, V) {
+        unsafe { self.table.remove(self.elem) }
+    }
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> VacantEntry<'a, K, V, S, A> {
+    /// Gets a reference to the key that would be used when inserting a value
+    /// through the `VacantEntry`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take ownership of the key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Sets the value of the entry with the `VacantEntry`'s key, and returns a
+    /// mutable reference to it.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        let entry = self.table.insert_entry(
+            self.hash,
+            (self.key, value),
+            make_hasher::<K, V, S>(self.hash_builder),
+        );
+        &mut entry.1
+    }
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for Entry<'_, K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Entry::Vacant(ref v) => f.debug_tuple("Entry").field(v).finish(),
+            Entry::Occupied(ref o) => f.debug_tuple("Entry").field(o).finish(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, A: Allocator + Clone> Debug for OccupiedEntry<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<K: Debug, V, S, A: Allocator + Clone> Debug for VacantEntry<'_, K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntry").field(self.key()).finish()
+    }
+}
+
+/// Read-locks a shard. A panic while a shard was locked cannot leave its
+/// table in an inconsistent state, so lock poisoning is ignored.
+#[inline]
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Write-locks a shard, ignoring lock poisoning like [`read`].
+#[inline]
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test_concurrent {
+    use super::{Entry, ShardedHashMap};
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_shard_amount() {
+        assert_eq!(
+            ShardedHashMap::<u32, u32>::with_shard_amount(0).shard_amount(),
+            1
+        );
+        assert_eq!(
+            ShardedHashMap::<u32, u32>::with_shard_amount(1).shard_amount(),
+            1
+        );
+        assert_eq!(
+            ShardedHashMap::<u32, u32>::with_shard_amount(5).shard_amount(),
+            8
+        );
+        assert!(ShardedHashMap::<u32, u32>::new()
+            .shard_amount()
+            .is_power_of_two());
+    }
+
+    #[test]
+    fn test_shard_allocators() {
+        use crate::hash_map::DefaultHashBuilder;
+        use crate::map::equivalent_key;
+        use crate::raw::Global;
+
+        let mut created = Vec::new();
+        let map = ShardedHashMap::with_shard_amount_and_hasher_in(
+            6,
+            DefaultHashBuilder::default(),
+            |i| {
+                created.push(i);
+                Global
+            },
+        );
+        assert_eq!(created, [0, 1, 2, 3, 4, 5, 6, 7]);
+
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 0..1000 {
+            let shard = map.shard_of(&i);
+            let hash = crate::map::make_hash::<i32, _>(map.hasher(), &i);
+            let table = super::read(&map.shards[shard]);
+            assert!(table
+                .find(hash, equivalent_key::<i32, i32, i32>(&i))
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_single_shard() {
+        let map = ShardedHashMap::with_shard_amount(1);
+        for i in 0..100 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(i));
+        }
+        assert_eq!(map.len(), 100);
+    }
+
+    #[test]
+    fn test_entry() {
+        let map = ShardedHashMap::new();
+        map.entry(1, |e| {
+            assert!(matches!(e, Entry::Vacant(_)));
+            e.or_insert(10);
+        });
+        map.entry(1, |e| {
+            assert_eq!(*e.key(), 1);
+            *e.and_modify(|v| *v += 1).or_insert(0) += 1;
+        });
+        assert_eq!(map.get(&1), Some(12));
+        let removed = map.entry(1, |e| match e {
+            Entry::Occupied(o) => o.remove_entry(),
+            Entry::Vacant(_) => unreachable!(),
+        });
+        assert_eq!(removed, (1, 12));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent_insert_remove() {
+        const COUNTERS: u64 = 1_000_000;
+        let map = Arc::new(ShardedHashMap::with_shard_amount(8));
+        let threads: Vec<_> = (0..8u64)
+            .map(|t| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        assert_eq!(map.insert(t * 1000 + i, i), None);
+                        map.entry(COUNTERS + i, |e| *e.or_default() += 1);
+                    }
+                    for i in (0..1000).step_by(2) {
+                        assert_eq!(map.remove(&(t * 1000 + i)), Some(i));
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        // Every thread incremented each counter once.
+        let mut count = 0;
+        map.for_each(|&k, &v| {
+            count += 1;
+            if k >= COUNTERS {
+                assert_eq!(v, 8);
+            } else {
+                assert_eq!(k % 2, 1);
+                assert_eq!(v, k % 1000);
+            }
+        });
+        assert_eq!(count, map.len());
+        assert_eq!(count, 1000 + 8 * 500);
+    }
+
+    #[test]
+    fn test_into_iter_retain() {
+        let map = ShardedHashMap::with_shard_amount(4);
+        for i in 0..100 {
+            map.insert(i, i * 2);
+        }
+        map.retain(|&k, v| {
+            *v += 1;
+            k % 4 == 0
+        });
+        map.for_each_mut(|_, v| *v -= 1);
+        let mut items: Vec<(i32, i32)> = map.into_iter().collect();
+        items.sort_unstable();
+        let expected: Vec<(i32, i32)> = (0..100).step_by(4).map(|i| (i, i * 2)).collect();
+        assert_eq!(items, expected);
+    }
+}
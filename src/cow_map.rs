@@ -0,0 +1,278 @@
+use crate::map::{DefaultHashBuilder, HashMap};
+use crate::raw::{Allocator, Global};
+use alloc::sync::Arc;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+
+/// A [`HashMap`] whose clones share the same table until one of them is
+/// modified.
+///
+/// Cloning a `CowHashMap` only increments a reference count. The first
+/// modification of a map whose table is shared with another clone copies the
+/// whole table, as [`HashMap::clone`] would, and later modifications of that
+/// map change its own table in place. This suits maps which are cloned often,
+/// for example to take snapshots of a large read-mostly map, but which are
+/// rarely modified afterwards. Use a [`PersistentHashMap`] instead when
+/// snapshots keep being modified, since it only copies the part of the map a
+/// modification touches.
+///
+/// Reading methods are available through [`Deref`] to the underlying
+/// [`HashMap`], and [`to_mut`] gives mutable access to it, copying it first
+/// if it is shared.
+///
+/// [`PersistentHashMap`]: crate::PersistentHashMap
+/// [`to_mut`]: CowHashMap::to_mut
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::{CowHashMap, HashMap};
+///
+/// let mut map: HashMap<i32, &str> = HashMap::new();
+/// map.insert(1, "a");
+/// let original = CowHashMap::from(map);
+///
+/// // Taking a snapshot does not copy the table.
+/// let mut snapshot = original.clone();
+/// assert!(snapshot.ptr_eq(&original));
+///
+/// // The first modification does.
+/// snapshot.insert(2, "b");
+/// assert!(!snapshot.ptr_eq(&original));
+/// assert_eq!(original.len(), 1);
+/// assert_eq!(snapshot.len(), 2);
+/// ```
+pub struct CowHashMap<K, V, S = DefaultHashBuilder, A: Allocator + Clone = Global> {
+    map: Arc<HashMap<K, V, S, A>>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> CowHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `CowHashMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CowHashMap;
+    /// let map: CowHashMap<&str, i32> = CowHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::from(HashMap::new())
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> CowHashMap<K, V, S, A> {
+    /// Returns `true` if the two maps share the same table, so that neither
+    /// has been modified since one was cloned from the other.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.map, &other.map)
+    }
+
+    /// Returns `true` if the table is shared with another clone, so that the
+    /// next modification copies it.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.map) > 1
+    }
+
+    /// Returns a reference to the underlying [`HashMap`].
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn as_map(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+}
+
+impl<K, V, S, A> CowHashMap<K, V, S, A>
+where
+    K: Clone,
+    V: Clone,
+    S: Clone,
+    A: Allocator + Clone,
+{
+    /// Returns a mutable reference to the underlying [`HashMap`], copying it
+    /// first if its table is shared with another clone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CowHashMap;
+    ///
+    /// let mut map = CowHashMap::new();
+    /// map.to_mut().insert(1, 10);
+    /// let snapshot = map.clone();
+    ///
+    /// *map.to_mut().get_mut(&1).unwrap() += 1;
+    /// assert_eq!(map[&1], 11);
+    /// assert_eq!(snapshot[&1], 10);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn to_mut(&mut self) -> &mut HashMap<K, V, S, A> {
+        Arc::make_mut(&mut self.map)
+    }
+
+    /// Returns the underlying [`HashMap`], copying it if its table is shared
+    /// with another clone.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_map(self) -> HashMap<K, V, S, A> {
+        Arc::try_unwrap(self.map).unwrap_or_else(|map| (*map).clone())
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    ///
+    /// A shared table is left to the other clones instead of being copied,
+    /// and the map starts over with an empty table.
+    pub fn clear(&mut self) {
+        match Arc::get_mut(&mut self.map) {
+            Some(map) => map.clear(),
+            None => {
+                let empty = HashMap::with_hasher_in(
+                    self.map.hasher().clone(),
+                    self.map.allocator().clone(),
+                );
+                self.map = Arc::new(empty);
+            }
+        }
+    }
+}
+
+impl<K, V, S, A> CowHashMap<K, V, S, A>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+    A: Allocator + Clone,
+{
+    /// Inserts a key-value pair into the map, copying the table first if it
+    /// is shared. See [`HashMap::insert`].
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        self.to_mut().insert(k, v)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map. See [`HashMap::remove`].
+    ///
+    /// The table is only copied if it is shared and contains the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CowHashMap;
+    ///
+    /// let mut map = CowHashMap::new();
+    /// map.insert(1, "a");
+    /// let snapshot = map.clone();
+    ///
+    /// assert_eq!(map.remove(&2), None);
+    /// assert!(map.ptr_eq(&snapshot));
+    /// assert_eq!(map.remove(&1), Some("a"));
+    /// assert!(!map.ptr_eq(&snapshot));
+    /// ```
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + crate::Equivalent<K>,
+    {
+        if !self.map.contains_key(k) {
+            return None;
+        }
+        self.to_mut().remove(k)
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Clone for CowHashMap<K, V, S, A> {
+    /// Returns a map which shares the table of this one, in constant time.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        CowHashMap {
+            map: Arc::clone(&self.map),
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Deref for CowHashMap<K, V, S, A> {
+    type Target = HashMap<K, V, S, A>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn deref(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> From<HashMap<K, V, S, A>> for CowHashMap<K, V, S, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from(map: HashMap<K, V, S, A>) -> Self {
+        CowHashMap { map: Arc::new(map) }
+    }
+}
+
+impl<K, V, S: Default, A: Default + Allocator + Clone> Default for CowHashMap<K, V, S, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::from(HashMap::default())
+    }
+}
+
+impl<K, V, S, A> PartialEq for CowHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || *self.map == *other.map
+    }
+}
+
+impl<K, V, S, A> Eq for CowHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for CowHashMap<K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_cow_map {
+    use super::CowHashMap;
+    use crate::HashMap;
+
+    #[test]
+    fn test_copy_on_first_write() {
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut a = CowHashMap::from(map);
+        assert!(!a.is_shared());
+
+        let mut b = a.clone();
+        let c = b.clone();
+        assert!(a.is_shared() && a.ptr_eq(&b) && b.ptr_eq(&c));
+        assert_eq!(a, b);
+
+        b.insert(1000, 1000);
+        assert!(!b.ptr_eq(&a));
+        assert!(a.ptr_eq(&c));
+        assert!(!b.is_shared());
+        let table = b.as_map() as *const _;
+        b.insert(1001, 1001);
+        assert_eq!(b.as_map() as *const _, table);
+        assert_eq!((a.len(), b.len(), c.len()), (1000, 1002, 1000));
+        assert_ne!(a, b);
+
+        a.clear();
+        assert!(a.is_empty());
+        assert_eq!(c.len(), 1000);
+        drop(c);
+        assert_eq!(b.into_map().len(), 1002);
+    }
+}
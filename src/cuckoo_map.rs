@@ -0,0 +1,842 @@
+//! A hash map which uses cuckoo hashing to bound the length of lookups.
+//!
+//! [`CuckooHashMap`] derives two candidate groups of slots from the hash of
+//! every key, and keeps each key in one of them.
+//! A lookup therefore loads at most two groups of control bytes however full
+//! the table is, where the probe sequence of a [`HashMap`] can grow long
+//! around clusters of colliding keys.
+//!
+//! The cost moves to insertion: when both groups of a new key are full, it
+//! takes the slot of a key in one of them, which moves to its other group,
+//! possibly displacing another key, until a key finds a free slot. If that
+//! takes too many steps the table is resized. This suits lookup-heavy maps
+//! with a tight budget for the slowest lookups.
+//!
+//! The bound only holds for a hasher which spreads keys over the groups. Keys
+//! whose hashes send more of them to the same pair of groups than it has
+//! slots, which only a weak hasher or keys chosen to collide cause, go to an
+//! overflow list instead. That list is not bounded, and every lookup which
+//! misses both groups scans it, comparing the stored hashes of its keys
+//! before the keys themselves, so such keys make lookups linear in the size
+//! of the list. [`CuckooHashMap::overflow_len`] reports its length.
+//!
+//! [`HashMap`]: crate::HashMap
+
+use crate::hasher::hash_integer;
+use crate::map::{make_hash, DefaultHashBuilder};
+use crate::raw::{h2, Group, EMPTY};
+use crate::Equivalent;
+use alloc::boxed::Box;
+use alloc::vec::{self, Vec};
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::{FromIterator, FusedIterator};
+use core::mem;
+use core::ops::Index;
+use core::slice;
+
+/// The number of displacements an insertion may make before the table is
+/// resized.
+const MAX_DISPLACEMENTS: usize = 128;
+
+/// Returns the number of groups needed to hold `cap` keys, which is zero or a
+/// power of two.
+fn capacity_to_groups(cap: usize) -> usize {
+    if cap == 0 {
+        return 0;
+    }
+    // Keep one slot in eight free, so that insertions rarely need long
+    // chains of displacements.
+    let slots = cap
+        .checked_mul(8)
+        .map(|n| n / 7 + 1)
+        .expect("Hash table capacity overflow");
+    let groups = (slots + Group::WIDTH - 1) / Group::WIDTH;
+    groups
+        .checked_next_power_of_two()
+        .expect("Hash table capacity overflow")
+        .max(2)
+}
+
+/// Returns the two candidate groups of a key with the given hash.
+///
+/// The groups differ whenever the table has more than one group.
+#[inline]
+#[allow(clippy::cast_possible_truncation)]
+fn groups_of(hash: u64, group_mask: usize) -> (usize, usize) {
+    let first = hash as usize & group_mask;
+    let offset = hash_integer(hash) as usize | 1;
+    (first, (first ^ offset) & group_mask)
+}
+
+/// A hash map which finds every key in one of two groups of slots.
+///
+/// See the [module documentation](self) for how it works. Lookups compare the
+/// control bytes of both candidate groups of a key with SIMD instructions, as
+/// [`HashMap`] does for each group of its probe sequence, and never look
+/// further.
+///
+/// If the hasher sends more keys to the same pair of groups than they have
+/// slots, which only happens with a weak hasher or keys chosen to collide,
+/// the keys which do not fit are kept in an unbounded overflow list which
+/// lookups scan after the two groups. [`overflow_len`] reports its length.
+///
+/// The hash of every key is stored next to it, so moving keys between groups
+/// and growing the table never call the hasher again.
+///
+/// [`HashMap`]: crate::HashMap
+/// [`overflow_len`]: CuckooHashMap::overflow_len
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::CuckooHashMap;
+///
+/// let mut map = CuckooHashMap::new();
+/// for i in 0..1000 {
+///     map.insert(i, i * 2);
+/// }
+/// assert_eq!(map.get(&500), Some(&1000));
+/// assert_eq!(map.remove(&500), Some(1000));
+/// assert_eq!(map.get(&500), None);
+/// assert_eq!(map.overflow_len(), 0);
+/// ```
+pub struct CuckooHashMap<K, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    // One control byte per slot: `EMPTY`, or the `h2` tag of the key in the
+    // slot.
+    ctrl: Box<[u8]>,
+    slots: Box<[Option<(K, V)>]>,
+    // The hash of the key in every full slot.
+    hashes: Box<[u64]>,
+    overflow: Vec<(K, V)>,
+    // The hash of every key in `overflow`, at the same position.
+    overflow_hashes: Vec<u64>,
+    items: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> CuckooHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `CuckooHashMap`.
+    ///
+    /// The map is initially created with a capacity of 0, so it will not
+    /// allocate until it is first inserted into.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CuckooHashMap;
+    /// let map: CuckooHashMap<&str, i32> = CuckooHashMap::new();
+    /// assert!(map.is_empty());
+    /// assert_eq!(map.capacity(), 0);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty `CuckooHashMap` which can hold at least `capacity`
+    /// elements without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> CuckooHashMap<K, V, S> {
+    /// Creates an empty `CuckooHashMap` which will use the given hash builder
+    /// to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    /// Creates an empty `CuckooHashMap` which can hold at least `capacity`
+    /// elements without reallocating, and which will use the given hash
+    /// builder to hash keys.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let slots = capacity_to_groups(capacity) * Group::WIDTH;
+        CuckooHashMap {
+            hash_builder,
+            ctrl: alloc::vec![EMPTY; slots].into_boxed_slice(),
+            slots: (0..slots).map(|_| None).collect(),
+            hashes: alloc::vec![0; slots].into_boxed_slice(),
+            overflow: Vec::new(),
+            overflow_hashes: Vec::new(),
+            items: 0,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements the map can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.slots.len() / 8 * 7
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// Returns the number of elements which did not fit in either of their
+    /// groups and are kept in the overflow list.
+    ///
+    /// This is zero unless many keys have colliding hashes.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn overflow_len(&self) -> usize {
+        self.overflow.len()
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    pub fn clear(&mut self) {
+        self.ctrl.fill(EMPTY);
+        self.slots.iter_mut().for_each(|slot| *slot = None);
+        self.overflow.clear();
+        self.overflow_hashes.clear();
+        self.items = 0;
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            slots: self.slots.iter(),
+            overflow: self.overflow.iter(),
+            items: self.items,
+        }
+    }
+
+    #[inline]
+    fn group_mask(&self) -> usize {
+        (self.slots.len() / Group::WIDTH).wrapping_sub(1)
+    }
+
+    /// Returns the control bytes of the given group.
+    #[inline]
+    fn group(&self, group: usize) -> Group {
+        debug_assert!((group + 1) * Group::WIDTH <= self.ctrl.len());
+        // SAFETY: the group lies within the control bytes.
+        unsafe { Group::load(self.ctrl.as_ptr().add(group * Group::WIDTH)) }
+    }
+
+    /// Returns the index of an empty slot in the given group.
+    #[inline]
+    fn empty_slot(&self, group: usize) -> Option<usize> {
+        let bit = self.group(group).match_empty().lowest_set_bit()?;
+        Some(group * Group::WIDTH + bit)
+    }
+
+    /// Stores an entry in a slot, returning the entry which was there with
+    /// its hash.
+    #[inline]
+    fn fill_slot(&mut self, index: usize, hash: u64, entry: (K, V)) -> Option<(u64, (K, V))> {
+        self.ctrl[index] = h2(hash);
+        let old_hash = mem::replace(&mut self.hashes[index], hash);
+        self.slots[index].replace(entry).map(|old| (old_hash, old))
+    }
+
+    #[inline]
+    fn push_overflow(&mut self, hash: u64, entry: (K, V)) {
+        self.overflow.push(entry);
+        self.overflow_hashes.push(hash);
+    }
+}
+
+impl<K, V, S> CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns the index of the slot holding the key, or its position in the
+    /// overflow list as `Err`.
+    #[inline]
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<Result<usize, usize>>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        if !self.slots.is_empty() {
+            let tag = h2(hash);
+            let (first, second) = groups_of(hash, self.group_mask());
+            for group in [first, second] {
+                for bit in self.group(group).match_byte(tag) {
+                    let index = group * Group::WIDTH + bit;
+                    if let Some((key, _)) = &self.slots[index] {
+                        if k.equivalent(key) {
+                            return Some(Ok(index));
+                        }
+                    }
+                }
+            }
+        }
+        self.overflow_hashes
+            .iter()
+            .zip(&self.overflow)
+            .position(|(&h, (key, _))| h == hash && k.equivalent(key))
+            .map(Err)
+    }
+
+    #[inline]
+    fn get_inner<Q>(&self, k: &Q) -> Option<&(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        match self.find(make_hash::<Q, S>(&self.hash_builder, k), k)? {
+            Ok(index) => self.slots[index].as_ref(),
+            Err(pos) => Some(&self.overflow[pos]),
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CuckooHashMap;
+    ///
+    /// let mut map = CuckooHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get(&1), Some(&"a"));
+    /// assert_eq!(map.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_inner(k).map(|(_, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_inner(k).map(|(k, v)| (k, v))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        let entry = match self.find(make_hash::<Q, S>(&self.hash_builder, k), k)? {
+            Ok(index) => self.slots[index].as_mut()?,
+            Err(pos) => &mut self.overflow[pos],
+        };
+        Some(&mut entry.1)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_inner(k).is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    ///
+    /// If the map did have this key present, the value is updated, and the old
+    /// value is returned. The key is not updated.
+    ///
+    /// When both groups of the key are full, keys already in the map are moved
+    /// to their other group to make room, and the table grows if that does
+    /// not free a slot within a bounded number of moves.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CuckooHashMap;
+    ///
+    /// let mut map = CuckooHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        match self.find(hash, &k) {
+            Some(Ok(index)) => {
+                let (_, value) = self.slots[index].as_mut().unwrap();
+                return Some(mem::replace(value, v));
+            }
+            Some(Err(pos)) => return Some(mem::replace(&mut self.overflow[pos].1, v)),
+            None => {}
+        }
+
+        if self.items >= self.capacity() {
+            self.grow();
+        }
+        self.items += 1;
+        if let Err((hash, entry)) = self.place(hash, (k, v)) {
+            // Only grow a table which is reasonably full: a displacement
+            // chain which fails in a table with plenty of room is caused by
+            // keys whose groups collide, and would fail again after growing.
+            if self.items > self.slots.len() / 2 {
+                self.grow();
+                if let Err((hash, entry)) = self.place(hash, entry) {
+                    self.push_overflow(hash, entry);
+                }
+            } else {
+                self.push_overflow(hash, entry);
+            }
+        }
+        None
+    }
+
+    /// Stores an entry which is not in the map, moving other entries to
+    /// their second group if both groups of the new one are full.
+    ///
+    /// Returns the entry left without a slot, which may be another one than
+    /// `entry`, if that takes more than `MAX_DISPLACEMENTS` moves.
+    fn place(&mut self, mut hash: u64, mut entry: (K, V)) -> Result<(), (u64, (K, V))> {
+        if self.slots.is_empty() {
+            return Err((hash, entry));
+        }
+        let (first, second) = groups_of(hash, self.group_mask());
+        if let Some(index) = self.empty_slot(first).or_else(|| self.empty_slot(second)) {
+            self.fill_slot(index, hash, entry);
+            return Ok(());
+        }
+
+        let mut group = first;
+        for step in 0..MAX_DISPLACEMENTS {
+            // Vary the victim with every step so that chains do not cycle
+            // between the same two slots.
+            let index = group * Group::WIDTH + (hash as usize).wrapping_add(step) % Group::WIDTH;
+            (hash, entry) = self.fill_slot(index, hash, entry).unwrap();
+            let (first, second) = groups_of(hash, self.group_mask());
+            group = if first == group { second } else { first };
+            if let Some(index) = self.empty_slot(group) {
+                self.fill_slot(index, hash, entry);
+                return Ok(());
+            }
+        }
+        Err((hash, entry))
+    }
+
+    /// Doubles the number of groups and places every entry again, including
+    /// those in the overflow list.
+    #[cold]
+    #[inline(never)]
+    fn grow(&mut self) {
+        let slots = (self.slots.len() * 2).max(2 * Group::WIDTH);
+        let old_slots = mem::replace(&mut self.slots, (0..slots).map(|_| None).collect());
+        let old_hashes = mem::replace(&mut self.hashes, alloc::vec![0; slots].into_boxed_slice());
+        self.ctrl = alloc::vec![EMPTY; slots].into_boxed_slice();
+        let old_overflow = mem::take(&mut self.overflow);
+        let old_overflow_hashes = mem::take(&mut self.overflow_hashes);
+        let in_slots = Vec::from(old_slots)
+            .into_iter()
+            .zip(Vec::from(old_hashes))
+            .filter_map(|(slot, hash)| Some((hash, slot?)));
+        let in_overflow = old_overflow_hashes.into_iter().zip(old_overflow);
+        for (hash, entry) in in_slots.chain(in_overflow) {
+            if let Err((hash, entry)) = self.place(hash, entry) {
+                self.push_overflow(hash, entry);
+            }
+        }
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::CuckooHashMap;
+    ///
+    /// let mut map = CuckooHashMap::new();
+    /// map.insert(1, "a");
+    /// assert_eq!(map.remove(&1), Some("a"));
+    /// assert_eq!(map.remove(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.remove_entry(k).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.items == 0 {
+            return None;
+        }
+        let entry = match self.find(make_hash::<Q, S>(&self.hash_builder, k), k)? {
+            Ok(index) => {
+                // Slots are never part of a probe sequence, so they can be
+                // emptied without leaving a tombstone.
+                self.ctrl[index] = EMPTY;
+                self.slots[index].take()?
+            }
+            Err(pos) => {
+                self.overflow_hashes.swap_remove(pos);
+                self.overflow.swap_remove(pos)
+            }
+        };
+        self.items -= 1;
+        Some(entry)
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for CuckooHashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        CuckooHashMap {
+            hash_builder: self.hash_builder.clone(),
+            ctrl: self.ctrl.clone(),
+            slots: self.slots.clone(),
+            hashes: self.hashes.clone(),
+            overflow: self.overflow.clone(),
+            overflow_hashes: self.overflow_hashes.clone(),
+            items: self.items,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for CuckooHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S: Default> Default for CuckooHashMap<K, V, S> {
+    /// Creates an empty `CuckooHashMap`, with the `Default` value for the
+    /// hasher.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> PartialEq for CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, Q, V, S> Index<&Q> for CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `CuckooHashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for CuckooHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let iter = iter.into_iter();
+        let mut map = Self::with_capacity_and_hasher(iter.size_hint().0, S::default());
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a CuckooHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for CuckooHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Creates a consuming iterator, that is, one that moves each key-value
+    /// pair out of the map in arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            slots: Vec::from(self.slots).into_iter(),
+            overflow: self.overflow.into_iter(),
+            items: self.items,
+        }
+    }
+}
+
+/// An iterator over the entries of a `CuckooHashMap`.
+///
+/// This `struct` is created by the [`iter`] method on [`CuckooHashMap`].
+///
+/// [`iter`]: CuckooHashMap::iter
+pub struct Iter<'a, K, V> {
+    slots: slice::Iter<'a, Option<(K, V)>>,
+    overflow: slice::Iter<'a, (K, V)>,
+    items: usize,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            slots: self.slots.clone(),
+            overflow: self.overflow.clone(),
+            items: self.items,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let (k, v) = self
+            .slots
+            .by_ref()
+            .flatten()
+            .next()
+            .or_else(|| self.overflow.next())?;
+        self.items -= 1;
+        Some((k, v))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.items, Some(self.items))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An owning iterator over the entries of a `CuckooHashMap`.
+///
+/// This `struct` is created by the [`into_iter`] method on [`CuckooHashMap`]
+/// (provided by the [`IntoIterator`] trait).
+///
+/// [`into_iter`]: CuckooHashMap::into_iter
+pub struct IntoIter<K, V> {
+    slots: vec::IntoIter<Option<(K, V)>>,
+    overflow: vec::IntoIter<(K, V)>,
+    items: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(K, V)> {
+        let entry = self
+            .slots
+            .by_ref()
+            .flatten()
+            .next()
+            .or_else(|| self.overflow.next())?;
+        self.items -= 1;
+        Some(entry)
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.items, Some(self.items))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K: Debug, V: Debug> Debug for IntoIter<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IntoIter")
+            .field("remaining", &self.items)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test_cuckoo_map {
+    use super::{groups_of, CuckooHashMap};
+    use crate::hasher::IntegerHashBuilder;
+    use crate::raw::Group;
+    use core::hash::BuildHasherDefault;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_insert_remove() {
+        let mut map: CuckooHashMap<u64, u64> = CuckooHashMap::new();
+        for i in 0..10_000 {
+            assert_eq!(map.insert(i, i), None);
+            assert!(map.len() <= map.capacity());
+        }
+        assert_eq!(map.insert(5, 50), Some(5));
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(map.overflow_len(), 0);
+        assert!((0..10_000).all(|i| map.get(&i) == Some(&if i == 5 { 50 } else { i })));
+        for i in (0..10_000).step_by(2) {
+            assert!(map.remove(&i).is_some());
+        }
+        assert_eq!(map.len(), 5000);
+        assert_eq!(map.iter().count(), 5000);
+        assert!(map.iter().all(|(k, _)| k % 2 == 1));
+        *map.get_mut(&1).unwrap() += 1;
+        assert_eq!(map[&1], 2);
+
+        let mut sorted: Vec<_> = map.clone().into_iter().collect();
+        sorted.sort_unstable();
+        assert_eq!(sorted.len(), 5000);
+        assert_eq!(map.clone(), map);
+        map.clear();
+        assert!(map.is_empty() && map.get(&1).is_none());
+    }
+
+    #[test]
+    fn test_lookups_stay_in_two_groups() {
+        let mut map: CuckooHashMap<u64, u64, IntegerHashBuilder> = CuckooHashMap::default();
+        for i in 0..50_000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.overflow_len(), 0);
+        let mask = map.group_mask();
+        for (index, slot) in map.slots.iter().enumerate() {
+            if let Some((k, _)) = slot {
+                let hash = crate::map::make_hash::<u64, _>(&map.hash_builder, k);
+                let (first, second) = groups_of(hash, mask);
+                assert_ne!(first, second);
+                assert!(index / Group::WIDTH == first || index / Group::WIDTH == second);
+            }
+        }
+    }
+
+    #[test]
+    fn test_colliding_hashes() {
+        #[derive(Default)]
+        struct ConstantHasher;
+        impl core::hash::Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+        let mut map: CuckooHashMap<u32, u32, BuildHasherDefault<ConstantHasher>> =
+            CuckooHashMap::default();
+        for i in 0..200 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.len(), 200);
+        assert_eq!(map.overflow_len(), 200 - 2 * Group::WIDTH);
+        assert!((0..200).all(|i| map[&i] == i));
+        assert_eq!(map.remove(&199), Some(199));
+        assert_eq!(map.remove(&50), Some(50));
+        assert_eq!(map.iter().count(), 198);
+        assert!((0..199).filter(|&i| i != 50).all(|i| map[&i] == i));
+    }
+
+    #[test]
+    fn test_moves_dont_rehash() {
+        use core::hash::BuildHasher;
+        use std::cell::Cell;
+
+        struct CountingBuilder<'a>(&'a Cell<usize>, IntegerHashBuilder);
+        impl BuildHasher for CountingBuilder<'_> {
+            type Hasher = <IntegerHashBuilder as BuildHasher>::Hasher;
+            fn build_hasher(&self) -> Self::Hasher {
+                self.0.set(self.0.get() + 1);
+                self.1.build_hasher()
+            }
+        }
+
+        let calls = Cell::new(0);
+        let mut map =
+            CuckooHashMap::with_hasher(CountingBuilder(&calls, IntegerHashBuilder::default()));
+        // Enough keys to grow the table several times and displace keys on
+        // the way, all of which must reuse the stored hashes.
+        for i in 0..10_000u64 {
+            map.insert(i, i);
+        }
+        assert_eq!(calls.get(), 10_000);
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut map: CuckooHashMap<String, usize> = ["a", "bb", "ccc"]
+            .iter()
+            .map(|s| (s.to_string(), s.len()))
+            .collect();
+        assert_eq!(map.get("bb"), Some(&2));
+        assert_eq!(map.remove_entry("ccc"), Some(("ccc".to_string(), 3)));
+        assert!(!map.contains_key("ccc"));
+    }
+}
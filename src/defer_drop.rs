@@ -0,0 +1,211 @@
+//! Dropping large tables on a background thread.
+//!
+//! Dropping a map runs the destructor of every entry and frees their memory,
+//! which for a map with millions of heap-allocated keys or values can block
+//! the dropping thread for hundreds of milliseconds. The helpers here move
+//! the map to another thread and drop it there instead: [`defer_drop`] spawns
+//! a thread for a single value, and a [`DropQueue`] keeps one thread around
+//! for a stream of them.
+//!
+//! Anything which is `Send + 'static` can be dropped this way, not only maps.
+//! Deferring the drop of a small value costs more than dropping it, so these
+//! are only worth it for large ones.
+//!
+//! This module is only available if the `defer-drop` feature is enabled.
+
+use std::boxed::Box;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Sender};
+use std::thread::{self, JoinHandle};
+
+/// Drops `value` on a new thread.
+///
+/// The drop happens some time after this returns, and nothing waits for it.
+/// If the thread can't be spawned, `value` is dropped before returning.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::defer_drop::defer_drop;
+/// use hashbrown::HashMap;
+///
+/// let map: HashMap<String, usize> = (0..10_000).map(|i| (i.to_string(), i)).collect();
+/// // Returns without waiting for the keys to be freed.
+/// defer_drop(map);
+/// ```
+pub fn defer_drop<T: Send + 'static>(value: T) {
+    // If the thread can't be spawned, the closure, and `value` with it, is
+    // dropped by `spawn`.
+    let _ = thread::Builder::new()
+        .name("hashbrown-drop".into())
+        .spawn(move || drop(value));
+}
+
+enum Message {
+    Drop(Box<dyn Send>),
+    Flush(Sender<()>),
+}
+
+/// A background thread which drops the values sent to it, in order.
+///
+/// The thread is started by [`DropQueue::new`], and stops when the queue is
+/// dropped, after dropping all the values still queued, which the drop of the
+/// queue waits for. A panic in the destructor of a queued value is caught on
+/// the thread, which goes on with the next value.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::defer_drop::DropQueue;
+/// use hashbrown::HashMap;
+///
+/// let queue = DropQueue::new();
+/// for round in 0..3 {
+///     let map: HashMap<String, usize> =
+///         (0..1000).map(|i| (format!("{round}-{i}"), i)).collect();
+///     assert_eq!(map.len(), 1000);
+///     queue.defer(map);
+/// }
+/// // Waits until the three maps are dropped.
+/// queue.flush();
+/// ```
+pub struct DropQueue {
+    sender: Option<Sender<Message>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl DropQueue {
+    /// Creates a `DropQueue` and starts its thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the thread can't be spawned.
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel::<Message>();
+        let worker = thread::Builder::new()
+            .name("hashbrown-drop-queue".into())
+            .spawn(move || {
+                for message in receiver {
+                    match message {
+                        Message::Drop(value) => {
+                            let _ = panic::catch_unwind(AssertUnwindSafe(move || drop(value)));
+                        }
+                        Message::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })
+            .expect("failed to spawn the thread of a DropQueue");
+        Self {
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues `value` to be dropped on the thread of the queue.
+    pub fn defer<T: Send + 'static>(&self, value: T) {
+        self.send(Message::Drop(Box::new(value)));
+    }
+
+    /// Waits until every value queued before this call has been dropped.
+    pub fn flush(&self) {
+        let (done, wait) = mpsc::channel();
+        self.send(Message::Flush(done));
+        let _ = wait.recv();
+    }
+
+    fn send(&self, message: Message) {
+        // The thread only stops once the sender is gone, so this can't fail
+        // while the queue is alive.
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(message);
+        }
+    }
+}
+
+impl Default for DropQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for DropQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DropQueue").finish_non_exhaustive()
+    }
+}
+
+impl Drop for DropQueue {
+    fn drop(&mut self) {
+        // Closing the channel stops the thread after the queued values.
+        drop(self.sender.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_defer_drop {
+    use super::{defer_drop, DropQueue};
+    use crate::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+    use std::sync::Arc;
+
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Drop for Counted {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_defer_drop() {
+        let (sender, receiver) = mpsc::channel();
+        struct Notify(mpsc::Sender<std::thread::ThreadId>);
+        impl Drop for Notify {
+            fn drop(&mut self) {
+                let _ = self.0.send(std::thread::current().id());
+            }
+        }
+
+        let map: HashMap<i32, Notify> = (0..1).map(|i| (i, Notify(sender.clone()))).collect();
+        defer_drop(map);
+        let dropped_on = receiver.recv().unwrap();
+        assert_ne!(dropped_on, std::thread::current().id());
+    }
+
+    #[test]
+    fn test_drop_queue() {
+        let drops = Arc::new(AtomicUsize::new(0));
+        let queue = DropQueue::new();
+        for _ in 0..10 {
+            let map: HashMap<i32, Counted> =
+                (0..100).map(|i| (i, Counted(drops.clone()))).collect();
+            queue.defer(map);
+        }
+        queue.flush();
+        assert_eq!(drops.load(Ordering::SeqCst), 1000);
+
+        // A panicking destructor doesn't stop the queue.
+        struct Panics;
+        impl Drop for Panics {
+            fn drop(&mut self) {
+                panic!("drop");
+            }
+        }
+        queue.defer(Panics);
+        queue.defer(Counted(drops.clone()));
+        queue.flush();
+        assert_eq!(drops.load(Ordering::SeqCst), 1001);
+
+        // Dropping the queue drops what is still queued.
+        queue.defer(Counted(drops.clone()));
+        drop(queue);
+        assert_eq!(drops.load(Ordering::SeqCst), 1002);
+    }
+}
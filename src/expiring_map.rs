@@ -0,0 +1,408 @@
+//! A hash map whose entries expire at a deadline.
+//!
+//! [`ExpiringMap`] stores a deadline with each entry and treats entries whose
+//! deadline has passed as absent. The map does not read a clock itself: the
+//! current time is passed to each operation, and deadlines can be of any
+//! ordered type, such as `std::time::Instant` or a tick counter.
+
+use crate::map::{self, DefaultHashBuilder, HashMap};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+
+/// A hash map whose entries carry a deadline, after which they are treated
+/// as absent.
+///
+/// An entry has expired once the current time is equal to or later than its
+/// deadline. Lookups skip expired entries, but do not remove them: they stay
+/// in the table, and are counted by [`len`], until they are overwritten,
+/// removed, or swept out by [`purge_expired`], which scans the whole table
+/// once.
+///
+/// [`len`]: ExpiringMap::len
+/// [`purge_expired`]: ExpiringMap::purge_expired
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::ExpiringMap;
+/// use std::time::{Duration, Instant};
+///
+/// let start = Instant::now();
+/// let mut sessions = ExpiringMap::new();
+/// sessions.insert("alice", 1, start + Duration::from_secs(60));
+/// sessions.insert("bob", 2, start + Duration::from_secs(600));
+///
+/// let later = start + Duration::from_secs(120);
+/// assert_eq!(sessions.get("alice", later), None);
+/// assert_eq!(sessions.get("bob", later), Some(&2));
+///
+/// assert_eq!(sessions.purge_expired(later), 1);
+/// assert_eq!(sessions.len(), 1);
+/// ```
+pub struct ExpiringMap<K, V, T, S = DefaultHashBuilder> {
+    map: HashMap<K, (V, T), S>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V, T> ExpiringMap<K, V, T, DefaultHashBuilder> {
+    /// Creates an empty `ExpiringMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let map: ExpiringMap<&str, i32, u64> = ExpiringMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `ExpiringMap` with at least the specified capacity.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, T, S> ExpiringMap<K, V, T, S> {
+    /// Creates an empty `ExpiringMap` which will use the given hash builder to
+    /// hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_hasher(hash_builder),
+        }
+    }
+
+    /// Creates an empty `ExpiringMap` with at least the specified capacity,
+    /// using `hash_builder` to hash the keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Returns the number of elements the map can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Returns the number of entries in the map, including expired entries
+    /// which have not been removed yet.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no entries, expired or not.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Clears the map, removing all entries. Keeps the allocated memory for
+    /// reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+impl<K, V, T: Ord, S> ExpiringMap<K, V, T, S> {
+    /// An iterator visiting the entries which have not expired at `now`, in
+    /// arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let mut map = ExpiringMap::new();
+    /// map.insert("a", 1, 10);
+    /// map.insert("b", 2, 20);
+    /// let live: Vec<_> = map.iter(15).collect();
+    /// assert_eq!(live, [(&"b", &2)]);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self, now: T) -> Iter<'_, K, V, T> {
+        Iter {
+            inner: self.map.iter(),
+            now,
+        }
+    }
+
+    /// Removes all the entries which have expired at `now`, and returns how
+    /// many were removed.
+    ///
+    /// This scans the control bytes of the table a group at a time and only
+    /// reads the entries of full buckets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let mut map = ExpiringMap::new();
+    /// for i in 0..10 {
+    ///     map.insert(i, i, i);
+    /// }
+    /// assert_eq!(map.purge_expired(4), 5);
+    /// assert_eq!(map.len(), 5);
+    /// ```
+    pub fn purge_expired(&mut self, now: T) -> usize {
+        let len = self.map.len();
+        self.map.retain(|_, (_, deadline)| *deadline > now);
+        len - self.map.len()
+    }
+}
+
+impl<K, V, T, S> ExpiringMap<K, V, T, S>
+where
+    K: Eq + Hash,
+    T: Ord,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair which expires at `deadline`.
+    ///
+    /// If the map already had an entry for the key, expired or not, its value
+    /// and deadline are replaced and returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let mut map = ExpiringMap::new();
+    /// assert_eq!(map.insert(1, "a", 10), None);
+    /// assert_eq!(map.insert(1, "b", 20), Some(("a", 10)));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V, deadline: T) -> Option<(V, T)> {
+        self.map.insert(k, (v, deadline))
+    }
+
+    /// Returns a reference to the value corresponding to the key, unless the
+    /// entry has expired at `now`.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q, now: T) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_with_deadline(k, now).map(|(v, _)| v)
+    }
+
+    /// Returns a reference to the value corresponding to the key and its
+    /// deadline, unless the entry has expired at `now`.
+    #[inline]
+    pub fn get_with_deadline<Q>(&self, k: &Q, now: T) -> Option<(&V, &T)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        match self.map.get(k) {
+            Some((v, deadline)) if *deadline > now => Some((v, deadline)),
+            _ => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// unless the entry has expired at `now`.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q, now: T) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        match self.map.get_mut(k) {
+            Some((v, deadline)) if *deadline > now => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the map contains an entry for the key which has not
+    /// expired at `now`.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q, now: T) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get(k, now).is_some()
+    }
+
+    /// Moves the deadline of the key's entry to `deadline`, unless the entry
+    /// has expired at `now`. Returns whether the entry was found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let mut map = ExpiringMap::new();
+    /// map.insert("session", (), 10);
+    /// assert!(map.set_deadline("session", 20, 5));
+    /// assert!(map.contains_key("session", 15));
+    /// assert!(!map.set_deadline("session", 40, 30));
+    /// ```
+    pub fn set_deadline<Q>(&mut self, k: &Q, deadline: T, now: T) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        match self.map.get_mut(k) {
+            Some((_, old)) if *old > now => {
+                *old = deadline;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a key from the map, returning its value unless the entry has
+    /// expired at `now`. An expired entry is removed as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::ExpiringMap;
+    ///
+    /// let mut map = ExpiringMap::new();
+    /// map.insert(1, "a", 10);
+    /// map.insert(2, "b", 10);
+    /// assert_eq!(map.remove(&1, 5), Some("a"));
+    /// assert_eq!(map.remove(&2, 15), None);
+    /// assert!(map.is_empty());
+    /// ```
+    pub fn remove<Q>(&mut self, k: &Q, now: T) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        match self.map.remove(k) {
+            Some((v, deadline)) if deadline > now => Some(v),
+            _ => None,
+        }
+    }
+}
+
+impl<K: Clone, V: Clone, T: Clone, S: Clone> Clone for ExpiringMap<K, V, T, S> {
+    fn clone(&self) -> Self {
+        ExpiringMap {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, T: Debug, S> Debug for ExpiringMap<K, V, T, S> {
+    /// Formats every entry, expired or not, with its deadline.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<K, V, T, S: Default> Default for ExpiringMap<K, V, T, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+/// An iterator over the entries of an `ExpiringMap` which have not expired.
+///
+/// This `struct` is created by the [`iter`] method on [`ExpiringMap`].
+///
+/// [`iter`]: ExpiringMap::iter
+pub struct Iter<'a, K, V, T> {
+    inner: map::Iter<'a, K, (V, T)>,
+    now: T,
+}
+
+impl<K, V, T: Clone> Clone for Iter<'_, K, V, T> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+            now: self.now.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, T: Ord> Iterator for Iter<'a, K, V, T> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let now = &self.now;
+        self.inner
+            .find(|(_, (_, deadline))| deadline > now)
+            .map(|(k, (v, _))| (k, v))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.size_hint().1)
+    }
+}
+
+impl<K, V, T: Ord> FusedIterator for Iter<'_, K, V, T> {}
+
+impl<K: Debug, V: Debug, T: Ord + Clone> Debug for Iter<'_, K, V, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_expiring_map {
+    use super::ExpiringMap;
+    use std::string::{String, ToString};
+
+    #[test]
+    fn test_expiry_boundary() {
+        let mut map = ExpiringMap::new();
+        map.insert(1, 1, 10u32);
+        assert_eq!(map.get(&1, 9), Some(&1));
+        assert_eq!(map.get(&1, 10), None);
+        assert_eq!(map.get_mut(&1, 10), None);
+        assert_eq!(map.get_with_deadline(&1, 0), Some((&1, &10)));
+        // Expired entries can be replaced.
+        assert_eq!(map.insert(1, 2, 20), Some((1, 10)));
+        assert_eq!(map.get(&1, 10), Some(&2));
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let mut map = ExpiringMap::new();
+        for i in 0..1000u32 {
+            map.insert(i, i, i % 100);
+        }
+        assert_eq!(map.iter(50).count(), 490);
+        assert_eq!(map.purge_expired(50), 510);
+        assert_eq!(map.len(), 490);
+        assert!(map.iter(50).all(|(k, _)| k % 100 > 50));
+        assert_eq!(map.purge_expired(50), 0);
+        assert_eq!(map.purge_expired(100), 490);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut map: ExpiringMap<String, u32, u32> = ExpiringMap::default();
+        map.insert("a".to_string(), 1, 5);
+        *map.get_mut("a", 0).unwrap() += 1;
+        assert!(map.contains_key("a", 4));
+        assert!(map.set_deadline("a", 8, 4));
+        assert_eq!(map.remove("a", 6), Some(2));
+        assert!(map.is_empty());
+    }
+}
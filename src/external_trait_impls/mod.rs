@@ -1,4 +1,4 @@
 #[cfg(feature = "rayon")]
 pub(crate) mod rayon;
 #[cfg(feature = "serde")]
-mod serde;
+pub(crate) mod serde;
@@ -0,0 +1,121 @@
+//! Rayon extensions for `ArrayHashMap`.
+
+use super::map::{ParIter, ParIterMut, ParKeys, ParValues, ParValuesMut};
+use super::raw::RawParIter;
+use crate::ArrayHashMap;
+use core::marker::PhantomData;
+use rayon::iter::IntoParallelIterator;
+
+impl<K: Sync, V: Sync, const N: usize, S> ArrayHashMap<K, V, N, S> {
+    /// Visits (potentially in parallel) immutably borrowed key-value pairs in
+    /// an arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn par_iter(&self) -> ParIter<'_, K, V> {
+        ParIter {
+            inner: unsafe { RawParIter::from(self.raw_iter()) },
+            marker: PhantomData,
+        }
+    }
+
+    /// Visits (potentially in parallel) immutably borrowed keys in an arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn par_keys(&self) -> ParKeys<'_, K, V> {
+        ParKeys {
+            inner: unsafe { RawParIter::from(self.raw_iter()) },
+            marker: PhantomData,
+        }
+    }
+
+    /// Visits (potentially in parallel) immutably borrowed values in an arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn par_values(&self) -> ParValues<'_, K, V> {
+        ParValues {
+            inner: unsafe { RawParIter::from(self.raw_iter()) },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Sync, V: Send, const N: usize, S> ArrayHashMap<K, V, N, S> {
+    /// Visits (potentially in parallel) immutably borrowed keys and mutably
+    /// borrowed values in an arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, K, V> {
+        ParIterMut {
+            inner: unsafe { RawParIter::from(self.raw_iter_mut()) },
+            marker: PhantomData,
+        }
+    }
+
+    /// Visits (potentially in parallel) mutably borrowed values in an arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn par_values_mut(&mut self) -> ParValuesMut<'_, K, V> {
+        ParValuesMut {
+            inner: unsafe { RawParIter::from(self.raw_iter_mut()) },
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K: Sync, V: Sync, const N: usize, S> IntoParallelIterator
+    for &'a ArrayHashMap<K, V, N, S>
+{
+    type Item = (&'a K, &'a V);
+    type Iter = ParIter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<'a, K: Sync, V: Send, const N: usize, S> IntoParallelIterator
+    for &'a mut ArrayHashMap<K, V, N, S>
+{
+    type Item = (&'a K, &'a mut V);
+    type Iter = ParIterMut<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod test_par_array_map {
+    use crate::ArrayHashMap;
+    use rayon::prelude::*;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_par_iter() {
+        let mut map = ArrayHashMap::<u32, u32, 256>::new();
+        for i in 0..200 {
+            map.insert(i, i * 2).unwrap();
+        }
+        for i in 0..100 {
+            map.remove(&(i * 2));
+        }
+
+        let sum: u32 = map.par_iter().map(|(_, v)| *v).sum();
+        assert_eq!(sum, map.values().sum());
+        assert_eq!(map.par_keys().count(), 100);
+
+        let mut keys: Vec<u32> = map.par_keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..100).map(|i| i * 2 + 1).collect::<Vec<_>>());
+
+        map.par_values_mut().for_each(|v| *v += 1);
+        (&mut map).into_par_iter().for_each(|(k, v)| *v -= k);
+        assert!(map.par_iter().all(|(&k, &v)| v == k + 1));
+    }
+
+    #[test]
+    fn test_par_iter_small() {
+        let mut map = ArrayHashMap::<u8, u8, 4>::new();
+        assert_eq!(map.par_iter().count(), 0);
+        map.insert(1, 1).unwrap();
+        map.insert(2, 2).unwrap();
+        assert_eq!(map.par_iter_mut().map(|(k, v)| *k + *v).sum::<u8>(), 6);
+    }
+}
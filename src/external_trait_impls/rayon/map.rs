@@ -2,7 +2,10 @@
 
 use super::raw::{RawIntoParIter, RawParDrain, RawParIter};
 use crate::hash_map::HashMap;
-use crate::raw::{Allocator, Global};
+use crate::map::{make_hash, shard_index};
+use crate::raw::{Allocator, Bucket, Global, RawTable};
+use alloc::collections::LinkedList;
+use alloc::vec::Vec;
 use core::fmt;
 use core::hash::{BuildHasher, Hash};
 use core::marker::PhantomData;
@@ -19,8 +22,8 @@ use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, Pa
 /// [`HashMap`]: /hashbrown/struct.HashMap.html
 /// [`IntoParallelRefIterator`]: https://docs.rs/rayon/1.0/rayon/iter/trait.IntoParallelRefIterator.html
 pub struct ParIter<'a, K, V> {
-    inner: RawParIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a V)>,
+    pub(crate) inner: RawParIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a V)>,
 }
 
 impl<'a, K: Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
@@ -68,8 +71,8 @@ impl<K: fmt::Debug + Eq + Hash, V: fmt::Debug> fmt::Debug for ParIter<'_, K, V>
 /// [`par_keys`]: /hashbrown/struct.HashMap.html#method.par_keys
 /// [`HashMap`]: /hashbrown/struct.HashMap.html
 pub struct ParKeys<'a, K, V> {
-    inner: RawParIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a V)>,
+    pub(crate) inner: RawParIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a V)>,
 }
 
 impl<'a, K: Sync, V: Sync> ParallelIterator for ParKeys<'a, K, V> {
@@ -111,8 +114,8 @@ impl<K: fmt::Debug + Eq + Hash, V> fmt::Debug for ParKeys<'_, K, V> {
 /// [`par_values`]: /hashbrown/struct.HashMap.html#method.par_values
 /// [`HashMap`]: /hashbrown/struct.HashMap.html
 pub struct ParValues<'a, K, V> {
-    inner: RawParIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a V)>,
+    pub(crate) inner: RawParIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a V)>,
 }
 
 impl<'a, K: Sync, V: Sync> ParallelIterator for ParValues<'a, K, V> {
@@ -156,8 +159,8 @@ impl<K: Eq + Hash, V: fmt::Debug> fmt::Debug for ParValues<'_, K, V> {
 /// [`HashMap`]: /hashbrown/struct.HashMap.html
 /// [`IntoParallelRefMutIterator`]: https://docs.rs/rayon/1.0/rayon/iter/trait.IntoParallelRefMutIterator.html
 pub struct ParIterMut<'a, K, V> {
-    inner: RawParIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a mut V)>,
+    pub(crate) inner: RawParIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a mut V)>,
 }
 
 impl<'a, K: Sync, V: Send> ParallelIterator for ParIterMut<'a, K, V> {
@@ -195,8 +198,8 @@ impl<K: fmt::Debug + Eq + Hash, V: fmt::Debug> fmt::Debug for ParIterMut<'_, K,
 /// [`par_values_mut`]: /hashbrown/struct.HashMap.html#method.par_values_mut
 /// [`HashMap`]: /hashbrown/struct.HashMap.html
 pub struct ParValuesMut<'a, K, V> {
-    inner: RawParIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a mut V)>,
+    pub(crate) inner: RawParIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a mut V)>,
 }
 
 impl<'a, K: Sync, V: Send> ParallelIterator for ParValuesMut<'a, K, V> {
@@ -295,6 +298,35 @@ impl<K: fmt::Debug + Eq + Hash, V: fmt::Debug, A: Allocator + Clone> fmt::Debug
     }
 }
 
+/// Parallel iterator over the entries removed from a map by a predicate.
+///
+/// This iterator is created by the [`par_drain_filter`] method on [`HashMap`].
+/// See its documentation for more.
+///
+/// [`par_drain_filter`]: /hashbrown/struct.HashMap.html#method.par_drain_filter
+/// [`HashMap`]: /hashbrown/struct.HashMap.html
+pub struct ParDrainFilter<K, V> {
+    inner: Vec<(K, V)>,
+}
+
+impl<K: Send, V: Send> ParallelIterator for ParDrainFilter<K, V> {
+    type Item = (K, V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.inner.into_par_iter().drive_unindexed(consumer)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ParDrainFilter<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.inner.iter()).finish()
+    }
+}
+
 impl<K: Sync, V: Sync, S, A: Allocator + Clone> HashMap<K, V, S, A> {
     /// Visits (potentially in parallel) immutably borrowed keys in an arbitrary order.
     #[cfg_attr(feature = "inline-more", inline)]
@@ -335,6 +367,96 @@ impl<K: Send, V: Send, S, A: Allocator + Clone> HashMap<K, V, S, A> {
     }
 }
 
+impl<K: Sync, V: Send, S, A: Allocator + Clone> HashMap<K, V, S, A> {
+    /// Retains only the elements specified by the predicate, evaluating it
+    /// (potentially in parallel) on every element.
+    ///
+    /// The predicate runs in parallel, the elements it rejects are then
+    /// removed and dropped sequentially. This pays off when the predicate is
+    /// expensive compared to removing an element.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..8).map(|x| (x, x * 10)).collect();
+    /// map.par_retain(|&k, v| {
+    ///     *v += 1;
+    ///     k % 2 == 0
+    /// });
+    ///
+    /// let mut vec: Vec<(i32, i32)> = map.into_iter().collect();
+    /// vec.sort_unstable();
+    /// assert_eq!(vec, [(0, 1), (2, 21), (4, 41), (6, 61)]);
+    /// ```
+    pub fn par_retain<F>(&mut self, f: F)
+    where
+        F: Fn(&K, &mut V) -> bool + Sync + Send,
+    {
+        unsafe {
+            for item in self.par_select(|k, v| !f(k, v)) {
+                self.table.erase(item);
+            }
+        }
+    }
+
+    /// Removes every element for which the predicate returns `true`,
+    /// evaluating it (potentially in parallel) on every element, and returns
+    /// a parallel iterator over the removed key-value pairs.
+    ///
+    /// Unlike [`drain_filter`], the elements are removed eagerly, before this
+    /// method returns: the map no longer contains them even if the returned
+    /// iterator is dropped without being consumed.
+    ///
+    /// [`drain_filter`]: /hashbrown/struct.HashMap.html#method.drain_filter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..8).map(|x| (x, x)).collect();
+    /// let mut evens: Vec<i32> = map.par_drain_filter(|k, _v| k % 2 == 0).map(|(k, _)| k).collect();
+    /// let mut odds: Vec<i32> = map.keys().copied().collect();
+    /// evens.sort_unstable();
+    /// odds.sort_unstable();
+    ///
+    /// assert_eq!(evens, [0, 2, 4, 6]);
+    /// assert_eq!(odds, [1, 3, 5, 7]);
+    /// ```
+    pub fn par_drain_filter<F>(&mut self, f: F) -> ParDrainFilter<K, V>
+    where
+        F: Fn(&K, &mut V) -> bool + Sync + Send,
+    {
+        let mut removed = Vec::new();
+        unsafe {
+            let selected = self.par_select(f);
+            removed.reserve_exact(selected.len());
+            for item in selected {
+                removed.push(self.table.remove(item));
+            }
+        }
+        ParDrainFilter { inner: removed }
+    }
+
+    /// Evaluates `f` (potentially in parallel) on every element and returns
+    /// the buckets for which it returned `true`.
+    unsafe fn par_select<F>(&mut self, f: F) -> Vec<Bucket<(K, V)>>
+    where
+        F: Fn(&K, &mut V) -> bool + Sync + Send,
+    {
+        self.table
+            .par_iter()
+            .filter(|item| {
+                let (key, value) = item.as_mut();
+                f(key, value)
+            })
+            .collect()
+    }
+}
+
 impl<K, V, S, A> HashMap<K, V, S, A>
 where
     K: Eq + Hash + Sync,
@@ -345,12 +467,36 @@ where
     /// Returns `true` if the map is equal to another,
     /// i.e. both maps contain the same keys mapped to the same values.
     ///
-    /// This method runs in a potentially parallel fashion.
+    /// This method runs in a potentially parallel fashion: the workers split
+    /// the entries of one map between them and look each key up in the other.
+    /// They scan the map with fewer buckets, which reads fewer control bytes
+    /// when the other map has grown large and then had most entries removed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let a: HashMap<u32, u32> = (0..10_000).map(|i| (i, i * 2)).collect();
+    /// let mut b: HashMap<u32, u32> = (0..100_000).map(|i| (i, i * 2)).collect();
+    /// b.retain(|&k, _| k < 10_000);
+    /// assert!(a.par_eq(&b));
+    ///
+    /// b.insert(0, 1);
+    /// assert!(!a.par_eq(&b));
+    /// ```
     pub fn par_eq(&self, other: &Self) -> bool {
-        self.len() == other.len()
-            && self
-                .into_par_iter()
+        if self.len() != other.len() {
+            return false;
+        }
+        if self.table.buckets() <= other.table.buckets() {
+            self.into_par_iter()
                 .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+        } else {
+            other
+                .into_par_iter()
+                .all(|(key, v)| self.get(key).map_or(false, |value| *value == *v))
+        }
     }
 }
 
@@ -402,22 +548,108 @@ impl<'a, K: Sync, V: Send, S, A: Allocator + Clone> IntoParallelIterator
 /// hashmap. If multiple pairs correspond to the same key, then the
 /// ones produced earlier in the parallel iterator will be
 /// overwritten, just as with a sequential iterator.
-impl<K, V, S> FromParallelIterator<(K, V)> for HashMap<K, V, S, Global>
+///
+/// The workers hash the keys and split the pairs into shards by hash, and
+/// then remove duplicate keys from every shard in parallel. Only moving the
+/// pairs of all shards into the map remains sequential, and it reuses the
+/// hashes computed by the workers.
+impl<K, V, S, A> FromParallelIterator<(K, V)> for HashMap<K, V, S, A>
 where
     K: Eq + Hash + Send,
     V: Send,
-    S: BuildHasher + Default,
+    S: BuildHasher + Default + Sync,
+    A: Default + Allocator + Clone,
 {
     fn from_par_iter<P>(par_iter: P) -> Self
     where
         P: IntoParallelIterator<Item = (K, V)>,
     {
-        let mut map = HashMap::default();
-        map.par_extend(par_iter);
+        let mut map = HashMap::with_hasher_in(S::default(), A::default());
+        let shards = collect_shards(&map.hash_builder, par_iter);
+
+        let len = shards.iter().map(RawTable::len).sum();
+        map.reserve(len);
+        for shard in shards {
+            for (hash, key, value) in shard {
+                // SAFETY: We reserved room for all pairs, and keys are unique
+                // since equal keys end up in the same shard.
+                unsafe {
+                    map.table.insert_no_grow(hash, (key, value));
+                }
+            }
+        }
         map
     }
 }
 
+/// Hashes the keys of all pairs of `par_iter` and splits the pairs into
+/// shards by hash, with one table per shard which keeps the last pair of
+/// every key along with its hash.
+fn collect_shards<K, V, S, I>(hash_builder: &S, par_iter: I) -> Vec<RawTable<(u64, K, V)>>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Sync,
+    I: IntoParallelIterator<Item = (K, V)>,
+{
+    // A few shards per thread keep the threads busy even if the shards
+    // differ in size.
+    let shard_bits = (rayon::current_num_threads() * 4)
+        .next_power_of_two()
+        .trailing_zeros();
+    let new_parts = || {
+        (0..1_usize << shard_bits)
+            .map(|_| Vec::new())
+            .collect::<Vec<Vec<(u64, K, V)>>>()
+    };
+
+    // Every worker splits its pairs into parts, one per shard. The list
+    // keeps the workers' parts in the order of the iterator.
+    #[allow(clippy::linkedlist)]
+    let list = par_iter
+        .into_par_iter()
+        .fold(new_parts, |mut parts, (key, value)| {
+            let hash = make_hash::<K, S>(hash_builder, &key);
+            parts[shard_index(hash, shard_bits)].push((hash, key, value));
+            parts
+        })
+        .map(|parts| {
+            let mut list = LinkedList::new();
+            list.push_back(parts);
+            list
+        })
+        .reduce(LinkedList::new, |mut list1, mut list2| {
+            list1.append(&mut list2);
+            list1
+        });
+
+    let mut shards: Vec<Vec<Vec<(u64, K, V)>>> = (0..1 << shard_bits).map(|_| Vec::new()).collect();
+    for parts in list {
+        for (shard, part) in shards.iter_mut().zip(parts) {
+            shard.push(part);
+        }
+    }
+
+    shards
+        .into_par_iter()
+        .map(|parts| {
+            let len = parts.iter().map(Vec::len).sum();
+            let mut table = RawTable::with_capacity(len);
+            let hasher = |&(hash, _, _): &(u64, K, V)| hash;
+            for (hash, key, value) in parts.into_iter().flatten() {
+                match table.find_or_find_insert_slot(hash, |x| x.1 == key, hasher) {
+                    // Later pairs replace the values of earlier ones.
+                    Ok(bucket) => unsafe { bucket.as_mut().2 = value },
+                    Err(slot) => unsafe {
+                        table.insert_in_slot(hash, slot, (hash, key, value));
+                    },
+                }
+            }
+            table
+        })
+        .collect()
+}
+
 /// Extend a hash map with items from a parallel iterator.
 impl<K, V, S, A> ParallelExtend<(K, V)> for HashMap<K, V, S, A>
 where
@@ -574,6 +806,57 @@ mod test_par_map {
         assert_eq!(value.load(Ordering::Relaxed), 0);
     }
 
+    #[test]
+    fn test_into_iter_short_circuit_drops() {
+        let key = AtomicUsize::new(0);
+        let value = AtomicUsize::new(0);
+
+        let mut hm = HashMap::new();
+        for i in 0..1000 {
+            hm.insert(Dropable::new(i, &key), Dropable::new(i, &value));
+        }
+
+        // Stopping early drops the entries which were never yielded.
+        let found = hm.into_par_iter().find_any(|(k, _)| k.k == 500);
+        assert_eq!(found.as_ref().map(|(k, _)| k.k), Some(500));
+        assert_eq!(key.load(Ordering::Relaxed), 1);
+        assert_eq!(value.load(Ordering::Relaxed), 1);
+        drop(found);
+        assert_eq!(key.load(Ordering::Relaxed), 0);
+        assert_eq!(value.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "nightly"))]
+    fn test_into_iter_deallocates_with_allocator() {
+        use crate::raw::{Allocator, Global};
+        use alloc::alloc::Layout;
+        use core::ptr::NonNull;
+
+        #[derive(Clone)]
+        struct Counting<'a>(&'a AtomicUsize);
+
+        unsafe impl Allocator for Counting<'_> {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, ()> {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                Global.allocate(layout)
+            }
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.fetch_sub(1, Ordering::Relaxed);
+                Global.deallocate(ptr, layout);
+            }
+        }
+
+        let live = AtomicUsize::new(0);
+        let mut hm = HashMap::with_capacity_in(100, Counting(&live));
+        for i in 0..100 {
+            hm.insert(i, i);
+        }
+        assert_eq!(live.load(Ordering::Relaxed), 1);
+        assert_eq!(hm.into_par_iter().map(|(_, v)| v).sum::<i32>(), 4950);
+        assert_eq!(live.load(Ordering::Relaxed), 0);
+    }
+
     #[test]
     fn test_drain_drops() {
         let key = AtomicUsize::new(0);
@@ -703,6 +986,16 @@ mod test_par_map {
         m2.insert(3, 4);
 
         assert!(m1.par_eq(&m2));
+
+        // Maps with the same entries in tables of different sizes compare the
+        // same from either side.
+        let mut m3: HashMap<i32, i32> = (0..1000).map(|i| (i, i + 1)).collect();
+        m3.retain(|&k, _| (1..=3).contains(&k));
+        assert!(m1.par_eq(&m3));
+        assert!(m3.par_eq(&m1));
+        m3.insert(3, 5);
+        assert!(!m1.par_eq(&m3));
+        assert!(!m3.par_eq(&m1));
     }
 
     #[test]
@@ -716,6 +1009,29 @@ mod test_par_map {
         }
     }
 
+    #[test]
+    fn test_from_iter_shards() {
+        let map: HashMap<u32, u32> = (0..100_000_u32).into_par_iter().map(|i| (i, i)).collect();
+        assert_eq!(map.len(), 100_000);
+        assert!((0..100_000).all(|i| map[&i] == i));
+        map.verify();
+
+        // The last pair of every key wins, as with a sequential iterator.
+        let map: HashMap<u32, u32> = (0..100_000_u32)
+            .into_par_iter()
+            .map(|i| (i % 1000, i))
+            .collect();
+        assert_eq!(map.len(), 1000);
+        assert!((0..1000).all(|i| map[&i] == 99_000 + i));
+        assert_eq!(
+            map.capacity(),
+            HashMap::<u32, u32>::with_capacity(1000).capacity()
+        );
+
+        let empty: HashMap<u32, u32> = (0..0_u32).into_par_iter().map(|i| (i, i)).collect();
+        assert!(empty.is_empty());
+    }
+
     #[test]
     fn test_extend_ref() {
         let mut a = HashMap::new();
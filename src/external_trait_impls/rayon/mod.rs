@@ -1,3 +1,4 @@
+mod array_map;
 mod helpers;
 pub(crate) mod map;
 pub(crate) mod raw;
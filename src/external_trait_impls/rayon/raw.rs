@@ -1,7 +1,6 @@
 use crate::raw::Bucket;
 use crate::raw::{Allocator, Global, RawIter, RawIterRange, RawTable};
 use crate::scopeguard::guard;
-use alloc::alloc::dealloc;
 use core::marker::PhantomData;
 use core::mem;
 use core::ptr::NonNull;
@@ -95,11 +94,14 @@ impl<T: Send, A: Allocator + Clone + Send> ParallelIterator for RawIntoParIter<T
     where
         C: UnindexedConsumer<Self::Item>,
     {
-        let iter = unsafe { self.table.iter().iter };
-        let _guard = guard(self.table.into_allocation(), |alloc| {
-            if let Some((ptr, layout)) = *alloc {
+        let iter = unsafe { self.table.iter_in_order().iter };
+        // The allocation must go back to the table's allocator, after the
+        // producers have dropped whatever elements were not consumed.
+        let alloc = self.table.allocator().clone();
+        let _guard = guard(self.table.into_allocation(), move |allocation| {
+            if let Some((ptr, layout)) = *allocation {
                 unsafe {
-                    dealloc(ptr.as_ptr(), layout);
+                    alloc.deallocate(ptr, layout);
                 }
             }
         });
@@ -136,7 +138,7 @@ impl<T: Send, A: Allocator + Clone> ParallelIterator for RawParDrain<'_, T, A> {
         let _guard = guard(self.table, |table| unsafe {
             table.as_mut().clear_no_drop();
         });
-        let iter = unsafe { self.table.as_ref().iter().iter };
+        let iter = unsafe { self.table.as_ref().iter_in_order().iter };
         mem::forget(self);
         let producer = ParDrainProducer { iter };
         plumbing::bridge_unindexed(producer, consumer)
@@ -209,7 +211,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     #[cfg_attr(feature = "inline-more", inline)]
     pub unsafe fn par_iter(&self) -> RawParIter<T> {
         RawParIter {
-            iter: self.iter().iter,
+            iter: self.iter_in_order().iter,
         }
     }
 
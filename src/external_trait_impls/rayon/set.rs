@@ -53,6 +53,28 @@ impl<T: Send, A: Allocator + Clone + Send + Sync> ParallelIterator for ParDrain<
     }
 }
 
+/// Parallel iterator over the elements removed from a set by a predicate.
+///
+/// This iterator is created by the [`par_drain_filter`] method on [`HashSet`].
+/// See its documentation for more.
+///
+/// [`par_drain_filter`]: /hashbrown/struct.HashSet.html#method.par_drain_filter
+/// [`HashSet`]: /hashbrown/struct.HashSet.html
+pub struct ParDrainFilter<T> {
+    inner: map::ParDrainFilter<T, ()>,
+}
+
+impl<T: Send> ParallelIterator for ParDrainFilter<T> {
+    type Item = T;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.inner.map(|(k, _)| k).drive_unindexed(consumer)
+    }
+}
+
 /// Parallel iterator over shared references to elements in a set.
 ///
 /// This iterator is created by the [`par_iter`] method on [`HashSet`]
@@ -280,9 +302,17 @@ where
     /// Returns `true` if the set is equal to another,
     /// i.e. both sets contain the same values.
     ///
-    /// This method runs in a potentially parallel fashion.
+    /// This method runs in a potentially parallel fashion, scanning the set
+    /// with fewer buckets and looking its values up in the other.
     pub fn par_eq(&self, other: &Self) -> bool {
-        self.len() == other.len() && self.par_is_subset(other)
+        if self.len() != other.len() {
+            return false;
+        }
+        if self.map.table.buckets() <= other.map.table.buckets() {
+            self.par_is_subset(other)
+        } else {
+            other.par_is_subset(self)
+        }
     }
 }
 
@@ -301,6 +331,60 @@ where
     }
 }
 
+impl<T: Sync, S, A: Allocator + Clone> HashSet<T, S, A> {
+    /// Retains only the elements specified by the predicate, evaluating it
+    /// (potentially in parallel) on every element.
+    ///
+    /// The predicate runs in parallel, the elements it rejects are then
+    /// removed and dropped sequentially.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut set: HashSet<i32> = (0..8).collect();
+    /// set.par_retain(|&x| x % 2 == 0);
+    /// assert_eq!(set.len(), 4);
+    /// ```
+    pub fn par_retain<F>(&mut self, f: F)
+    where
+        F: Fn(&T) -> bool + Sync + Send,
+    {
+        self.map.par_retain(|k, _| f(k));
+    }
+
+    /// Removes every element for which the predicate returns `true`,
+    /// evaluating it (potentially in parallel) on every element, and returns
+    /// a parallel iterator over the removed elements.
+    ///
+    /// Unlike [`drain_filter`], the elements are removed eagerly, before this
+    /// method returns.
+    ///
+    /// [`drain_filter`]: /hashbrown/struct.HashSet.html#method.drain_filter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// let mut set: HashSet<i32> = (0..8).collect();
+    /// let drained: HashSet<i32> = set.par_drain_filter(|v| v % 2 == 0).collect();
+    /// assert_eq!(drained.len(), 4);
+    /// assert_eq!(set.len(), 4);
+    /// assert!(drained.is_disjoint(&set));
+    /// ```
+    pub fn par_drain_filter<F>(&mut self, f: F) -> ParDrainFilter<T>
+    where
+        F: Fn(&T) -> bool + Sync + Send,
+    {
+        ParDrainFilter {
+            inner: self.map.par_drain_filter(|k, _| f(k)),
+        }
+    }
+}
+
 impl<T: Send, S, A: Allocator + Clone + Send> IntoParallelIterator for HashSet<T, S, A> {
     type Item = T;
     type Iter = IntoParIter<T, A>;
@@ -326,26 +410,31 @@ impl<'a, T: Sync, S, A: Allocator + Clone> IntoParallelIterator for &'a HashSet<
 }
 
 /// Collect values from a parallel iterator into a hashset.
-impl<T, S> FromParallelIterator<T> for HashSet<T, S, Global>
+///
+/// Like the map, the set is built from shards which the workers fill in
+/// parallel.
+impl<T, S, A> FromParallelIterator<T> for HashSet<T, S, A>
 where
     T: Eq + Hash + Send,
-    S: BuildHasher + Default,
+    S: BuildHasher + Default + Sync,
+    A: Default + Allocator + Clone,
 {
     fn from_par_iter<P>(par_iter: P) -> Self
     where
         P: IntoParallelIterator<Item = T>,
     {
-        let mut set = HashSet::default();
-        set.par_extend(par_iter);
-        set
+        HashSet {
+            map: par_iter.into_par_iter().map(|k| (k, ())).collect(),
+        }
     }
 }
 
 /// Extend a hash set with items from a parallel iterator.
-impl<T, S> ParallelExtend<T> for HashSet<T, S, Global>
+impl<T, S, A> ParallelExtend<T> for HashSet<T, S, A>
 where
     T: Eq + Hash + Send,
     S: BuildHasher,
+    A: Allocator + Clone,
 {
     fn par_extend<I>(&mut self, par_iter: I)
     where
@@ -356,10 +445,11 @@ where
 }
 
 /// Extend a hash set with copied items from a parallel iterator.
-impl<'a, T, S> ParallelExtend<&'a T> for HashSet<T, S, Global>
+impl<'a, T, S, A> ParallelExtend<&'a T> for HashSet<T, S, A>
 where
     T: 'a + Copy + Eq + Hash + Sync,
     S: BuildHasher,
+    A: Allocator + Clone,
 {
     fn par_extend<I>(&mut self, par_iter: I)
     where
@@ -607,6 +697,20 @@ mod test_par_set {
         assert!(v == ['a', 'b'] || v == ['b', 'a']);
     }
 
+    #[test]
+    fn test_move_iter_short_circuit() {
+        use alloc::sync::Arc;
+
+        let counter = Arc::new(());
+        let hs: HashSet<(usize, Arc<()>)> = (0..1000).map(|i| (i, counter.clone())).collect();
+        assert_eq!(Arc::strong_count(&counter), 1001);
+        let found = hs.into_par_iter().find_any(|(i, _)| *i == 500);
+        assert_eq!(found.as_ref().map(|(i, _)| *i), Some(500));
+        assert_eq!(Arc::strong_count(&counter), 2);
+        drop(found);
+        assert_eq!(Arc::strong_count(&counter), 1);
+    }
+
     #[test]
     fn test_eq() {
         // These constants once happened to expose a bug in insert().
@@ -627,6 +731,15 @@ mod test_par_set {
         s2.insert(3);
 
         assert!(s1.par_eq(&s2));
+
+        let mut s3: HashSet<i32> = (0..1000).collect();
+        s3.retain(|&x| (1..=3).contains(&x));
+        assert!(s1.par_eq(&s3));
+        assert!(s3.par_eq(&s1));
+        s3.remove(&3);
+        s3.insert(4);
+        assert!(!s1.par_eq(&s3));
+        assert!(!s3.par_eq(&s1));
     }
 
     #[test]
@@ -18,14 +18,16 @@ mod map {
     use serde::ser::{Serialize, Serializer};
 
     use crate::hash_map::HashMap;
+    use crate::raw::Allocator;
 
     use super::size_hint;
 
-    impl<K, V, H> Serialize for HashMap<K, V, H>
+    impl<K, V, H, A> Serialize for HashMap<K, V, H, A>
     where
         K: Serialize + Eq + Hash,
         V: Serialize,
         H: BuildHasher,
+        A: Allocator + Clone,
     {
         #[cfg_attr(feature = "inline-more", inline)]
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -36,40 +38,46 @@ mod map {
         }
     }
 
-    impl<'de, K, V, S> Deserialize<'de> for HashMap<K, V, S>
+    impl<'de, K, V, S, A> Deserialize<'de> for HashMap<K, V, S, A>
     where
         K: Deserialize<'de> + Eq + Hash,
         V: Deserialize<'de>,
         S: BuildHasher + Default,
+        A: Allocator + Clone + Default,
     {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
             D: Deserializer<'de>,
         {
-            struct MapVisitor<K, V, S> {
-                marker: PhantomData<HashMap<K, V, S>>,
+            struct MapVisitor<K, V, S, A>
+            where
+                A: Allocator + Clone,
+            {
+                marker: PhantomData<HashMap<K, V, S, A>>,
             }
 
-            impl<'de, K, V, S> Visitor<'de> for MapVisitor<K, V, S>
+            impl<'de, K, V, S, A> Visitor<'de> for MapVisitor<K, V, S, A>
             where
                 K: Deserialize<'de> + Eq + Hash,
                 V: Deserialize<'de>,
                 S: BuildHasher + Default,
+                A: Allocator + Clone + Default,
             {
-                type Value = HashMap<K, V, S>;
+                type Value = HashMap<K, V, S, A>;
 
                 fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
                     formatter.write_str("a map")
                 }
 
                 #[cfg_attr(feature = "inline-more", inline)]
-                fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+                fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
                 where
-                    A: MapAccess<'de>,
+                    M: MapAccess<'de>,
                 {
-                    let mut values = HashMap::with_capacity_and_hasher(
+                    let mut values = HashMap::with_capacity_and_hasher_in(
                         size_hint::cautious(map.size_hint()),
                         S::default(),
+                        A::default(),
                     );
 
                     while let Some((key, value)) = map.next_entry()? {
@@ -85,6 +93,49 @@ mod map {
             };
             deserializer.deserialize_map(visitor)
         }
+
+        #[allow(clippy::missing_errors_doc)]
+        fn deserialize_in_place<D>(deserializer: D, place: &mut Self) -> Result<(), D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct MapInPlaceVisitor<'a, K, V, S, A>(&'a mut HashMap<K, V, S, A>)
+            where
+                A: Allocator + Clone;
+
+            impl<'a, 'de, K, V, S, A> Visitor<'de> for MapInPlaceVisitor<'a, K, V, S, A>
+            where
+                K: Deserialize<'de> + Eq + Hash,
+                V: Deserialize<'de>,
+                S: BuildHasher + Default,
+                A: Allocator + Clone + Default,
+            {
+                type Value = ();
+
+                fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    formatter.write_str("a map")
+                }
+
+                #[cfg_attr(feature = "inline-more", inline)]
+                fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+                where
+                    M: MapAccess<'de>,
+                {
+                    // Clearing keeps the allocation, so this only grows the
+                    // table if it is too small for the incoming entries.
+                    self.0.clear();
+                    self.0.reserve(size_hint::cautious(map.size_hint()));
+
+                    while let Some((key, value)) = map.next_entry()? {
+                        self.0.insert(key, value);
+                    }
+
+                    Ok(())
+                }
+            }
+
+            deserializer.deserialize_map(MapInPlaceVisitor(place))
+        }
     }
 }
 
@@ -96,13 +147,15 @@ mod set {
     use serde::ser::{Serialize, Serializer};
 
     use crate::hash_set::HashSet;
+    use crate::raw::Allocator;
 
     use super::size_hint;
 
-    impl<T, H> Serialize for HashSet<T, H>
+    impl<T, H, A> Serialize for HashSet<T, H, A>
     where
         T: Serialize + Eq + Hash,
         H: BuildHasher,
+        A: Allocator + Clone,
     {
         #[cfg_attr(feature = "inline-more", inline)]
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -113,38 +166,44 @@ mod set {
         }
     }
 
-    impl<'de, T, S> Deserialize<'de> for HashSet<T, S>
+    impl<'de, T, S, A> Deserialize<'de> for HashSet<T, S, A>
     where
         T: Deserialize<'de> + Eq + Hash,
         S: BuildHasher + Default,
+        A: Allocator + Clone + Default,
     {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
             D: Deserializer<'de>,
         {
-            struct SeqVisitor<T, S> {
-                marker: PhantomData<HashSet<T, S>>,
+            struct SeqVisitor<T, S, A>
+            where
+                A: Allocator + Clone,
+            {
+                marker: PhantomData<HashSet<T, S, A>>,
             }
 
-            impl<'de, T, S> Visitor<'de> for SeqVisitor<T, S>
+            impl<'de, T, S, A> Visitor<'de> for SeqVisitor<T, S, A>
             where
                 T: Deserialize<'de> + Eq + Hash,
                 S: BuildHasher + Default,
+                A: Allocator + Clone + Default,
             {
-                type Value = HashSet<T, S>;
+                type Value = HashSet<T, S, A>;
 
                 fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
                     formatter.write_str("a sequence")
                 }
 
                 #[cfg_attr(feature = "inline-more", inline)]
-                fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+                fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
                 where
-                    A: SeqAccess<'de>,
+                    M: SeqAccess<'de>,
                 {
-                    let mut values = HashSet::with_capacity_and_hasher(
+                    let mut values = HashSet::with_capacity_and_hasher_in(
                         size_hint::cautious(seq.size_hint()),
                         S::default(),
+                        A::default(),
                     );
 
                     while let Some(value) = seq.next_element()? {
@@ -166,12 +225,15 @@ mod set {
         where
             D: Deserializer<'de>,
         {
-            struct SeqInPlaceVisitor<'a, T, S>(&'a mut HashSet<T, S>);
+            struct SeqInPlaceVisitor<'a, T, S, A>(&'a mut HashSet<T, S, A>)
+            where
+                A: Allocator + Clone;
 
-            impl<'a, 'de, T, S> Visitor<'de> for SeqInPlaceVisitor<'a, T, S>
+            impl<'a, 'de, T, S, A> Visitor<'de> for SeqInPlaceVisitor<'a, T, S, A>
             where
                 T: Deserialize<'de> + Eq + Hash,
                 S: BuildHasher + Default,
+                A: Allocator + Clone + Default,
             {
                 type Value = ();
 
@@ -180,9 +242,9 @@ mod set {
                 }
 
                 #[cfg_attr(feature = "inline-more", inline)]
-                fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+                fn visit_seq<M>(self, mut seq: M) -> Result<Self::Value, M::Error>
                 where
-                    A: SeqAccess<'de>,
+                    M: SeqAccess<'de>,
                 {
                     self.0.clear();
                     self.0.reserve(size_hint::cautious(seq.size_hint()));
@@ -199,3 +261,124 @@ mod set {
         }
     }
 }
+
+pub(crate) mod sorted {
+    use alloc::vec::Vec;
+    use serde::ser::{Serialize, Serializer};
+
+    use crate::hash_map::HashMap;
+    use crate::hash_set::HashSet;
+    use crate::raw::Allocator;
+
+    /// Serializes a [`HashMap`] or a [`HashSet`] with its entries sorted by
+    /// key.
+    ///
+    /// The `Serialize` impls of maps and sets write their entries in the
+    /// order of their tables, which depends on the hasher and on how the
+    /// table was built, so the output of a map with a random hasher differs
+    /// between runs. Through this wrapper it only depends on the contents, as
+    /// content-addressed storage and reproducible dumps require. Sorting
+    /// collects a reference to every entry first, in a vector as long as the
+    /// map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::serde_sorted::Sorted;
+    /// use hashbrown::HashMap;
+    /// use serde_test::{assert_ser_tokens, Token};
+    ///
+    /// let map: HashMap<char, i32> = [('b', 2), ('a', 1), ('c', 3)].into();
+    /// assert_ser_tokens(
+    ///     &Sorted(&map),
+    ///     &[
+    ///         Token::Map { len: Some(3) },
+    ///         Token::Char('a'),
+    ///         Token::I32(1),
+    ///         Token::Char('b'),
+    ///         Token::I32(2),
+    ///         Token::Char('c'),
+    ///         Token::I32(3),
+    ///         Token::MapEnd,
+    ///     ],
+    /// );
+    /// ```
+    #[derive(Clone, Copy, Debug)]
+    pub struct Sorted<'a, C: ?Sized>(pub &'a C);
+
+    impl<K, V, H, A> Serialize for Sorted<'_, HashMap<K, V, H, A>>
+    where
+        K: Serialize + Ord,
+        V: Serialize,
+        A: Allocator + Clone,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut entries: Vec<(&K, &V)> = self.0.iter().collect();
+            // Keys are distinct, so an unstable sort is deterministic.
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            serializer.collect_map(entries)
+        }
+    }
+
+    impl<T, H, A> Serialize for Sorted<'_, HashSet<T, H, A>>
+    where
+        T: Serialize + Ord,
+        A: Allocator + Clone,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut values: Vec<&T> = self.0.iter().collect();
+            values.sort_unstable();
+            serializer.collect_seq(values)
+        }
+    }
+
+    /// Serializes a [`HashMap`] or a [`HashSet`] with its entries sorted by
+    /// key, in the form `#[serde(serialize_with = "...")]` expects.
+    ///
+    /// Only the annotated field is sorted, not the maps and sets nested in
+    /// it, which need a [`Sorted`] of their own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    /// use serde::ser::{Serialize, Serializer};
+    /// use serde_test::{assert_ser_tokens, Token};
+    ///
+    /// // What `#[serde(serialize_with = "hashbrown::serde_sorted::serialize")]`
+    /// // on a field expands to.
+    /// struct Tags(HashSet<u8>);
+    ///
+    /// impl Serialize for Tags {
+    ///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    ///         hashbrown::serde_sorted::serialize(&self.0, serializer)
+    ///     }
+    /// }
+    ///
+    /// let tags = Tags([3, 1, 2].into());
+    /// assert_ser_tokens(
+    ///     &tags,
+    ///     &[
+    ///         Token::Seq { len: Some(3) },
+    ///         Token::U8(1),
+    ///         Token::U8(2),
+    ///         Token::U8(3),
+    ///         Token::SeqEnd,
+    ///     ],
+    /// );
+    /// ```
+    pub fn serialize<C, S>(collection: &C, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        C: ?Sized,
+        for<'a> Sorted<'a, C>: Serialize,
+        S: Serializer,
+    {
+        Sorted(collection).serialize(serializer)
+    }
+}
@@ -0,0 +1,423 @@
+use crate::map::{make_hash, DefaultHashBuilder, HashMap, IntoIter, Iter, Keys, Values};
+use crate::raw::{Allocator, Global, RawTable};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::ops::Index;
+
+/// An immutable hash map, built by [freezing] a [`HashMap`].
+///
+/// Freezing moves the entries of a map into a table of the smallest size
+/// which can hold them, without any tombstones left behind by removals, so a
+/// `FrozenMap` is as compact and as fast to search as the map allows.
+///
+/// A `FrozenMap` can only be read, which makes it `Sync` whenever its keys,
+/// values, hasher and allocator are: it can be shared between threads, for
+/// example behind an `Arc` or in a `static`, and looked up without any
+/// locking. Use [`thaw`] to turn it back into a `HashMap` for modification.
+///
+/// [freezing]: HashMap::freeze
+/// [`thaw`]: FrozenMap::thaw
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::{FrozenMap, HashMap};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let mut map = HashMap::new();
+/// map.insert("en", "Hello");
+/// map.insert("fr", "Bonjour");
+/// let frozen: Arc<FrozenMap<_, _>> = Arc::new(map.freeze());
+///
+/// let handle = {
+///     let frozen = Arc::clone(&frozen);
+///     thread::spawn(move || frozen.get("fr").copied())
+/// };
+/// assert_eq!(frozen.get("en"), Some(&"Hello"));
+/// assert_eq!(handle.join().unwrap(), Some("Bonjour"));
+/// ```
+pub struct FrozenMap<K, V, S = DefaultHashBuilder, A: Allocator + Clone = Global> {
+    map: HashMap<K, V, S, A>,
+}
+
+impl<K, V, S, A> HashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Converts the map into an immutable [`FrozenMap`].
+    ///
+    /// The entries are moved into a newly allocated table of the smallest
+    /// size which can hold them, which also gets rid of the tombstones left
+    /// behind by earlier removals.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = HashMap::with_capacity(1000);
+    /// map.insert(1, 2);
+    /// map.insert(3, 4);
+    ///
+    /// let frozen = map.freeze();
+    /// assert!(frozen.capacity() < 1000);
+    /// assert_eq!(frozen[&3], 4);
+    /// ```
+    pub fn freeze(self) -> FrozenMap<K, V, S, A> {
+        let HashMap {
+            hash_builder,
+            table,
+            ..
+        } = self;
+        let mut compact = RawTable::with_capacity_in(table.len(), table.allocator().clone());
+        for item in table {
+            let hash = make_hash::<K, S>(&hash_builder, &item.0);
+            // SAFETY: `compact` was allocated with room for every item.
+            unsafe {
+                compact.insert_no_grow(hash, item);
+            }
+        }
+        FrozenMap {
+            map: HashMap {
+                hash_builder,
+                table: compact,
+            },
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> FrozenMap<K, V, S, A> {
+    /// Converts the frozen map back into a [`HashMap`] which can be modified.
+    ///
+    /// This does not move any entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert(1, "a");
+    /// let frozen = map.freeze();
+    ///
+    /// let mut map = frozen.thaw();
+    /// map.insert(2, "b");
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn thaw(self) -> HashMap<K, V, S, A> {
+        self.map
+    }
+
+    /// Returns a reference to the underlying [`HashMap`].
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn as_map(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Returns a reference to the underlying allocator.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn allocator(&self) -> &A {
+        self.map.allocator()
+    }
+
+    /// Returns the number of elements the underlying table can hold, which is
+    /// the smallest capacity which fits [`len`] elements.
+    ///
+    /// [`len`]: FrozenMap::len
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a"), (2, "b")].into();
+    /// assert_eq!(map.freeze().len(), 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<i32, i32> = HashMap::new();
+    /// assert!(map.freeze().is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [("a", 1), ("b", 2)].into();
+    /// let frozen = map.freeze();
+    ///
+    /// let mut vec: Vec<(&str, i32)> = frozen.iter().map(|(k, v)| (*k, *v)).collect();
+    /// vec.sort_unstable();
+    /// assert_eq!(vec, [("a", 1), ("b", 2)]);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter()
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    /// The iterator element type is `&'a K`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        self.map.keys()
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    /// The iterator element type is `&'a V`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn values(&self) -> Values<'_, K, V> {
+        self.map.values()
+    }
+}
+
+impl<K, V, S, A> FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a")].into();
+    /// let frozen = map.freeze();
+    /// assert_eq!(frozen.get(&1), Some(&"a"));
+    /// assert_eq!(frozen.get(&2), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get(k)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a")].into();
+    /// let frozen = map.freeze();
+    /// assert_eq!(frozen.get_key_value(&1), Some((&1, &"a")));
+    /// assert_eq!(frozen.get_key_value(&2), None);
+    /// ```
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get_key_value(k)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a")].into();
+    /// let frozen = map.freeze();
+    /// assert!(frozen.contains_key(&1));
+    /// assert!(!frozen.contains_key(&2));
+    /// ```
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.contains_key(k)
+    }
+}
+
+impl<K, V, S, A> From<HashMap<K, V, S, A>> for FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Freezes the map, see [`HashMap::freeze`].
+    fn from(map: HashMap<K, V, S, A>) -> Self {
+        map.freeze()
+    }
+}
+
+impl<K, V, S, A> FromIterator<(K, V)> for FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+    A: Default + Allocator + Clone,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        iter.into_iter().collect::<HashMap<K, V, S, A>>().freeze()
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for FrozenMap<K, V, S, A> {
+    fn clone(&self) -> Self {
+        FrozenMap {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<K, V, S, A> PartialEq for FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<K, V, S, A> Eq for FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+}
+
+impl<K, V, S, A> Debug for FrozenMap<K, V, S, A>
+where
+    K: Debug,
+    V: Debug,
+    A: Allocator + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+impl<K, Q, V, S, A> Index<&Q> for FrozenMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `FrozenMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> IntoIterator for &'a FrozenMap<K, V, S, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> IntoIterator for FrozenMap<K, V, S, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        self.map.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test_frozen_map {
+    use super::FrozenMap;
+    use crate::HashMap;
+
+    #[test]
+    fn test_freeze_compacts() {
+        let mut map: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+        map.retain(|&k, _| k < 10);
+        let frozen = map.freeze();
+        assert_eq!(frozen.len(), 10);
+        assert_eq!(frozen.capacity(), 14);
+        for i in 0..1000 {
+            assert_eq!(frozen.get(&i), if i < 10 { Some(&i) } else { None });
+        }
+    }
+
+    #[test]
+    fn test_freeze_empty() {
+        let frozen = HashMap::<i32, i32>::with_capacity(100).freeze();
+        assert!(frozen.is_empty());
+        assert_eq!(frozen.capacity(), 0);
+        assert_eq!(frozen.get(&0), None);
+    }
+
+    #[test]
+    fn test_thaw_roundtrip() {
+        let frozen: FrozenMap<i32, i32> = (0..100).map(|i| (i, i * 2)).collect();
+        let copy = frozen.clone();
+        let mut map = frozen.thaw();
+        map.insert(100, 200);
+        assert_eq!(map.len(), 101);
+        assert_eq!(map.freeze().into_iter().count(), 101);
+        assert_eq!(copy[&50], 100);
+    }
+
+    #[test]
+    fn test_sync() {
+        fn assert_sync<T: Sync + Send>(_: &T) {}
+        let frozen: FrozenMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        assert_sync(&frozen);
+    }
+}
@@ -0,0 +1,436 @@
+//! A hash table which never hashes its values itself.
+//!
+//! [`HashTable`] has no [`BuildHasher`]: every operation takes the 64-bit
+//! hash of the value it is about, computed by the caller. The table stores
+//! the hash next to each value and reuses it when it grows, so there is no
+//! way for the table, or for code using it, to hash a value behind the
+//! caller's back. This suits engines which keep the hashes of their rows in a
+//! separate column and must use exactly those hashes.
+//!
+//! [`BuildHasher`]: core::hash::BuildHasher
+
+use crate::raw::{Allocator, Global, RawIntoIter, RawIter, RawTable};
+use crate::TryReserveError;
+use core::fmt::{self, Debug};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+/// Returns the hash stored with an entry, for growing the table.
+#[inline]
+fn stored_hash<T>(&(hash, _): &(u64, T)) -> u64 {
+    hash
+}
+
+/// A hash table whose operations all take caller-provided hashes.
+///
+/// `find`, `insert` and `remove` take the hash of the value explicitly, and
+/// lookups take a closure which decides whether a stored value is the one
+/// searched for. Values with equal hashes are told apart only by that
+/// closure, so the table can hold several values with the same hash.
+///
+/// Each entry stores its hash, eight bytes per value, which the table uses
+/// to move the entry when it grows.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::HashTable;
+///
+/// // Hashes computed elsewhere, for example by a vectorized kernel.
+/// let rows = [("apple", 0x1234_u64), ("pear", 0x5678)];
+///
+/// let mut table = HashTable::new();
+/// for &(name, hash) in &rows {
+///     table.insert(hash, name);
+/// }
+///
+/// assert_eq!(table.find(0x5678, |&name| name == "pear"), Some(&"pear"));
+/// assert_eq!(table.find(0x5678, |&name| name == "plum"), None);
+/// assert_eq!(table.remove(0x1234, |&name| name == "apple"), Some("apple"));
+/// assert_eq!(table.len(), 1);
+/// ```
+pub struct HashTable<T, A: Allocator + Clone = Global> {
+    table: RawTable<(u64, T), A>,
+}
+
+impl<T> HashTable<T> {
+    /// Creates an empty `HashTable`.
+    ///
+    /// The table does not allocate until the first value is inserted.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn new() -> Self {
+        Self {
+            table: RawTable::new(),
+        }
+    }
+
+    /// Creates an empty `HashTable` which can hold at least `capacity`
+    /// values without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: RawTable::with_capacity(capacity),
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> HashTable<T, A> {
+    /// Creates an empty `HashTable` which allocates with `alloc`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new_in(alloc: A) -> Self {
+        Self {
+            table: RawTable::new_in(alloc),
+        }
+    }
+
+    /// Creates an empty `HashTable` which can hold at least `capacity`
+    /// values without reallocating, and allocates with `alloc`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        Self {
+            table: RawTable::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    /// Returns the number of values in the table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the table contains no values.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns the number of values the table can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Removes all values, keeping the allocated memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Reserves capacity for at least `additional` more values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new capacity overflows `usize`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve(&mut self, additional: usize) {
+        self.table.reserve(additional, stored_hash);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more values.
+    ///
+    /// # Errors
+    ///
+    /// If the capacity overflows, or the allocator reports a failure, then
+    /// an error is returned.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.table.try_reserve(additional, stored_hash)
+    }
+
+    /// Shrinks the capacity of the table as much as possible.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn shrink_to_fit(&mut self) {
+        self.table.shrink_to(0, stored_hash);
+    }
+
+    /// Returns a reference to the value with hash `hash` for which `eq`
+    /// returns `true`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn find(&self, hash: u64, mut eq: impl FnMut(&T) -> bool) -> Option<&T> {
+        match self.table.get(hash, |(_, value)| eq(value)) {
+            Some((_, value)) => Some(value),
+            None => None,
+        }
+    }
+
+    /// Returns a mutable reference to the value with hash `hash` for which
+    /// `eq` returns `true`.
+    ///
+    /// The value must not be changed in a way which changes its hash.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn find_mut(&mut self, hash: u64, mut eq: impl FnMut(&T) -> bool) -> Option<&mut T> {
+        match self.table.get_mut(hash, |(_, value)| eq(value)) {
+            Some((_, value)) => Some(value),
+            None => None,
+        }
+    }
+
+    /// Inserts `value` with hash `hash` and returns a mutable reference to
+    /// it.
+    ///
+    /// The table does not look for an equal value first; use
+    /// [`find_or_insert_with`] to insert only values which are not present
+    /// yet.
+    ///
+    /// [`find_or_insert_with`]: HashTable::find_or_insert_with
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, hash: u64, value: T) -> &mut T {
+        &mut self.table.insert_entry(hash, (hash, value), stored_hash).1
+    }
+
+    /// Returns a mutable reference to the value with hash `hash` for which
+    /// `eq` returns `true`, inserting the value returned by `default` if
+    /// there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashTable;
+    ///
+    /// let mut counts: HashTable<(&str, u32)> = HashTable::new();
+    /// for (word, hash) in [("a", 1), ("b", 2), ("a", 1)] {
+    ///     counts.find_or_insert_with(hash, |&(w, _)| w == word, || (word, 0)).1 += 1;
+    /// }
+    /// assert_eq!(counts.find(1, |&(w, _)| w == "a"), Some(&("a", 2)));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn find_or_insert_with(
+        &mut self,
+        hash: u64,
+        mut eq: impl FnMut(&T) -> bool,
+        default: impl FnOnce() -> T,
+    ) -> &mut T {
+        match self.table.find(hash, |(_, value)| eq(value)) {
+            // SAFETY: the bucket was just found in the table, which stays
+            // borrowed for as long as the returned reference.
+            Some(bucket) => unsafe { &mut bucket.as_mut().1 },
+            None => self.insert(hash, default()),
+        }
+    }
+
+    /// Removes and returns the value with hash `hash` for which `eq` returns
+    /// `true`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove(&mut self, hash: u64, mut eq: impl FnMut(&T) -> bool) -> Option<T> {
+        match self.table.remove_entry(hash, |(_, value)| eq(value)) {
+            Some((_, value)) => Some(value),
+            None => None,
+        }
+    }
+
+    /// Retains only the values for which `f` returns `true`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn retain(&mut self, mut f: impl FnMut(&mut T) -> bool) {
+        // SAFETY: every bucket is visited once while the table is borrowed,
+        // and erasing the current bucket does not disturb the iterator.
+        unsafe {
+            for bucket in self.table.iter() {
+                if !f(&mut bucket.as_mut().1) {
+                    self.table.erase(bucket);
+                }
+            }
+        }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            // SAFETY: the iterator borrows the table.
+            inner: unsafe { self.table.iter() },
+            marker: PhantomData,
+        }
+    }
+
+    /// An iterator visiting all values together with their hashes, in
+    /// arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashTable;
+    ///
+    /// let mut table = HashTable::new();
+    /// table.insert(7, "seven");
+    /// assert_eq!(table.iter_hashed().collect::<Vec<_>>(), [(7, &"seven")]);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter_hashed(&self) -> impl Iterator<Item = (u64, &T)> + '_ {
+        // SAFETY: the iterator borrows the table.
+        unsafe { self.table.iter() }.map(|bucket| {
+            // SAFETY: the iterator only yields full buckets.
+            let (hash, value) = unsafe { bucket.as_ref() };
+            (*hash, value)
+        })
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for HashTable<T, A> {
+    fn clone(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone + Default> Default for HashTable<T, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+
+impl<T: Debug, A: Allocator + Clone> Debug for HashTable<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, A: Allocator + Clone> IntoIterator for &'a HashTable<T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T, A: Allocator + Clone> IntoIterator for HashTable<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IntoIter<T, A> {
+        IntoIter {
+            inner: self.table.into_iter(),
+        }
+    }
+}
+
+/// An iterator over the values of a [`HashTable`].
+///
+/// This `struct` is created by the [`iter`](HashTable::iter) method.
+pub struct Iter<'a, T> {
+    inner: RawIter<(u64, T)>,
+    marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<&'a T> {
+        let bucket = self.inner.next()?;
+        // SAFETY: the iterator borrows the table it came from.
+        Some(unsafe { &bucket.as_ref().1 })
+    }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+impl<T> Clone for Iter<'_, T> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Debug> Debug for Iter<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An owning iterator over the values of a [`HashTable`].
+///
+/// This `struct` is created by the `into_iter` method of [`HashTable`].
+pub struct IntoIter<T, A: Allocator + Clone = Global> {
+    inner: RawIntoIter<(u64, T), A>,
+}
+
+impl<T, A: Allocator + Clone> Iterator for IntoIter<T, A> {
+    type Item = T;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<T> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T, A: Allocator + Clone> ExactSizeIterator for IntoIter<T, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+impl<T, A: Allocator + Clone> FusedIterator for IntoIter<T, A> {}
+
+impl<T: Debug, A: Allocator + Clone> Debug for IntoIter<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // SAFETY: The iterator owns the values it hasn't yielded yet.
+        let values = self
+            .inner
+            .iter()
+            .map(|bucket| unsafe { &bucket.as_ref().1 });
+        f.debug_list().entries(values).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_hash_table {
+    use super::HashTable;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_grow_keeps_caller_hashes() {
+        // Every value gets the same hash, which the table must never replace
+        // with one of its own, even while it grows.
+        let mut table = HashTable::new();
+        for i in 0..200_u32 {
+            table.insert(42, i);
+        }
+        assert_eq!(table.len(), 200);
+        assert!(table.iter_hashed().all(|(hash, _)| hash == 42));
+        assert_eq!(table.find(42, |&v| v == 150), Some(&150));
+    }
+
+    #[test]
+    fn test_find_or_insert_remove_retain() {
+        let mut table = HashTable::with_capacity(4);
+        for i in 0..100_u64 {
+            table
+                .find_or_insert_with(i % 10, |&(k, _)| k == i % 10, || (i % 10, 0))
+                .1 += 1;
+        }
+        assert_eq!(table.len(), 10);
+        assert_eq!(table.find(3, |&(k, _)| k == 3), Some(&(3, 10)));
+        table.find_mut(3, |&(k, _)| k == 3).unwrap().1 = 0;
+        assert_eq!(table.remove(4, |&(k, _)| k == 4), Some((4, 10)));
+        table.retain(|&mut (k, _)| k % 2 == 0);
+        let mut keys: Vec<u64> = table.into_iter().map(|(k, _)| k).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, [0, 2, 6, 8]);
+    }
+}
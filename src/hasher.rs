@@ -0,0 +1,955 @@
+use crate::raw::{compose_hash, fold_hash128};
+use core::fmt;
+use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
+use core::marker::PhantomData;
+
+/// Multiplier of the integer hash: the fractional part of the golden ratio,
+/// an odd number with well distributed bits.
+const INTEGER_HASH_MULTIPLIER: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// A fast [`Hasher`] for integer keys.
+///
+/// Hashing an integer costs a single wide multiplication: the 128-bit product
+/// of the integer and a constant is folded to 64 bits by xoring its halves.
+/// This is enough for a hash table, which picks the first bucket to probe
+/// from the low bits of the hash and stores the top 7 bits as the tag which
+/// is compared against 16 buckets at once. Unlike with the identity function,
+/// every bit of the key affects the tag, so even small sequential keys get
+/// distinct tags and lookups rarely compare keys which do not match.
+///
+/// Other keys, like strings, are hashed 8 bytes at a time in the same way,
+/// which is fast but weaker than a general-purpose hasher.
+///
+/// The hash is neither keyed nor random, so anyone who controls the keys can
+/// make them collide. Only use it for keys which do not come from untrusted
+/// input.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::IntegerHashBuilder;
+/// use hashbrown::HashMap;
+///
+/// let mut map: HashMap<u64, &str, IntegerHashBuilder> = HashMap::default();
+/// map.insert(1, "a");
+/// assert_eq!(map[&1], "a");
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntegerHasher {
+    hash: u64,
+}
+
+/// A [`BuildHasher`] for [`IntegerHasher`].
+pub type IntegerHashBuilder = BuildHasherDefault<IntegerHasher>;
+
+impl IntegerHasher {
+    #[inline]
+    fn mix(&mut self, value: u64) {
+        self.hash = hash_integer(self.hash ^ value);
+    }
+}
+
+/// Returns the hash of an integer key of up to 64 bits under
+/// [`IntegerHasher`].
+#[inline]
+pub(crate) const fn hash_integer(value: u64) -> u64 {
+    let product = (value as u128) * (INTEGER_HASH_MULTIPLIER as u128);
+    (product as u64) ^ ((product >> 64) as u64)
+}
+
+impl Hasher for IntegerHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.mix(u64::from_le_bytes(word));
+        }
+        let mut word = [0; 8];
+        word[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+        // Mixing in the length keeps trailing zero bytes significant.
+        self.mix(u64::from_le_bytes(word) ^ ((bytes.len() as u64) << 56));
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.mix(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.mix(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.mix(u64::from(i));
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.mix(i);
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.mix(i as u64);
+        self.mix((i >> 64) as u64);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.mix(i as u64);
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.write_usize(i as usize);
+    }
+}
+
+impl Hasher128 for IntegerHasher {
+    /// Returns the hash of [`finish`] in the low half, and an independent mix
+    /// of it in the high half.
+    ///
+    /// [`finish`]: Hasher::finish
+    #[inline]
+    fn finish128(&self) -> u128 {
+        let high = hash_integer(self.hash ^ HIGH_HALF_SEED);
+        u128::from(high) << 64 | u128::from(self.hash)
+    }
+}
+
+/// Xored into the state of an [`IntegerHasher`] to derive the high half of its
+/// 128-bit hash.
+const HIGH_HALF_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// A [`BuildHasher`] with a fixed seed, whose hashes are the same on every
+/// run and on every platform.
+///
+/// Since the iteration order of a map only depends on the hashes of its keys
+/// and on the operations which built it, maps using the same `FixedState`
+/// iterate in the same order every time they are built the same way. With the
+/// `deterministic` feature, which makes `FixedState` the default hasher and
+/// lays tables out the same way on every platform, this also holds across
+/// platforms, which is useful for tests comparing output with snapshots. The
+/// `random-order` feature, which is meant to find code depending on the
+/// iteration order, overrides this.
+///
+/// `FixedState` builds [`IntegerHasher`]s starting from its seed, so it is
+/// just as fast, and just as unsuited to keys from untrusted input.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::FixedState;
+/// use hashbrown::HashMap;
+///
+/// let build = || {
+///     let mut map = HashMap::with_hasher(FixedState::new());
+///     map.extend((0..100).map(|i| (i, i)));
+///     map
+/// };
+/// # #[cfg(not(feature = "random-order"))]
+/// assert!(build().iter().eq(build().iter()));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixedState {
+    seed: u64,
+}
+
+/// Seed of [`FixedState::new`]: the first hexadecimal digits of pi.
+const FIXED_STATE_SEED: u64 = 0x243f_6a88_85a3_08d3;
+
+impl FixedState {
+    /// Creates a `FixedState` with the default seed.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_seed(FIXED_STATE_SEED)
+    }
+
+    /// Creates a `FixedState` with the given seed.
+    ///
+    /// Different seeds give unrelated hashes, and so different iteration
+    /// orders.
+    #[inline]
+    pub const fn with_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+}
+
+impl Default for FixedState {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildHasher for FixedState {
+    type Hasher = IntegerHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> IntegerHasher {
+        IntegerHasher { hash: self.seed }
+    }
+}
+
+/// Multiplier of the 32-bit integer hash: the fractional part of the golden
+/// ratio, truncated to 32 bits.
+const INTEGER_HASH32_MULTIPLIER: u32 = 0x9e37_79b9;
+
+/// A [`Hasher`] for integer keys which keeps a 32-bit hash word.
+///
+/// This is [`IntegerHasher`] cut down to 32 bits: every mixing step is a
+/// 32 by 32 bit multiplication folded back to 32 bits, which is a single
+/// instruction on 32-bit targets, where the 64-bit hasher needs several. Keys
+/// of up to 32 bits are mixed in one step.
+///
+/// The table takes the tag of a key from the top 7 bits of the 32-bit word and
+/// the bucket index from all of its bits, so the two are independent as long
+/// as the table has at most 2<sup>25</sup> buckets. Larger tables start
+/// probing buckets whose keys share their tag, and with more than
+/// 2<sup>32</sup> buckets not all buckets can be reached first; maps which may
+/// grow that large should use the 64-bit [`IntegerHashBuilder`].
+///
+/// Like [`IntegerHasher`], the hash is neither keyed nor random.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::IntegerHash32Builder;
+/// use hashbrown::HashMap;
+///
+/// let mut map: HashMap<u16, &str, IntegerHash32Builder> = HashMap::default();
+/// map.insert(1, "a");
+/// assert_eq!(map[&1], "a");
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IntegerHasher32 {
+    hash: u32,
+}
+
+/// A [`BuildHasher`] for [`IntegerHasher32`].
+pub type IntegerHash32Builder = BuildHasherDefault<IntegerHasher32>;
+
+impl IntegerHasher32 {
+    #[inline]
+    fn mix(&mut self, value: u32) {
+        self.hash = hash_integer32(self.hash ^ value);
+    }
+}
+
+/// Returns the hash of an integer key of up to 32 bits under
+/// [`IntegerHasher32`], before it is spread into the table's hash word.
+#[inline]
+pub(crate) const fn hash_integer32(value: u32) -> u32 {
+    let product = (value as u64) * (INTEGER_HASH32_MULTIPLIER as u64);
+    (product as u32) ^ ((product >> 32) as u32)
+}
+
+/// Spreads a 32-bit hash into the table's hash word: the tag comes from its
+/// top 7 bits and the bucket index from all 32 bits.
+#[inline]
+pub(crate) fn widen_hash32(hash: u32) -> u64 {
+    compose_hash(u64::from(hash), (hash >> 25) as u8)
+}
+
+impl Hasher for IntegerHasher32 {
+    #[inline]
+    fn finish(&self) -> u64 {
+        widen_hash32(self.hash)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(4);
+        for chunk in &mut chunks {
+            let mut word = [0; 4];
+            word.copy_from_slice(chunk);
+            self.mix(u32::from_le_bytes(word));
+        }
+        let mut word = [0; 4];
+        word[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+        self.mix(u32::from_le_bytes(word));
+        // Mixing in the length keeps trailing zero bytes significant.
+        self.mix(bytes.len() as u32);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.mix(u32::from(i));
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.mix(u32::from(i));
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.mix(i);
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.mix(i as u32);
+        self.mix((i >> 32) as u32);
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.write_u64(i as u64);
+        self.write_u64((i >> 64) as u64);
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        if usize::BITS <= 32 {
+            self.mix(i as u32);
+        } else {
+            self.write_u64(i as u64);
+        }
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.write_usize(i as usize);
+    }
+}
+
+/// A [`Hasher`] which can produce a 128-bit hash.
+///
+/// Wrapping the hash builder of a map in [`WideHashBuilder`] makes the map
+/// use both halves of this hash.
+pub trait Hasher128: Hasher {
+    /// Returns the 128-bit hash of the values written so far.
+    ///
+    /// The two halves of the hash should be independent of each other:
+    /// knowing the bits of one should say nothing about the bits of the other.
+    fn finish128(&self) -> u128;
+}
+
+/// A [`BuildHasher`] which takes the bucket index and the tag of a key from
+/// different halves of a 128-bit hash.
+///
+/// A map normally derives both the bucket which a key is looked up from and
+/// the 7-bit tag which is compared against 16 buckets at once from the same
+/// 64-bit hash. If the hash function is weak, keys which land in the same
+/// bucket are then more likely to share a tag too, and lookups compare more
+/// keys which do not match. With a [`Hasher128`], `WideHashBuilder` instead
+/// takes the tag from the high half of the hash and the bucket index from the
+/// low half, so the two are independent.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::{IntegerHashBuilder, WideHashBuilder};
+/// use hashbrown::HashMap;
+///
+/// let mut map = HashMap::with_hasher(WideHashBuilder::new(IntegerHashBuilder::default()));
+/// map.insert(1, "a");
+/// assert_eq!(map[&1], "a");
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WideHashBuilder<S> {
+    hash_builder: S,
+}
+
+impl<S> WideHashBuilder<S> {
+    /// Creates a `WideHashBuilder` which uses the 128-bit hashes of the
+    /// hashers built by `hash_builder`.
+    #[inline]
+    pub const fn new(hash_builder: S) -> Self {
+        Self { hash_builder }
+    }
+
+    /// Returns a reference to the wrapped hash builder.
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the wrapped hash builder.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.hash_builder
+    }
+}
+
+impl<S> BuildHasher for WideHashBuilder<S>
+where
+    S: BuildHasher,
+    S::Hasher: Hasher128,
+{
+    type Hasher = WideHasher<S::Hasher>;
+
+    #[inline]
+    fn build_hasher(&self) -> Self::Hasher {
+        WideHasher {
+            hasher: self.hash_builder.build_hasher(),
+        }
+    }
+}
+
+/// The [`Hasher`] of a [`WideHashBuilder`].
+///
+/// Its 64-bit hash is folded from the 128-bit hash of the wrapped hasher such
+/// that the map takes the tag of a key from the high half and the bucket index
+/// from the low half.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WideHasher<H> {
+    hasher: H,
+}
+
+macro_rules! forward_writes {
+    ($($method:ident($t:ty))*) => {
+        $(
+            #[inline]
+            fn $method(&mut self, i: $t) {
+                self.hasher.$method(i);
+            }
+        )*
+    };
+}
+
+impl<H: Hasher128> Hasher for WideHasher<H> {
+    #[inline]
+    fn finish(&self) -> u64 {
+        fold_hash128(self.hasher.finish128())
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+    }
+
+    forward_writes! {
+        write_u8(u8) write_u16(u16) write_u32(u32) write_u64(u64) write_u128(u128) write_usize(usize)
+        write_i8(i8) write_i16(i16) write_i32(i32) write_i64(i64) write_i128(i128) write_isize(isize)
+    }
+}
+
+impl<H: Hasher128> Hasher128 for WideHasher<H> {
+    #[inline]
+    fn finish128(&self) -> u128 {
+        self.hasher.finish128()
+    }
+}
+
+/// Decides how the hash of a key is split into the index of the bucket which
+/// lookups start from and the 7-bit tag stored in the control byte of the
+/// bucket.
+///
+/// A map normally takes the tag from the top 7 bits of the hash and the
+/// bucket index from its low bits, which suits hashers whose bits are all of
+/// the same quality. [`SplitHashBuilder`] applies another split to the hashes
+/// of any hash builder, for hashers which are weaker in some bits than in
+/// others.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::{HashSplit, SplitHashBuilder};
+/// use hashbrown::HashMap;
+/// use std::collections::hash_map::RandomState;
+///
+/// /// Takes the tag from the bits right above those of the bucket index.
+/// struct MiddleTag;
+///
+/// impl HashSplit for MiddleTag {
+///     fn index(hash: u64) -> u64 {
+///         hash
+///     }
+///
+///     fn tag(hash: u64) -> u8 {
+///         (hash >> 32) as u8 & 0x7f
+///     }
+/// }
+///
+/// let mut map = HashMap::with_hasher(SplitHashBuilder::<_, MiddleTag>::new(RandomState::new()));
+/// map.insert(1, "a");
+/// assert_eq!(map[&1], "a");
+/// ```
+pub trait HashSplit {
+    /// Returns the bits to take the bucket index from.
+    ///
+    /// The table takes the index from the low bits, as many as it needs for
+    /// its number of buckets. Only the low 25 bits are guaranteed to be used,
+    /// or the low 57 bits on 64-bit platforms.
+    fn index(hash: u64) -> u64;
+
+    /// Returns the tag, which must be less than 128.
+    fn tag(hash: u64) -> u8;
+}
+
+/// A split which takes the tag from the top 7 bits of the hash and the bucket
+/// index from the low bits, which is what maps do by default on 64-bit
+/// platforms.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HighTag;
+
+impl HashSplit for HighTag {
+    #[inline]
+    fn index(hash: u64) -> u64 {
+        hash
+    }
+
+    #[inline]
+    fn tag(hash: u64) -> u8 {
+        (hash >> 57) as u8
+    }
+}
+
+/// A split which takes the tag from the low 7 bits of the hash and the
+/// bucket index from the bits above them, for hashers whose low bits are
+/// the best mixed, like multiplicative hashes which are not folded.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LowTag;
+
+impl HashSplit for LowTag {
+    #[inline]
+    fn index(hash: u64) -> u64 {
+        hash >> 7
+    }
+
+    #[inline]
+    fn tag(hash: u64) -> u8 {
+        hash as u8 & 0x7f
+    }
+}
+
+/// A [`BuildHasher`] which splits the hashes of another hash builder into the
+/// bucket index and the tag of a key with the [`HashSplit`] `P`.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::{IntegerHashBuilder, LowTag, SplitHashBuilder};
+/// use hashbrown::HashMap;
+///
+/// let mut map: HashMap<u32, &str, SplitHashBuilder<IntegerHashBuilder, LowTag>> =
+///     HashMap::default();
+/// map.insert(1, "a");
+/// assert_eq!(map[&1], "a");
+/// ```
+pub struct SplitHashBuilder<S, P> {
+    hash_builder: S,
+    marker: PhantomData<fn() -> P>,
+}
+
+impl<S, P> SplitHashBuilder<S, P> {
+    /// Creates a `SplitHashBuilder` which splits the hashes of the hashers
+    /// built by `hash_builder`.
+    #[inline]
+    pub const fn new(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns a reference to the wrapped hash builder.
+    #[inline]
+    pub fn get_ref(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the wrapped hash builder.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.hash_builder
+    }
+}
+
+impl<S: Clone, P> Clone for SplitHashBuilder<S, P> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new(self.hash_builder.clone())
+    }
+}
+
+impl<S: Default, P> Default for SplitHashBuilder<S, P> {
+    #[inline]
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S: fmt::Debug, P> fmt::Debug for SplitHashBuilder<S, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SplitHashBuilder")
+            .field(&self.hash_builder)
+            .finish()
+    }
+}
+
+impl<S: BuildHasher, P: HashSplit> BuildHasher for SplitHashBuilder<S, P> {
+    type Hasher = SplitHasher<S::Hasher, P>;
+
+    #[inline]
+    fn build_hasher(&self) -> Self::Hasher {
+        SplitHasher {
+            hasher: self.hash_builder.build_hasher(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// The [`Hasher`] of a [`SplitHashBuilder`].
+pub struct SplitHasher<H, P> {
+    hasher: H,
+    marker: PhantomData<fn() -> P>,
+}
+
+impl<H: Clone, P> Clone for SplitHasher<H, P> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            hasher: self.hasher.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<H: fmt::Debug, P> fmt::Debug for SplitHasher<H, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SplitHasher").field(&self.hasher).finish()
+    }
+}
+
+impl<H: Hasher, P: HashSplit> Hasher for SplitHasher<H, P> {
+    #[inline]
+    fn finish(&self) -> u64 {
+        let hash = self.hasher.finish();
+        compose_hash(P::index(hash), P::tag(hash))
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+    }
+
+    forward_writes! {
+        write_u8(u8) write_u16(u16) write_u32(u32) write_u64(u64) write_u128(u128) write_usize(usize)
+        write_i8(i8) write_i16(i16) write_i32(i32) write_i64(i64) write_i128(i128) write_isize(isize)
+    }
+}
+
+/// A [`BuildHasher`] for [`AHash`] with either random or explicitly chosen
+/// keys.
+///
+/// [`RandomState::new`] and [`Default`] pick new keys for every instance, so
+/// the hashes of two maps, even in the same process, are unrelated. The keys
+/// are only as random as the `ahash` crate makes them: enable its
+/// `runtime-rng` feature to draw them from the operating system.
+///
+/// [`RandomState::with_seed`] and [`RandomState::with_seeds`] instead produce
+/// the same hashes for the same seeds in every process and on every run, for
+/// example to let several machines agree on how keys are partitioned.
+/// Anyone who knows the seeds can choose keys which collide, so they should
+/// be kept secret if the keys come from untrusted input.
+///
+/// [`AHash`]: https://crates.io/crates/ahash
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::RandomState;
+/// use hashbrown::HashMap;
+/// use std::hash::{BuildHasher, Hash, Hasher};
+///
+/// let mut map = HashMap::with_hasher(RandomState::with_seed(42));
+/// map.insert("a", 1);
+///
+/// // Another process using the same seed computes the same hashes.
+/// let hash = |state: &RandomState| {
+///     let mut hasher = state.build_hasher();
+///     "key".hash(&mut hasher);
+///     hasher.finish()
+/// };
+/// assert_eq!(hash(&RandomState::with_seed(42)), hash(map.hasher()));
+/// ```
+#[cfg(feature = "ahash")]
+#[derive(Clone)]
+pub struct RandomState {
+    inner: ahash::RandomState,
+}
+
+#[cfg(feature = "ahash")]
+impl RandomState {
+    /// Creates a `RandomState` with new random keys.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            inner: ahash::RandomState::new(),
+        }
+    }
+
+    /// Creates a `RandomState` whose keys are derived from `seed` alone.
+    ///
+    /// All `RandomState`s created from the same seed produce identical
+    /// hashers, in any process.
+    #[inline]
+    pub const fn with_seed(seed: u64) -> Self {
+        Self::with_seeds(seed, seed, seed, seed)
+    }
+
+    /// Creates a `RandomState` with the given keys.
+    ///
+    /// All `RandomState`s created from the same keys produce identical
+    /// hashers, in any process. It is fine for some of the keys to be zero or
+    /// equal to each other.
+    #[inline]
+    pub const fn with_seeds(k0: u64, k1: u64, k2: u64, k3: u64) -> Self {
+        Self {
+            inner: ahash::RandomState::with_seeds(k0, k1, k2, k3),
+        }
+    }
+}
+
+#[cfg(feature = "ahash")]
+impl Default for RandomState {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "ahash")]
+impl BuildHasher for RandomState {
+    type Hasher = ahash::AHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> ahash::AHasher {
+        self.inner.build_hasher()
+    }
+}
+
+#[cfg(feature = "ahash")]
+impl fmt::Debug for RandomState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("RandomState { .. }")
+    }
+}
+
+#[cfg(test)]
+mod test_integer_hasher {
+    use super::{
+        hash_integer32, FixedState, Hasher128, HighTag, IntegerHash32Builder, IntegerHashBuilder,
+        IntegerHasher, LowTag, SplitHashBuilder, WideHashBuilder,
+    };
+    use crate::map::make_hash;
+    use crate::raw::{h1, h2};
+    use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_tags_of_small_keys() {
+        // Small sequential keys must spread over all the tags.
+        let tags: HashSet<u8> = (0..1024u32)
+            .map(|i| h2(make_hash::<u32, _>(&IntegerHashBuilder::default(), &i)))
+            .collect();
+        assert_eq!(tags.len(), 128);
+    }
+
+    #[test]
+    fn test_hash32_tags_and_index() {
+        // Small sequential keys must spread over all the tags, and the index
+        // must keep all 32 bits of the hash word.
+        let hash_builder = IntegerHash32Builder::default();
+        let hashes: std::vec::Vec<u64> = (0..1024u16)
+            .map(|i| make_hash::<u16, _>(&hash_builder, &i))
+            .collect();
+        let tags: HashSet<u8> = hashes.iter().map(|&hash| h2(hash)).collect();
+        assert_eq!(tags.len(), 128);
+        for (i, &hash) in hashes.iter().enumerate() {
+            let word = hash_integer32(i as u32);
+            assert_eq!(h2(hash), (word >> 25) as u8);
+            assert_eq!(h1(hash) & 0x01ff_ffff, word as usize & 0x01ff_ffff);
+        }
+
+        let mut map: crate::HashMap<u32, u32, IntegerHash32Builder> = crate::HashMap::default();
+        map.extend((0..10_000).map(|i| (i, i)));
+        assert!((0..10_000).all(|i| map[&i] == i));
+    }
+
+    #[test]
+    fn test_hash32_byte_keys() {
+        let hash = |bytes: &[u8]| make_hash::<[u8], _>(&IntegerHash32Builder::default(), bytes);
+        assert_ne!(hash(b"a"), hash(b"a\0"));
+        assert_ne!(hash(b"abcd"), hash(b"abce"));
+    }
+
+    #[test]
+    fn test_byte_keys() {
+        let hash = |bytes: &[u8]| make_hash::<[u8], _>(&IntegerHashBuilder::default(), bytes);
+        assert_ne!(hash(b"a"), hash(b"a\0"));
+        assert_ne!(hash(b"abcdefgh"), hash(b"abcdefgi"));
+        assert_eq!(hash(b"hello world"), hash(b"hello world"));
+    }
+
+    #[test]
+    fn test_wide_hash_halves() {
+        let hash_builder = WideHashBuilder::new(IntegerHashBuilder::default());
+        for i in 0..1000u64 {
+            let mut hasher = IntegerHasher::default();
+            i.hash(&mut hasher);
+            let wide = hasher.finish128();
+            assert_eq!(wide as u64, hasher.finish());
+
+            let hash = make_hash::<u64, _>(&hash_builder, &i);
+            assert_eq!(h2(hash), (wide >> 121) as u8);
+            assert_eq!(h1(hash) & 0xffff, (wide as usize) & 0xffff);
+
+            let mut hasher = hash_builder.build_hasher();
+            i.hash(&mut hasher);
+            assert_eq!(hasher.finish128(), wide);
+        }
+    }
+
+    #[test]
+    fn test_wide_hash_independent_tags() {
+        // The low half is the identity, whose top bits are zero for small
+        // keys, so only the high half can spread them over the tags.
+        #[derive(Default)]
+        struct SplitHasher(u64);
+
+        impl Hasher for SplitHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+
+            fn write(&mut self, bytes: &[u8]) {
+                for &byte in bytes {
+                    self.0 = self.0.rotate_left(8) ^ u64::from(byte);
+                }
+            }
+
+            fn write_u64(&mut self, i: u64) {
+                self.0 = i;
+            }
+        }
+
+        impl Hasher128 for SplitHasher {
+            fn finish128(&self) -> u128 {
+                u128::from(self.0.wrapping_mul(0x9e37_79b9_7f4a_7c15)) << 64 | u128::from(self.0)
+            }
+        }
+
+        let narrow = BuildHasherDefault::<SplitHasher>::default();
+        let wide = WideHashBuilder::new(narrow.clone());
+        let tags = |hash_builder: &dyn Fn(&u64) -> u64| {
+            (0..1024u64)
+                .map(|i| h2(hash_builder(&i)))
+                .collect::<HashSet<u8>>()
+                .len()
+        };
+        assert_eq!(tags(&|i| make_hash::<u64, _>(&narrow, i)), 1);
+        assert_eq!(tags(&|i| make_hash::<u64, _>(&wide, i)), 128);
+    }
+
+    #[test]
+    fn test_hash_splits() {
+        let plain = IntegerHashBuilder::default();
+        let high = SplitHashBuilder::<_, HighTag>::new(plain.clone());
+        let low = SplitHashBuilder::<_, LowTag>::new(plain.clone());
+        for i in 0..1000u64 {
+            let hash = make_hash::<u64, _>(&plain, &i);
+            if cfg!(target_pointer_width = "64") {
+                // The default split leaves the hash as it is.
+                assert_eq!(make_hash::<u64, _>(&high, &i), hash);
+            }
+
+            let split = make_hash::<u64, _>(&low, &i);
+            assert_eq!(h2(split), hash as u8 & 0x7f);
+            assert_eq!(h1(split) & 0xffff, (hash >> 7) as usize & 0xffff);
+        }
+    }
+
+    #[test]
+    fn test_split_hash_map() {
+        let mut map: crate::HashMap<u32, u32, SplitHashBuilder<IntegerHashBuilder, LowTag>> =
+            crate::HashMap::default();
+        for i in 0..1000 {
+            map.insert(i, i * 2);
+        }
+        assert!((0..1000).all(|i| map[&i] == i * 2));
+        assert_eq!(map.get(&1000), None);
+    }
+
+    #[test]
+    fn test_fixed_state() {
+        let hash = |state: &FixedState, key: &str| make_hash::<str, _>(state, key);
+        assert_eq!(
+            hash(&FixedState::new(), "a"),
+            hash(&FixedState::default(), "a")
+        );
+        assert_ne!(hash(&FixedState::new(), "a"), hash(&FixedState::new(), "b"));
+        assert_ne!(
+            hash(&FixedState::with_seed(1), "a"),
+            hash(&FixedState::with_seed(2), "a")
+        );
+        // The hashes are part of the iteration order which the `deterministic`
+        // feature guarantees, so they must never change.
+        assert_eq!(
+            make_hash::<u64, _>(&FixedState::new(), &1),
+            0x4fcd_fcac_ca5a_aa31
+        );
+    }
+}
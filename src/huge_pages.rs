@@ -0,0 +1,165 @@
+//! An allocator which backs large tables with transparent huge pages.
+//!
+//! Lookups in a table spread over gigabytes of memory touch a different page
+//! almost every time, so they spend much of their time on TLB misses. Backing
+//! such a table with 2 MiB pages instead of 4 KiB ones lets the TLB cover 512
+//! times as much of it.
+//!
+//! [`HugePageAlloc`] rounds allocations above a threshold up to whole huge
+//! pages, aligns them to a huge page boundary and asks Linux to back them with
+//! transparent huge pages through `madvise(MADV_HUGEPAGE)`. Smaller
+//! allocations, and all allocations on other platforms, are passed to the
+//! global allocator unchanged.
+//!
+//! # Examples
+//!
+//! ```
+//! use hashbrown::huge_pages::HugePageAlloc;
+//! use hashbrown::HashMap;
+//!
+//! let mut map = HashMap::new_in(HugePageAlloc::new());
+//! map.insert(1, "a");
+//! assert_eq!(map[&1], "a");
+//! ```
+
+use crate::alloc::alloc::{alloc, dealloc, Layout};
+use core::ptr::NonNull;
+
+/// The size of a huge page on x86-64 and most configurations of AArch64.
+pub const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// An allocator which requests transparent huge pages for allocations of at
+/// least a threshold size.
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HugePageAlloc {
+    threshold: usize,
+}
+
+impl HugePageAlloc {
+    /// Creates an allocator which uses huge pages for allocations of at
+    /// least [`HUGE_PAGE_SIZE`] bytes.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_threshold(HUGE_PAGE_SIZE)
+    }
+
+    /// Creates an allocator which uses huge pages for allocations of at
+    /// least `threshold` bytes.
+    ///
+    /// Such allocations are rounded up to a multiple of [`HUGE_PAGE_SIZE`],
+    /// so a threshold well below it wastes memory. Zero-sized allocations
+    /// never allocate, even with a threshold of 0.
+    #[inline]
+    pub const fn with_threshold(threshold: usize) -> Self {
+        Self { threshold }
+    }
+
+    /// Returns the size in bytes from which allocations use huge pages.
+    #[inline]
+    pub const fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Returns the layout which is actually allocated for `layout`.
+    #[inline]
+    fn huge_layout(&self, layout: Layout) -> Option<Layout> {
+        if !cfg!(target_os = "linux") || layout.size() < self.threshold {
+            return None;
+        }
+        let size = layout.size().checked_add(HUGE_PAGE_SIZE - 1)? & !(HUGE_PAGE_SIZE - 1);
+        Layout::from_size_align(size, usize::max(layout.align(), HUGE_PAGE_SIZE)).ok()
+    }
+
+    /// Allocates memory for `layout`, returning the pointer and the usable
+    /// size of the allocation.
+    pub(crate) fn allocate_huge(&self, layout: Layout) -> Option<(NonNull<u8>, usize)> {
+        if layout.size() == 0 {
+            // The global allocator must not be asked for zero bytes, whatever
+            // the threshold is. Any non-null aligned pointer will do.
+            return Some((
+                unsafe { NonNull::new_unchecked(layout.align() as *mut u8) },
+                0,
+            ));
+        }
+        match self.huge_layout(layout) {
+            Some(huge) => {
+                let ptr = NonNull::new(unsafe { alloc(huge) })?;
+                #[cfg(target_os = "linux")]
+                unsafe {
+                    // This is only a hint: if the kernel has transparent huge
+                    // pages disabled the memory is still usable as it is.
+                    libc::madvise(ptr.as_ptr().cast(), huge.size(), libc::MADV_HUGEPAGE);
+                }
+                Some((ptr, huge.size()))
+            }
+            None => Some((NonNull::new(unsafe { alloc(layout) })?, layout.size())),
+        }
+    }
+
+    /// Frees memory returned by `allocate_huge` for the same `layout`.
+    pub(crate) unsafe fn deallocate_huge(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() == 0 {
+            return;
+        }
+        match self.huge_layout(layout) {
+            Some(huge) => dealloc(ptr.as_ptr(), huge),
+            None => dealloc(ptr.as_ptr(), layout),
+        }
+    }
+}
+
+impl Default for HugePageAlloc {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test_huge_pages {
+    use super::{HugePageAlloc, HUGE_PAGE_SIZE};
+    use crate::HashMap;
+
+    #[test]
+    fn test_small_and_large_tables() {
+        let alloc = HugePageAlloc::with_threshold(64 * 1024);
+        let mut small = HashMap::new_in(alloc);
+        let mut large = HashMap::with_capacity_in(100_000, alloc);
+        for i in 0..100_000_u64 {
+            large.insert(i, i * 2);
+            if i < 100 {
+                small.insert(i, i);
+            }
+        }
+        assert_eq!(small.len(), 100);
+        assert_eq!(large.len(), 100_000);
+        assert!((0..100_000).all(|i| large[&i] == i * 2));
+
+        let (ptr, layout) = large.table.allocation_info();
+        assert!(layout.size() >= 64 * 1024);
+        if cfg!(target_os = "linux") {
+            assert_eq!(ptr.as_ptr() as usize % HUGE_PAGE_SIZE, 0);
+        }
+
+        large.shrink_to(10);
+        assert_eq!(large.len(), 100_000);
+        large.clear();
+        large.shrink_to_fit();
+    }
+
+    #[test]
+    fn test_zero_sized() {
+        let alloc = HugePageAlloc::with_threshold(0);
+        let layout = core::alloc::Layout::from_size_align(0, 16).unwrap();
+        let (ptr, size) = alloc.allocate_huge(layout).unwrap();
+        assert_eq!(size, 0);
+        assert_eq!(ptr.as_ptr() as usize % 16, 0);
+        unsafe { alloc.deallocate_huge(ptr, layout) };
+
+        let mut map = HashMap::new_in(alloc);
+        map.insert(1, ());
+        assert_eq!(map.len(), 1);
+    }
+}
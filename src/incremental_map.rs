@@ -0,0 +1,596 @@
+//! A hash map which moves its entries to a larger table a few at a time.
+//!
+//! When a [`HashMap`] runs out of room, the insertion which triggers the
+//! resize moves every entry to the new table before it returns. For tables
+//! with millions of entries that takes many milliseconds, which an async task
+//! cannot spend without stalling the other tasks of its executor.
+//!
+//! [`IncrementalHashMap`] instead keeps the old table next to the new one
+//! while a resize is pending, looks keys up in both, and moves the entries
+//! over only when asked to by [`poll_resize`], in chunks of bounded size.
+//!
+//! [`HashMap`]: crate::HashMap
+//! [`poll_resize`]: IncrementalHashMap::poll_resize
+
+use crate::map::{equivalent_key, make_hash, make_insert_hash, DefaultHashBuilder};
+use crate::raw::{RawIter, RawTable};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem;
+
+/// A table whose entries are being moved into the current table.
+struct Pending<K, V> {
+    table: RawTable<(K, V)>,
+    // The index of the next bucket to move.
+    next_bucket: usize,
+}
+
+/// A hash map whose resizes are spread over many calls instead of being done
+/// all at once.
+///
+/// When an insertion finds the table full, a table of twice the capacity is
+/// allocated and becomes the current one, but the entries stay in the old
+/// table. Lookups, updates and removals check both tables, and new keys go
+/// into the current one. [`poll_resize`] moves a bounded number of entries
+/// to the current table and returns whether the resize is complete, so that
+/// callers can interleave the moves with other work:
+///
+/// ```
+/// use hashbrown::IncrementalHashMap;
+///
+/// let mut map = IncrementalHashMap::new();
+/// for i in 0..100_000 {
+///     map.insert(i, i);
+///     // In an async task, this would yield to the executor between chunks:
+///     //     while !map.poll_resize(1024) { yield_now().await; }
+///     while !map.poll_resize(1024) {}
+/// }
+/// assert_eq!(map.len(), 100_000);
+/// assert_eq!(map.get(&500), Some(&500));
+/// ```
+///
+/// A resize which has not been completed when the current table fills up
+/// again is completed by the insertion which finds it full, before that
+/// insertion starts the next resize. Since the current table has room for
+/// twice the entries of the old one, this only happens if the map grows by
+/// as many entries as it had when the resize started without
+/// [`poll_resize`] being called in between.
+///
+/// Alternatively, [`set_steps_per_insert`] makes every insertion move the
+/// entries of a few buckets itself. This bounds the work of any single
+/// insertion, which matters more than throughput for soft real-time code.
+///
+/// Lookups in a map with a pending resize probe both tables, so they are
+/// slower than in a [`HashMap`] until the resize completes.
+///
+/// [`poll_resize`]: IncrementalHashMap::poll_resize
+/// [`set_steps_per_insert`]: IncrementalHashMap::set_steps_per_insert
+/// [`HashMap`]: crate::HashMap
+pub struct IncrementalHashMap<K, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    table: RawTable<(K, V)>,
+    pending: Option<Pending<K, V>>,
+    // Number of buckets of a pending resize which every insertion moves.
+    steps_per_insert: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> IncrementalHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `IncrementalHashMap`.
+    ///
+    /// The map is initially created with a capacity of 0, so it will not
+    /// allocate until it is first inserted into.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `IncrementalHashMap` with at least the specified
+    /// capacity.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> IncrementalHashMap<K, V, S> {
+    /// Creates an empty `IncrementalHashMap` which will use the given hash
+    /// builder to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::new(),
+            pending: None,
+            steps_per_insert: 0,
+        }
+    }
+
+    /// Creates an empty `IncrementalHashMap` with at least the specified
+    /// capacity, using `hash_builder` to hash the keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::with_capacity(capacity),
+            pending: None,
+            steps_per_insert: 0,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements the map can hold without starting
+    /// another resize.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.table.len() + self.pending_len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if a resize is pending, so that some entries are still
+    /// in the old table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_resizing(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Returns the number of buckets of a pending resize which every
+    /// insertion moves.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn steps_per_insert(&self) -> usize {
+        self.steps_per_insert
+    }
+
+    /// Makes every insertion move the entries of `buckets` buckets of a
+    /// pending resize, as if it called [`poll_resize`] with `buckets`, in
+    /// addition to its own work. The default is 0, which leaves all moves to
+    /// [`poll_resize`].
+    ///
+    /// With at least 2 buckets per insertion, a resize always completes
+    /// before the current table fills up, so that no insertion ever moves
+    /// more entries than that. Starting a resize then only allocates the new
+    /// table and marks its buckets as empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IncrementalHashMap;
+    ///
+    /// let mut map = IncrementalHashMap::new();
+    /// map.set_steps_per_insert(4);
+    /// for i in 0..100_000 {
+    ///     // Never moves more than 4 buckets' worth of entries.
+    ///     map.insert(i, i);
+    /// }
+    /// assert_eq!(map.len(), 100_000);
+    /// ```
+    ///
+    /// [`poll_resize`]: IncrementalHashMap::poll_resize
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn set_steps_per_insert(&mut self, buckets: usize) {
+        self.steps_per_insert = buckets;
+    }
+
+    /// Returns the number of entries which a pending resize still has to
+    /// move.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn pending_len(&self) -> usize {
+        self.pending.as_ref().map_or(0, |p| p.table.len())
+    }
+
+    /// Clears the map, removing all key-value pairs and dropping the old
+    /// table of a pending resize. Keeps the allocated memory of the current
+    /// table for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.pending = None;
+        self.table.clear();
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        // SAFETY: The tables outlive the iterators, which borrow the map.
+        unsafe {
+            Iter {
+                current: self.table.iter(),
+                pending: self.pending.as_ref().map(|p| p.table.iter()),
+                remaining: self.len(),
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<K, V, S> IncrementalHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Moves the entries of at most `max_buckets` buckets of the old table
+    /// to the current table, and returns `true` if no resize is pending
+    /// afterwards.
+    ///
+    /// Every bucket takes a small, bounded amount of work to check and, if
+    /// it is full, to move. The old table is freed as soon as its last entry
+    /// has been moved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IncrementalHashMap;
+    ///
+    /// let mut map = IncrementalHashMap::with_capacity(3);
+    /// for i in 0..4 {
+    ///     map.insert(i, i);
+    /// }
+    /// assert!(map.is_resizing());
+    /// assert_eq!(map.pending_len(), 3);
+    ///
+    /// let mut polls = 1;
+    /// while !map.poll_resize(1) {
+    ///     polls += 1;
+    /// }
+    /// assert!(polls <= 4);
+    /// assert!(!map.is_resizing());
+    /// assert_eq!(map.len(), 4);
+    /// ```
+    pub fn poll_resize(&mut self, max_buckets: usize) -> bool {
+        let pending = match self.pending {
+            Some(ref mut pending) => pending,
+            None => return true,
+        };
+        let end = usize::min(
+            pending.next_bucket.saturating_add(max_buckets),
+            pending.table.buckets(),
+        );
+        while pending.next_bucket < end && !pending.table.is_empty() {
+            let index = pending.next_bucket;
+            pending.next_bucket += 1;
+            // SAFETY: `index` is below the number of buckets of the old table
+            // and the current table has room for all of its entries.
+            unsafe {
+                if pending.table.is_bucket_full(index) {
+                    let item = pending.table.remove(pending.table.bucket(index));
+                    let hash = make_insert_hash::<K, S>(&self.hash_builder, &item.0);
+                    self.table.insert_no_grow(hash, item);
+                }
+            }
+        }
+        if pending.table.is_empty() {
+            self.pending = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Moves all remaining entries of a pending resize to the current table.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn finish_resize(&mut self) {
+        self.poll_resize(usize::MAX);
+    }
+
+    /// Makes room for one more entry in the current table, completing a
+    /// pending resize and starting a new one if it is full.
+    fn reserve_one(&mut self) {
+        if self.len() < self.table.capacity() {
+            return;
+        }
+        self.finish_resize();
+        let capacity = usize::max(self.table.len() * 2, 3);
+        let old = mem::replace(&mut self.table, RawTable::with_capacity(capacity));
+        self.pending = Some(Pending {
+            table: old,
+            next_bucket: 0,
+        });
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let found = match self.table.get(hash, equivalent_key(k)) {
+            Some(found) => Some(found),
+            None => self.pending.as_ref()?.table.get(hash, equivalent_key(k)),
+        };
+        found.map(|(key, value)| (key, value))
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let found = match self.table.get_mut(hash, equivalent_key(k)) {
+            Some(found) => Some(found),
+            None => self
+                .pending
+                .as_mut()?
+                .table
+                .get_mut(hash, equivalent_key(k)),
+        };
+        found.map(|(_, value)| value)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(k).is_some()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    /// Otherwise the value is updated and the old value is returned.
+    ///
+    /// If a resize is pending, this first moves the entries of
+    /// [`steps_per_insert`] buckets to the current table. If the table is
+    /// full, this starts a resize, completing a previous one first if it is
+    /// still pending.
+    ///
+    /// [`steps_per_insert`]: IncrementalHashMap::steps_per_insert
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IncrementalHashMap;
+    ///
+    /// let mut map = IncrementalHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map.get(&37), Some(&"b"));
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        if self.steps_per_insert > 0 {
+            self.poll_resize(self.steps_per_insert);
+        }
+        if let Some(item) = self.get_mut(&k) {
+            return Some(mem::replace(item, v));
+        }
+        self.reserve_one();
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+        // SAFETY: `reserve_one` left room for one more entry besides those
+        // which a pending resize still has to move.
+        unsafe {
+            self.table.insert_no_grow(hash, (k, v));
+        }
+        None
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        match self.table.remove_entry(hash, equivalent_key(k)) {
+            Some(entry) => Some(entry),
+            None => self
+                .pending
+                .as_mut()?
+                .table
+                .remove_entry(hash, equivalent_key(k)),
+        }
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(k).map(|(_, v)| v)
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for IncrementalHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S: Default> Default for IncrementalHashMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for IncrementalHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a IncrementalHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an `IncrementalHashMap` in arbitrary
+/// order.
+///
+/// This `struct` is created by the [`iter`] method on
+/// [`IncrementalHashMap`].
+///
+/// [`iter`]: IncrementalHashMap::iter
+pub struct Iter<'a, K, V> {
+    current: RawIter<(K, V)>,
+    pending: Option<RawIter<(K, V)>>,
+    remaining: usize,
+    marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            current: self.current.clone(),
+            pending: self.pending.clone(),
+            remaining: self.remaining,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let bucket = match self.current.next() {
+            Some(bucket) => bucket,
+            None => self.pending.as_mut()?.next()?,
+        };
+        self.remaining -= 1;
+        // SAFETY: The tables outlive the iterator.
+        let (k, v) = unsafe { bucket.as_ref() };
+        Some((k, v))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_incremental_map {
+    use super::IncrementalHashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_operations_during_resize() {
+        let mut map = IncrementalHashMap::new();
+        let mut resizes = 0;
+        for i in 0..10_000_u32 {
+            let was_resizing = map.is_resizing();
+            map.insert(i, i);
+            if map.is_resizing() && !was_resizing {
+                resizes += 1;
+                // Everything is still reachable while nothing has moved.
+                assert_eq!(map.pending_len(), i as usize);
+                assert!((0..=i).all(|k| map.get(&k) == Some(&k)));
+            }
+            if i % 3 == 0 {
+                *map.get_mut(&(i / 2)).unwrap() += 1;
+                *map.get_mut(&(i / 2)).unwrap() -= 1;
+            }
+            if i % 5 == 0 {
+                assert_eq!(map.remove(&(i / 5)), Some(i / 5));
+                assert_eq!(map.insert(i / 5, i / 5), None);
+            }
+            map.poll_resize(16);
+            assert_eq!(map.iter().count(), map.len());
+        }
+        assert!(resizes > 5);
+        assert_eq!(map.len(), 10_000);
+        map.finish_resize();
+        assert!(!map.is_resizing());
+        let mut keys: Vec<u32> = map
+            .iter()
+            .map(|(k, v)| {
+                assert_eq!(k, v);
+                *k
+            })
+            .collect();
+        keys.sort_unstable();
+        assert!(keys.into_iter().eq(0..10_000));
+    }
+
+    #[test]
+    fn test_unpolled_resizes_complete_on_insert() {
+        let mut map = IncrementalHashMap::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+            assert!(map.len() <= map.capacity());
+        }
+        assert!((0..1000).all(|i| map.get(&i) == Some(&i)));
+        map.clear();
+        assert!(map.is_empty());
+        assert!(!map.is_resizing());
+    }
+
+    #[test]
+    fn test_steps_per_insert() {
+        for steps in [2, 3, 16] {
+            let mut map = IncrementalHashMap::new();
+            map.set_steps_per_insert(steps);
+            for i in 0..20_000_u32 {
+                let before = map.pending_len();
+                let was_resizing = map.is_resizing();
+                map.insert(i, i);
+                if was_resizing {
+                    // The previous resize was never completed all at once.
+                    assert!(before - map.pending_len() <= steps);
+                }
+                if i % 4 == 0 {
+                    map.remove(&(i / 4));
+                }
+            }
+            assert_eq!(map.len(), 15_000);
+            assert!((5_000..20_000).all(|i| map.get(&i) == Some(&i)));
+        }
+    }
+}
@@ -0,0 +1,948 @@
+//! A hash map which keeps its entries in insertion order.
+//!
+//! [`IndexMap`] stores its entries densely in a vector, in the order they were
+//! inserted, and uses a [`RawTable`] of indices into that vector to look keys
+//! up. Each entry can therefore be addressed both by its key and by its
+//! position, and iteration follows the insertion order.
+
+use crate::map::{make_hash, DefaultHashBuilder};
+use crate::raw::RawTable;
+use crate::Equivalent;
+use alloc::vec::{self, Vec};
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::Index;
+use core::slice;
+
+/// An entry of an [`IndexMap`], stored with the hash of its key so that the
+/// index table can be rebuilt without hashing the keys again.
+#[derive(Clone)]
+struct Entry<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+}
+
+/// A hash map which iterates in insertion order and whose entries can also be
+/// accessed by their position.
+///
+/// The entries live in a vector, in the order they were first inserted, and
+/// the hash table only stores the index of each entry. Lookups by key probe
+/// the table like a [`HashMap`] does, and then compare the key of the entry at
+/// the index found; lookups by position are plain vector accesses.
+///
+/// Inserting a key which is already present replaces its value but keeps its
+/// position. Removing an entry with [`swap_remove`] moves the last entry into
+/// its place, which makes removal O(1) but perturbs the order.
+///
+/// [`HashMap`]: crate::HashMap
+/// [`swap_remove`]: IndexMap::swap_remove
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::IndexMap;
+///
+/// let mut map = IndexMap::new();
+/// map.insert("a", 1);
+/// map.insert("b", 2);
+/// map.insert("c", 3);
+/// assert_eq!(map.get_index(1), Some((&"b", &2)));
+/// assert_eq!(map.get_full("c"), Some((2, &"c", &3)));
+///
+/// assert_eq!(map.swap_remove("a"), Some(1));
+/// let keys: Vec<_> = map.keys().copied().collect();
+/// assert_eq!(keys, ["c", "b"]);
+/// ```
+pub struct IndexMap<K, V, S = DefaultHashBuilder> {
+    indices: RawTable<usize>,
+    entries: Vec<Entry<K, V>>,
+    hash_builder: S,
+}
+
+/// Returns a function computing the hash of the entry an index points to, for
+/// use when the index table grows.
+fn index_hasher<K, V>(entries: &[Entry<K, V>]) -> impl Fn(&usize) -> u64 + '_ {
+    move |&i| entries[i].hash
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> IndexMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `IndexMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let map: IndexMap<i32, i32> = IndexMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `IndexMap` with at least the specified capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let map: IndexMap<i32, i32> = IndexMap::with_capacity(10);
+    /// assert!(map.capacity() >= 10);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S> {
+    /// Creates an empty `IndexMap` which will use the given hash builder to
+    /// hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            indices: RawTable::new(),
+            entries: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    /// Creates an empty `IndexMap` with at least the specified capacity, using
+    /// `hash_builder` to hash the keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            indices: RawTable::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            hash_builder,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements the map can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        usize::min(self.indices.capacity(), self.entries.capacity())
+    }
+
+    /// Returns the number of elements in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// assert_eq!(map.len(), 0);
+    /// map.insert(1, "a");
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.entries.clear();
+    }
+
+    /// Returns the key-value pair at position `index`, in insertion order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// assert_eq!(map.get_index(0), Some((&"a", &1)));
+    /// assert_eq!(map.get_index(2), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries.get(index).map(|e| (&e.key, &e.value))
+    }
+
+    /// Returns the key and a mutable reference to the value at position
+    /// `index`, in insertion order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        self.entries.get_mut(index).map(|e| (&e.key, &mut e.value))
+    }
+
+    /// Returns the first key-value pair in insertion order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn first(&self) -> Option<(&K, &V)> {
+        self.get_index(0)
+    }
+
+    /// Returns the last key-value pair in insertion order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn last(&self) -> Option<(&K, &V)> {
+        self.entries.last().map(|e| (&e.key, &e.value))
+    }
+
+    /// Removes the last key-value pair and returns it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// assert_eq!(map.pop(), Some((2, "b")));
+    /// assert!(!map.contains_key(&2));
+    /// ```
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        let entry = self.entries.pop()?;
+        let index = self.entries.len();
+        self.indices
+            .remove_entry(entry.hash, |&i| i == index)
+            .expect("index missing from the table");
+        Some((entry.key, entry.value))
+    }
+
+    /// Removes the key-value pair at position `index` and returns it, moving
+    /// the last pair into its place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map: IndexMap<_, _> = (0..4).map(|i| (i, i)).collect();
+    /// assert_eq!(map.swap_remove_index(1), Some((1, 1)));
+    /// assert_eq!(map.get_index(1), Some((&3, &3)));
+    /// assert_eq!(map.get_index_of(&3), Some(1));
+    /// ```
+    pub fn swap_remove_index(&mut self, index: usize) -> Option<(K, V)> {
+        let hash = self.entries.get(index)?.hash;
+        self.indices
+            .remove_entry(hash, |&i| i == index)
+            .expect("index missing from the table");
+        Some(self.swap_remove_finish(index))
+    }
+
+    /// Removes the entry at `index`, whose index has already been removed from
+    /// the table, and points the table to the new position of the entry moved
+    /// into its place.
+    fn swap_remove_finish(&mut self, index: usize) -> (K, V) {
+        let entry = self.entries.swap_remove(index);
+        if let Some(moved) = self.entries.get(index) {
+            let last = self.entries.len();
+            *self
+                .indices
+                .get_mut(moved.hash, |&i| i == last)
+                .expect("index missing from the table") = index;
+        }
+        (entry.key, entry.value)
+    }
+
+    /// Retains only the elements specified by the predicate, keeping the
+    /// order of the retained ones.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map: IndexMap<i32, i32> = (0..8).map(|x| (x, x * 10)).collect();
+    /// map.retain(|&k, _| k % 2 == 0);
+    /// let keys: Vec<_> = map.keys().copied().collect();
+    /// assert_eq!(keys, [0, 2, 4, 6]);
+    /// assert_eq!(map.get_index_of(&4), Some(2));
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let len = self.entries.len();
+        self.entries.retain_mut(|e| f(&e.key, &mut e.value));
+        if self.entries.len() < len {
+            self.rebuild_indices();
+        }
+    }
+
+    /// Refills the index table from the entries, after they were moved.
+    fn rebuild_indices(&mut self) {
+        self.indices.clear();
+        for (i, entry) in self.entries.iter().enumerate() {
+            // SAFETY: The table had room for at least as many entries before.
+            unsafe {
+                self.indices.insert_no_grow(entry.hash, i);
+            }
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in insertion order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// An iterator visiting all key-value pairs in insertion order, with
+    /// mutable references to the values.
+    /// The iterator element type is `(&'a K, &'a mut V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    /// An iterator visiting all keys in insertion order.
+    /// The iterator element type is `&'a K`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in insertion order.
+    /// The iterator element type is `&'a V`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+}
+
+impl<K, V, S> IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Reserves capacity for at least `additional` more elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve(&mut self, additional: usize) {
+        self.indices
+            .reserve(additional, index_hasher(&self.entries));
+        self.entries.reserve(additional);
+    }
+
+    /// Returns the position of the entry for `k`.
+    #[inline]
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        let entries = &self.entries;
+        self.indices
+            .get(hash, |&i| k.equivalent(&entries[i].key))
+            .copied()
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, the pair is appended and
+    /// [`None`] is returned. Otherwise the value is updated, the key keeps its
+    /// position, and the old value is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        self.insert_full(k, v).1
+    }
+
+    /// Inserts a key-value pair into the map, and returns the position of the
+    /// key along with its old value, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// assert_eq!(map.insert_full("a", 1), (0, None));
+    /// assert_eq!(map.insert_full("b", 2), (1, None));
+    /// assert_eq!(map.insert_full("a", 3), (0, Some(1)));
+    /// ```
+    pub fn insert_full(&mut self, k: K, v: V) -> (usize, Option<V>) {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        if let Some(i) = self.find(hash, &k) {
+            return (i, Some(mem::replace(&mut self.entries[i].value, v)));
+        }
+        let i = self.entries.len();
+        self.indices.insert(hash, i, index_hasher(&self.entries));
+        self.entries.push(Entry {
+            hash,
+            key: k,
+            value: v,
+        });
+        (i, None)
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_full(k).map(|(_, _, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_full(k).map(|(_, k, v)| (k, v))
+    }
+
+    /// Returns the position of the key along with the stored key and its
+    /// value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    /// assert_eq!(map.get_full("b"), Some((1, &"b", &2)));
+    /// assert_eq!(map.get_full("c"), None);
+    /// ```
+    #[inline]
+    pub fn get_full<Q>(&self, k: &Q) -> Option<(usize, &K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let i = self.find(hash, k)?;
+        let entry = &self.entries[i];
+        Some((i, &entry.key, &entry.value))
+    }
+
+    /// Returns the position of the key in insertion order.
+    #[inline]
+    pub fn get_index_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        self.find(hash, k)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let i = self.get_index_of(k)?;
+        Some(&mut self.entries[i].value)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_index_of(k).is_some()
+    }
+
+    /// Removes a key from the map, returning its value, if any. The last
+    /// entry is moved into the position of the removed one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::IndexMap;
+    ///
+    /// let mut map = IndexMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(2, "b");
+    /// map.insert(3, "c");
+    /// assert_eq!(map.swap_remove(&1), Some("a"));
+    /// assert_eq!(map.swap_remove(&1), None);
+    /// assert_eq!(map.get_index(0), Some((&3, &"c")));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn swap_remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.swap_remove_full(k).map(|(_, _, v)| v)
+    }
+
+    /// Removes a key from the map, returning its former position, the stored
+    /// key and its value. The last entry is moved into the position of the
+    /// removed one.
+    pub fn swap_remove_full<Q>(&mut self, k: &Q) -> Option<(usize, K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let entries = &self.entries;
+        let i = self
+            .indices
+            .remove_entry(hash, |&i| k.equivalent(&entries[i].key))?;
+        let (key, value) = self.swap_remove_finish(i);
+        Some((i, key, value))
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for IndexMap<K, V, S> {
+    fn clone(&self) -> Self {
+        IndexMap {
+            indices: self.indices.clone(),
+            entries: self.entries.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K, V, S> PartialEq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    /// Compares the maps as maps: the order of the entries does not matter.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<K, V, S> Eq for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K: Debug, V: Debug, S> Debug for IndexMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S: Default> Default for IndexMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `IndexMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a IndexMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a mut IndexMap<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K, V, S> IntoIterator for IndexMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    /// Creates a consuming iterator, that is, one that moves each key-value
+    /// pair out of the map in insertion order.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+/// An iterator over the entries of an `IndexMap` in insertion order.
+///
+/// This `struct` is created by the [`iter`] method on [`IndexMap`].
+///
+/// [`iter`]: IndexMap::iter
+pub struct Iter<'a, K, V> {
+    inner: slice::Iter<'a, Entry<K, V>>,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next().map(|e| (&e.key, &e.value))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|e| (&e.key, &e.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// A mutable iterator over the entries of an `IndexMap` in insertion order.
+///
+/// This `struct` is created by the [`iter_mut`] method on [`IndexMap`].
+///
+/// [`iter_mut`]: IndexMap::iter_mut
+pub struct IterMut<'a, K, V> {
+    inner: slice::IterMut<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        self.inner.next().map(|e| (&e.key, &mut e.value))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|e| (&e.key, &mut e.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+impl<K, V> FusedIterator for IterMut<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for IterMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.inner.as_slice().iter().map(|e| (&e.key, &e.value)))
+            .finish()
+    }
+}
+
+/// An iterator over the keys of an `IndexMap` in insertion order.
+///
+/// This `struct` is created by the [`keys`] method on [`IndexMap`].
+///
+/// [`keys`]: IndexMap::keys
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Keys {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<&'a K> {
+        self.inner.next().map(|(k, _)| k)
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Keys<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(k, _)| k)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+impl<K: Debug, V> Debug for Keys<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the values of an `IndexMap` in insertion order.
+///
+/// This `struct` is created by the [`values`] method on [`IndexMap`].
+///
+/// [`values`]: IndexMap::values
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Values {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<&'a V> {
+        self.inner.next().map(|(_, v)| v)
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Values<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, v)| v)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+impl<K, V: Debug> Debug for Values<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An owning iterator over the entries of an `IndexMap` in insertion order.
+///
+/// This `struct` is created by the [`into_iter`] method on [`IndexMap`]
+/// (provided by the [`IntoIterator`] trait).
+///
+/// [`into_iter`]: IndexMap::into_iter
+pub struct IntoIter<K, V> {
+    inner: vec::IntoIter<Entry<K, V>>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(K, V)> {
+        self.inner.next().map(|e| (e.key, e.value))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|e| (e.key, e.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K: Debug, V: Debug> Debug for IntoIter<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.inner.as_slice().iter().map(|e| (&e.key, &e.value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test_index_map {
+    use super::IndexMap;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    /// Checks that every entry can be found at its position.
+    fn check(map: &IndexMap<u32, u32>) {
+        for (i, (k, v)) in map.iter().enumerate() {
+            assert_eq!(map.get_full(k), Some((i, k, v)));
+        }
+        assert_eq!(map.indices.len(), map.len());
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = IndexMap::new();
+        for i in (0..1000u32).rev() {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.insert(500, 0), Some(1000));
+        let keys: Vec<u32> = map.keys().copied().collect();
+        assert_eq!(keys, (0..1000).rev().collect::<Vec<_>>());
+        assert_eq!(map.get_index(499), Some((&500, &0)));
+        check(&map);
+    }
+
+    #[test]
+    fn test_swap_remove() {
+        let mut map: IndexMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        for i in (0..100).step_by(3) {
+            let index = map.get_index_of(&i).unwrap();
+            assert_eq!(map.swap_remove_full(&i), Some((index, i, i)));
+            assert_eq!(map.swap_remove(&i), None);
+            check(&map);
+        }
+        assert_eq!(map.len(), 66);
+        // The last entry has nothing to move into its place.
+        let last = *map.last().unwrap().0;
+        assert_eq!(map.swap_remove_index(65), Some((last, last)));
+        assert_eq!(map.swap_remove_index(65), None);
+        let last = *map.last().unwrap().0;
+        assert_eq!(map.pop(), Some((last, last)));
+        assert!(!map.contains_key(&last));
+        check(&map);
+    }
+
+    #[test]
+    fn test_retain_keeps_order() {
+        let mut map: IndexMap<u32, u32> = (0..200).rev().map(|i| (i, i)).collect();
+        map.retain(|k, v| {
+            *v += 1;
+            k % 3 == 0
+        });
+        let keys: Vec<u32> = map.keys().copied().collect();
+        assert_eq!(
+            keys,
+            (0..200).rev().filter(|k| k % 3 == 0).collect::<Vec<_>>()
+        );
+        assert!(map.values().zip(map.keys()).all(|(v, k)| *v == k + 1));
+        check(&map);
+        map.insert(1000, 0);
+        check(&map);
+    }
+
+    #[test]
+    fn test_borrowed_keys_and_eq() {
+        let mut a: IndexMap<String, usize> = IndexMap::new();
+        a.insert("x".to_string(), 1);
+        a.insert("y".to_string(), 2);
+        *a.get_mut("x").unwrap() += 10;
+        assert_eq!(a["x"], 11);
+        let b: IndexMap<String, usize> = [("y".to_string(), 2), ("x".to_string(), 11)]
+            .into_iter()
+            .collect();
+        assert_eq!(a, b);
+        let pairs: Vec<(String, usize)> = b.into_iter().collect();
+        assert_eq!(pairs, [("y".to_string(), 2), ("x".to_string(), 11)]);
+    }
+}
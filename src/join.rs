@@ -0,0 +1,276 @@
+//! The build and probe sides of a hash join.
+//!
+//! A hash join builds a table from the hashes of the join keys of one input,
+//! the build side, then looks up the hashes of the keys of the other input,
+//! the probe side, and emits every pair of rows whose keys are equal.
+//! [`JoinTable`] holds the build side as `(hash, row)` pairs; the rows
+//! themselves stay wherever the caller keeps them, and are only compared
+//! through a closure taking the two row ids.
+//!
+//! # Examples
+//!
+//! ```
+//! use hashbrown::join::JoinTable;
+//! use std::collections::hash_map::DefaultHasher;
+//! use std::hash::{Hash, Hasher};
+//!
+//! fn hash(key: &str) -> u64 {
+//!     let mut hasher = DefaultHasher::new();
+//!     key.hash(&mut hasher);
+//!     hasher.finish()
+//! }
+//!
+//! let build = ["a", "b", "a"];
+//! let probe = ["a", "c", "b"];
+//!
+//! let table = JoinTable::build(build.iter().enumerate().map(|(row, key)| (hash(key), row)));
+//!
+//! let hashes: Vec<u64> = probe.iter().map(|key| hash(key)).collect();
+//! let mut pairs = Vec::new();
+//! table.probe_batch(&hashes, |b, p| build[b] == probe[p], &mut pairs);
+//! assert_eq!(pairs, [(0, 0), (2, 0), (1, 2)]);
+//! ```
+
+use crate::batch::BATCH;
+use crate::raw::RawTable;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Marks the end of a chain of rows.
+const END: usize = usize::MAX;
+
+/// The build side of a hash join: a table of rows by the hash of their keys.
+///
+/// The table holds one bucket per distinct hash, pointing to the chain of all
+/// the rows with that hash, in the order they were inserted. A probe walks the
+/// chain and asks the caller which of the rows actually match, so rows with
+/// equal keys, and rows whose different keys happen to have the same hash,
+/// are all handled the same way. See the [module documentation](self).
+pub struct JoinTable {
+    // The hash of each chain, with the indices in `rows` of its first and
+    // last rows.
+    table: RawTable<(u64, usize, usize)>,
+    // The id of every row, with the index in `rows` of the next row of its
+    // chain, or `END`.
+    rows: Vec<(usize, usize)>,
+}
+
+impl JoinTable {
+    /// Creates an empty `JoinTable`.
+    pub fn new() -> Self {
+        Self {
+            table: RawTable::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Creates an empty `JoinTable` with room for at least `capacity` rows
+    /// with distinct hashes.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            table: RawTable::with_capacity(capacity),
+            rows: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Creates a `JoinTable` from the `(hash, row)` pairs of the build side.
+    pub fn build<I: IntoIterator<Item = (u64, usize)>>(rows: I) -> Self {
+        let rows = rows.into_iter();
+        let mut table = Self::with_capacity(rows.size_hint().0);
+        for (hash, row) in rows {
+            table.insert(hash, row);
+        }
+        table
+    }
+
+    /// Returns the number of rows in the table.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Returns `true` if the table contains no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the number of distinct hashes in the table.
+    pub fn distinct_hashes(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Removes all rows, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.table.clear();
+        self.rows.clear();
+    }
+
+    /// Adds a row of the build side whose key has the given hash.
+    ///
+    /// Probes return the rows with the same hash in the order they were
+    /// inserted.
+    pub fn insert(&mut self, hash: u64, row: usize) {
+        let index = self.rows.len();
+        self.rows.push((row, END));
+        match self.table.get_mut(hash, |&(h, _, _)| h == hash) {
+            Some((_, _, last)) => {
+                self.rows[*last].1 = index;
+                *last = index;
+            }
+            None => {
+                self.table
+                    .insert(hash, (hash, index, index), |&(hash, _, _)| hash);
+            }
+        }
+    }
+
+    /// Returns the rows whose keys have the given hash, in the order they
+    /// were inserted.
+    ///
+    /// These are candidates: rows whose keys differ but collide on the hash
+    /// are included, and must be told apart by comparing the keys.
+    pub fn candidates(&self, hash: u64) -> Candidates<'_> {
+        let next = match self.table.get(hash, |&(h, _, _)| h == hash) {
+            Some(&(_, first, _)) => first,
+            None => END,
+        };
+        Candidates {
+            rows: &self.rows,
+            next,
+        }
+    }
+
+    /// Probes the table with a batch of rows of the probe side, and appends
+    /// a `(build row, probe row)` pair to `pairs` for every build row which
+    /// matches a probe row.
+    ///
+    /// The probe row with the hash `hashes[i]` is identified by `i`. `eq` is
+    /// called with a build row and a probe row with the same hash, and
+    /// returns whether their keys are equal. A probe row is paired with every
+    /// build row it matches, in the order the build rows were inserted, and
+    /// the pairs of the probe rows follow the order of `hashes`.
+    ///
+    /// The control bytes the hashes of a batch probe are prefetched before
+    /// any of them is looked up. Returns the number of pairs appended.
+    pub fn probe_batch<F>(
+        &self,
+        hashes: &[u64],
+        mut eq: F,
+        pairs: &mut Vec<(usize, usize)>,
+    ) -> usize
+    where
+        F: FnMut(usize, usize) -> bool,
+    {
+        let len = pairs.len();
+        for (chunk, hashes) in hashes.chunks(BATCH).enumerate() {
+            for &hash in hashes {
+                self.table.prefetch(hash);
+            }
+            for (i, &hash) in hashes.iter().enumerate() {
+                let probe = chunk * BATCH + i;
+                for build in self.candidates(hash) {
+                    if eq(build, probe) {
+                        pairs.push((build, probe));
+                    }
+                }
+            }
+        }
+        pairs.len() - len
+    }
+}
+
+impl Default for JoinTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for JoinTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinTable")
+            .field("len", &self.len())
+            .field("distinct_hashes", &self.distinct_hashes())
+            .finish()
+    }
+}
+
+/// An iterator over the rows of a [`JoinTable`] with a given hash.
+///
+/// This `struct` is created by [`JoinTable::candidates`].
+#[derive(Clone)]
+pub struct Candidates<'a> {
+    rows: &'a [(usize, usize)],
+    next: usize,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        if self.next == END {
+            return None;
+        }
+        let (row, next) = self.rows[self.next];
+        self.next = next;
+        Some(row)
+    }
+}
+
+impl core::iter::FusedIterator for Candidates<'_> {}
+
+impl fmt::Debug for Candidates<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_join {
+    use super::JoinTable;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_multi_match() {
+        // Keys 0..10 on the build side, every key three times, with a hash
+        // which makes every pair of keys collide.
+        let keys: Vec<u64> = (0..30).map(|i| i % 10).collect();
+        let hash = |key: u64| key / 2;
+        let table = JoinTable::build(keys.iter().enumerate().map(|(row, &k)| (hash(k), row)));
+        assert_eq!(table.len(), 30);
+        assert_eq!(table.distinct_hashes(), 5);
+        assert_eq!(
+            table.candidates(hash(3)).collect::<Vec<_>>(),
+            [2, 3, 12, 13, 22, 23]
+        );
+        assert_eq!(table.candidates(100).next(), None);
+
+        let probe: Vec<u64> = (0..40).map(|i| 39 - i).collect();
+        let hashes: Vec<u64> = probe.iter().map(|&k| hash(k)).collect();
+        let mut pairs = Vec::new();
+        let count = table.probe_batch(&hashes, |b, p| keys[b] == probe[p], &mut pairs);
+        assert_eq!(count, 30);
+
+        let mut expected = Vec::new();
+        for (p, &k) in probe.iter().enumerate() {
+            for (b, &key) in keys.iter().enumerate() {
+                if key == k {
+                    expected.push((b, p));
+                }
+            }
+        }
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn test_empty() {
+        let mut table = JoinTable::new();
+        let mut pairs = Vec::new();
+        assert_eq!(table.probe_batch(&[1, 2, 3], |_, _| true, &mut pairs), 0);
+        table.insert(1, 7);
+        assert_eq!(table.probe_batch(&[1, 2, 1], |_, _| true, &mut pairs), 2);
+        assert_eq!(pairs, [(7, 0), (7, 2)]);
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(table.candidates(1).count(), 0);
+    }
+}
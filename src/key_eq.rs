@@ -0,0 +1,228 @@
+//! Vectorized equality for fixed-size keys.
+//!
+//! Once the control bytes have narrowed a lookup down to a few candidate
+//! buckets, comparing the stored keys is the remaining cost of a hit. For
+//! small fixed-size keys the whole comparison fits in one or two SIMD
+//! registers, so [`FastKeyEq`] compares them with a single vector compare
+//! and mask instead of going through the generic `PartialEq` code path.
+//!
+//! The trait is sealed and implemented for `u64`, `u128`, `[u8; 16]` and
+//! `[u8; 32]`. It is used by [`HashMap::get_fast`] on every toolchain and,
+//! with the `nightly` feature, by every lookup whose key type is one of these
+//! types, through a specialization of [`Equivalent`].
+//!
+//! [`HashMap::get_fast`]: crate::HashMap::get_fast
+//! [`Equivalent`]: crate::Equivalent
+
+#[cfg(all(
+    target_feature = "sse2",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(miri)
+))]
+mod imp {
+    #[cfg(target_arch = "x86")]
+    use core::arch::x86;
+    #[cfg(target_arch = "x86_64")]
+    use core::arch::x86_64 as x86;
+
+    /// Returns whether the 16 bytes at `a` and `b` are equal.
+    #[inline]
+    unsafe fn eq_16(a: *const u8, b: *const u8) -> bool {
+        let a = x86::_mm_loadu_si128(a.cast());
+        let b = x86::_mm_loadu_si128(b.cast());
+        x86::_mm_movemask_epi8(x86::_mm_cmpeq_epi8(a, b)) == 0xffff
+    }
+
+    #[inline]
+    pub fn bytes_16(a: &[u8; 16], b: &[u8; 16]) -> bool {
+        // SAFETY: both arrays are 16 bytes long and unaligned loads are used.
+        unsafe { eq_16(a.as_ptr(), b.as_ptr()) }
+    }
+
+    #[inline]
+    pub fn bytes_32(a: &[u8; 32], b: &[u8; 32]) -> bool {
+        // SAFETY: both arrays are 32 bytes long, so both halves are in bounds.
+        unsafe {
+            let lo = x86::_mm_cmpeq_epi8(
+                x86::_mm_loadu_si128(a.as_ptr().cast()),
+                x86::_mm_loadu_si128(b.as_ptr().cast()),
+            );
+            let hi = x86::_mm_cmpeq_epi8(
+                x86::_mm_loadu_si128(a.as_ptr().add(16).cast()),
+                x86::_mm_loadu_si128(b.as_ptr().add(16).cast()),
+            );
+            x86::_mm_movemask_epi8(x86::_mm_and_si128(lo, hi)) == 0xffff
+        }
+    }
+
+    #[inline]
+    pub fn int_128(a: u128, b: u128) -> bool {
+        bytes_16(&a.to_ne_bytes(), &b.to_ne_bytes())
+    }
+}
+
+#[cfg(not(all(
+    target_feature = "sse2",
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(miri)
+)))]
+mod imp {
+    // Without SSE2 the keys are compared as whole machine words, which is
+    // still branch-free and avoids a byte-wise loop.
+
+    #[inline]
+    fn word(bytes: &[u8], i: usize) -> u64 {
+        let mut word = [0; 8];
+        word.copy_from_slice(&bytes[i * 8..i * 8 + 8]);
+        u64::from_ne_bytes(word)
+    }
+
+    #[inline]
+    fn words_eq(a: &[u8], b: &[u8]) -> bool {
+        let mut diff = 0;
+        for i in 0..a.len() / 8 {
+            diff |= word(a, i) ^ word(b, i);
+        }
+        diff == 0
+    }
+
+    #[inline]
+    pub fn bytes_16(a: &[u8; 16], b: &[u8; 16]) -> bool {
+        words_eq(a, b)
+    }
+
+    #[inline]
+    pub fn bytes_32(a: &[u8; 32], b: &[u8; 32]) -> bool {
+        words_eq(a, b)
+    }
+
+    #[inline]
+    pub fn int_128(a: u128, b: u128) -> bool {
+        a == b
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for u64 {}
+    impl Sealed for u128 {}
+    impl Sealed for [u8; 16] {}
+    impl Sealed for [u8; 32] {}
+}
+
+/// Key types with a vectorized equality comparison.
+///
+/// This trait is sealed: it is implemented for `u64`, `u128`, `[u8; 16]`
+/// and `[u8; 32]` and cannot be implemented outside of this crate. The
+/// comparison always agrees with `PartialEq`.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::FastKeyEq;
+///
+/// let a = [7u8; 32];
+/// let mut b = a;
+/// assert!(a.fast_eq(&b));
+/// b[31] = 0;
+/// assert!(!a.fast_eq(&b));
+/// ```
+pub trait FastKeyEq: private::Sealed + Eq {
+    /// Returns whether `self` and `other` are equal.
+    fn fast_eq(&self, other: &Self) -> bool;
+}
+
+impl FastKeyEq for u64 {
+    #[inline]
+    fn fast_eq(&self, other: &Self) -> bool {
+        // A single scalar compare is already as wide as the key.
+        *self == *other
+    }
+}
+
+impl FastKeyEq for u128 {
+    #[inline]
+    fn fast_eq(&self, other: &Self) -> bool {
+        imp::int_128(*self, *other)
+    }
+}
+
+impl FastKeyEq for [u8; 16] {
+    #[inline]
+    fn fast_eq(&self, other: &Self) -> bool {
+        imp::bytes_16(self, other)
+    }
+}
+
+impl FastKeyEq for [u8; 32] {
+    #[inline]
+    fn fast_eq(&self, other: &Self) -> bool {
+        imp::bytes_32(self, other)
+    }
+}
+
+/// Specializations of [`Equivalent`] which route lookups of the
+/// [`FastKeyEq`] types through the vectorized comparison.
+///
+/// [`Equivalent`]: crate::Equivalent
+#[cfg(feature = "nightly")]
+mod spec {
+    use super::FastKeyEq;
+    use crate::Equivalent;
+
+    macro_rules! fast_equivalent {
+        ($($ty:ty),*) => {$(
+            impl Equivalent<$ty> for $ty {
+                #[inline]
+                fn equivalent(&self, key: &$ty) -> bool {
+                    self.fast_eq(key)
+                }
+            }
+        )*};
+    }
+
+    fast_equivalent!(u64, u128, [u8; 16], [u8; 32]);
+}
+
+#[cfg(test)]
+mod test_key_eq {
+    use super::FastKeyEq;
+
+    #[test]
+    fn test_bytes_16() {
+        let mut a = [0u8; 16];
+        for (i, byte) in a.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+        assert!(a.fast_eq(&a));
+        for i in 0..16 {
+            let mut b = a;
+            b[i] ^= 0x80;
+            assert!(!a.fast_eq(&b));
+        }
+    }
+
+    #[test]
+    fn test_bytes_32() {
+        let mut a = [0u8; 32];
+        for (i, byte) in a.iter_mut().enumerate() {
+            *byte = (i * 7) as u8;
+        }
+        assert!(a.fast_eq(&a));
+        for i in 0..32 {
+            let mut b = a;
+            b[i] = b[i].wrapping_add(1);
+            assert!(!a.fast_eq(&b));
+        }
+    }
+
+    #[test]
+    fn test_integers() {
+        assert!(5u64.fast_eq(&5));
+        assert!(!5u64.fast_eq(&6));
+        let a = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210u128;
+        assert!(a.fast_eq(&a));
+        assert!(!a.fast_eq(&(a ^ (1 << 127))));
+        assert!(!a.fast_eq(&(a ^ 1)));
+    }
+}
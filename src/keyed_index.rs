@@ -0,0 +1,410 @@
+use crate::batch::HashBatch;
+use crate::map::{equivalent_key, make_hash, make_hasher, DefaultHashBuilder};
+use crate::raw::{Allocator, Global, RawTable};
+use crate::Equivalent;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+
+/// Number of keys which the batch methods hash and prefetch at once.
+const BATCH: usize = 16;
+
+/// A table which assigns dense `u32` ids to distinct keys.
+///
+/// The first key inserted gets id 0, the next distinct key id 1, and so on,
+/// and inserting a key again returns the id it already has. The table stores
+/// nothing but the keys and their ids, so all state attached to a key lives
+/// outside of it, in arrays indexed by id. This is the layout of the build
+/// side of a columnar group-by or hash join: the table turns a column of keys
+/// into a column of group ids, and aggregates or matching rows are then kept
+/// in vectors, one entry per group.
+///
+/// [`insert_batch`] and [`probe_batch`] process a whole column of keys,
+/// hashing them in batches with [`HashBatch::hash_batch`] and prefetching the
+/// control bytes of a batch before any key of it is looked up.
+///
+/// [`insert_batch`]: KeyedIndexTable::insert_batch
+/// [`probe_batch`]: KeyedIndexTable::probe_batch
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::IntegerHashBuilder;
+/// use hashbrown::KeyedIndexTable;
+///
+/// // Sum `amounts` grouped by `customers`.
+/// let customers = [7_u64, 3, 7, 9, 3, 7];
+/// let amounts = [10, 20, 30, 40, 50, 60];
+///
+/// let mut groups = KeyedIndexTable::with_hasher(IntegerHashBuilder::default());
+/// let mut ids = vec![0; customers.len()];
+/// groups.insert_batch(&customers, &mut ids);
+/// assert_eq!(ids, [0, 1, 0, 2, 1, 0]);
+///
+/// let mut sums = vec![0; groups.len()];
+/// for (&id, &amount) in ids.iter().zip(&amounts) {
+///     sums[id as usize] += amount;
+/// }
+/// assert_eq!(groups.into_keys(), [7, 3, 9]);
+/// assert_eq!(sums, [100, 70, 40]);
+/// ```
+pub struct KeyedIndexTable<K, S = DefaultHashBuilder, A: Allocator + Clone = Global> {
+    hash_builder: S,
+    table: RawTable<(K, u32), A>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K> KeyedIndexTable<K, DefaultHashBuilder> {
+    /// Creates an empty `KeyedIndexTable`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::KeyedIndexTable;
+    ///
+    /// let mut table = KeyedIndexTable::new();
+    /// assert_eq!(table.insert("a"), 0);
+    /// assert_eq!(table.insert("b"), 1);
+    /// assert_eq!(table.insert("a"), 0);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `KeyedIndexTable` which can hold at least `capacity`
+    /// keys without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, S> KeyedIndexTable<K, S> {
+    /// Creates an empty `KeyedIndexTable` which uses `hash_builder` to hash
+    /// keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::new(),
+        }
+    }
+
+    /// Creates an empty `KeyedIndexTable` which uses `hash_builder` to hash
+    /// keys and can hold at least `capacity` keys without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher_in(capacity, hash_builder, Global)
+    }
+}
+
+impl<K, S, A: Allocator + Clone> KeyedIndexTable<K, S, A> {
+    /// Creates an empty `KeyedIndexTable` which uses `hash_builder` to hash
+    /// keys, can hold at least `capacity` keys without reallocating, and
+    /// allocates its table with `alloc`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher_in(capacity: usize, hash_builder: S, alloc: A) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::with_capacity_in(capacity, alloc),
+        }
+    }
+
+    /// Returns a reference to the table's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of keys in the table, which is also the id the next
+    /// new key will get.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the table contains no keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns the number of keys the table can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Removes all keys, so that ids are handed out from 0 again. Keeps the
+    /// allocated memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Returns the keys of the table, with each key at the position of its
+    /// id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::KeyedIndexTable;
+    ///
+    /// let mut table = KeyedIndexTable::new();
+    /// for word in ["b", "a", "b", "c"] {
+    ///     table.insert(word);
+    /// }
+    /// assert_eq!(table.into_keys(), ["b", "a", "c"]);
+    /// ```
+    pub fn into_keys(self) -> Vec<K> {
+        let mut keys: Vec<Option<K>> = (0..self.table.len()).map(|_| None).collect();
+        for (key, id) in self.table {
+            keys[id as usize] = Some(key);
+        }
+        keys.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+impl<K, S, A> KeyedIndexTable<K, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Reserves capacity for at least `additional` more keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve(&mut self, additional: usize) {
+        self.table
+            .reserve(additional, make_hasher::<K, u32, S>(&self.hash_builder));
+    }
+
+    /// Returns the id of the key, or `None` if it is not in the table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::KeyedIndexTable;
+    ///
+    /// let mut table = KeyedIndexTable::new();
+    /// table.insert("a".to_string());
+    /// assert_eq!(table.get("a"), Some(0));
+    /// assert_eq!(table.get("b"), None);
+    /// ```
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<u32>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.table.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        self.find(hash, k)
+    }
+
+    /// Returns the id of the key, inserting it with the next id if it is not
+    /// in the table yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the table already holds `u32::MAX + 1` keys, so that a new
+    /// key cannot get an id.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K) -> u32 {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        self.find_or_insert(hash, k)
+    }
+
+    #[inline]
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<u32>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        match self.table.get(hash, equivalent_key(k)) {
+            Some(&(_, id)) => Some(id),
+            None => None,
+        }
+    }
+
+    #[inline]
+    fn find_or_insert(&mut self, hash: u64, k: K) -> u32 {
+        if let Some(id) = self.find(hash, &k) {
+            return id;
+        }
+        let id = u32::try_from(self.table.len()).expect("more keys than u32 ids");
+        self.table
+            .insert(hash, (k, id), make_hasher::<K, u32, S>(&self.hash_builder));
+        id
+    }
+}
+
+impl<K, S, A> KeyedIndexTable<K, S, A>
+where
+    K: Eq + Hash + Clone,
+    S: HashBatch<K>,
+    A: Allocator + Clone,
+{
+    /// Writes the id of every key of `keys` to the same position of `ids`,
+    /// inserting the keys which are not in the table yet, in order, with new
+    /// ids.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `ids` have different lengths, or if the table
+    /// runs out of `u32` ids.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::KeyedIndexTable;
+    ///
+    /// let mut table = KeyedIndexTable::with_hasher(IntegerHashBuilder::default());
+    /// let mut ids = [0; 4];
+    /// table.insert_batch(&[5_u32, 6, 5, 7], &mut ids);
+    /// assert_eq!(ids, [0, 1, 0, 2]);
+    /// ```
+    pub fn insert_batch(&mut self, keys: &[K], ids: &mut [u32]) {
+        assert_eq!(keys.len(), ids.len(), "one id is needed per key");
+        let mut hashes = [0; BATCH];
+        for (keys, ids) in keys.chunks(BATCH).zip(ids.chunks_mut(BATCH)) {
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(keys, hashes);
+            for &hash in hashes.iter() {
+                self.table.prefetch(hash);
+            }
+            for ((k, &hash), id) in keys.iter().zip(hashes.iter()).zip(ids) {
+                *id = self.find_or_insert(hash, k.clone());
+            }
+        }
+    }
+
+    /// Writes the id of every key of `keys` to the same position of `ids`,
+    /// or `None` for keys which are not in the table, and returns how many
+    /// keys were found.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `keys` and `ids` have different lengths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::hash_map::IntegerHashBuilder;
+    /// use hashbrown::KeyedIndexTable;
+    ///
+    /// let mut table = KeyedIndexTable::with_hasher(IntegerHashBuilder::default());
+    /// table.insert(10_u64);
+    /// table.insert(20);
+    ///
+    /// let mut ids = [None; 3];
+    /// assert_eq!(table.probe_batch(&[20, 30, 10], &mut ids), 2);
+    /// assert_eq!(ids, [Some(1), None, Some(0)]);
+    /// ```
+    pub fn probe_batch(&self, keys: &[K], ids: &mut [Option<u32>]) -> usize {
+        assert_eq!(keys.len(), ids.len(), "one id is needed per key");
+        if self.table.is_empty() {
+            ids.fill(None);
+            return 0;
+        }
+        let mut found = 0;
+        let mut hashes = [0; BATCH];
+        for (keys, ids) in keys.chunks(BATCH).zip(ids.chunks_mut(BATCH)) {
+            let hashes = &mut hashes[..keys.len()];
+            self.hash_builder.hash_batch(keys, hashes);
+            for &hash in hashes.iter() {
+                self.table.prefetch(hash);
+            }
+            for ((k, &hash), id) in keys.iter().zip(hashes.iter()).zip(ids) {
+                *id = self.find(hash, k);
+                found += usize::from(id.is_some());
+            }
+        }
+        found
+    }
+}
+
+impl<K: Clone, S: Clone, A: Allocator + Clone> Clone for KeyedIndexTable<K, S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            hash_builder: self.hash_builder.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<K, S: Default> Default for KeyedIndexTable<K, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Debug, S, A: Allocator + Clone> Debug for KeyedIndexTable<K, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = unsafe { self.table.iter().map(|bucket| bucket.as_ref()) };
+        f.debug_map()
+            .entries(entries.map(|(k, id)| (k, id)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test_keyed_index {
+    use super::KeyedIndexTable;
+    use crate::hash_map::{FixedState, IntegerHashBuilder};
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_dense_ids() {
+        let mut table = KeyedIndexTable::with_hasher(IntegerHashBuilder::default());
+        let keys: Vec<u64> = (0..10_000).map(|i| (i * 7919) % 1000).collect();
+        let mut ids = std::vec![0; keys.len()];
+        table.insert_batch(&keys, &mut ids);
+        assert_eq!(table.len(), 1000);
+        // Ids are handed out in order of first appearance.
+        assert!(ids[..1000]
+            .iter()
+            .enumerate()
+            .all(|(i, &id)| id == i as u32));
+        for (&k, &id) in keys.iter().zip(&ids) {
+            assert_eq!(table.get(&k), Some(id));
+        }
+
+        let probes: Vec<u64> = (0..2000).collect();
+        let mut found = std::vec![None; probes.len()];
+        assert_eq!(table.probe_batch(&probes, &mut found), 1000);
+        assert!(found[1000..].iter().all(Option::is_none));
+
+        let keys_by_id = table.clone().into_keys();
+        for (id, k) in keys_by_id.iter().enumerate() {
+            assert_eq!(table.get(k), Some(id as u32));
+        }
+
+        table.clear();
+        assert_eq!(table.insert(5), 0);
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut table: KeyedIndexTable<String, FixedState> = KeyedIndexTable::default();
+        assert_eq!(table.get("a"), None);
+        let mut ids = [None; 1];
+        assert_eq!(table.probe_batch(&["a".to_string()], &mut ids), 0);
+        assert_eq!(table.insert("a".to_string()), 0);
+        assert_eq!(table.insert("b".to_string()), 1);
+        assert_eq!(table.get("b"), Some(1));
+        assert_eq!(table.into_keys(), ["a", "b"]);
+    }
+
+    #[test]
+    #[should_panic = "one id is needed per key"]
+    fn test_batch_length_mismatch() {
+        let mut table = KeyedIndexTable::with_hasher(IntegerHashBuilder::default());
+        table.insert_batch(&[1_u32, 2], &mut [0]);
+    }
+}
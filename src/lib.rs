@@ -38,8 +38,15 @@
 #![warn(missing_docs)]
 #![warn(rust_2018_idioms)]
 
-#[cfg(test)]
-#[macro_use]
+#[cfg(any(
+    test,
+    feature = "concurrent",
+    feature = "defer-drop",
+    feature = "persist",
+    feature = "siphash",
+    feature = "shadow"
+))]
+#[cfg_attr(test, macro_use)]
 extern crate std;
 
 #[cfg_attr(test, macro_use)]
@@ -75,17 +82,72 @@ pub mod raw {
 #[cfg(not(feature = "raw"))]
 mod raw;
 
+mod adaptive;
+pub mod aggregate;
+pub mod arena_map;
+mod array_map;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+mod batch;
+mod bounded_map;
+mod budgeted_map;
+#[cfg(feature = "concurrent")]
+pub mod concurrent;
+#[cfg(target_has_atomic = "ptr")]
+mod cow_map;
+pub mod cuckoo_map;
+#[cfg(feature = "defer-drop")]
+pub mod defer_drop;
+pub mod expiring_map;
 mod external_trait_impls;
+#[cfg(feature = "ffi")]
+pub use crate::raw::ffi;
+mod frozen_map;
+pub mod hash_table;
+mod hasher;
+#[cfg(feature = "huge-pages")]
+pub mod huge_pages;
+pub mod incremental_map;
+pub mod index_map;
+pub mod join;
+mod key_eq;
+mod keyed_index;
+pub mod lru_map;
 mod map;
+pub mod multi_map;
+#[cfg(feature = "numa")]
+pub mod numa;
+pub mod perfect_map;
+#[cfg(feature = "persist")]
+pub mod persist;
+#[cfg(target_has_atomic = "ptr")]
+pub mod persistent_map;
+mod reusable_map;
 #[cfg(feature = "rustc-internal-api")]
 mod rustc_entry;
 mod scopeguard;
 mod set;
+#[cfg(feature = "shadow")]
+pub mod shadow;
+mod sso_key;
+pub mod static_map;
 
 pub mod hash_map {
     //! A hash map implemented with quadratic probing and SIMD lookup.
     pub use crate::map::*;
 
+    pub use crate::batch::HashBatch;
+    #[cfg(feature = "ahash")]
+    pub use crate::hasher::RandomState;
+    pub use crate::hasher::{
+        FixedState, HashSplit, Hasher128, HighTag, IntegerHash32Builder, IntegerHashBuilder,
+        IntegerHasher, IntegerHasher32, LowTag, SplitHashBuilder, SplitHasher, WideHashBuilder,
+        WideHasher,
+    };
+    #[cfg(feature = "metrics")]
+    pub use crate::raw::TableMetrics;
+    pub use crate::raw::TableStats;
+
     #[cfg(feature = "rustc-internal-api")]
     pub use crate::rustc_entry::*;
 
@@ -101,6 +163,10 @@ pub mod hash_map {
 }
 pub mod hash_set {
     //! A hash set implemented as a `HashMap` where the value is `()`.
+    pub use crate::map::{buckets_to_capacity, capacity_to_buckets};
+    #[cfg(feature = "metrics")]
+    pub use crate::raw::TableMetrics;
+    pub use crate::raw::TableStats;
     pub use crate::set::*;
 
     #[cfg(feature = "rayon")]
@@ -114,8 +180,37 @@ pub mod hash_set {
     }
 }
 
-pub use crate::map::HashMap;
+#[cfg(feature = "serde")]
+/// Deterministic serialization of maps and sets, with their entries sorted by
+/// key. This module is only available if the `serde` feature is enabled.
+pub mod serde_sorted {
+    pub use crate::external_trait_impls::serde::sorted::*;
+}
+
+pub use crate::adaptive::{AdaptiveHashMap, Reseed};
+pub use crate::arena_map::{ArenaKey, ArenaMap};
+pub use crate::array_map::ArrayHashMap;
+pub use crate::bounded_map::BoundedHashMap;
+pub use crate::budgeted_map::BudgetedMap;
+#[cfg(target_has_atomic = "ptr")]
+pub use crate::cow_map::CowHashMap;
+pub use crate::cuckoo_map::CuckooHashMap;
+pub use crate::expiring_map::ExpiringMap;
+pub use crate::frozen_map::FrozenMap;
+pub use crate::hash_table::HashTable;
+pub use crate::incremental_map::IncrementalHashMap;
+pub use crate::index_map::IndexMap;
+pub use crate::key_eq::FastKeyEq;
+pub use crate::keyed_index::KeyedIndexTable;
+pub use crate::lru_map::{LruMap, WeightedLruMap};
+pub use crate::map::{DefaultHashBuilder, HashMap};
+pub use crate::multi_map::HashMultiMap;
+pub use crate::perfect_map::PerfectMap;
+#[cfg(target_has_atomic = "ptr")]
+pub use crate::persistent_map::PersistentHashMap;
+pub use crate::reusable_map::{ClearPolicy, ReusableHashMap};
 pub use crate::set::HashSet;
+pub use crate::sso_key::SsoKey;
 
 /// Key equivalence trait.
 ///
@@ -145,8 +240,11 @@ where
     Q: Eq,
     K: core::borrow::Borrow<Q>,
 {
-    fn equivalent(&self, key: &K) -> bool {
-        self == key.borrow()
+    default_fn! {
+        #[inline]
+        fn equivalent(&self, key: &K) -> bool {
+            self == key.borrow()
+        }
     }
 }
 
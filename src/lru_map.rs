@@ -0,0 +1,1067 @@
+//! A hash map which evicts its least recently used entry when full.
+//!
+//! [`LruMap`] keeps its entries in a vector, linked into a list from the most
+//! to the least recently used one by indices stored in the entries
+//! themselves, and uses a [`RawTable`] of indices into that vector to look
+//! keys up. Finding a key and moving it to the front of the list therefore
+//! takes a single probe of the table.
+//!
+//! [`WeightedLruMap`] evicts by the total weight of its entries instead of
+//! their number, for caches of values of very different sizes.
+
+use crate::map::{make_hash, DefaultHashBuilder};
+use crate::raw::RawTable;
+use crate::Equivalent;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::mem;
+
+/// The link of the first and last entries of the list.
+const NIL: usize = usize::MAX;
+
+/// An entry of an [`LruMap`] with its links to the entries used just before
+/// and just after it.
+#[derive(Clone)]
+struct Node<K, V> {
+    hash: u64,
+    key: K,
+    value: V,
+    // The more recently used neighbour.
+    prev: usize,
+    // The less recently used neighbour.
+    next: usize,
+}
+
+/// A hash map holding at most a fixed number of entries, which evicts the
+/// least recently used entry to make room for a new one.
+///
+/// Inserting a key and looking it up with [`get`] or [`get_mut`] mark it as
+/// the most recently used; [`peek`] and [`contains_key`] do not. Each of
+/// these operations probes the table once, and updating the order of the
+/// entries takes constant time.
+///
+/// The entries are stored in a vector which grows as entries are inserted,
+/// up to the capacity given when the map is created, and are never moved
+/// when they are used. [`iter`] visits them from the most to the least
+/// recently used.
+///
+/// [`get`]: LruMap::get
+/// [`get_mut`]: LruMap::get_mut
+/// [`peek`]: LruMap::peek
+/// [`contains_key`]: LruMap::contains_key
+/// [`iter`]: LruMap::iter
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::LruMap;
+///
+/// let mut cache = LruMap::new(2);
+/// cache.insert("a", 1);
+/// cache.insert("b", 2);
+/// assert_eq!(cache.get("a"), Some(&1));
+///
+/// // "b" is now the least recently used entry, so it is evicted.
+/// cache.insert("c", 3);
+/// assert!(!cache.contains_key("b"));
+/// let keys: Vec<_> = cache.iter().map(|(k, _)| *k).collect();
+/// assert_eq!(keys, ["c", "a"]);
+/// ```
+pub struct LruMap<K, V, S = DefaultHashBuilder> {
+    indices: RawTable<usize>,
+    entries: Vec<Node<K, V>>,
+    // The most recently used entry.
+    head: usize,
+    // The least recently used entry.
+    tail: usize,
+    capacity: usize,
+    hash_builder: S,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> LruMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `LruMap` which holds at most `capacity` entries.
+    ///
+    /// No memory is allocated until entries are inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let cache: LruMap<i32, i32> = LruMap::new(100);
+    /// assert_eq!(cache.capacity(), 100);
+    /// assert!(cache.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> LruMap<K, V, S> {
+    /// Creates an empty `LruMap` which holds at most `capacity` entries and
+    /// uses the given hash builder to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            indices: RawTable::new(),
+            entries: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+            hash_builder,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the maximum number of entries the map holds.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.entries.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+
+    /// Returns the least recently used key-value pair, without marking it as
+    /// used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(10);
+    /// cache.insert(1, "a");
+    /// cache.insert(2, "b");
+    /// assert_eq!(cache.peek_lru(), Some((&1, &"a")));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.entries.get(self.tail).map(|e| (&e.key, &e.value))
+    }
+
+    /// Removes the least recently used key-value pair and returns it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(10);
+    /// cache.insert(1, "a");
+    /// cache.insert(2, "b");
+    /// assert_eq!(cache.pop_lru(), Some((1, "a")));
+    /// assert_eq!(cache.pop_lru(), Some((2, "b")));
+    /// assert_eq!(cache.pop_lru(), None);
+    /// ```
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let index = self.tail;
+        let hash = self.entries.get(index)?.hash;
+        self.indices
+            .remove_entry(hash, |&i| i == index)
+            .expect("index missing from the table");
+        Some(self.remove_node(index))
+    }
+
+    /// An iterator visiting all key-value pairs from the most to the least
+    /// recently used one, without marking them as used.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            entries: &self.entries,
+            next: self.head,
+            len: self.entries.len(),
+        }
+    }
+
+    /// Takes the entry at `index` out of the list.
+    fn unlink(&mut self, index: usize) {
+        let Node { prev, next, .. } = self.entries[index];
+        match self.entries.get_mut(prev) {
+            Some(e) => e.next = next,
+            None => self.head = next,
+        }
+        match self.entries.get_mut(next) {
+            Some(e) => e.prev = prev,
+            None => self.tail = prev,
+        }
+    }
+
+    /// Inserts the entry at `index`, which is not in the list, at the front.
+    fn push_front(&mut self, index: usize) {
+        let head = self.head;
+        let node = &mut self.entries[index];
+        node.prev = NIL;
+        node.next = head;
+        match self.entries.get_mut(head) {
+            Some(e) => e.prev = index,
+            None => self.tail = index,
+        }
+        self.head = index;
+    }
+
+    /// Marks the entry at `index` as the most recently used one.
+    fn promote(&mut self, index: usize) {
+        if self.head != index {
+            self.unlink(index);
+            self.push_front(index);
+        }
+    }
+
+    /// Removes the entry at `index`, whose index has already been removed
+    /// from the table, and moves the last entry into its place.
+    fn remove_node(&mut self, index: usize) -> (K, V) {
+        self.unlink(index);
+        let node = self.entries.swap_remove(index);
+        if let Some(moved) = self.entries.get(index) {
+            let (last, hash, prev, next) = (self.entries.len(), moved.hash, moved.prev, moved.next);
+            *self
+                .indices
+                .get_mut(hash, |&i| i == last)
+                .expect("index missing from the table") = index;
+            match self.entries.get_mut(prev) {
+                Some(e) => e.next = index,
+                None => self.head = index,
+            }
+            match self.entries.get_mut(next) {
+                Some(e) => e.prev = index,
+                None => self.tail = index,
+            }
+        }
+        (node.key, node.value)
+    }
+}
+
+impl<K, V, S> LruMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns the position of the entry for `k`.
+    #[inline]
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Equivalent<K>,
+    {
+        let entries = &self.entries;
+        self.indices
+            .get(hash, |&i| k.equivalent(&entries[i].key))
+            .copied()
+    }
+
+    /// Inserts a key-value pair into the map and marks it as the most
+    /// recently used one.
+    ///
+    /// If the key is already present, its value is replaced and the old
+    /// value is returned. Otherwise, if the map is full, the least recently
+    /// used entry is evicted to make room.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(1);
+    /// assert_eq!(cache.insert(1, "a"), None);
+    /// assert_eq!(cache.insert(1, "b"), Some("a"));
+    /// assert_eq!(cache.insert(2, "c"), None);
+    /// assert!(!cache.contains_key(&1));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        match self.find(hash, &k) {
+            Some(i) => {
+                self.promote(i);
+                Some(mem::replace(&mut self.entries[i].value, v))
+            }
+            None => {
+                self.insert_new(hash, k, v);
+                None
+            }
+        }
+    }
+
+    /// Inserts a key-value pair into the map and marks it as the most
+    /// recently used one, returning the pair it displaced.
+    ///
+    /// If the key is already present, its value is replaced and the key
+    /// passed in is returned with the old value. Otherwise, if the map is
+    /// full, the least recently used entry is evicted and returned. A map
+    /// with a capacity of 0 returns the pair passed in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(2);
+    /// assert_eq!(cache.push(1, "a"), None);
+    /// assert_eq!(cache.push(2, "b"), None);
+    /// assert_eq!(cache.push(1, "c"), Some((1, "a")));
+    /// assert_eq!(cache.push(3, "d"), Some((2, "b")));
+    /// ```
+    pub fn push(&mut self, k: K, v: V) -> Option<(K, V)> {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        match self.find(hash, &k) {
+            Some(i) => {
+                self.promote(i);
+                Some((k, mem::replace(&mut self.entries[i].value, v)))
+            }
+            None => self.insert_new(hash, k, v),
+        }
+    }
+
+    /// Inserts a key which is not in the map, evicting the least recently
+    /// used entry if the map is full.
+    fn insert_new(&mut self, hash: u64, k: K, v: V) -> Option<(K, V)> {
+        if self.capacity == 0 {
+            return Some((k, v));
+        }
+        let node = Node {
+            hash,
+            key: k,
+            value: v,
+            prev: NIL,
+            next: NIL,
+        };
+        if self.entries.len() >= self.capacity {
+            // Reuse the slot of the least recently used entry.
+            let index = self.tail;
+            let old_hash = self.entries[index].hash;
+            self.indices
+                .remove_entry(old_hash, |&i| i == index)
+                .expect("index missing from the table");
+            self.unlink(index);
+            let old = mem::replace(&mut self.entries[index], node);
+            self.insert_index(hash, index);
+            Some((old.key, old.value))
+        } else {
+            let index = self.entries.len();
+            self.entries.push(node);
+            self.insert_index(hash, index);
+            None
+        }
+    }
+
+    /// Adds the entry at `index` to the table and to the front of the list.
+    fn insert_index(&mut self, hash: u64, index: usize) {
+        let entries = &self.entries;
+        self.indices.insert(hash, index, |&i| entries[i].hash);
+        self.push_front(index);
+    }
+
+    /// Returns a reference to the value corresponding to the key, and marks
+    /// the key as the most recently used one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(2);
+    /// cache.insert(1, "a");
+    /// cache.insert(2, "b");
+    /// assert_eq!(cache.get(&1), Some(&"a"));
+    /// assert_eq!(cache.peek_lru(), Some((&2, &"b")));
+    /// ```
+    #[inline]
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_mut(k).map(|v| &*v)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// and marks the key as the most recently used one.
+    #[inline]
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let i = self.find(hash, k)?;
+        self.promote(i);
+        Some(&mut self.entries[i].value)
+    }
+
+    /// Returns the key and a mutable reference to the value corresponding to
+    /// the key, and marks the key as the most recently used one.
+    #[inline]
+    fn get_key_value_mut<Q>(&mut self, k: &Q) -> Option<(&K, &mut V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let i = self.find(hash, k)?;
+        self.promote(i);
+        let node = &mut self.entries[i];
+        Some((&node.key, &mut node.value))
+    }
+
+    /// Returns a reference to the value corresponding to the key, without
+    /// marking the key as used.
+    #[inline]
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        self.find(hash, k).map(|i| &self.entries[i].value)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key,
+    /// without marking the key as used.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.peek(k).is_some()
+    }
+
+    /// Removes a key from the map, returning its value, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(10);
+    /// cache.insert(1, "a");
+    /// assert_eq!(cache.remove(&1), Some("a"));
+    /// assert_eq!(cache.remove(&1), None);
+    /// ```
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let entries = &self.entries;
+        let i = self
+            .indices
+            .remove_entry(hash, |&i| k.equivalent(&entries[i].key))?;
+        Some(self.remove_node(i).1)
+    }
+
+    /// Changes the maximum number of entries of the map, evicting the least
+    /// recently used entries if there are more than `capacity`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::LruMap;
+    ///
+    /// let mut cache = LruMap::new(3);
+    /// cache.insert(1, "a");
+    /// cache.insert(2, "b");
+    /// cache.insert(3, "c");
+    /// cache.set_capacity(1);
+    /// assert_eq!(cache.len(), 1);
+    /// assert!(cache.contains_key(&3));
+    /// ```
+    pub fn set_capacity(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            self.pop_lru();
+        }
+        self.capacity = capacity;
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for LruMap<K, V, S> {
+    fn clone(&self) -> Self {
+        LruMap {
+            indices: self.indices.clone(),
+            entries: self.entries.clone(),
+            head: self.head,
+            tail: self.tail,
+            capacity: self.capacity,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for LruMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a LruMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an `LruMap`, from the most to the least
+/// recently used one.
+///
+/// This `struct` is created by the [`iter`] method on [`LruMap`].
+///
+/// [`iter`]: LruMap::iter
+pub struct Iter<'a, K, V> {
+    entries: &'a [Node<K, V>],
+    next: usize,
+    len: usize,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            entries: self.entries,
+            next: self.next,
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let node = self.entries.get(self.next)?;
+        self.next = node.next;
+        self.len -= 1;
+        Some((&node.key, &node.value))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// A hash map which keeps the total weight of its entries under a budget by
+/// evicting the least recently used entries.
+///
+/// The weight of an entry is computed once, when it is inserted or updated,
+/// by the `weigher` given when the map is created; it could be the size of a
+/// cached response body in bytes, for example. Whenever the total weight goes
+/// over the budget, the least recently used entries are evicted until it fits
+/// again. The entry just inserted or updated is never evicted, so an entry
+/// which is heavier than the whole budget is still stored, on its own.
+///
+/// Apart from that, the map behaves like an [`LruMap`] of unbounded
+/// capacity. Values cannot be modified in place, since that could change
+/// their weight behind the map's back; use [`update`] instead.
+///
+/// [`update`]: WeightedLruMap::update
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::lru_map::WeightedLruMap;
+///
+/// let mut cache = WeightedLruMap::new(10, |_: &&str, v: &Vec<u8>| v.len());
+/// cache.insert("a", vec![0; 4]);
+/// cache.insert("b", vec![0; 4]);
+/// assert_eq!(cache.total_weight(), 8);
+/// assert!(cache.get("a").is_some());
+///
+/// // "b" is the least recently used entry, and evicting it makes room.
+/// cache.insert("c", vec![0; 5]);
+/// assert!(!cache.contains_key("b"));
+/// assert_eq!(cache.total_weight(), 9);
+/// ```
+pub struct WeightedLruMap<K, V, W, S = DefaultHashBuilder> {
+    map: LruMap<K, (V, usize), S>,
+    weigher: W,
+    budget: usize,
+    total_weight: usize,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V, W> WeightedLruMap<K, V, W, DefaultHashBuilder>
+where
+    W: Fn(&K, &V) -> usize,
+{
+    /// Creates an empty `WeightedLruMap` which keeps the total weight of its
+    /// entries, as computed by `weigher`, at most `budget`.
+    ///
+    /// No memory is allocated until entries are inserted.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new(budget: usize, weigher: W) -> Self {
+        Self::with_hasher(budget, weigher, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, W, S> WeightedLruMap<K, V, W, S>
+where
+    W: Fn(&K, &V) -> usize,
+{
+    /// Creates an empty `WeightedLruMap` which keeps the total weight of its
+    /// entries at most `budget` and uses the given hash builder to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(budget: usize, weigher: W, hash_builder: S) -> Self {
+        Self {
+            map: LruMap::with_hasher(usize::MAX, hash_builder),
+            weigher,
+            budget,
+            total_weight: 0,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Returns the maximum total weight of the entries of the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Returns the total weight of the entries of the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Clears the map, removing all key-value pairs. Keeps the allocated
+    /// memory for reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.total_weight = 0;
+    }
+
+    /// Returns the least recently used key-value pair, without marking it as
+    /// used.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.map.peek_lru().map(|(k, (v, _))| (k, v))
+    }
+
+    /// Removes the least recently used key-value pair and returns it.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (k, (v, weight)) = self.map.pop_lru()?;
+        self.total_weight -= weight;
+        Some((k, v))
+    }
+
+    /// An iterator visiting all key-value pairs from the most to the least
+    /// recently used one, without marking them as used.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> WeightedIter<'_, K, V> {
+        WeightedIter {
+            inner: self.map.iter(),
+        }
+    }
+
+    /// Changes the maximum total weight of the map, evicting the least
+    /// recently used entries until the entries fit in `budget`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::lru_map::WeightedLruMap;
+    ///
+    /// let mut cache = WeightedLruMap::new(100, |_: &i32, v: &u32| *v as usize);
+    /// cache.insert(1, 30);
+    /// cache.insert(2, 30);
+    /// cache.insert(3, 30);
+    /// cache.set_budget(60);
+    /// assert_eq!(cache.len(), 2);
+    /// assert!(!cache.contains_key(&1));
+    /// ```
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.total_weight > budget && self.pop_lru().is_some() {}
+    }
+
+    /// Evicts the least recently used entries until the total weight fits in
+    /// the budget, keeping at least the most recently used entry.
+    fn enforce_budget(&mut self) {
+        while self.total_weight > self.budget && self.map.len() > 1 {
+            self.pop_lru();
+        }
+    }
+}
+
+impl<K, V, W, S> WeightedLruMap<K, V, W, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    W: Fn(&K, &V) -> usize,
+{
+    /// Inserts a key-value pair into the map and marks it as the most
+    /// recently used one.
+    ///
+    /// If the key is already present, its value is replaced and the old
+    /// value is returned. The least recently used entries are then evicted
+    /// until the total weight fits in the budget again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::lru_map::WeightedLruMap;
+    ///
+    /// let mut cache = WeightedLruMap::new(10, |_: &i32, v: &String| v.len());
+    /// assert_eq!(cache.insert(1, "abc".to_string()), None);
+    /// assert_eq!(cache.insert(1, "de".to_string()), Some("abc".to_string()));
+    /// assert_eq!(cache.total_weight(), 2);
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let weight = (self.weigher)(&k, &v);
+        self.total_weight += weight;
+        let old = self.map.insert(k, (v, weight)).map(|(old, old_weight)| {
+            self.total_weight -= old_weight;
+            old
+        });
+        self.enforce_budget();
+        old
+    }
+
+    /// Returns a reference to the value corresponding to the key, and marks
+    /// the key as the most recently used one.
+    #[inline]
+    pub fn get<Q>(&mut self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.get(k).map(|(v, _)| v)
+    }
+
+    /// Returns a reference to the value corresponding to the key, without
+    /// marking the key as used.
+    #[inline]
+    pub fn peek<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.peek(k).map(|(v, _)| v)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key,
+    /// without marking the key as used.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.contains_key(k)
+    }
+
+    /// Returns the weight of the entry for the key, without marking the key
+    /// as used.
+    #[inline]
+    pub fn weight_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.map.peek(k).map(|&(_, weight)| weight)
+    }
+
+    /// Calls `f` on the value of the key, if it is present, marks the key as
+    /// the most recently used one and weighs the entry again, evicting other
+    /// entries if the map goes over its budget. Returns whether the key was
+    /// present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::lru_map::WeightedLruMap;
+    ///
+    /// let mut cache = WeightedLruMap::new(10, |_: &i32, v: &Vec<u8>| v.len());
+    /// cache.insert(1, vec![0; 3]);
+    /// cache.insert(2, vec![0; 3]);
+    /// assert!(cache.update(&2, |v| v.resize(8, 0)));
+    /// assert!(!cache.contains_key(&1));
+    /// assert_eq!(cache.total_weight(), 8);
+    /// ```
+    pub fn update<Q, U>(&mut self, k: &Q, f: U) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+        U: FnOnce(&mut V),
+    {
+        let (key, (value, weight)) = match self.map.get_key_value_mut(k) {
+            Some(entry) => entry,
+            None => return false,
+        };
+        f(value);
+        let new_weight = (self.weigher)(key, value);
+        self.total_weight = self.total_weight - *weight + new_weight;
+        *weight = new_weight;
+        self.enforce_budget();
+        true
+    }
+
+    /// Removes a key from the map, returning its value, if any.
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let (v, weight) = self.map.remove(k)?;
+        self.total_weight -= weight;
+        Some(v)
+    }
+}
+
+impl<K: Clone, V: Clone, W: Clone, S: Clone> Clone for WeightedLruMap<K, V, W, S> {
+    fn clone(&self) -> Self {
+        WeightedLruMap {
+            map: self.map.clone(),
+            weigher: self.weigher.clone(),
+            budget: self.budget,
+            total_weight: self.total_weight,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, W, S> Debug for WeightedLruMap<K, V, W, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.map.iter().map(|(k, (v, _))| (k, v)))
+            .finish()
+    }
+}
+
+impl<'a, K, V, W, S> IntoIterator for &'a WeightedLruMap<K, V, W, S>
+where
+    W: Fn(&K, &V) -> usize,
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = WeightedIter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> WeightedIter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `WeightedLruMap`, from the most to the
+/// least recently used one.
+///
+/// This `struct` is created by the [`iter`] method on [`WeightedLruMap`].
+///
+/// [`iter`]: WeightedLruMap::iter
+pub struct WeightedIter<'a, K, V> {
+    inner: Iter<'a, K, (V, usize)>,
+}
+
+impl<K, V> Clone for WeightedIter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        WeightedIter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for WeightedIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next().map(|(k, (v, _))| (k, v))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for WeightedIter<'_, K, V> {}
+impl<K, V> FusedIterator for WeightedIter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for WeightedIter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_lru_map {
+    use super::{LruMap, WeightedLruMap};
+    use std::vec::Vec;
+
+    fn keys(map: &LruMap<u32, u32>) -> Vec<u32> {
+        map.iter().map(|(k, _)| *k).collect()
+    }
+
+    /// Checks that the links and the table agree with the entries.
+    fn check(map: &LruMap<u32, u32>) {
+        assert_eq!(map.iter().len(), map.len());
+        assert_eq!(map.iter().count(), map.len());
+        assert_eq!(map.indices.len(), map.len());
+        for (k, v) in map.iter() {
+            assert_eq!(map.peek(k), Some(v));
+        }
+        assert!(map.len() <= map.capacity());
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let mut map = LruMap::new(3);
+        map.insert(1, 1);
+        map.insert(2, 2);
+        map.insert(3, 3);
+        assert_eq!(keys(&map), [3, 2, 1]);
+        map.get(&1);
+        assert_eq!(keys(&map), [1, 3, 2]);
+        assert_eq!(map.push(4, 4), Some((2, 2)));
+        assert_eq!(keys(&map), [4, 1, 3]);
+        map.insert(3, 30);
+        assert_eq!(keys(&map), [3, 4, 1]);
+        assert_eq!(map.peek(&1), Some(&1));
+        assert_eq!(keys(&map), [3, 4, 1]);
+        check(&map);
+    }
+
+    #[test]
+    fn test_remove_moves_entries() {
+        let mut map = LruMap::new(100);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        for i in (0..100).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i));
+            check(&map);
+        }
+        let expected: Vec<u32> = (0..100).rev().filter(|i| i % 3 != 0).collect();
+        assert_eq!(keys(&map), expected);
+        while let Some((k, _)) = map.pop_lru() {
+            assert!(map.iter().all(|(&other, _)| other > k));
+        }
+        check(&map);
+    }
+
+    #[test]
+    fn test_churn() {
+        let mut map = LruMap::new(64);
+        for i in 0..10_000u32 {
+            map.insert(i % 97, i);
+            if i % 5 == 0 {
+                map.get(&(i % 13));
+            }
+            if i % 7 == 0 {
+                map.remove(&(i % 11));
+            }
+        }
+        check(&map);
+        map.set_capacity(10);
+        check(&map);
+        assert_eq!(map.len(), 10);
+        map.clear();
+        assert!(map.peek_lru().is_none());
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut map = LruMap::new(0);
+        assert_eq!(map.push(1, 1), Some((1, 1)));
+        assert_eq!(map.insert(1, 1), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_weighted_eviction() {
+        let mut map = WeightedLruMap::new(100, |_: &u32, v: &u32| *v as usize);
+        for i in 0..10 {
+            map.insert(i, 10);
+        }
+        assert_eq!(map.total_weight(), 100);
+        assert_eq!(map.len(), 10);
+
+        // Using 0 makes 1 the least recently used entry.
+        map.get(&0);
+        map.insert(10, 25);
+        let keys: Vec<u32> = map.iter().map(|(k, _)| *k).collect();
+        assert_eq!(keys, [10, 0, 9, 8, 7, 6, 5, 4]);
+        assert_eq!(map.total_weight(), 95);
+
+        // Replacing a value accounts for the old weight.
+        assert_eq!(map.insert(10, 5), Some(25));
+        assert_eq!(map.total_weight(), 75);
+        assert_eq!(map.weight_of(&10), Some(5));
+
+        // Updating a value weighs it again and evicts other entries.
+        assert!(map.update(&4, |v| *v = 50));
+        assert_eq!(map.total_weight(), 95);
+        assert_eq!(map.peek_lru(), Some((&7, &10)));
+        assert!(!map.update(&1, |_| unreachable!()));
+
+        // An entry heavier than the budget is kept on its own.
+        map.insert(11, 1000);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.total_weight(), 1000);
+        assert_eq!(map.remove(&11), Some(1000));
+        assert_eq!(map.total_weight(), 0);
+        assert!(map.is_empty());
+
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        map.set_budget(20);
+        assert!(map.total_weight() <= 20);
+        assert_eq!(
+            map.iter().map(|(_, v)| *v as usize).sum::<usize>(),
+            map.total_weight()
+        );
+        map.clear();
+        assert_eq!(map.total_weight(), 0);
+    }
+}
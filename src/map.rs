@@ -1,5 +1,12 @@
-use crate::raw::{Allocator, Bucket, Global, RawDrain, RawIntoIter, RawIter, RawTable};
-use crate::{Equivalent, TryReserveError};
+#[cfg(feature = "metrics")]
+use crate::raw::TableMetrics;
+use crate::raw::{
+    bucket_mask_to_capacity, Allocator, Bucket, Global, RawDrain, RawIntoIter, RawIter,
+    RawIterRange, RawTable, TableStats,
+};
+use crate::set::HashSet;
+use crate::{Equivalent, FastKeyEq, TryReserveError};
+use alloc::vec::Vec;
 use core::borrow::Borrow;
 use core::fmt::{self, Debug};
 use core::hash::{BuildHasher, Hash};
@@ -8,12 +15,54 @@ use core::marker::PhantomData;
 use core::mem;
 use core::ops::Index;
 
+// Both features replace `DefaultHashBuilder`, and a crate asking for SipHash
+// shouldn't silently get a fixed seed because another one asked for that.
+#[cfg(all(feature = "siphash", feature = "deterministic"))]
+compile_error!(
+    "the `siphash` and `deterministic` features both choose the default hasher and \
+     can't be enabled together; use `FixedState` or `std::collections::hash_map::RandomState` \
+     explicitly instead of one of them"
+);
+
 /// Default hasher for `HashMap`.
-#[cfg(feature = "ahash")]
+#[cfg(all(
+    feature = "ahash",
+    not(feature = "siphash"),
+    not(feature = "deterministic")
+))]
 pub type DefaultHashBuilder = core::hash::BuildHasherDefault<ahash::AHasher>;
 
+/// Default hasher for `HashMap`.
+///
+/// With the `siphash` feature this is the randomly keyed SipHash 1-3 hasher of
+/// the standard library, which protects against HashDoS.
+///
+/// Cargo enables a feature for every crate in the build which depends on
+/// `hashbrown` as soon as one of them asks for it, so this changes the default
+/// hasher of all of them. It can't be combined with the `deterministic`
+/// feature.
+#[cfg(all(feature = "siphash", not(feature = "deterministic")))]
+pub type DefaultHashBuilder = std::collections::hash_map::RandomState;
+
+/// Default hasher for `HashMap`.
+///
+/// With the `deterministic` feature this is [`FixedState`], whose hashes are
+/// the same on every run and every platform, so that maps which are built the
+/// same way iterate in the same order.
+///
+/// Cargo enables a feature for every crate in the build which depends on
+/// `hashbrown` as soon as one of them asks for it, so this changes the default
+/// hasher of all of them, including ones which rely on a random seed against
+/// HashDoS. Libraries should name [`FixedState`] in the type of their maps
+/// instead of enabling the feature. It can't be combined with the `siphash`
+/// feature.
+///
+/// [`FixedState`]: crate::hash_map::FixedState
+#[cfg(feature = "deterministic")]
+pub type DefaultHashBuilder = crate::hasher::FixedState;
+
 /// Dummy default hasher for `HashMap`.
-#[cfg(not(feature = "ahash"))]
+#[cfg(not(any(feature = "ahash", feature = "siphash", feature = "deterministic")))]
 pub enum DefaultHashBuilder {}
 
 /// A hash map implemented with quadratic probing and SIMD lookup.
@@ -21,7 +70,12 @@ pub enum DefaultHashBuilder {}
 /// The default hashing algorithm is currently [`AHash`], though this is
 /// subject to change at any point in the future. This hash function is very
 /// fast for all types of keys, but this algorithm will typically *not* protect
-/// against attacks such as HashDoS.
+/// against attacks such as HashDoS. Enabling the `siphash` feature switches
+/// the default to the standard library's SipHash 1-3, which does, at the cost
+/// of slower hashing. Enabling the `deterministic` feature switches it to
+/// [`FixedState`], which makes the iteration order of a map depend only on the
+/// operations which built it, on every run and every platform. The default
+/// hasher is available as [`DefaultHashBuilder`].
 ///
 /// The hashing algorithm can be replaced on a per-`HashMap` basis using the
 /// [`default`], [`with_hasher`], and [`with_capacity_and_hasher`] methods. Many
@@ -146,6 +200,7 @@ pub enum DefaultHashBuilder {}
 /// [`with_capacity_and_hasher`]: #method.with_capacity_and_hasher
 /// [`fnv`]: https://crates.io/crates/fnv
 /// [`AHash`]: https://crates.io/crates/ahash
+/// [`FixedState`]: crate::hash_map::FixedState
 ///
 /// ```
 /// use hashbrown::HashMap;
@@ -206,6 +261,62 @@ impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for HashMap<K, V,
     }
 }
 
+/// Returns the number of buckets a map or set allocates to hold `capacity`
+/// elements, or `None` if that number overflows `usize`.
+///
+/// This is the bucket count that [`HashMap::with_capacity`] and
+/// [`HashMap::reserve_exact`] pick: the smallest power of two which keeps at
+/// least one bucket empty in tables of up to 8 buckets, and at least 1/8 of
+/// the buckets empty in larger ones. No buckets are allocated for a capacity
+/// of zero.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::{buckets_to_capacity, capacity_to_buckets};
+///
+/// assert_eq!(capacity_to_buckets(0), Some(0));
+/// assert_eq!(capacity_to_buckets(3), Some(4));
+/// assert_eq!(capacity_to_buckets(100), Some(128));
+/// assert_eq!(capacity_to_buckets(usize::MAX), None);
+/// assert_eq!(buckets_to_capacity(128), 112);
+/// ```
+pub fn capacity_to_buckets(capacity: usize) -> Option<usize> {
+    if capacity == 0 {
+        Some(0)
+    } else {
+        crate::raw::capacity_to_buckets(capacity)
+    }
+}
+
+/// Returns the number of elements a table with `buckets` buckets can hold
+/// before it has to grow.
+///
+/// # Panics
+///
+/// Panics if `buckets` is neither zero nor a power of two.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::hash_map::buckets_to_capacity;
+///
+/// assert_eq!(buckets_to_capacity(0), 0);
+/// assert_eq!(buckets_to_capacity(4), 3);
+/// assert_eq!(buckets_to_capacity(16), 14);
+/// ```
+pub fn buckets_to_capacity(buckets: usize) -> usize {
+    assert!(
+        buckets == 0 || buckets.is_power_of_two(),
+        "bucket count must be zero or a power of two"
+    );
+    if buckets == 0 {
+        0
+    } else {
+        bucket_mask_to_capacity(buckets - 1)
+    }
+}
+
 /// Ensures that a single closure type across uses of this which, in turn prevents multiple
 /// instances of any functions like RawTable::reserve from being generated
 #[cfg_attr(feature = "inline-more", inline)]
@@ -217,10 +328,27 @@ where
     move |val| make_hash::<Q, S>(hash_builder, &val.0)
 }
 
+/// Returns the shard of `hash` when entries are split over `1 << shard_bits`
+/// tables.
+///
+/// Hashers don't have to fill all 64 bits: a 32-bit hasher may only set the
+/// bucket bits at the bottom and the tag at the top. So the whole hash is
+/// multiplied by an odd constant, whose top bits depend on every bit of the
+/// hash, and those pick the shard. Entries stay evenly spread within a shard
+/// too, since no single bit of the hash is the same for all of them.
+#[inline]
+pub(crate) fn shard_index(hash: u64, shard_bits: u32) -> usize {
+    if shard_bits == 0 {
+        0
+    } else {
+        (hash.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - shard_bits)) as usize
+    }
+}
+
 /// Ensures that a single closure type across uses of this which, in turn prevents multiple
 /// instances of any functions like RawTable::reserve from being generated
 #[cfg_attr(feature = "inline-more", inline)]
-fn equivalent_key<Q, K, V>(k: &Q) -> impl Fn(&(K, V)) -> bool + '_
+pub(crate) fn equivalent_key<Q, K, V>(k: &Q) -> impl Fn(&(K, V)) -> bool + '_
 where
     Q: ?Sized + Equivalent<K>,
 {
@@ -283,7 +411,7 @@ where
     hash_builder.hash_one(val)
 }
 
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<K, V> HashMap<K, V, DefaultHashBuilder> {
     /// Creates an empty `HashMap`.
     ///
@@ -346,7 +474,7 @@ impl<K, V> HashMap<K, V, DefaultHashBuilder> {
     }
 }
 
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<K, V, A: Allocator + Clone> HashMap<K, V, DefaultHashBuilder, A> {
     /// Creates an empty `HashMap` using the given allocator.
     ///
@@ -656,6 +784,106 @@ impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
         self.table.capacity()
     }
 
+    /// Returns the number of buckets the map has allocated.
+    ///
+    /// Every bucket holds one `(K, V)` pair, so together with
+    /// [`capacity_to_buckets`] this tells how much memory the map uses. The
+    /// count is zero until the map first allocates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = HashMap::new();
+    /// assert_eq!(map.bucket_count(), 0);
+    ///
+    /// map.reserve_exact(100);
+    /// assert_eq!(map.bucket_count(), 128);
+    /// assert_eq!(map.capacity(), 112);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn bucket_count(&self) -> usize {
+        self.table.allocated_buckets()
+    }
+
+    /// Returns the key-value pair in the bucket at `index`, as returned by
+    /// [`index_of`], or `None` if that bucket is empty or `index` is not less
+    /// than [`bucket_count`].
+    ///
+    /// Indices stay valid until the entry is removed or the table is resized
+    /// or rehashed, which any insertion may do unless enough capacity was
+    /// [reserved] for it. After that, an index may refer to another entry or
+    /// to none. [`insert_with_relocation`] reports the moves instead.
+    ///
+    /// [`index_of`]: HashMap::index_of
+    /// [`bucket_count`]: HashMap::bucket_count
+    /// [reserved]: HashMap::reserve
+    /// [`insert_with_relocation`]: HashMap::insert_with_relocation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// let index = map.index_of("a").unwrap();
+    /// assert_eq!(map.get_index(index), Some((&"a", &1)));
+    ///
+    /// map.remove("a");
+    /// assert_eq!(map.get_index(index), None);
+    /// assert_eq!(map.get_index(usize::MAX), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        if index >= self.table.allocated_buckets() {
+            return None;
+        }
+        unsafe {
+            if !self.table.is_bucket_full(index) {
+                return None;
+            }
+            let (key, value) = self.table.bucket(index).as_ref();
+            Some((key, value))
+        }
+    }
+
+    /// Returns the key and a mutable reference to the value in the bucket at
+    /// `index`, as returned by [`index_of`], or `None` if that bucket is empty
+    /// or `index` is not less than [`bucket_count`].
+    ///
+    /// See [`get_index`] for how long indices stay valid.
+    ///
+    /// [`index_of`]: HashMap::index_of
+    /// [`bucket_count`]: HashMap::bucket_count
+    /// [`get_index`]: HashMap::get_index
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert("a", 1);
+    /// let index = map.index_of("a").unwrap();
+    /// *map.get_index_mut(index).unwrap().1 += 1;
+    /// assert_eq!(map["a"], 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_index_mut(&mut self, index: usize) -> Option<(&K, &mut V)> {
+        if index >= self.table.allocated_buckets() {
+            return None;
+        }
+        unsafe {
+            if !self.table.is_bucket_full(index) {
+                return None;
+            }
+            let (key, value) = self.table.bucket(index).as_mut();
+            Some((key, value))
+        }
+    }
+
     /// An iterator visiting all keys in arbitrary order.
     /// The iterator element type is `&'a K`.
     ///
@@ -798,6 +1026,90 @@ impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
         }
     }
 
+    /// An iterator visiting the key-value pairs from the given position to the
+    /// end of the map, which resumes an earlier scan of the map.
+    ///
+    /// Positions are bucket indices: a scan starts at position 0 and
+    /// [`IterStartingAt::next_position`] returns where to resume it after the
+    /// pairs it has yielded, so that a huge map can be visited in slices without
+    /// holding an iterator, and with it a borrow of the map, in between. The
+    /// map may be modified between slices. Pairs inserted in between may or
+    /// may not be visited, and if the map was resized in between, the scan
+    /// may skip or repeat some pairs. Positions past the end of the map yield
+    /// nothing.
+    ///
+    /// The pairs are visited in the order of the buckets, even with the
+    /// `random-order` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    ///
+    /// // Remove the odd keys, looking at ten entries at a time.
+    /// let mut position = Some(0);
+    /// while let Some(start) = position {
+    ///     let mut iter = map.iter_starting_at(start);
+    ///     let odd: Vec<i32> = iter.by_ref().take(10).map(|(k, _)| *k).filter(|k| k % 2 == 1).collect();
+    ///     position = iter.next_position();
+    ///     for k in &odd {
+    ///         map.remove(k);
+    ///     }
+    /// }
+    /// assert_eq!(map.len(), 50);
+    /// assert!(map.keys().all(|k| k % 2 == 0));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter_starting_at(&self, position: usize) -> IterStartingAt<'_, K, V, A> {
+        let position = position.min(self.table.buckets());
+        IterStartingAt {
+            // SAFETY: The position is at most the number of buckets, and the
+            // iterator borrows the table.
+            inner: unsafe { self.table.iter_from(position) },
+            table: &self.table,
+            position: Some(position),
+        }
+    }
+
+    /// Splits the map into `n` disjoint chunks which together visit every
+    /// key-value pair once, so that the pairs can be processed on `n` threads
+    /// without Rayon.
+    ///
+    /// The chunks are ranges of buckets of about the same size, and each is an
+    /// iterator which can be sent to another thread when the keys and values
+    /// are `Sync`. Small maps may have fewer non-empty chunks than requested.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<u64, u64> = (0..10_000).map(|i| (i, i * 2)).collect();
+    ///
+    /// // Each chunk could be summed on a thread of its own.
+    /// let sums: Vec<u64> = map
+    ///     .iter_chunks(4)
+    ///     .map(|chunk| chunk.map(|(_, v)| v).sum())
+    ///     .collect();
+    /// let sum: u64 = sums.iter().sum();
+    /// assert_eq!(sum, map.values().sum());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter_chunks(&self, n: usize) -> IterChunks<'_, K, V, A> {
+        assert!(n > 0, "cannot split a map into zero chunks");
+        IterChunks {
+            table: &self.table,
+            next: 0,
+            chunks: n,
+        }
+    }
+
     /// An iterator visiting all key-value pairs in arbitrary order,
     /// with mutable references to the values.
     /// The iterator element type is `(&'a K, &'a mut V)`.
@@ -1025,6 +1337,49 @@ impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
         }
     }
 
+    /// Returns a cursor which visits the entries of the map in arbitrary order
+    /// and can remove the entry it is at.
+    ///
+    /// Unlike [`drain_filter`], which decides whether to remove an entry with a
+    /// closure, the cursor lets the caller inspect an entry, remove it and keep
+    /// the removed key and value, then move on, all in a single pass over the
+    /// table. Removed entries are never looked up again.
+    ///
+    /// Keeps the allocated memory for reuse.
+    ///
+    /// [`drain_filter`]: HashMap::drain_filter
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, String> = (0..8).map(|x| (x, x.to_string())).collect();
+    /// let mut evens = Vec::new();
+    ///
+    /// let mut cursor = map.cursor_mut();
+    /// while let Some((k, v)) = cursor.next() {
+    ///     v.push('!');
+    ///     if k % 2 == 0 {
+    ///         let (_, v) = cursor.remove().unwrap();
+    ///         evens.push(v);
+    ///     }
+    /// }
+    ///
+    /// evens.sort();
+    /// assert_eq!(evens, ["0!", "2!", "4!", "6!"]);
+    /// assert_eq!(map.len(), 4);
+    /// assert_eq!(map[&1], "1!");
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V, A> {
+        CursorMut {
+            iter: unsafe { self.table.iter() },
+            table: &mut self.table,
+            current: None,
+        }
+    }
+
     /// Clears the map, removing all key-value pairs. Keeps the allocated memory
     /// for reuse.
     ///
@@ -1049,6 +1404,85 @@ impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
         self.table.clear();
     }
 
+    /// Clears the map, removing all key-value pairs, and frees the allocated
+    /// memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut a: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// a.clear_and_shrink();
+    /// assert!(a.is_empty());
+    /// assert_eq!(a.capacity(), 0);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_and_shrink(&mut self) {
+        self.table.clear_and_shrink_to(0);
+    }
+
+    /// Clears the map, removing all key-value pairs, and shrinks its table so
+    /// that it holds at most about `min_capacity` elements without
+    /// reallocating. A table which is already that small is kept as it is.
+    ///
+    /// This suits a scratch map which is reused, but shouldn't keep the
+    /// memory of an unusually large batch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut scratch: HashMap<i32, i32> = HashMap::with_capacity(16);
+    /// scratch.extend((0..1000).map(|i| (i, i)));
+    /// scratch.clear_and_shrink_to(16);
+    /// assert!(scratch.is_empty());
+    /// assert!(scratch.capacity() >= 16);
+    /// assert!(scratch.capacity() < 1000);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_and_shrink_to(&mut self, min_capacity: usize) {
+        self.table.clear_and_shrink_to(min_capacity);
+    }
+
+    /// Removes an arbitrary key-value pair from the map and returns it, or
+    /// returns `None` if the map is empty.
+    ///
+    /// The pair is found by scanning the control bytes a group at a time from
+    /// the start of the table, and removed without looking its key up again,
+    /// so this doesn't need the key to be hashed or cloned. Which pair is
+    /// returned is unspecified. To remove every pair at once, [`drain`] is
+    /// faster than calling this in a loop.
+    ///
+    /// [`drain`]: HashMap::drain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut queue: HashMap<i32, &str> = [(1, "a"), (2, "b")].into();
+    /// let mut done = Vec::new();
+    /// while let Some((k, v)) = queue.pop() {
+    ///     if k == 1 {
+    ///         queue.insert(3, "c");
+    ///     }
+    ///     done.push(v);
+    /// }
+    /// done.sort_unstable();
+    /// assert_eq!(done, ["a", "b", "c"]);
+    /// assert!(queue.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        // SAFETY: The bucket comes from the table and is removed right away.
+        unsafe {
+            let bucket = self.table.iter().next()?;
+            Some(self.table.remove(bucket))
+        }
+    }
+
     /// Creates a consuming iterator visiting all the keys in arbitrary order.
     /// The map cannot be used after calling this.
     /// The iterator element type is `K`.
@@ -1104,6 +1538,35 @@ impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
             inner: self.into_iter(),
         }
     }
+
+    /// Converts the map into a set of its keys, dropping the values.
+    ///
+    /// Unlike collecting [`into_keys`] into a set, this doesn't hash any key:
+    /// the keys stay in the buckets they were in, and the control bytes are
+    /// kept. If the values are zero-sized, the table is reused as it is;
+    /// otherwise the keys are moved into a table of the same size.
+    ///
+    /// [`into_keys`]: HashMap::into_keys
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<&str, i32> = [("a", 1), ("b", 2)].into();
+    /// let set = map.into_key_set();
+    /// assert_eq!(set.len(), 2);
+    /// assert!(set.contains("a"));
+    /// assert!(set.contains("b"));
+    /// ```
+    pub fn into_key_set(self) -> HashSet<K, S, A> {
+        HashSet {
+            map: HashMap {
+                hash_builder: self.hash_builder,
+                table: self.table.map_into(|(k, _)| (k, ())),
+            },
+        }
+    }
 }
 
 impl<K, V, S, A> HashMap<K, V, S, A>
@@ -1144,6 +1607,53 @@ where
             .reserve(additional, make_hasher::<_, V, S>(&self.hash_builder));
     }
 
+    /// Reserves capacity for at least `additional` more elements as
+    /// [`reserve`] does, calling `relocate(old_index, new_index)` for every
+    /// entry moved to another bucket on the way.
+    ///
+    /// Entries move when the table grows, and when it is rehashed in place to
+    /// reclaim the buckets of removed entries. The indices are those returned
+    /// by [`index_of`] before and after the move, so that structures which
+    /// refer to entries by index can follow them instead of being rebuilt.
+    /// The moves are reported one at a time while entries may still be
+    /// swapped, so `new_index` can be the `old_index` of an entry reported
+    /// later: apply them to a copy of such a structure rather than in place.
+    ///
+    /// `relocate` is not called if the map already has room.
+    ///
+    /// [`reserve`]: HashMap::reserve
+    /// [`index_of`]: HashMap::index_of
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..3).map(|i| (i, i)).collect();
+    /// let indices: Vec<usize> = (0..3).map(|i| map.index_of(&i).unwrap()).collect();
+    ///
+    /// let mut moved = indices.clone();
+    /// map.reserve_with_relocation(100, |old_index, new_index| {
+    ///     let key = indices.iter().position(|&i| i == old_index).unwrap();
+    ///     moved[key] = new_index;
+    /// });
+    /// for (key, &index) in moved.iter().enumerate() {
+    ///     assert_eq!(map.get_index(index), Some((&(key as i32), &(key as i32))));
+    /// }
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve_with_relocation(
+        &mut self,
+        additional: usize,
+        relocate: impl FnMut(usize, usize),
+    ) {
+        self.table.reserve_with_relocation(
+            additional,
+            make_hasher::<_, V, S>(&self.hash_builder),
+            relocate,
+        );
+    }
+
     /// Tries to reserve capacity for at least `additional` more elements to be inserted
     /// in the given `HashMap<K,V>`. The collection may reserve more space to avoid
     /// frequent reallocations.
@@ -1194,6 +1704,60 @@ where
             .try_reserve(additional, make_hasher::<_, V, S>(&self.hash_builder))
     }
 
+    /// Reserves capacity for at least `additional` more elements, growing to
+    /// the smallest table which holds them if the map has to reallocate.
+    ///
+    /// When removals have left tombstones behind and the map needs more room,
+    /// [`reserve`](HashMap::reserve) grows to at least the next bucket count
+    /// up, so that a map with churning keys does not rehash over and over.
+    /// This instead keeps the current bucket count if it is large enough, and
+    /// otherwise grows to the one [`capacity_to_buckets`] gives for
+    /// `len() + additional` elements, which keeps memory use predictable.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new allocation size overflows [`usize`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = HashMap::new();
+    /// map.reserve_exact(100);
+    /// assert_eq!(map.bucket_count(), 128);
+    /// assert_eq!(map.capacity(), 112);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.table
+            .reserve_exact(additional, make_hasher::<_, V, S>(&self.hash_builder));
+    }
+
+    /// Tries to reserve capacity for at least `additional` more elements,
+    /// growing to the smallest table which holds them if the map has to
+    /// reallocate.
+    ///
+    /// # Errors
+    ///
+    /// If the capacity overflows, or the allocator reports a failure, then an error
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<&str, isize> = HashMap::new();
+    /// map.try_reserve_exact(10).expect("why is the test harness OOMing on 10 bytes?");
+    /// assert_eq!(map.bucket_count(), 16);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.table
+            .try_reserve_exact(additional, make_hasher::<_, V, S>(&self.hash_builder))
+    }
+
     /// Shrinks the capacity of the map as much as possible. It will drop
     /// down as much as possible while maintaining the internal rules
     /// and possibly leaving some space in accordance with the resize policy.
@@ -1245,17 +1809,136 @@ where
             .shrink_to(min_capacity, make_hasher::<_, V, S>(&self.hash_builder));
     }
 
-    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    /// Shrinks the capacity of the map as [`shrink_to`] does, calling
+    /// `relocate(old_index, new_index)` for every entry moved to another
+    /// bucket.
+    ///
+    /// See [`reserve_with_relocation`] for how to apply the moves.
+    ///
+    /// [`shrink_to`]: HashMap::shrink_to
+    /// [`reserve_with_relocation`]: HashMap::reserve_with_relocation
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn shrink_to_with_relocation(
+        &mut self,
+        min_capacity: usize,
+        relocate: impl FnMut(usize, usize),
+    ) {
+        self.table.shrink_to_with_relocation(
+            min_capacity,
+            make_hasher::<_, V, S>(&self.hash_builder),
+            relocate,
+        );
+    }
+
+    /// Returns statistics about the layout of the map's table: the number of
+    /// occupied, empty and deleted buckets, the load factor, and the longest
+    /// and mean number of groups which lookups of the keys probe.
+    ///
+    /// Many deleted buckets, left behind by removals, or long probes are a
+    /// sign that the map would be faster after being rebuilt, for example
+    /// with [`shrink_to_fit`].
+    ///
+    /// This scans the whole table and hashes every key, so it takes time
+    /// linear in the capacity of the map.
+    ///
+    /// [`shrink_to_fit`]: HashMap::shrink_to_fit
     ///
     /// # Examples
     ///
     /// ```
     /// use hashbrown::HashMap;
     ///
-    /// let mut letters = HashMap::new();
+    /// let mut map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// let stats = map.stats();
+    /// assert_eq!(stats.full, 100);
+    /// assert_eq!(stats.full + stats.deleted + stats.empty, stats.buckets);
+    /// assert!(stats.max_probe_length >= 1);
+    /// assert!(stats.load_factor <= 0.875);
+    /// ```
+    pub fn stats(&self) -> TableStats {
+        self.table
+            .stats(|(k, _)| make_hash::<K, S>(&self.hash_builder, k))
+    }
+
+    /// Returns counters of the work the map's table has done since the map
+    /// was created or [`reset_metrics`] was last called: how often it was
+    /// resized or rehashed in place, how many tombstones removals left
+    /// behind, and how many groups of buckets lookups and insertions probed.
     ///
-    /// for ch in "a short treatise on fungi".chars() {
-    ///     let counter = letters.entry(ch).or_insert(0);
+    /// Where [`stats`] describes the table as it is now, these counters only
+    /// ever grow, which suits exporting them to monitoring systems. Lookups
+    /// running concurrently on several threads are all counted, but each of
+    /// them then writes to the table, so counting slows down maps which are
+    /// read from many threads at once.
+    ///
+    /// [`reset_metrics`]: HashMap::reset_metrics
+    /// [`stats`]: HashMap::stats
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// for i in 0..100 {
+    ///     map.insert(i, i);
+    /// }
+    /// let metrics = map.metrics();
+    /// assert!(metrics.resizes > 0);
+    /// assert!(metrics.probe_steps >= 100);
+    ///
+    /// map.reset_metrics();
+    /// assert_eq!(map.metrics().resizes, 0);
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> TableMetrics {
+        self.table.metrics()
+    }
+
+    /// Sets all counters returned by [`metrics`] back to zero.
+    ///
+    /// [`metrics`]: HashMap::metrics
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&mut self) {
+        self.table.reset_metrics();
+    }
+
+    /// Checks the invariants of the map's table, panicking if any is broken.
+    ///
+    /// Every control byte must be valid, every key must be stored in a bucket
+    /// where lookups find it and with the tag of its hash, and the counts
+    /// kept by the table must match the buckets. This only fails if the table
+    /// has a bug, or if the keys' hashes changed after they were inserted,
+    /// for example because of interior mutability.
+    ///
+    /// It is meant for debugging and scans the whole table, hashing every
+    /// key, so it should not be called on hot paths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+    /// map.retain(|&k, _| k % 3 == 0);
+    /// map.verify();
+    /// ```
+    pub fn verify(&self) {
+        self.table
+            .verify(|(k, _)| make_hash::<K, S>(&self.hash_builder, k));
+    }
+
+    /// Gets the given key's corresponding entry in the map for in-place manipulation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut letters = HashMap::new();
+    ///
+    /// for ch in "a short treatise on fungi".chars() {
+    ///     let counter = letters.entry(ch).or_insert(0);
     ///     *counter += 1;
     /// }
     ///
@@ -1267,6 +1950,12 @@ where
     #[cfg_attr(feature = "inline-more", inline)]
     pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S, A> {
         let hash = make_insert_hash::<K, S>(&self.hash_builder, &key);
+        self.entry_hashed(hash, key)
+    }
+
+    /// Gets the entry of a key whose hash has already been computed.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub(crate) fn entry_hashed(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S, A> {
         if let Some(elem) = self.table.find(hash, equivalent_key(&key)) {
             Entry::Occupied(OccupiedEntry {
                 hash,
@@ -1353,6 +2042,39 @@ where
         }
     }
 
+    /// Returns a reference to the value corresponding to the key, comparing
+    /// candidate keys with [`FastKeyEq`].
+    ///
+    /// This is [`get`](HashMap::get) for maps whose key type is one of the
+    /// fixed-size types with a vectorized equality, such as `[u8; 16]` or
+    /// `u128`. With the `nightly` feature `get` already uses the same
+    /// comparison for these key types.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// map.insert([1u8; 16], "a");
+    /// assert_eq!(map.get_fast(&[1u8; 16]), Some(&"a"));
+    /// assert_eq!(map.get_fast(&[2u8; 16]), None);
+    /// ```
+    #[inline]
+    pub fn get_fast(&self, k: &K) -> Option<&V>
+    where
+        K: FastKeyEq,
+    {
+        if self.table.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<K, S>(&self.hash_builder, k);
+        match self.table.get(hash, |x| k.fast_eq(&x.0)) {
+            Some((_, v)) => Some(v),
+            None => None,
+        }
+    }
+
     /// Returns the key-value pair corresponding to the supplied key.
     ///
     /// The supplied key may be any borrowed form of the map's key type, but
@@ -1459,6 +2181,45 @@ where
         self.get_inner(k).is_some()
     }
 
+    /// Returns the index of the bucket holding the supplied key, which
+    /// [`get_index`] turns back into the key-value pair.
+    ///
+    /// Other data structures can refer to entries of the map by this index
+    /// instead of keeping a copy of their keys, as long as the map is not
+    /// resized or rehashed in between. See [`get_index`] for details.
+    ///
+    /// [`get_index`]: HashMap::get_index
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::with_capacity(3);
+    /// map.insert("a".to_string(), 1);
+    /// map.insert("b".to_string(), 2);
+    ///
+    /// // Refer to "b" without cloning the key.
+    /// let b = map.index_of("b").unwrap();
+    /// map.insert("c".to_string(), 3);
+    /// assert_eq!(map.get_index(b), Some((&"b".to_string(), &2)));
+    /// assert_eq!(map.index_of("d"), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn index_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.table.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        match self.table.find(hash, equivalent_key(k)) {
+            Some(bucket) => Some(unsafe { self.table.bucket_index(&bucket) }),
+            None => None,
+        }
+    }
+
     /// Returns a mutable reference to the value corresponding to the key.
     ///
     /// The key may be any borrowed form of the map's key type, but
@@ -1796,6 +2557,97 @@ where
         }
     }
 
+    /// Inserts a key-value pair into the map as [`insert`] does, calling
+    /// `relocate(old_index, new_index)` for every entry which the insertion
+    /// moves to another bucket, and returns the index of the entry's bucket
+    /// with the old value, if any.
+    ///
+    /// This lets structures which refer to entries by their [`index_of`]
+    /// follow them when the table grows or is rehashed. See
+    /// [`reserve_with_relocation`] for how to apply the moves.
+    ///
+    /// [`insert`]: HashMap::insert
+    /// [`index_of`]: HashMap::index_of
+    /// [`reserve_with_relocation`]: HashMap::reserve_with_relocation
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map = HashMap::new();
+    /// let mut order = Vec::new();
+    /// for key in ["a", "b", "c", "d", "e"] {
+    ///     let mut moved = order.clone();
+    ///     let (index, old) = map.insert_with_relocation(key, 0, |old_index, new_index| {
+    ///         let pos = order.iter().position(|&i| i == old_index).unwrap();
+    ///         moved[pos] = new_index;
+    ///     });
+    ///     assert_eq!(old, None);
+    ///     order = moved;
+    ///     order.push(index);
+    /// }
+    ///
+    /// // `order` still holds the buckets of the keys in insertion order.
+    /// let keys: Vec<_> = order.iter().map(|&i| *map.get_index(i).unwrap().0).collect();
+    /// assert_eq!(keys, ["a", "b", "c", "d", "e"]);
+    /// ```
+    pub fn insert_with_relocation(
+        &mut self,
+        k: K,
+        v: V,
+        relocate: impl FnMut(usize, usize),
+    ) -> (usize, Option<V>) {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+        unsafe {
+            if let Some(bucket) = self.table.find(hash, equivalent_key(&k)) {
+                let index = self.table.bucket_index(&bucket);
+                return (index, Some(mem::replace(&mut bucket.as_mut().1, v)));
+            }
+            self.table.reserve_with_relocation(
+                1,
+                make_hasher::<_, V, S>(&self.hash_builder),
+                relocate,
+            );
+            // SAFETY: `reserve_with_relocation` made room for the entry.
+            let bucket = self.table.insert_no_grow(hash, (k, v));
+            (self.table.bucket_index(&bucket), None)
+        }
+    }
+
+    /// Inserts all key-value pairs of `iter` as `insert` would, for `extend`
+    /// and `from_iter` once they reserved room for them.
+    ///
+    /// Every pair takes at most one bucket, so as many pairs as the table has
+    /// room for are inserted without checking whether it must grow, probing
+    /// the table once for both the key and a free bucket. Pairs beyond that,
+    /// which only occur if the iterator's size hint was too low, take the
+    /// path of `insert`.
+    fn insert_all<I: Iterator<Item = (K, V)>>(&mut self, iter: I) {
+        let mut room = self.table.growth_left();
+        iter.for_each(|(k, v)| {
+            if room == 0 {
+                self.insert(k, v);
+                return;
+            }
+            room -= 1;
+            let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+            // SAFETY: Inserting at most `room` elements into empty buckets
+            // cannot use up the `growth_left` we started from.
+            unsafe {
+                match self
+                    .table
+                    .find_or_find_insert_slot_no_grow(hash, equivalent_key(&k))
+                {
+                    Ok(bucket) => bucket.as_mut().1 = v,
+                    Err(slot) => {
+                        self.table.insert_in_slot(hash, slot, (k, v));
+                    }
+                }
+            }
+        });
+    }
+
     /// Insert a key-value pair into the map without checking
     /// if the key already exists in the map.
     ///
@@ -1895,6 +2747,128 @@ where
         }
     }
 
+    /// Returns a mutable reference to the value of the key, inserting the
+    /// result of `default` first if the key is missing.
+    ///
+    /// This is equivalent to `map.entry(key).or_insert_with(default)`, but
+    /// looks for the key and for a free bucket in a single pass over the
+    /// table, and `default` is only called if the key is missing. If the map
+    /// is full, it grows even if the key is present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut counts: HashMap<&str, u32> = HashMap::new();
+    /// for word in ["a", "b", "a"] {
+    ///     *counts.get_or_insert_with(word, || 0) += 1;
+    /// }
+    /// assert_eq!(counts["a"], 2);
+    /// assert_eq!(counts["b"], 1);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_or_insert_with<F>(&mut self, key: K, default: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        self.get_or_insert_with_key(key, |_| default())
+    }
+
+    /// Returns a mutable reference to the value of the key, inserting the
+    /// result of calling `default` with the key first if the key is missing.
+    ///
+    /// This works like [`get_or_insert_with`], except that `default` is given
+    /// a reference to the key, which is useful for memoizing a function of
+    /// the key.
+    ///
+    /// [`get_or_insert_with`]: HashMap::get_or_insert_with
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut lengths: HashMap<&str, usize> = HashMap::new();
+    /// assert_eq!(*lengths.get_or_insert_with_key("hello", |s| s.len()), 5);
+    /// assert_eq!(*lengths.get_or_insert_with_key("hello", |_| unreachable!()), 5);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_or_insert_with_key<F>(&mut self, key: K, default: F) -> &mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &key);
+        let bucket = match self.table.find_or_find_insert_slot(
+            hash,
+            equivalent_key(&key),
+            make_hasher::<_, V, S>(&self.hash_builder),
+        ) {
+            Ok(bucket) => bucket,
+            Err(slot) => {
+                let value = default(&key);
+                // SAFETY: The slot was just found for this hash.
+                unsafe { self.table.insert_in_slot(hash, slot, (key, value)) }
+            }
+        };
+        unsafe { &mut bucket.as_mut().1 }
+    }
+
+    /// Inserts all key-value pairs from the iterator, calling `merge` with the
+    /// value already in the map and the new value whenever a key is present.
+    ///
+    /// Unlike [`extend`], which replaces the old value, `merge` decides how the
+    /// two values are combined; keys missing from the map are inserted as is.
+    /// Room is reserved up front the same way as by [`extend`]. Each pair is
+    /// hashed and probed only once.
+    ///
+    /// [`extend`]: #method.extend
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut totals = HashMap::new();
+    /// totals.insert("a", 1);
+    ///
+    /// let events = [("a", 2), ("b", 5), ("a", 3)];
+    /// totals.extend_with(events, |total, amount| *total += amount);
+    ///
+    /// assert_eq!(totals[&"a"], 6);
+    /// assert_eq!(totals[&"b"], 5);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn extend_with<I, F>(&mut self, iter: I, mut merge: F)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        F: FnMut(&mut V, V),
+    {
+        let iter = iter.into_iter();
+        let reserve = if self.is_empty() {
+            iter.size_hint().0
+        } else {
+            (iter.size_hint().0 + 1) / 2
+        };
+        self.reserve(reserve);
+        for (k, v) in iter {
+            let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+            match self.table.find_or_find_insert_slot(
+                hash,
+                equivalent_key(&k),
+                make_hasher::<_, V, S>(&self.hash_builder),
+            ) {
+                Ok(bucket) => merge(unsafe { &mut bucket.as_mut().1 }, v),
+                Err(slot) => {
+                    // SAFETY: The slot was just found for this hash.
+                    unsafe {
+                        self.table.insert_in_slot(hash, slot, (k, v));
+                    }
+                }
+            }
+        }
+    }
+
     /// Removes a key from the map, returning the value at the key if the key
     /// was previously in the map. Keeps the allocated memory for reuse.
     ///
@@ -1969,6 +2943,353 @@ where
         let hash = make_hash::<Q, S>(&self.hash_builder, k);
         self.table.remove_entry(hash, equivalent_key(k))
     }
+
+    /// Splits the map into `n` independent maps, partitioning the entries by
+    /// their hash.
+    ///
+    /// The shard is picked from a mix of all the bits of the hash, so the
+    /// shards are about the same size even with hashers which only fill some
+    /// of the bits, like [`IntegerHash32Builder`].
+    ///
+    /// [`IntegerHash32Builder`]: crate::hash_map::IntegerHash32Builder
+    ///
+    /// Every key is hashed exactly once and each shard is allocated with the
+    /// exact capacity it needs, so no shard grows while being filled. The
+    /// shards can then be processed on separate threads, and put back
+    /// together with [`merge_shards`].
+    ///
+    /// The shard of a key only depends on its hash, so a key which is looked
+    /// up or inserted later can be routed to its shard with the shards' own
+    /// hasher: it is the shard at the same position in the returned `Vec`.
+    ///
+    /// [`merge_shards`]: HashMap::merge_shards
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    /// use std::thread;
+    ///
+    /// let map: HashMap<i32, i32> = (0..1000).map(|i| (i, i)).collect();
+    /// let shards = map.split_into_shards(4);
+    /// assert_eq!(shards.len(), 4);
+    /// assert_eq!(shards.iter().map(|s| s.len()).sum::<usize>(), 1000);
+    ///
+    /// let shards: Vec<HashMap<i32, i32>> = shards
+    ///     .into_iter()
+    ///     .map(|mut shard| {
+    ///         thread::spawn(move || {
+    ///             shard.values_mut().for_each(|v| *v *= 2);
+    ///             shard
+    ///         })
+    ///     })
+    ///     .map(|handle| handle.join().unwrap())
+    ///     .collect();
+    ///
+    /// let map = HashMap::merge_shards(shards);
+    /// assert_eq!(map.len(), 1000);
+    /// assert_eq!(map[&21], 42);
+    /// ```
+    pub fn split_into_shards(self, n: usize) -> Vec<Self>
+    where
+        S: Clone,
+    {
+        assert!(
+            n.is_power_of_two(),
+            "the number of shards must be a power of two"
+        );
+        let shard_bits = n.trailing_zeros();
+        if n == 1 {
+            return alloc::vec![self];
+        }
+
+        // The entries are moved out by a copy of the iterator used to hash
+        // them, so that the hashes can be matched up with the entries below.
+        // SAFETY: The iterator is only used while the table is alive, and
+        // covers all of its items.
+        let iter = unsafe { self.table.iter() };
+        let hashes: Vec<u64> = iter
+            .clone()
+            .map(|item| unsafe { make_hash::<K, S>(&self.hash_builder, &item.as_ref().0) })
+            .collect();
+        let mut lens = alloc::vec![0; n];
+        for &hash in &hashes {
+            lens[shard_index(hash, shard_bits)] += 1;
+        }
+
+        let mut shards: Vec<Self> = lens
+            .into_iter()
+            .map(|len| {
+                Self::with_capacity_and_hasher_in(
+                    len,
+                    self.hash_builder.clone(),
+                    self.table.allocator().clone(),
+                )
+            })
+            .collect();
+        let items = unsafe { self.table.into_iter_from(iter) };
+        for (item, hash) in items.zip(hashes) {
+            let shard = &mut shards[shard_index(hash, shard_bits)];
+            // SAFETY: Each shard was allocated with room for all its entries.
+            unsafe {
+                shard.table.insert_no_grow(hash, item);
+            }
+        }
+        shards
+    }
+
+    /// Merges maps produced by [`split_into_shards`] back into a single map.
+    ///
+    /// The table of the first shard is reused, and entries which are still in
+    /// the shard they were assigned to are moved without looking them up.
+    /// Entries which were inserted into the wrong shard in the meantime are
+    /// inserted normally, so if a key is present in several shards the
+    /// resulting map contains one of their values, and which one is
+    /// unspecified. Keys are hashed with the hasher of the first shard.
+    ///
+    /// [`split_into_shards`]: HashMap::split_into_shards
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of shards is not a power of two.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<i32, &str> = [(1, "a"), (2, "b"), (3, "c")].into();
+    /// let mut shards = map.split_into_shards(2);
+    /// shards[0].insert(4, "d");
+    ///
+    /// let map = HashMap::merge_shards(shards);
+    /// assert_eq!(map.len(), 4);
+    /// assert_eq!(map[&4], "d");
+    /// ```
+    pub fn merge_shards(shards: Vec<Self>) -> Self {
+        assert!(
+            shards.len().is_power_of_two(),
+            "the number of shards must be a power of two"
+        );
+        let shard_bits = shards.len().trailing_zeros();
+        let len = shards.iter().map(Self::len).sum::<usize>();
+        let mut shards = shards.into_iter();
+        // There is at least one shard since 0 is not a power of two.
+        let mut map = shards.next().unwrap();
+        if shard_bits == 0 {
+            return map;
+        }
+
+        // Entries of other shards which are in their own shard cannot be in
+        // the first one unless they are misplaced there, so take those out.
+        let mut misplaced = Vec::new();
+        // Here we only use the iterator as a temporary, preventing use-after-free
+        unsafe {
+            for item in map.table.iter() {
+                let hash = make_hash::<K, S>(&map.hash_builder, &item.as_ref().0);
+                if shard_index(hash, shard_bits) != 0 {
+                    misplaced.push(map.table.remove(item));
+                }
+            }
+        }
+        map.reserve(len - map.len());
+
+        for (index, shard) in shards.enumerate() {
+            for (k, v) in shard {
+                let hash = make_hash::<K, S>(&map.hash_builder, &k);
+                if shard_index(hash, shard_bits) == index + 1 {
+                    map.table
+                        .insert(hash, (k, v), make_hasher::<K, V, S>(&map.hash_builder));
+                } else {
+                    misplaced.push((k, v));
+                }
+            }
+        }
+        map.extend(misplaced);
+        map
+    }
+
+    /// Moves all key-value pairs of `other` into the map.
+    ///
+    /// If the map already contains a key of `other`, its value is replaced by
+    /// the one from `other`, as with [`extend`]. Unlike extending the map with
+    /// `other.drain()`, the map is grown at most once, up front, to hold all
+    /// the entries of `other`, and each key is hashed and probed only once:
+    /// the probe which looks the key up also finds the slot to insert it
+    /// into. If the map is empty, no lookups are performed at all since the
+    /// keys of `other` are known to be distinct.
+    ///
+    /// Every key is hashed with the hasher of this map, since `other` may use
+    /// a different one, so no hash computed by `other` is reused.
+    ///
+    /// Room is reserved for every entry of `other`, so the map may end up with
+    /// more capacity than needed if many keys are present in both maps.
+    ///
+    /// [`extend`]: HashMap::extend
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut a: HashMap<i32, &str> = [(1, "a"), (2, "b")].into();
+    /// let b: HashMap<i32, &str> = [(2, "B"), (3, "C")].into();
+    ///
+    /// a.merge_from(b);
+    /// assert_eq!(a.len(), 3);
+    /// assert_eq!(a[&1], "a");
+    /// assert_eq!(a[&2], "B");
+    /// assert_eq!(a[&3], "C");
+    /// ```
+    pub fn merge_from<S2, A2: Allocator + Clone>(&mut self, other: HashMap<K, V, S2, A2>) {
+        if self.table.is_empty() {
+            self.table
+                .absorb(other.table, make_hasher::<K, V, S>(&self.hash_builder));
+            return;
+        }
+
+        self.reserve(other.len());
+        for (k, v) in other.table {
+            let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+            // SAFETY: We reserved room for all entries of `other` above, and
+            // each iteration inserts at most one of them.
+            unsafe {
+                match self
+                    .table
+                    .find_or_find_insert_slot_no_grow(hash, equivalent_key(&k))
+                {
+                    Ok(bucket) => bucket.as_mut().1 = v,
+                    Err(slot) => {
+                        self.table.insert_in_slot(hash, slot, (k, v));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Moves all key-value pairs of `other` into the map, calling `combine`
+    /// with the key, the value in the map and the value from `other` for each
+    /// key present in both maps.
+    ///
+    /// This works like [`merge_from`], growing the map at most once and
+    /// hashing and probing each key of `other` once, except that colliding
+    /// values are combined by `combine` instead of being replaced. It is meant
+    /// for reducing partial results, such as maps built by several threads,
+    /// into one map.
+    ///
+    /// [`merge_from`]: HashMap::merge_from
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut counts: HashMap<&str, u32> = [("apple", 2), ("pear", 1)].into();
+    /// let other: HashMap<&str, u32> = [("pear", 4), ("plum", 3)].into();
+    ///
+    /// counts.merge(other, |_, count, other_count| *count += other_count);
+    /// assert_eq!(counts.len(), 3);
+    /// assert_eq!(counts["apple"], 2);
+    /// assert_eq!(counts["pear"], 5);
+    /// assert_eq!(counts["plum"], 3);
+    /// ```
+    pub fn merge<S2, A2, F>(&mut self, other: HashMap<K, V, S2, A2>, mut combine: F)
+    where
+        A2: Allocator + Clone,
+        F: FnMut(&K, &mut V, V),
+    {
+        if self.table.is_empty() {
+            self.table
+                .absorb(other.table, make_hasher::<K, V, S>(&self.hash_builder));
+            return;
+        }
+
+        self.reserve(other.len());
+        for (k, v) in other.table {
+            let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+            // SAFETY: We reserved room for all entries of `other` above, and
+            // each iteration inserts at most one of them.
+            unsafe {
+                match self
+                    .table
+                    .find_or_find_insert_slot_no_grow(hash, equivalent_key(&k))
+                {
+                    Ok(bucket) => {
+                        let (key, item) = bucket.as_mut();
+                        combine(key, item, v);
+                    }
+                    Err(slot) => {
+                        self.table.insert_in_slot(hash, slot, (k, v));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Converts the map into a map with the same keys, whose values are the
+    /// results of calling `f` with each key and value.
+    ///
+    /// If the key-value pairs of both maps have the same size and alignment,
+    /// each value is converted where it is stored, and the table is reused
+    /// without hashing any key or touching the control bytes. Otherwise the
+    /// pairs are moved into a new table with exactly enough room for them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let raw: HashMap<&str, &str> = [("a", "1"), ("b", "22")].into();
+    /// let parsed = raw.map_values(|_, v| v.len());
+    /// assert_eq!(parsed["a"], 1);
+    /// assert_eq!(parsed["b"], 2);
+    ///
+    /// let doubled = parsed.map_values(|_, v| v * 2);
+    /// assert_eq!(doubled["b"], 4);
+    /// ```
+    pub fn map_values<V2, F>(self, mut f: F) -> HashMap<K, V2, S, A>
+    where
+        F: FnMut(&K, V) -> V2,
+    {
+        let HashMap {
+            hash_builder,
+            table,
+        } = self;
+        if core::alloc::Layout::new::<(K, V)>() == core::alloc::Layout::new::<(K, V2)>() {
+            // SAFETY: The pairs have the same size and alignment, and keeping
+            // the keys in their buckets keeps the table valid.
+            let table = unsafe {
+                table.map_in_place(|(k, v)| {
+                    let v = f(&k, v);
+                    (k, v)
+                })
+            };
+            return HashMap {
+                hash_builder,
+                table,
+            };
+        }
+
+        let mut map = HashMap::with_capacity_and_hasher_in(
+            table.len(),
+            hash_builder,
+            table.allocator().clone(),
+        );
+        for (k, v) in table {
+            let hash = make_insert_hash::<K, S>(&map.hash_builder, &k);
+            let v = f(&k, v);
+            // SAFETY: The keys are distinct and the map has room for all of
+            // them.
+            unsafe {
+                map.table.insert_no_grow(hash, (k, v));
+            }
+        }
+        map
+    }
 }
 
 impl<K, V, S, A: Allocator + Clone> HashMap<K, V, S, A> {
@@ -2197,8 +3518,30 @@ where
             return false;
         }
 
-        self.iter()
-            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+        // Maps built from the same keys with hashers sharing a seed usually
+        // place every key in the same bucket. Check the bucket of `other` at
+        // the same index first, which needs no hashing, and only look a key
+        // up if it isn't there. Either way every key of `self` is found in
+        // `other`, which is enough since both maps have the same length.
+        let same_layout = self.table.buckets() == other.table.buckets();
+        // SAFETY: The buckets outlive the iterator since `self` is borrowed,
+        // and both tables have the same number of buckets when indexing
+        // `other` with an index of `self`.
+        unsafe {
+            self.table.iter().all(|bucket| {
+                let (key, value) = bucket.as_ref();
+                if same_layout {
+                    let index = self.table.bucket_index(&bucket);
+                    if other.table.ctrl_byte(index) == self.table.ctrl_byte(index) {
+                        let (other_key, other_value) = other.table.bucket(index).as_ref();
+                        if key == other_key {
+                            return value == other_value;
+                        }
+                    }
+                }
+                other.get(key).map_or(false, |v| *value == *v)
+            })
+        }
     }
 }
 
@@ -2211,6 +3554,52 @@ where
 {
 }
 
+/// Hashes the length of the map and the wrapping sum of the hashes of its
+/// entries, so that equal maps hash the same whatever the order of their
+/// tables. The entries are hashed with [`FixedState`] rather than the hasher
+/// of the map, since equal maps may use hashers with different seeds.
+///
+/// Only available with the `unordered-hash` feature. Hashing a map visits
+/// every entry, so maps used as keys should be small or rarely looked up.
+///
+/// [`FixedState`]: crate::hash_map::FixedState
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "unordered-hash")]
+/// # fn test() {
+/// use hashbrown::{HashMap, HashSet};
+///
+/// let a: HashMap<&str, i32> = [("a", 1), ("b", 2)].into();
+/// let b: HashMap<&str, i32> = [("b", 2), ("a", 1)].into();
+///
+/// let mut seen = HashSet::new();
+/// assert!(seen.insert(a));
+/// assert!(!seen.insert(b));
+/// # }
+/// # fn main() {
+/// #     #[cfg(feature = "unordered-hash")]
+/// #     test()
+/// # }
+/// ```
+#[cfg(feature = "unordered-hash")]
+impl<K, V, S, A> Hash for HashMap<K, V, S, A>
+where
+    K: Hash,
+    V: Hash,
+    A: Allocator + Clone,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        let fixed = crate::hasher::FixedState::new();
+        let sum = self.iter().fold(0_u64, |sum, entry| {
+            sum.wrapping_add(make_hash::<(&K, &V), _>(&fixed, &entry))
+        });
+        state.write_usize(self.len());
+        state.write_u64(sum);
+    }
+}
+
 impl<K, V, S, A> Debug for HashMap<K, V, S, A>
 where
     K: Debug,
@@ -2280,7 +3669,7 @@ where
 }
 
 // The default hasher is used to match the std implementation signature
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<K, V, A, const N: usize> From<[(K, V); N]> for HashMap<K, V, DefaultHashBuilder, A>
 where
     K: Eq + Hash,
@@ -2295,8 +3684,21 @@ where
     /// let map2: HashMap<_, _> = [(1, 2), (3, 4)].into();
     /// assert_eq!(map1, map2);
     /// ```
+    ///
+    /// The table is allocated once, with room for exactly `N` entries:
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = HashMap::from([(1, 2), (3, 4), (5, 6)]);
+    /// assert_eq!(map.capacity(), HashMap::<i32, i32>::with_capacity(3).capacity());
+    /// ```
     fn from(arr: [(K, V); N]) -> Self {
-        arr.into_iter().collect()
+        let mut map = Self::with_capacity_and_hasher_in(N, Default::default(), A::default());
+        for (k, v) in arr {
+            map.insert(k, v);
+        }
+        map
     }
 }
 
@@ -2329,8 +3731,8 @@ where
 /// assert_eq!(iter.next(), None);
 /// ```
 pub struct Iter<'a, K, V> {
-    inner: RawIter<(K, V)>,
-    marker: PhantomData<(&'a K, &'a V)>,
+    pub(crate) inner: RawIter<(K, V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a V)>,
 }
 
 // FIXME(#26925) Remove in favor of `#[derive(Clone)]`
@@ -2378,9 +3780,9 @@ impl<K: Debug, V: Debug> fmt::Debug for Iter<'_, K, V> {
 /// assert_eq!(map.get(&2).unwrap(), &"Two Mississippi".to_owned());
 /// ```
 pub struct IterMut<'a, K, V> {
-    inner: RawIter<(K, V)>,
+    pub(crate) inner: RawIter<(K, V)>,
     // To ensure invariance with respect to V
-    marker: PhantomData<(&'a K, &'a mut V)>,
+    pub(crate) marker: PhantomData<(&'a K, &'a mut V)>,
 }
 
 // We override the default Send impl which has K: Sync instead of K: Send. Both
@@ -2430,7 +3832,7 @@ impl<K, V> IterMut<'_, K, V> {
 /// assert_eq!(iter.next(), None);
 /// ```
 pub struct IntoIter<K, V, A: Allocator + Clone = Global> {
-    inner: RawIntoIter<(K, V), A>,
+    pub(crate) inner: RawIntoIter<(K, V), A>,
 }
 
 impl<K, V, A: Allocator + Clone> IntoIter<K, V, A> {
@@ -2598,7 +4000,7 @@ impl<K, V: Debug, A: Allocator + Clone> fmt::Debug for IntoValues<K, V, A> {
 /// assert_eq!(keys.next(), None);
 /// ```
 pub struct Keys<'a, K, V> {
-    inner: Iter<'a, K, V>,
+    pub(crate) inner: Iter<'a, K, V>,
 }
 
 // FIXME(#26925) Remove in favor of `#[derive(Clone)]`
@@ -2646,7 +4048,7 @@ impl<K: Debug, V> fmt::Debug for Keys<'_, K, V> {
 /// assert_eq!(values.next(), None);
 /// ```
 pub struct Values<'a, K, V> {
-    inner: Iter<'a, K, V>,
+    pub(crate) inner: Iter<'a, K, V>,
 }
 
 // FIXME(#26925) Remove in favor of `#[derive(Clone)]`
@@ -2697,121 +4099,395 @@ pub struct Drain<'a, K, V, A: Allocator + Clone = Global> {
     inner: RawDrain<'a, (K, V), A>,
 }
 
-impl<K, V, A: Allocator + Clone> Drain<'_, K, V, A> {
-    /// Returns a iterator of references over the remaining items.
+impl<K, V, A: Allocator + Clone> Drain<'_, K, V, A> {
+    /// Returns a iterator of references over the remaining items.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub(super) fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.inner.iter(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A draining iterator over entries of a `HashMap` which don't satisfy the predicate
+/// `f(&k, &mut v)` in arbitrary order. The iterator element type is `(K, V)`.
+///
+/// This `struct` is created by the [`drain_filter`] method on [`HashMap`]. See its
+/// documentation for more.
+///
+/// [`drain_filter`]: struct.HashMap.html#method.drain_filter
+/// [`HashMap`]: struct.HashMap.html
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::HashMap;
+///
+/// let mut map: HashMap<i32, &str> = [(1, "a"), (2, "b"), (3, "c")].into();
+///
+/// let mut drain_filter = map.drain_filter(|k, _v| k % 2 != 0);
+/// let mut vec = vec![drain_filter.next(), drain_filter.next()];
+///
+/// // The `DrainFilter` iterator produces items in arbitrary order, so the
+/// // items must be sorted to test them against a sorted array.
+/// vec.sort_unstable();
+/// assert_eq!(vec, [Some((1, "a")),Some((3, "c"))]);
+///
+/// // It is fused iterator
+/// assert_eq!(drain_filter.next(), None);
+/// assert_eq!(drain_filter.next(), None);
+/// drop(drain_filter);
+///
+/// assert_eq!(map.len(), 1);
+/// ```
+pub struct DrainFilter<'a, K, V, F, A: Allocator + Clone = Global>
+where
+    F: FnMut(&K, &mut V) -> bool,
+{
+    f: F,
+    inner: DrainFilterInner<'a, K, V, A>,
+}
+
+impl<'a, K, V, F, A> Drop for DrainFilter<'a, K, V, F, A>
+where
+    F: FnMut(&K, &mut V) -> bool,
+    A: Allocator + Clone,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn drop(&mut self) {
+        while let Some(item) = self.next() {
+            let guard = ConsumeAllOnDrop(self);
+            drop(item);
+            mem::forget(guard);
+        }
+    }
+}
+
+pub(super) struct ConsumeAllOnDrop<'a, T: Iterator>(pub &'a mut T);
+
+impl<T: Iterator> Drop for ConsumeAllOnDrop<'_, T> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn drop(&mut self) {
+        self.0.for_each(drop);
+    }
+}
+
+impl<K, V, F, A> Iterator for DrainFilter<'_, K, V, F, A>
+where
+    F: FnMut(&K, &mut V) -> bool,
+    A: Allocator + Clone,
+{
+    type Item = (K, V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next(&mut self.f)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.inner.iter.size_hint().1)
+    }
+}
+
+impl<K, V, F, A> FusedIterator for DrainFilter<'_, K, V, F, A>
+where
+    F: FnMut(&K, &mut V) -> bool,
+    A: Allocator + Clone,
+{
+}
+
+impl<K, V, F, A> fmt::Debug for DrainFilter<'_, K, V, F, A>
+where
+    F: FnMut(&K, &mut V) -> bool,
+    A: Allocator + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrainFilter").finish_non_exhaustive()
+    }
+}
+
+/// Portions of `DrainFilter` shared with `set::DrainFilter`
+pub(super) struct DrainFilterInner<'a, K, V, A: Allocator + Clone> {
+    pub iter: RawIter<(K, V)>,
+    pub table: &'a mut RawTable<(K, V), A>,
+}
+
+impl<K, V, A: Allocator + Clone> DrainFilterInner<'_, K, V, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub(super) fn next<F>(&mut self, f: &mut F) -> Option<(K, V)>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        unsafe {
+            for item in &mut self.iter {
+                let &mut (ref key, ref mut value) = item.as_mut();
+                if f(key, value) {
+                    return Some(self.table.remove(item));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A cursor over the entries of a `HashMap` in arbitrary order, which can
+/// remove the entry it is at.
+///
+/// This `struct` is created by the [`cursor_mut`] method on [`HashMap`]. See
+/// its documentation for more.
+///
+/// [`cursor_mut`]: struct.HashMap.html#method.cursor_mut
+/// [`HashMap`]: struct.HashMap.html
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::HashMap;
+///
+/// let mut map: HashMap<_, _> = [(1, "a"), (2, "b"), (3, "c")].into();
+///
+/// let mut cursor = map.cursor_mut();
+/// let mut removed = None;
+/// while let Some((&k, _)) = cursor.next() {
+///     if k == 2 {
+///         removed = cursor.remove();
+///     }
+/// }
+/// // Removing an entry doesn't stop the cursor, and the cursor can't remove
+/// // it twice.
+/// assert_eq!(cursor.remove(), None);
+/// assert_eq!(removed, Some((2, "b")));
+/// assert_eq!(map.len(), 2);
+/// ```
+pub struct CursorMut<'a, K, V, A: Allocator + Clone = Global> {
+    iter: RawIter<(K, V)>,
+    table: &'a mut RawTable<(K, V), A>,
+    current: Option<Bucket<(K, V)>>,
+}
+
+/// An iterator over the chunks of a `HashMap`, each of which is an iterator over
+/// a disjoint part of its entries.
+///
+/// This `struct` is created by the [`iter_chunks`] method on [`HashMap`]. See
+/// its documentation for more.
+///
+/// [`iter_chunks`]: struct.HashMap.html#method.iter_chunks
+/// [`HashMap`]: struct.HashMap.html
+pub struct IterChunks<'a, K, V, A: Allocator + Clone = Global> {
+    table: &'a RawTable<(K, V), A>,
+    next: usize,
+    chunks: usize,
+}
+
+impl<'a, K, V, A: Allocator + Clone> Iterator for IterChunks<'a, K, V, A> {
+    type Item = IterChunk<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<IterChunk<'a, K, V>> {
+        if self.next == self.chunks {
+            return None;
+        }
+        // SAFETY: The chunk index is in range, and the chunk borrows the
+        // table for as long as this iterator does.
+        let inner = unsafe { self.table.iter_chunk(self.next, self.chunks) };
+        self.next += 1;
+        Some(IterChunk {
+            inner,
+            marker: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.chunks - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<K, V, A: Allocator + Clone> Clone for IterChunks<'_, K, V, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        IterChunks { ..*self }
+    }
+}
+
+impl<K, V, A: Allocator + Clone> ExactSizeIterator for IterChunks<'_, K, V, A> {}
+impl<K, V, A: Allocator + Clone> FusedIterator for IterChunks<'_, K, V, A> {}
+
+impl<K, V, A: Allocator + Clone> fmt::Debug for IterChunks<'_, K, V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IterChunks")
+            .field("remaining", &(self.chunks - self.next))
+            .finish()
+    }
+}
+
+/// An iterator over one chunk of the entries of a `HashMap`, in arbitrary
+/// order. The iterator element type is `(&'a K, &'a V)`.
+///
+/// This `struct` is created by the [`IterChunks`] iterator.
+pub struct IterChunk<'a, K, V> {
+    inner: RawIterRange<(K, V)>,
+    marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<K, V> Clone for IterChunk<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        IterChunk {
+            inner: self.inner.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for IterChunk<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
     #[cfg_attr(feature = "inline-more", inline)]
-    pub(super) fn iter(&self) -> Iter<'_, K, V> {
-        Iter {
-            inner: self.inner.iter(),
-            marker: PhantomData,
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        // Avoid `Option::map` because it bloats LLVM IR.
+        match self.inner.next() {
+            Some(x) => unsafe {
+                let r = x.as_ref();
+                Some((&r.0, &r.1))
+            },
+            None => None,
         }
     }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
 }
 
-/// A draining iterator over entries of a `HashMap` which don't satisfy the predicate
-/// `f(&k, &mut v)` in arbitrary order. The iterator element type is `(K, V)`.
+impl<K, V> FusedIterator for IterChunk<'_, K, V> {}
+
+impl<K: Debug, V: Debug> fmt::Debug for IterChunk<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+/// An iterator over the entries of a `HashMap` from a position, which can
+/// report where to resume it later. The iterator element type is
+/// `(&'a K, &'a V)`.
 ///
-/// This `struct` is created by the [`drain_filter`] method on [`HashMap`]. See its
-/// documentation for more.
+/// This `struct` is created by the [`iter_starting_at`] method on [`HashMap`].
+/// See its documentation for more.
 ///
-/// [`drain_filter`]: struct.HashMap.html#method.drain_filter
+/// [`iter_starting_at`]: struct.HashMap.html#method.iter_starting_at
 /// [`HashMap`]: struct.HashMap.html
-///
-/// # Examples
-///
-/// ```
-/// use hashbrown::HashMap;
-///
-/// let mut map: HashMap<i32, &str> = [(1, "a"), (2, "b"), (3, "c")].into();
-///
-/// let mut drain_filter = map.drain_filter(|k, _v| k % 2 != 0);
-/// let mut vec = vec![drain_filter.next(), drain_filter.next()];
-///
-/// // The `DrainFilter` iterator produces items in arbitrary order, so the
-/// // items must be sorted to test them against a sorted array.
-/// vec.sort_unstable();
-/// assert_eq!(vec, [Some((1, "a")),Some((3, "c"))]);
-///
-/// // It is fused iterator
-/// assert_eq!(drain_filter.next(), None);
-/// assert_eq!(drain_filter.next(), None);
-/// drop(drain_filter);
-///
-/// assert_eq!(map.len(), 1);
-/// ```
-pub struct DrainFilter<'a, K, V, F, A: Allocator + Clone = Global>
-where
-    F: FnMut(&K, &mut V) -> bool,
-{
-    f: F,
-    inner: DrainFilterInner<'a, K, V, A>,
+pub struct IterStartingAt<'a, K, V, A: Allocator + Clone = Global> {
+    inner: RawIterRange<(K, V)>,
+    table: &'a RawTable<(K, V), A>,
+    position: Option<usize>,
 }
 
-impl<'a, K, V, F, A> Drop for DrainFilter<'a, K, V, F, A>
-where
-    F: FnMut(&K, &mut V) -> bool,
-    A: Allocator + Clone,
-{
+impl<K, V, A: Allocator + Clone> IterStartingAt<'_, K, V, A> {
+    /// Returns the position from which [`HashMap::iter_starting_at`] resumes
+    /// the scan after the entries this iterator has yielded, or `None` once
+    /// the iterator has reached the end of the map.
+    #[inline]
+    pub fn next_position(&self) -> Option<usize> {
+        self.position
+    }
+}
+
+impl<'a, K, V, A: Allocator + Clone> Iterator for IterStartingAt<'a, K, V, A> {
+    type Item = (&'a K, &'a V);
+
     #[cfg_attr(feature = "inline-more", inline)]
-    fn drop(&mut self) {
-        while let Some(item) = self.next() {
-            let guard = ConsumeAllOnDrop(self);
-            drop(item);
-            mem::forget(guard);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        match self.inner.next() {
+            Some(bucket) => unsafe {
+                self.position = Some(self.table.bucket_index(&bucket) + 1);
+                let r = bucket.as_ref();
+                Some((&r.0, &r.1))
+            },
+            None => {
+                self.position = None;
+                None
+            }
         }
     }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.table.len()))
+    }
 }
 
-pub(super) struct ConsumeAllOnDrop<'a, T: Iterator>(pub &'a mut T);
+impl<K, V, A: Allocator + Clone> FusedIterator for IterStartingAt<'_, K, V, A> {}
 
-impl<T: Iterator> Drop for ConsumeAllOnDrop<'_, T> {
+impl<K, V, A: Allocator + Clone> Clone for IterStartingAt<'_, K, V, A> {
     #[cfg_attr(feature = "inline-more", inline)]
-    fn drop(&mut self) {
-        self.0.for_each(drop);
+    fn clone(&self) -> Self {
+        IterStartingAt {
+            inner: self.inner.clone(),
+            ..*self
+        }
     }
 }
 
-impl<K, V, F, A> Iterator for DrainFilter<'_, K, V, F, A>
+impl<K, V, A> fmt::Debug for IterStartingAt<'_, K, V, A>
 where
-    F: FnMut(&K, &mut V) -> bool,
     A: Allocator + Clone,
 {
-    type Item = (K, V);
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IterStartingAt")
+            .field("position", &self.position)
+            .finish()
+    }
+}
 
+impl<K, V, A: Allocator + Clone> CursorMut<'_, K, V, A> {
+    /// Moves the cursor to the next entry and returns a reference to its key
+    /// and a mutable reference to its value, or returns `None` once every
+    /// entry has been visited.
+    #[allow(clippy::should_implement_trait)]
     #[cfg_attr(feature = "inline-more", inline)]
-    fn next(&mut self) -> Option<Self::Item> {
-        self.inner.next(&mut self.f)
+    pub fn next(&mut self) -> Option<(&K, &mut V)> {
+        self.current = self.iter.next();
+        let bucket = self.current.as_ref()?;
+        // SAFETY: The bucket is full since the iterator just returned it.
+        let &mut (ref key, ref mut value) = unsafe { bucket.as_mut() };
+        Some((key, value))
     }
 
-    #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        (0, self.inner.iter.size_hint().1)
+    /// Removes the entry the cursor is at from the map and returns it.
+    ///
+    /// Returns `None` if the cursor hasn't been moved to an entry yet, has
+    /// visited every entry, or has already removed the entry it is at. The
+    /// next call to [`next`] moves on to the entry after the removed one.
+    ///
+    /// [`next`]: CursorMut::next
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove(&mut self) -> Option<(K, V)> {
+        let bucket = self.current.take()?;
+        // SAFETY: The iterator already yielded the bucket, so it doesn't need
+        // to be told about the removal.
+        unsafe { Some(self.table.remove(bucket)) }
     }
 }
 
-impl<K, V, F> FusedIterator for DrainFilter<'_, K, V, F> where F: FnMut(&K, &mut V) -> bool {}
-
-/// Portions of `DrainFilter` shared with `set::DrainFilter`
-pub(super) struct DrainFilterInner<'a, K, V, A: Allocator + Clone> {
-    pub iter: RawIter<(K, V)>,
-    pub table: &'a mut RawTable<(K, V), A>,
-}
-
-impl<K, V, A: Allocator + Clone> DrainFilterInner<'_, K, V, A> {
-    #[cfg_attr(feature = "inline-more", inline)]
-    pub(super) fn next<F>(&mut self, f: &mut F) -> Option<(K, V)>
-    where
-        F: FnMut(&K, &mut V) -> bool,
-    {
-        unsafe {
-            for item in &mut self.iter {
-                let &mut (ref key, ref mut value) = item.as_mut();
-                if f(key, value) {
-                    return Some(self.table.remove(item));
-                }
-            }
-        }
-        None
+impl<K, V, A> fmt::Debug for CursorMut<'_, K, V, A>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+    A: Allocator + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let current = self.current.as_ref().map(|bucket| unsafe {
+            let (key, value) = bucket.as_ref();
+            (key, value)
+        });
+        f.debug_struct("CursorMut")
+            .field("current", &current)
+            .finish()
     }
 }
 
@@ -2843,7 +4519,7 @@ impl<K, V, A: Allocator + Clone> DrainFilterInner<'_, K, V, A> {
 /// assert_eq!(map.get(&2).unwrap(), &"Two Mississippi".to_owned());
 /// ```
 pub struct ValuesMut<'a, K, V> {
-    inner: IterMut<'a, K, V>,
+    pub(crate) inner: IterMut<'a, K, V>,
 }
 
 /// A builder for computing where in a [`HashMap`] a key-value pair would be stored.
@@ -4063,6 +5739,14 @@ impl<'a, K, V, S, A: Allocator + Clone> RawVacantEntryMut<'a, K, V, S, A> {
 
     /// Set the value of an entry with a custom hasher function.
     ///
+    /// If the table is full, inserting resizes it, and `hasher` computes the
+    /// hashes of all elements already in the map to place them in the new
+    /// table. It must therefore return the same hashes with which these
+    /// elements were inserted, but neither `K: Hash` nor `S: BuildHasher` is
+    /// required. This allows keys whose hash depends on context outside of
+    /// them, such as indices into an interner, as long as every insertion
+    /// goes through the raw entry API.
+    ///
     /// # Examples
     ///
     /// ```
@@ -4097,6 +5781,45 @@ impl<'a, K, V, S, A: Allocator + Clone> RawVacantEntryMut<'a, K, V, S, A> {
     /// map.extend([("b", 200), ("c", 300), ("d", 400), ("e", 500), ("f", 600)]);
     /// assert_eq!(map[&"a"], 100);
     /// ```
+    ///
+    /// Keys which only hash through an interner they index into:
+    ///
+    /// ```
+    /// use core::hash::{BuildHasher, Hash, Hasher};
+    /// use hashbrown::hash_map::{DefaultHashBuilder, HashMap, RawEntryMut};
+    ///
+    /// // Not `Hash`: the symbol's hash is the hash of the string it names.
+    /// struct Symbol(usize);
+    ///
+    /// let strings = ["apple", "banana", "cherry", "date", "elderberry"];
+    /// let hash_builder = DefaultHashBuilder::default();
+    /// let hash_str = |s: &str| {
+    ///     let mut state = hash_builder.build_hasher();
+    ///     s.hash(&mut state);
+    ///     state.finish()
+    /// };
+    ///
+    /// let mut counts: HashMap<Symbol, u32, ()> = HashMap::with_hasher(());
+    /// for word in ["cherry", "apple", "cherry", "date", "elderberry", "banana"] {
+    ///     let hash = hash_str(word);
+    ///     match counts
+    ///         .raw_entry_mut()
+    ///         .from_hash(hash, |sym| strings[sym.0] == word)
+    ///     {
+    ///         RawEntryMut::Occupied(mut entry) => *entry.get_mut() += 1,
+    ///         RawEntryMut::Vacant(entry) => {
+    ///             let sym = Symbol(strings.iter().position(|&s| s == word).unwrap());
+    ///             entry.insert_with_hasher(hash, sym, 1, |sym| hash_str(strings[sym.0]));
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let cherry = counts
+    ///     .raw_entry()
+    ///     .from_hash(hash_str("cherry"), |sym| strings[sym.0] == "cherry");
+    /// assert_eq!(cherry.map(|(_, &count)| count), Some(2));
+    /// assert_eq!(counts.len(), 5);
+    /// ```
     #[cfg_attr(feature = "inline-more", inline)]
     pub fn insert_with_hasher<H>(
         self,
@@ -5099,6 +6822,87 @@ impl<'a, K, V, S, A: Allocator + Clone> Entry<'a, K, V, S, A> {
         }
     }
 
+    /// Ensures a value is in the entry by inserting, if empty, the result of a
+    /// fallible default function, and returns a mutable reference to the value
+    /// in the entry.
+    ///
+    /// If the function returns an error, nothing is inserted and the error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    ///
+    /// // nonexistent key, failing initializer
+    /// let result = map.entry("poneyland").or_try_insert_with(|| "12a".parse::<u32>());
+    /// assert!(result.is_err());
+    /// assert!(!map.contains_key("poneyland"));
+    ///
+    /// // nonexistent key, succeeding initializer
+    /// let value = map.entry("poneyland").or_try_insert_with(|| "12".parse::<u32>());
+    /// assert_eq!(value, Ok(&mut 12));
+    ///
+    /// // existing key, the initializer isn't called
+    /// let value = map.entry("poneyland").or_try_insert_with(|| "error".parse::<u32>());
+    /// assert_eq!(value, Ok(&mut 12));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_try_insert_with<E, F: FnOnce() -> Result<V, E>>(
+        self,
+        default: F,
+    ) -> Result<&'a mut V, E>
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => Ok(entry.insert(default()?)),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of a
+    /// fallible default function which is given a reference to the key, and
+    /// returns a mutable reference to the value in the entry.
+    ///
+    /// If the function returns an error, nothing is inserted and the error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<&str, u32> = HashMap::new();
+    ///
+    /// let result = map.entry("12a").or_try_insert_with_key(|key| key.parse::<u32>());
+    /// assert!(result.is_err());
+    /// assert!(map.is_empty());
+    ///
+    /// let value = map.entry("12").or_try_insert_with_key(|key| key.parse::<u32>());
+    /// assert_eq!(value, Ok(&mut 12));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_try_insert_with_key<E, F: FnOnce(&K) -> Result<V, E>>(
+        self,
+        default: F,
+    ) -> Result<&'a mut V, E>
+    where
+        K: Hash,
+        S: BuildHasher,
+    {
+        match self {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key())?;
+                Ok(entry.insert(value))
+            }
+        }
+    }
+
     /// Returns a reference to this entry's key.
     ///
     /// # Examples
@@ -5810,10 +7614,85 @@ impl<'a, 'b, K, Q: ?Sized, V, S, A: Allocator + Clone> EntryRef<'a, 'b, K, Q, V,
         S: BuildHasher,
     {
         match self {
-            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => {
+                let value = default(entry.key.as_ref());
+                entry.insert(value)
+            }
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of a
+    /// fallible default function, and returns a mutable reference to the value
+    /// in the entry.
+    ///
+    /// If the function returns an error, nothing is inserted and the error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<String, u32> = HashMap::new();
+    ///
+    /// let result = map.entry_ref("poneyland").or_try_insert_with(|| "12a".parse::<u32>());
+    /// assert!(result.is_err());
+    /// assert!(!map.contains_key("poneyland"));
+    ///
+    /// let value = map.entry_ref("poneyland").or_try_insert_with(|| "12".parse::<u32>());
+    /// assert_eq!(value, Ok(&mut 12));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_try_insert_with<E, F: FnOnce() -> Result<V, E>>(
+        self,
+        default: F,
+    ) -> Result<&'a mut V, E>
+    where
+        K: Hash + From<&'b Q>,
+        S: BuildHasher,
+    {
+        match self {
+            EntryRef::Occupied(entry) => Ok(entry.into_mut()),
+            EntryRef::Vacant(entry) => Ok(entry.insert(default()?)),
+        }
+    }
+
+    /// Ensures a value is in the entry by inserting, if empty, the result of a
+    /// fallible default function which is given the borrowed form of the key,
+    /// and returns a mutable reference to the value in the entry.
+    ///
+    /// If the function returns an error, nothing is inserted and the error is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let mut map: HashMap<String, u32> = HashMap::new();
+    ///
+    /// let result = map.entry_ref("12a").or_try_insert_with_key(|key| key.parse::<u32>());
+    /// assert!(result.is_err());
+    /// assert!(map.is_empty());
+    ///
+    /// let value = map.entry_ref("12").or_try_insert_with_key(|key| key.parse::<u32>());
+    /// assert_eq!(value, Ok(&mut 12));
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn or_try_insert_with_key<E, F: FnOnce(&Q) -> Result<V, E>>(
+        self,
+        default: F,
+    ) -> Result<&'a mut V, E>
+    where
+        K: Hash + Borrow<Q> + From<&'b Q>,
+        S: BuildHasher,
+    {
+        match self {
+            EntryRef::Occupied(entry) => Ok(entry.into_mut()),
             EntryRef::Vacant(entry) => {
-                let value = default(entry.key.as_ref());
-                entry.insert(value)
+                let value = default(entry.key.as_ref())?;
+                Ok(entry.insert(value))
             }
         }
     }
@@ -6437,12 +8316,8 @@ where
 {
     #[cfg_attr(feature = "inline-more", inline)]
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
-        let iter = iter.into_iter();
-        let mut map =
-            Self::with_capacity_and_hasher_in(iter.size_hint().0, S::default(), A::default());
-        iter.for_each(|(k, v)| {
-            map.insert(k, v);
-        });
+        let mut map = Self::with_hasher_in(S::default(), A::default());
+        map.extend(iter);
         map
     }
 }
@@ -6503,9 +8378,7 @@ where
             (iter.size_hint().0 + 1) / 2
         };
         self.reserve(reserve);
-        iter.for_each(move |(k, v)| {
-            self.insert(k, v);
-        });
+        self.insert_all(iter);
     }
 
     #[inline]
@@ -7246,6 +9119,140 @@ mod test_map {
         assert_eq!(m1, m2);
     }
 
+    #[test]
+    #[cfg(feature = "unordered-hash")]
+    fn test_unordered_hash() {
+        use core::hash::{BuildHasher, Hash, Hasher};
+
+        let state = DefaultHashBuilder::default();
+        let hash = |map: &HashMap<i32, i32>| {
+            let mut hasher = state.build_hasher();
+            map.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let m1: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let mut m2 = HashMap::with_capacity(1000);
+        m2.extend((0..100).rev().map(|i| (i, i)));
+        assert_eq!(hash(&m1), hash(&m2));
+
+        m2.insert(0, 1);
+        assert_ne!(hash(&m1), hash(&m2));
+        m2.remove(&0);
+        assert_ne!(hash(&m1), hash(&m2));
+        assert_eq!(hash(&HashMap::new()), hash(&HashMap::with_capacity(10)));
+    }
+
+    #[test]
+    fn test_eq_same_layout() {
+        use crate::hash_map::FixedState;
+        use core::cell::Cell;
+        use core::hash::BuildHasher;
+
+        // Both maps need the same hashes to share a layout, which two
+        // default `RandomState`s of the `siphash` feature wouldn't give.
+        struct Counting<'a>(FixedState, &'a Cell<usize>);
+
+        impl BuildHasher for Counting<'_> {
+            type Hasher = <FixedState as BuildHasher>::Hasher;
+
+            fn build_hasher(&self) -> Self::Hasher {
+                self.1.set(self.1.get() + 1);
+                self.0.build_hasher()
+            }
+        }
+
+        let hashes = Cell::new(0);
+        let mut m1 = HashMap::with_hasher(Counting(FixedState::default(), &hashes));
+        let mut m2 = HashMap::with_hasher(Counting(FixedState::default(), &hashes));
+        for i in 0..100 {
+            m1.insert(i, i);
+            m2.insert(i, i);
+        }
+
+        // Equal maps sharing a layout are compared without hashing.
+        hashes.set(0);
+        assert_eq!(m1, m2);
+        assert_eq!(hashes.get(), 0);
+
+        m2.insert(50, 0);
+        assert_ne!(m1, m2);
+        m2.remove(&50);
+        m2.insert(100, 50);
+        assert_ne!(m1, m2);
+
+        // Maps laid out differently are still compared by lookups.
+        let m3: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let m4: HashMap<i32, i32> = (0..100).rev().map(|i| (i, i)).collect();
+        assert_eq!(m3, m4);
+        let mut m5 = HashMap::with_capacity(1000);
+        m5.extend(m3.iter().map(|(&k, &v)| (k, v)));
+        assert_eq!(m3, m5);
+        assert_eq!(m5, m3);
+    }
+
+    #[test]
+    fn test_iterator_traits() {
+        use core::fmt::Debug;
+        use core::iter::FusedIterator;
+
+        fn exact<I: ExactSizeIterator + FusedIterator + Debug>(iter: I) -> usize {
+            iter.len()
+        }
+        fn cloned<I: Iterator + FusedIterator + Clone + Debug>(iter: I) -> usize {
+            iter.clone().count()
+        }
+        fn fused<I: FusedIterator + Debug>(iter: I) -> usize {
+            iter.count()
+        }
+
+        let mut map: HashMap<i32, i32> = (0..10).map(|i| (i, i)).collect();
+        assert_eq!(exact(map.iter()), 10);
+        assert_eq!(exact(map.iter_mut()), 10);
+        assert_eq!(exact(map.keys()), 10);
+        assert_eq!(exact(map.values()), 10);
+        assert_eq!(exact(map.values_mut()), 10);
+        assert_eq!(exact(map.iter_chunks(2)), 2);
+        assert_eq!(cloned(map.iter_chunks(2)), 2);
+        assert_eq!(cloned(map.iter_starting_at(0)), 10);
+        assert_eq!(exact(map.clone().into_iter()), 10);
+        assert_eq!(exact(map.clone().into_keys()), 10);
+        assert_eq!(exact(map.clone().into_values()), 10);
+        assert_eq!(fused(map.drain_filter(|&k, _| k < 5)), 5);
+        assert_eq!(
+            format!("{:?}", map.drain_filter(|_, _| false)),
+            "DrainFilter { .. }"
+        );
+        assert_eq!(exact(map.drain()), 5);
+    }
+
+    #[test]
+    fn test_clear_and_shrink() {
+        let mut m: HashMap<i32, i32> = (0..100).map(|i| (i, i)).collect();
+        let capacity = m.capacity();
+        m.clear();
+        assert!(m.is_empty());
+        assert_eq!(m.capacity(), capacity);
+
+        m.extend((0..100).map(|i| (i, i)));
+        m.clear_and_shrink_to(10);
+        assert!(m.is_empty());
+        assert!(m.capacity() >= 10);
+        assert!(m.capacity() < capacity);
+
+        // A table which is already small enough is kept.
+        let small = m.capacity();
+        m.clear_and_shrink_to(1000);
+        assert_eq!(m.capacity(), small);
+
+        m.extend((0..100).map(|i| (i, i)));
+        m.clear_and_shrink();
+        assert!(m.is_empty());
+        assert_eq!(m.capacity(), 0);
+        m.insert(1, 1);
+        assert_eq!(m[&1], 1);
+    }
+
     #[test]
     fn test_show() {
         let mut map = HashMap::new();
@@ -7384,6 +9391,79 @@ mod test_map {
         assert_eq!(map.iter().len(), xs.len() - 1);
     }
 
+    #[test]
+    fn test_from_iter_reserves_once() {
+        use super::capacity_to_buckets;
+
+        let map: HashMap<u32, u32> = (0..10_000).map(|i| (i, i)).collect();
+        assert_eq!(map.bucket_count(), capacity_to_buckets(10_000).unwrap());
+        assert!((0..10_000).all(|i| map[&i] == i));
+
+        // The last of duplicate keys wins, as with `insert`.
+        let map: HashMap<u32, u32> = (0..10_000).map(|i| (i % 100, i)).collect();
+        assert_eq!(map.len(), 100);
+        assert!((0..100).all(|i| map[&i] == 9_900 + i));
+
+        // An iterator whose size hint is too low still has all its items
+        // inserted.
+        struct Understated(core::ops::Range<u32>);
+
+        impl Iterator for Understated {
+            type Item = (u32, u32);
+
+            fn next(&mut self) -> Option<(u32, u32)> {
+                self.0.next().map(|i| (i, i))
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                (10, Some(10))
+            }
+        }
+
+        let mut map: HashMap<u32, u32> = Understated(0..1000).collect();
+        assert_eq!(map.len(), 1000);
+        map.extend(Understated(500..2000));
+        assert_eq!(map.len(), 2000);
+        assert!((0..2000).all(|i| map[&i] == i));
+        map.verify();
+    }
+
+    #[test]
+    fn test_extend_with() {
+        let mut map: HashMap<_, Vec<i32>> = HashMap::new();
+        map.insert(0, vec![0]);
+        let pairs = (1..100).map(|i| (i % 10, vec![i]));
+        map.extend_with(pairs, |old, new| old.extend(new));
+
+        assert_eq!(map.len(), 10);
+        assert_eq!(map[&0], [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]);
+        assert_eq!(map[&3], [3, 13, 23, 33, 43, 53, 63, 73, 83, 93]);
+
+        // The closure is never called for keys which aren't in the map yet.
+        let mut map = HashMap::new();
+        map.extend_with([(1, 1), (2, 2)], |_, _| panic!("no duplicates"));
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_from_array_allocates_once() {
+        let map: HashMap<_, _> =
+            HashMap::from([(1, 1), (2, 2), (3, 3), (4, 4), (5, 5), (6, 6), (7, 7)]);
+        let expected = HashMap::<i32, i32>::with_capacity(7);
+        assert_eq!(map.len(), 7);
+        assert_eq!(map.capacity(), expected.capacity());
+        assert_eq!(map.table.buckets(), expected.table.buckets());
+
+        // Duplicate keys don't shrink the table below the array's length.
+        let map: HashMap<_, _> = HashMap::from([(1, 1), (1, 2), (1, 3), (1, 4), (1, 5)]);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&1], 5);
+        assert_eq!(
+            map.capacity(),
+            HashMap::<i32, i32>::with_capacity(5).capacity()
+        );
+    }
+
     #[test]
     fn test_size_hint() {
         let xs = [(1, 1), (2, 2), (3, 3), (4, 4), (5, 5), (6, 6)];
@@ -8199,6 +10279,130 @@ mod test_map {
         }
     }
 
+    #[test]
+    fn test_reserve_exact() {
+        use super::{buckets_to_capacity, capacity_to_buckets};
+        use crate::TryReserveError::CapacityOverflow;
+
+        for cap in 0..1000 {
+            let buckets = capacity_to_buckets(cap).unwrap();
+            assert!(buckets_to_capacity(buckets) >= cap);
+            if buckets > 4 {
+                assert!(buckets_to_capacity(buckets / 2) < cap);
+            }
+        }
+
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(map.bucket_count(), 0);
+        map.reserve_exact(0);
+        assert_eq!(map.bucket_count(), 0);
+
+        map.extend((0..112).map(|i| (i, i)));
+        assert_eq!(map.bucket_count(), 128);
+        assert_eq!(map.capacity(), 112);
+
+        // Whether or not the removals leave tombstones which force a rehash,
+        // the remaining elements fit without growing the table.
+        map.retain(|&k, _| k % 2 == 0);
+        map.reserve_exact(10);
+        assert_eq!(map.bucket_count(), 128);
+        assert!(map.capacity() >= map.len() + 10);
+
+        map.reserve_exact(100);
+        assert_eq!(map.bucket_count(), capacity_to_buckets(156).unwrap());
+        assert!(map.capacity() >= 156);
+
+        assert!(matches!(
+            map.try_reserve_exact(usize::MAX),
+            Err(CapacityOverflow)
+        ));
+        assert_eq!(map.len(), 56);
+    }
+
+    #[test]
+    fn test_index_of() {
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(map.index_of(&0), None);
+        assert_eq!(map.get_index(0), None);
+        assert_eq!(map.get_index_mut(0), None);
+
+        map.reserve(1000);
+        map.extend((0..1000).map(|i| (i, i)));
+        let indices: Vec<usize> = (0..1000).map(|i| map.index_of(&i).unwrap()).collect();
+        let mut sorted = indices.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), 1000);
+        assert!(sorted.iter().all(|&index| index < map.bucket_count()));
+
+        for (i, &index) in (0..1000).zip(&indices) {
+            assert_eq!(map.get_index(index), Some((&i, &i)));
+            *map.get_index_mut(index).unwrap().1 += 1;
+        }
+        assert!((0..1000).all(|i| map[&i] == i + 1));
+
+        // Removals leave the indices of other entries alone.
+        map.retain(|&k, _| k % 2 == 0);
+        for (i, &index) in (0..1000).zip(&indices) {
+            let entry = map.get_index(index).map(|(&k, &v)| (k, v));
+            if i % 2 == 0 {
+                assert_eq!(entry, Some((i, i + 1)));
+            } else {
+                assert_eq!(entry, None);
+            }
+        }
+        let full = (0..map.bucket_count())
+            .filter(|&index| map.get_index(index).is_some())
+            .count();
+        assert_eq!(full, map.len());
+    }
+
+    #[test]
+    fn test_insert_with_relocation() {
+        // Keep a sliding window of keys, so that the table both grows and
+        // is rehashed in place to reclaim tombstones, and follow the
+        // entries through the reported moves.
+        let mut map: HashMap<u64, u64> = HashMap::new();
+        let mut indices: HashMap<u64, usize> = HashMap::new();
+        let mut moves = Vec::new();
+        for i in 0..20_000_u64 {
+            let (index, old) = map.insert_with_relocation(i, i, |old_index, new_index| {
+                moves.push((old_index, new_index));
+            });
+            assert_eq!(old, None);
+            if !moves.is_empty() {
+                let keys: HashMap<usize, u64> = indices.iter().map(|(&k, &i)| (i, k)).collect();
+                for (old_index, new_index) in moves.drain(..) {
+                    indices.insert(keys[&old_index], new_index);
+                }
+            }
+            indices.insert(i, index);
+            if i >= 100 {
+                map.remove(&(i - 100));
+                indices.remove(&(i - 100));
+            }
+            if i % 97 == 0 {
+                for (k, &index) in &indices {
+                    assert_eq!(map.get_index(index), Some((k, k)));
+                }
+            }
+        }
+        assert_eq!(
+            map.insert_with_relocation(19_999, 0, |_, _| panic!()).1,
+            Some(19_999)
+        );
+
+        map.shrink_to_with_relocation(0, |old_index, new_index| {
+            moves.push((old_index, new_index));
+        });
+        assert_eq!(moves.len(), 100);
+        let keys: HashMap<usize, u64> = indices.iter().map(|(&k, &i)| (i, k)).collect();
+        for (old_index, new_index) in moves {
+            let k = keys[&old_index];
+            assert_eq!(map.get_index(new_index).map(|(&k, _)| k), Some(k));
+        }
+    }
+
     #[test]
     fn test_raw_entry() {
         use super::RawEntryMut::{Occupied, Vacant};
@@ -8509,4 +10713,580 @@ mod test_map {
 
         map2.clone_from(&map1);
     }
+
+    #[test]
+    fn test_split_into_shards() {
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i * 2)).collect();
+        for n in [1, 2, 8, 64] {
+            let shards = map.clone().split_into_shards(n);
+            assert_eq!(shards.len(), n);
+            assert_eq!(shards.iter().map(HashMap::len).sum::<usize>(), 1000);
+            for (index, shard) in shards.iter().enumerate() {
+                assert!(shard.len() <= shard.capacity());
+                for (k, v) in shard {
+                    assert_eq!(*v, k * 2);
+                    let hash = super::make_hash::<u32, _>(shard.hasher(), k);
+                    assert_eq!(super::shard_index(hash, n.trailing_zeros()), index);
+                }
+            }
+            assert_eq!(HashMap::merge_shards(shards), map);
+        }
+    }
+
+    #[test]
+    fn test_shard_index_hash32() {
+        use crate::hash_map::IntegerHash32Builder;
+
+        // Only the low 32 bits and the tag of these hashes are set, which
+        // must still reach every shard evenly.
+        let hash_builder = IntegerHash32Builder::default();
+        assert_eq!(super::shard_index(u64::MAX, 0), 0);
+        for shard_bits in 1..=6 {
+            let mut counts = vec![0; 1 << shard_bits];
+            for i in 0..10_000_u32 {
+                let hash = super::make_hash::<u32, _>(&hash_builder, &i);
+                counts[super::shard_index(hash, shard_bits)] += 1;
+            }
+            let expected = 10_000 / counts.len();
+            for (shard, &count) in counts.iter().enumerate() {
+                assert!(
+                    count > expected / 2,
+                    "shard {} of {} has {} keys",
+                    shard,
+                    counts.len(),
+                    count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_misplaced_shards() {
+        let map: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let mut shards = map.split_into_shards(4);
+        // Insert keys into shards they don't belong to, including duplicates
+        // of keys which are correctly placed elsewhere.
+        for k in 0..200 {
+            shards[(k % 4) as usize].insert(k, k);
+        }
+        let merged = HashMap::merge_shards(shards);
+        assert_eq!(merged.len(), 200);
+        for k in 0..200 {
+            assert_eq!(merged[&k], k);
+        }
+    }
+
+    #[test]
+    #[should_panic = "power of two"]
+    fn test_split_into_shards_not_power_of_two() {
+        let map: HashMap<u32, u32> = HashMap::new();
+        map.split_into_shards(3);
+    }
+
+    #[test]
+    fn test_merge_from() {
+        let mut a: HashMap<u32, u32> = (0..100).map(|i| (i, i)).collect();
+        let b: HashMap<u32, u32> = (50..200).map(|i| (i, i * 10)).collect();
+        a.merge_from(b);
+        assert_eq!(a.len(), 200);
+        for i in 0..200 {
+            assert_eq!(a[&i], if i < 50 { i } else { i * 10 });
+        }
+
+        let mut empty = HashMap::new();
+        empty.merge_from(a.clone());
+        assert_eq!(empty, a);
+
+        a.merge_from(HashMap::<u32, u32>::new());
+        assert_eq!(a.len(), 200);
+    }
+
+    #[test]
+    fn test_map_values() {
+        // Same layout: converted in place.
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let buckets = map.table.buckets();
+        let map = map.map_values(|&k, v| -i32::try_from(k + v).unwrap());
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.table.buckets(), buckets);
+        for i in 0..1000 {
+            assert_eq!(map[&i], -2 * i as i32);
+        }
+
+        // Different layout: rebuilt with exact capacity.
+        let mut map: HashMap<u32, u8> = (0..1000).map(|i| (i, i as u8)).collect();
+        map.retain(|&k, _| k < 10);
+        let map = map.map_values(|_, v| u64::from(v) << 40);
+        assert_eq!(map.len(), 10);
+        assert_eq!(
+            map.capacity(),
+            HashMap::<u32, u64>::with_capacity(10).capacity()
+        );
+        assert_eq!(map[&2], 2 << 40);
+    }
+
+    #[test]
+    fn test_or_try_insert_with() {
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(map.entry(1).or_try_insert_with(|| Err("miss")), Err("miss"));
+        assert!(map.is_empty());
+        assert_eq!(
+            map.entry(1).or_try_insert_with::<(), _>(|| Ok(10)),
+            Ok(&mut 10)
+        );
+        *map.entry(1).or_try_insert_with(|| Err("hit")).unwrap() += 1;
+        assert_eq!(map[&1], 11);
+
+        assert_eq!(
+            map.entry(2)
+                .or_try_insert_with_key(|&k| if k > 1 { Err(k) } else { Ok(k) }),
+            Err(2)
+        );
+        assert_eq!(
+            map.entry(0).or_try_insert_with_key::<u32, _>(|&k| Ok(k)),
+            Ok(&mut 0)
+        );
+        assert_eq!(map.len(), 2);
+
+        let mut map: HashMap<std::string::String, usize> = HashMap::new();
+        assert_eq!(map.entry_ref("a").or_try_insert_with(|| Err(())), Err(()));
+        assert!(map.is_empty());
+        assert_eq!(
+            map.entry_ref("abc")
+                .or_try_insert_with_key::<(), _>(|k| Ok(k.len())),
+            Ok(&mut 3)
+        );
+        assert_eq!(map["abc"], 3);
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        let mut calls = 0;
+        for i in 0..1000 {
+            *map.get_or_insert_with(i % 100, || {
+                calls += 1;
+                0
+            }) += 1;
+        }
+        assert_eq!(calls, 100);
+        assert_eq!(map.len(), 100);
+        assert!(map.values().all(|&v| v == 10));
+
+        // Slots freed by removals are reused.
+        for i in 0..50 {
+            map.remove(&i);
+        }
+        for i in 100..150 {
+            assert_eq!(*map.get_or_insert_with_key(i, |&k| k * 2), i * 2);
+        }
+        assert_eq!(map.len(), 100);
+        for i in 50..150 {
+            assert!(map.contains_key(&i));
+        }
+
+        // Tables smaller than a group.
+        let mut small: HashMap<u32, u32> = HashMap::with_capacity(1);
+        for i in 0..3 {
+            small.get_or_insert_with(i, || i);
+        }
+        assert_eq!(small.len(), 3);
+        assert_eq!(small[&2], 2);
+    }
+
+    #[test]
+    fn test_cursor_mut() {
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut cursor = map.cursor_mut();
+        assert_eq!(cursor.remove(), None);
+        let mut visited = 0;
+        let mut removed = Vec::new();
+        while let Some((&k, v)) = cursor.next() {
+            visited += 1;
+            *v += 1;
+            if k % 3 == 0 {
+                removed.push(cursor.remove().unwrap());
+                assert_eq!(cursor.remove(), None);
+            }
+        }
+        assert_eq!(cursor.next(), None);
+        assert_eq!(cursor.remove(), None);
+
+        assert_eq!(visited, 1000);
+        assert_eq!(removed.len(), 334);
+        assert!(removed.iter().all(|&(k, v)| k % 3 == 0 && v == k + 1));
+        assert_eq!(map.len(), 666);
+        for i in 0..1000 {
+            assert_eq!(
+                map.get(&i).copied(),
+                if i % 3 == 0 { None } else { Some(i + 1) }
+            );
+        }
+    }
+
+    #[test]
+    fn test_iter_chunks() {
+        let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        for n in [1, 3, 8, 1000] {
+            let chunks = map.iter_chunks(n);
+            assert_eq!(chunks.len(), n);
+            let mut keys: Vec<u32> = chunks.flatten().map(|(&k, _)| k).collect();
+            keys.sort_unstable();
+            assert_eq!(keys, (0..1000).collect::<Vec<_>>());
+        }
+        fn assert_send<T: Send>(_: &T) {}
+        assert_send(&map.iter_chunks(2).next().unwrap());
+        let empty: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(empty.iter_chunks(4).flatten().count(), 0);
+    }
+
+    #[test]
+    #[should_panic = "zero chunks"]
+    fn test_iter_chunks_zero() {
+        let map: HashMap<u32, u32> = HashMap::new();
+        map.iter_chunks(0);
+    }
+
+    #[test]
+    fn test_iter_starting_at() {
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        let mut visited = Vec::new();
+        let mut position = Some(0);
+        while let Some(start) = position {
+            let mut iter = map.iter_starting_at(start);
+            let slice: Vec<u32> = iter.by_ref().take(7).map(|(&k, _)| k).collect();
+            position = iter.next_position();
+            // Modifications between slices don't disturb the scan.
+            for k in &slice {
+                if k % 2 == 0 {
+                    map.remove(k);
+                }
+            }
+            visited.extend(slice);
+        }
+        visited.sort_unstable();
+        assert_eq!(visited, (0..1000).collect::<Vec<_>>());
+        assert_eq!(map.len(), 500);
+
+        assert_eq!(map.iter_starting_at(usize::MAX).next(), None);
+        assert_eq!(
+            map.iter_starting_at(usize::MAX).next_position(),
+            Some(map.table.buckets())
+        );
+        let empty: HashMap<u32, u32> = HashMap::new();
+        let mut iter = empty.iter_starting_at(0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_position(), None);
+    }
+
+    #[test]
+    fn test_pop() {
+        let mut map: HashMap<u32, u32> = (0..100).map(|i| (i, i * 2)).collect();
+        let mut popped = Vec::new();
+        while let Some((k, v)) = map.pop() {
+            assert_eq!(v, k * 2);
+            assert!(!map.contains_key(&k));
+            popped.push(k);
+        }
+        popped.sort_unstable();
+        assert_eq!(popped, (0..100).collect::<Vec<_>>());
+        assert_eq!(map.pop(), None);
+
+        // The table is still usable after being emptied.
+        map.insert(7, 14);
+        assert_eq!(map.pop(), Some((7, 14)));
+        assert_eq!(HashMap::<u32, u32>::new().pop(), None);
+    }
+
+    #[test]
+    fn test_into_key_set() {
+        let mut map: HashMap<u32, u64> = (0..1000).map(|i| (i, u64::from(i))).collect();
+        map.retain(|&k, _| k % 3 != 0);
+        let buckets = map.table.buckets();
+        let set = map.into_key_set();
+        assert_eq!(set.len(), 666);
+        assert_eq!(set.map.table.buckets(), buckets);
+        for i in 0..1000 {
+            assert_eq!(set.contains(&i), i % 3 != 0);
+        }
+
+        let map: HashMap<u32, ()> = (0..10).map(|i| (i, ())).collect();
+        assert_eq!(map.into_key_set(), (0..10).collect());
+
+        let empty: HashMap<u32, u64> = HashMap::new();
+        assert!(empty.into_key_set().is_empty());
+    }
+
+    #[test]
+    fn test_into_key_set_drops_values() {
+        struct Counted<'a>(&'a std::cell::Cell<usize>);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = std::cell::Cell::new(0);
+        let map: HashMap<u32, Counted<'_>> = (0..100).map(|i| (i, Counted(&drops))).collect();
+        let mut set = map.into_key_set();
+        assert_eq!(drops.get(), 100);
+        assert!(set.insert(100));
+        assert!(!set.insert(50));
+    }
+
+    #[test]
+    fn test_map_values_panic() {
+        struct Counted<'a>(&'a std::cell::Cell<usize>);
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let old = std::cell::Cell::new(0);
+        let new = std::cell::Cell::new(0);
+        let map: HashMap<u32, Counted<'_>> = (0..100).map(|i| (i, Counted(&old))).collect();
+        let mut calls = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.map_values(|_, v| {
+                calls += 1;
+                if calls == 50 {
+                    panic!("conversion failed");
+                }
+                drop(v);
+                Counted(&new)
+            })
+        }));
+        assert!(result.is_err());
+        // Every old value was dropped exactly once, as was every new one.
+        assert_eq!(old.get(), 100);
+        assert_eq!(new.get(), 49);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a: HashMap<u32, Vec<u32>> = (0..100).map(|i| (i, vec![i])).collect();
+        let b: HashMap<u32, Vec<u32>> = (50..200).map(|i| (i, vec![i * 10])).collect();
+        a.merge(b, |&k, v, other| {
+            assert!((50..100).contains(&k));
+            v.extend(other);
+        });
+        assert_eq!(a.len(), 200);
+        for i in 0..200 {
+            match i {
+                0..=49 => assert_eq!(a[&i], [i]),
+                50..=99 => assert_eq!(a[&i], [i, i * 10]),
+                _ => assert_eq!(a[&i], [i * 10]),
+            }
+        }
+
+        let mut empty = HashMap::new();
+        empty.merge(a.clone(), |_, _, _| unreachable!());
+        assert_eq!(empty, a);
+    }
+
+    #[test]
+    fn test_stats() {
+        let empty: HashMap<u32, u32> = HashMap::new();
+        let stats = empty.stats();
+        assert_eq!(
+            (stats.full, stats.deleted, stats.max_probe_length),
+            (0, 0, 0)
+        );
+        assert_eq!(stats.mean_probe_length, 0.0);
+        assert_eq!(stats.load_factor, 0.0);
+
+        // A small table has fewer buckets than a group.
+        let small: HashMap<u32, u32> = (0..3).map(|i| (i, i)).collect();
+        let stats = small.stats();
+        assert_eq!(stats.buckets, 4);
+        assert_eq!((stats.full, stats.deleted, stats.empty), (3, 0, 1));
+        assert_eq!(stats.max_probe_length, 1);
+        assert_eq!(stats.load_factor, 0.75);
+
+        let mut map: HashMap<u32, u32> = (0..1000).map(|i| (i, i)).collect();
+        for i in 0..500 {
+            map.remove(&i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.buckets, map.table.buckets());
+        assert_eq!(stats.full, 500);
+        assert_eq!(stats.full + stats.deleted + stats.empty, stats.buckets);
+        assert!(stats.max_probe_length >= 1);
+        assert!(stats.mean_probe_length >= 1.0);
+        assert!(stats.mean_probe_length <= stats.max_probe_length as f64);
+
+        // Rebuilding the table drops the tombstones.
+        map.shrink_to_fit();
+        assert_eq!(map.stats().deleted, 0);
+
+        // Colliding keys probe further.
+        #[derive(Default)]
+        struct ConstantHasher;
+
+        impl core::hash::Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+
+            fn write(&mut self, _: &[u8]) {}
+        }
+
+        let mut map =
+            HashMap::with_hasher(core::hash::BuildHasherDefault::<ConstantHasher>::default());
+        for i in 0..64u32 {
+            map.insert(i, i);
+        }
+        let stats = map.stats();
+        assert_eq!(stats.max_probe_length, 64 / crate::raw::Group::WIDTH);
+        map.verify();
+    }
+
+    #[test]
+    #[cfg(feature = "instrument")]
+    fn test_instrument() {
+        let mut map: HashMap<u32, u32> = HashMap::with_capacity(1000);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        // Every insertion probes at least one group, and no key was there.
+        let metrics = map.metrics();
+        assert!(metrics.group_loads >= 1000);
+        assert!(metrics.group_loads >= metrics.probe_steps);
+        assert_eq!(metrics.tag_matches, metrics.false_matches);
+
+        // Every lookup of a present key matches its own tag once.
+        map.reset_metrics();
+        assert!((0..1000).all(|i| map.contains_key(&i)));
+        let metrics = map.metrics();
+        assert_eq!(metrics.tag_matches, 1000 + metrics.false_matches);
+        assert_eq!(metrics.group_loads, metrics.probe_steps);
+        assert!(metrics.false_positive_rate() < 0.5);
+
+        // Removals load the groups around the removed bucket.
+        map.reset_metrics();
+        map.remove(&0);
+        assert!(map.metrics().group_loads > map.metrics().probe_steps);
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn test_metrics_concurrent_reads() {
+        use std::sync::Arc;
+
+        let map: Arc<HashMap<u32, u32>> = Arc::new((0..1000).map(|i| (i, i)).collect());
+        let before = map.metrics().probe_steps;
+        assert!((0..1000).all(|i| map.contains_key(&i)));
+        let after = map.metrics().probe_steps;
+
+        // No lookup on another thread is lost.
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let map = map.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        assert!((0..1000).all(|i| map.contains_key(&i)));
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(map.metrics().probe_steps - after, 40 * (after - before));
+    }
+
+    #[test]
+    #[cfg(feature = "metrics")]
+    fn test_metrics() {
+        let mut map: HashMap<u32, u32> = HashMap::new();
+        assert_eq!(map.metrics(), Default::default());
+
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        let metrics = map.metrics();
+        // 4 buckets, then doubling up to 2048.
+        assert_eq!(metrics.resizes, 10);
+        assert_eq!((metrics.rehashes, metrics.tombstones), (0, 0));
+        assert!(metrics.probe_steps >= 1000);
+
+        map.reset_metrics();
+        assert!((0..1000).all(|i| map.contains_key(&i)));
+        assert!(map.metrics().probe_steps >= 1000);
+        assert!(map.metrics().false_match_rate() < 1.0);
+        for i in 0..500 {
+            map.remove(&i);
+        }
+        assert_eq!(map.metrics().tombstones, map.stats().deleted);
+        assert_eq!(map.metrics().resizes, 0);
+
+        // Counters survive resizes but not cloning.
+        map.shrink_to_fit();
+        assert_eq!(map.metrics().resizes, 1);
+        assert_eq!(map.clone().metrics(), Default::default());
+
+        // Keys which are their own hash fill the buckets of the same index.
+        #[derive(Default)]
+        struct IdentityHasher(u64);
+
+        impl core::hash::Hasher for IdentityHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+
+            fn write(&mut self, _: &[u8]) {
+                unreachable!()
+            }
+
+            fn write_u64(&mut self, n: u64) {
+                self.0 = n;
+            }
+        }
+
+        let mut map = HashMap::with_capacity_and_hasher(
+            112,
+            core::hash::BuildHasherDefault::<IdentityHasher>::default(),
+        );
+        for i in 0..112_u64 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.capacity(), 112);
+        // Removing keys from the middle of the full run of buckets leaves
+        // tombstones behind, which no insertion reclaims.
+        for i in 16..100 {
+            map.remove(&i);
+        }
+        assert!(map.metrics().tombstones > 64);
+        map.insert(120, 120);
+        let metrics = map.metrics();
+        assert_eq!((metrics.resizes, metrics.rehashes), (0, 1));
+        assert_eq!(map.stats().deleted, 0);
+
+        // Small keys have the same tag, so a lookup of a missing key matches
+        // every key in the groups it probes.
+        map.reset_metrics();
+        assert!((128..144).all(|i| !map.contains_key(&i)));
+        assert!(map.metrics().false_matches >= 16);
+        assert!(map.metrics().false_match_rate() > 1.0);
+    }
+
+    #[test]
+    fn test_verify() {
+        use std::string::{String, ToString};
+
+        let mut map: HashMap<String, usize> = HashMap::new();
+        map.verify();
+        for i in 0..1000usize {
+            map.insert(i.to_string(), i);
+            if i % 7 == 0 {
+                map.remove(&(i / 2).to_string());
+            }
+        }
+        map.verify();
+        map.retain(|_, v| *v % 2 == 0);
+        map.verify();
+        map.shrink_to_fit();
+        map.verify();
+    }
 }
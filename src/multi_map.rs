@@ -0,0 +1,521 @@
+//! A hash map which can hold several values for the same key.
+//!
+//! [`HashMultiMap`] stores every key-value pair in its own bucket of the
+//! table, so a key with a single value costs exactly as much as in a
+//! [`HashMap`], instead of the separate allocation a `HashMap<K, Vec<V>>`
+//! needs for each key.
+//!
+//! [`HashMap`]: crate::HashMap
+
+use crate::map::{equivalent_key, make_hash, make_hasher, DefaultHashBuilder, IntoIter, Iter};
+use crate::raw::{Allocator, Bucket, Global, RawIterHash, RawTable};
+use crate::Equivalent;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+/// A hash map which can map each key to several values.
+///
+/// Every pair inserted into the map gets its own bucket, even if the key is
+/// already present, so all the pairs of a key share its hash and are found by
+/// a single probe sequence, like the chain of a chained hash table. Looking up
+/// a key visits all the buckets of that chain, so the map is meant for keys
+/// with a few values each: a key with many values makes lookups of every key
+/// which probes through its buckets slower.
+///
+/// [`len`] counts pairs, not distinct keys, and iteration visits every pair
+/// in arbitrary order. The values of a key are also returned in arbitrary
+/// order.
+///
+/// [`len`]: HashMultiMap::len
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::HashMultiMap;
+///
+/// let mut map = HashMultiMap::new();
+/// map.insert("fruit", "apple");
+/// map.insert("fruit", "pear");
+/// map.insert("vegetable", "leek");
+/// assert_eq!(map.len(), 3);
+///
+/// let mut fruits: Vec<_> = map.get_all("fruit").copied().collect();
+/// fruits.sort_unstable();
+/// assert_eq!(fruits, ["apple", "pear"]);
+///
+/// assert!(map.remove_one("fruit").is_some());
+/// assert_eq!(map.get_all("fruit").count(), 1);
+/// assert_eq!(map.remove_all("vegetable"), ["leek"]);
+/// assert!(!map.contains_key("vegetable"));
+/// ```
+pub struct HashMultiMap<K, V, S = DefaultHashBuilder, A: Allocator + Clone = Global> {
+    hash_builder: S,
+    table: RawTable<(K, V), A>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> HashMultiMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `HashMultiMap`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let map: HashMultiMap<i32, i32> = HashMultiMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `HashMultiMap` which can hold at least `capacity`
+    /// pairs without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> HashMultiMap<K, V, S> {
+    /// Creates an empty `HashMultiMap` which will use the given hash builder
+    /// to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub const fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::new(),
+        }
+    }
+
+    /// Creates an empty `HashMultiMap` which can hold at least `capacity`
+    /// pairs without reallocating, using `hash_builder` to hash the keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::with_capacity(capacity),
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> HashMultiMap<K, V, S, A> {
+    /// Creates an empty `HashMultiMap` which will use the given hash builder
+    /// to hash keys, and allocate its table with `alloc`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher_in(hash_builder: S, alloc: A) -> Self {
+        Self {
+            hash_builder,
+            table: RawTable::new_in(alloc),
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns a reference to the underlying allocator.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn allocator(&self) -> &A {
+        self.table.allocator()
+    }
+
+    /// Returns the number of pairs the map can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Returns the number of key-value pairs in the map, counting every value
+    /// of a key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let mut map = HashMultiMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(1, "b");
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns `true` if the map contains no pairs.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Clears the map, removing all pairs. Keeps the allocated memory for
+    /// reuse.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order. A key
+    /// is yielded once for each of its values.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        // Here we tie the lifetime of self to the iter.
+        unsafe {
+            Iter {
+                inner: self.table.iter(),
+                marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<K, V, S, A> HashMultiMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Reserves capacity for at least `additional` more pairs.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve(&mut self, additional: usize) {
+        self.table
+            .reserve(additional, make_hasher::<K, V, S>(&self.hash_builder));
+    }
+
+    /// Adds a value for the key, keeping any values the key already has.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let mut map = HashMultiMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(1, "a");
+    /// assert_eq!(map.get_all(&1).count(), 2);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) {
+        let hash = make_hash::<K, S>(&self.hash_builder, &k);
+        self.table
+            .insert(hash, (k, v), make_hasher::<K, V, S>(&self.hash_builder));
+    }
+
+    /// Returns an iterator over all the values of the key, in arbitrary
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let mut map = HashMultiMap::new();
+    /// map.insert(1, 10);
+    /// map.insert(2, 20);
+    /// map.insert(1, 11);
+    /// assert_eq!(map.get_all(&1).sum::<i32>(), 21);
+    /// assert_eq!(map.get_all(&3).next(), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_all<'a, Q>(&'a self, k: &'a Q) -> GetAll<'a, K, V, Q, A>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        GetAll {
+            // SAFETY: The iterator borrows the table.
+            inner: unsafe { self.table.iter_hash(hash) },
+            key: k,
+        }
+    }
+
+    /// Returns a reference to one of the values of the key, if it has any.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        self.table.get(hash, equivalent_key(k)).map(|(_, v)| v)
+    }
+
+    /// Returns `true` if the key has at least one value.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get(k).is_some()
+    }
+
+    /// Removes one of the values of the key and returns it, if the key has
+    /// any. Which value is removed is unspecified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let mut map = HashMultiMap::new();
+    /// map.insert(1, "a");
+    /// map.insert(1, "b");
+    /// assert!(map.remove_one(&1).is_some());
+    /// assert!(map.remove_one(&1).is_some());
+    /// assert_eq!(map.remove_one(&1), None);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_one<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        self.table
+            .remove_entry(hash, equivalent_key(k))
+            .map(|(_, v)| v)
+    }
+
+    /// Removes all the values of the key and returns them, in arbitrary
+    /// order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMultiMap;
+    ///
+    /// let mut map = HashMultiMap::new();
+    /// map.insert(1, 10);
+    /// map.insert(1, 11);
+    /// map.insert(2, 20);
+    ///
+    /// let mut values = map.remove_all(&1);
+    /// values.sort_unstable();
+    /// assert_eq!(values, [10, 11]);
+    /// assert_eq!(map.len(), 1);
+    /// ```
+    pub fn remove_all<Q>(&mut self, k: &Q) -> Vec<V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        // Collect the buckets first: removing them while probing could end
+        // the probe sequence early.
+        let buckets: Vec<Bucket<(K, V)>> = unsafe {
+            self.table
+                .iter_hash(hash)
+                .filter(|bucket| k.equivalent(&bucket.as_ref().0))
+                .collect()
+        };
+        buckets
+            .into_iter()
+            // SAFETY: Each bucket is full and is removed only once.
+            .map(|bucket| unsafe { self.table.remove(bucket).1 })
+            .collect()
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for HashMultiMap<K, V, S, A> {
+    fn clone(&self) -> Self {
+        HashMultiMap {
+            hash_builder: self.hash_builder.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for HashMultiMap<K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S: Default> Default for HashMultiMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S, A> Extend<(K, V)> for HashMultiMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        let iter = iter.into_iter();
+        // Every pair is inserted, so the lower bound is exact.
+        self.reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for HashMultiMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K, V, S, A: Allocator + Clone> IntoIterator for &'a HashMultiMap<K, V, S, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> IntoIterator for HashMultiMap<K, V, S, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> IntoIter<K, V, A> {
+        IntoIter {
+            inner: self.table.into_iter(),
+        }
+    }
+}
+
+/// An iterator over the values of a key in a `HashMultiMap`.
+///
+/// This `struct` is created by the [`get_all`] method on [`HashMultiMap`].
+///
+/// [`get_all`]: HashMultiMap::get_all
+pub struct GetAll<'a, K, V, Q: ?Sized, A: Allocator + Clone = Global> {
+    inner: RawIterHash<'a, (K, V), A>,
+    key: &'a Q,
+}
+
+impl<'a, K: 'a, V: 'a, Q, A> Iterator for GetAll<'a, K, V, Q, A>
+where
+    Q: ?Sized + Equivalent<K>,
+    A: Allocator + Clone,
+{
+    type Item = &'a V;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<&'a V> {
+        for bucket in &mut self.inner {
+            // SAFETY: The table outlives the iterator.
+            let (k, v) = unsafe { bucket.as_ref() };
+            if self.key.equivalent(k) {
+                return Some(v);
+            }
+        }
+        None
+    }
+}
+
+impl<'a, K: 'a, V: 'a, Q, A> FusedIterator for GetAll<'a, K, V, Q, A>
+where
+    Q: ?Sized + Equivalent<K>,
+    A: Allocator + Clone,
+{
+}
+
+impl<K, V, Q: ?Sized, A: Allocator + Clone> Clone for GetAll<'_, K, V, Q, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        GetAll {
+            inner: self.inner.clone(),
+            key: self.key,
+        }
+    }
+}
+
+impl<K, V, Q: ?Sized + Debug, A: Allocator + Clone> Debug for GetAll<'_, K, V, Q, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GetAll").field("key", &self.key).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_multi_map {
+    use super::HashMultiMap;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    #[test]
+    fn test_many_values() {
+        let mut map = HashMultiMap::new();
+        for i in 0..1000u32 {
+            map.insert(i % 10, i);
+        }
+        assert_eq!(map.len(), 1000);
+        for k in 0..10 {
+            let mut values: Vec<u32> = map.get_all(&k).copied().collect();
+            values.sort_unstable();
+            assert_eq!(values, (0..100).map(|i| i * 10 + k).collect::<Vec<_>>());
+        }
+        assert_eq!(map.remove_all(&3).len(), 100);
+        assert_eq!(map.get_all(&3).count(), 0);
+        assert_eq!(map.remove_all(&3), []);
+        for _ in 0..50 {
+            assert!(map.remove_one(&4).is_some());
+        }
+        assert_eq!(map.get_all(&4).count(), 50);
+        assert_eq!(map.len(), 850);
+        assert_eq!(map.iter().count(), 850);
+        assert_eq!(map.into_iter().filter(|&(k, _)| k == 5).count(), 100);
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut map: HashMultiMap<String, u32> = [("a", 1), ("b", 2), ("a", 3)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(map.get_all("a").sum::<u32>(), 4);
+        assert!(map.get("b").is_some());
+        assert_eq!(map.remove_one("b"), Some(2));
+        assert!(!map.contains_key("b"));
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_colliding_keys() {
+        // All keys share a hash, so their values end up in a single probe
+        // sequence.
+        #[derive(Default)]
+        struct ConstantHasher;
+        impl core::hash::Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+        let mut map: HashMultiMap<u32, u32, core::hash::BuildHasherDefault<ConstantHasher>> =
+            HashMultiMap::default();
+        for i in 0..60 {
+            map.insert(i % 3, i);
+        }
+        assert_eq!(map.get_all(&1).count(), 20);
+        let removed = map.remove_all(&1);
+        assert!(removed.iter().all(|v| v % 3 == 1));
+        assert_eq!(removed.len(), 20);
+        assert_eq!(map.get_all(&2).count(), 20);
+        assert_eq!(map.get_all(&0).count(), 20);
+    }
+}
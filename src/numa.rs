@@ -0,0 +1,222 @@
+//! An allocator which places tables on a chosen NUMA node.
+//!
+//! On machines with several NUMA nodes, memory attached to another node is
+//! noticeably slower to reach than local memory. A table which is mostly used
+//! by threads running on one node should therefore live on that node, which
+//! the kernel's first-touch policy does not guarantee: the thread which grows
+//! a table is not necessarily the one which uses it most.
+//!
+//! [`NumaAlloc`] maps every allocation directly from the kernel and asks Linux
+//! to back it with memory from its node through `mbind(MPOL_PREFERRED)`. The
+//! kernel falls back to other nodes if the preferred one runs out of memory,
+//! and on other platforms allocations come from the global allocator.
+//!
+//! Allocations are rounded up to whole pages, so this is meant for large
+//! tables. To give each shard of a [`ShardedHashMap`] its own node, pass a
+//! `NumaAlloc` per shard to
+//! [`ShardedHashMap::with_shard_amount_and_hasher_in`] and route keys to the
+//! threads owning their shard with [`ShardedHashMap::shard_of`].
+//!
+//! # Examples
+//!
+//! ```
+//! use hashbrown::numa::NumaAlloc;
+//! use hashbrown::HashMap;
+//!
+//! let mut map = HashMap::new_in(NumaAlloc::local());
+//! map.insert(1, "a");
+//! assert_eq!(map[&1], "a");
+//! ```
+//!
+//! [`ShardedHashMap`]: crate::concurrent::ShardedHashMap
+//! [`ShardedHashMap::with_shard_amount_and_hasher_in`]: crate::concurrent::ShardedHashMap::with_shard_amount_and_hasher_in
+//! [`ShardedHashMap::shard_of`]: crate::concurrent::ShardedHashMap::shard_of
+
+/// An allocator which prefers memory from one NUMA node.
+///
+/// See the [module documentation](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NumaAlloc {
+    node: u32,
+}
+
+impl NumaAlloc {
+    /// Creates an allocator which prefers memory from NUMA node `node`.
+    ///
+    /// Nodes which do not exist are ignored and the kernel's default policy
+    /// applies instead.
+    #[inline]
+    pub const fn on_node(node: u32) -> Self {
+        Self { node }
+    }
+
+    /// Creates an allocator which prefers memory from the NUMA node the
+    /// calling thread currently runs on, or node 0 if that is unknown.
+    #[inline]
+    pub fn local() -> Self {
+        Self::on_node(current_node().unwrap_or(0))
+    }
+
+    /// Returns the NUMA node this allocator prefers.
+    #[inline]
+    pub const fn node(&self) -> u32 {
+        self.node
+    }
+}
+
+/// Returns the NUMA node the calling thread currently runs on, or `None` if
+/// it cannot be determined.
+///
+/// The scheduler may move the thread to another node at any time unless its
+/// affinity is restricted to CPUs of one node.
+pub fn current_node() -> Option<u32> {
+    imp::current_node()
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::NumaAlloc;
+    use crate::alloc::alloc::Layout;
+    use core::ptr::{self, NonNull};
+
+    /// Prefer the given node but fall back to others, from `<linux/mempolicy.h>`.
+    const MPOL_PREFERRED: i32 = 1;
+
+    /// The highest node number plus one which can be passed to `mbind`.
+    const MAX_NODES: usize = 1024;
+
+    pub(super) fn current_node() -> Option<u32> {
+        let mut cpu: u32 = 0;
+        let mut node: u32 = 0;
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_getcpu,
+                &mut cpu as *mut u32,
+                &mut node as *mut u32,
+                ptr::null_mut::<u8>(),
+            )
+        };
+        if ret == 0 {
+            Some(node)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the size of the mapping for `layout`, or `None` if it cannot
+    /// be mapped with the alignment it needs.
+    fn mapping_size(layout: Layout) -> Option<usize> {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        if layout.align() > page {
+            return None;
+        }
+        Some(layout.size().checked_add(page - 1)? & !(page - 1))
+    }
+
+    impl NumaAlloc {
+        pub(crate) fn allocate_on_node(&self, layout: Layout) -> Option<NonNull<u8>> {
+            let len = mapping_size(layout)?;
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return None;
+            }
+
+            let node = self.node as usize;
+            if node < MAX_NODES {
+                // The mask is an array of `unsigned long`, which has the size
+                // of a pointer on every Linux target.
+                const BITS: usize = usize::BITS as usize;
+                let mut mask = [0_usize; MAX_NODES / BITS];
+                mask[node / BITS] |= 1 << (node % BITS);
+                // This is only a hint: without NUMA support in the kernel, or
+                // for a node which does not exist, the memory is still usable.
+                // The kernel expects one more than the number of mask bits.
+                unsafe {
+                    libc::syscall(
+                        libc::SYS_mbind,
+                        ptr,
+                        len,
+                        MPOL_PREFERRED,
+                        mask.as_ptr(),
+                        MAX_NODES + 1,
+                        0_u32,
+                    );
+                }
+            }
+            NonNull::new(ptr.cast())
+        }
+
+        pub(crate) unsafe fn deallocate_on_node(&self, ptr: NonNull<u8>, layout: Layout) {
+            if let Some(len) = mapping_size(layout) {
+                libc::munmap(ptr.as_ptr().cast(), len);
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::NumaAlloc;
+    use crate::alloc::alloc::{alloc, dealloc, Layout};
+    use core::ptr::NonNull;
+
+    pub(super) fn current_node() -> Option<u32> {
+        None
+    }
+
+    impl NumaAlloc {
+        pub(crate) fn allocate_on_node(&self, layout: Layout) -> Option<NonNull<u8>> {
+            NonNull::new(unsafe { alloc(layout) })
+        }
+
+        pub(crate) unsafe fn deallocate_on_node(&self, ptr: NonNull<u8>, layout: Layout) {
+            dealloc(ptr.as_ptr(), layout);
+        }
+    }
+}
+
+impl Default for NumaAlloc {
+    /// Returns [`NumaAlloc::local`].
+    #[inline]
+    fn default() -> Self {
+        Self::local()
+    }
+}
+
+#[cfg(test)]
+mod test_numa {
+    use super::{current_node, NumaAlloc};
+    use crate::HashMap;
+
+    #[test]
+    #[cfg_attr(miri, ignore)] // no mmap
+    fn test_tables_on_nodes() {
+        if cfg!(target_os = "linux") {
+            assert!(current_node().is_some());
+        }
+        // Node 4000 does not exist anywhere, which only makes the hint fail.
+        for alloc in [
+            NumaAlloc::local(),
+            NumaAlloc::on_node(0),
+            NumaAlloc::on_node(4000),
+        ] {
+            let mut map = HashMap::new_in(alloc);
+            for i in 0..10_000_u32 {
+                map.insert(i, i + 1);
+            }
+            assert!((0..10_000).all(|i| map[&i] == i + 1));
+            map.retain(|&k, _| k < 10);
+            map.shrink_to_fit();
+            assert_eq!(map.len(), 10);
+        }
+    }
+}
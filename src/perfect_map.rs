@@ -0,0 +1,419 @@
+//! A read-only map built with a perfect hash function.
+//!
+//! [`PerfectMap`] is built once from a [`HashMap`] by
+//! [`HashMap::freeze_perfect`]. It stores its entries in an array of exactly
+//! as many slots as there are entries, and finds the slot of a key with a
+//! single computation instead of a probe sequence.
+
+use crate::hasher::hash_integer;
+use crate::map::{make_hash, DefaultHashBuilder, HashMap};
+use crate::Equivalent;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::ops::Index;
+use core::slice;
+
+/// The average number of keys sharing a displacement.
+const KEYS_PER_DISPLACEMENT: usize = 4;
+
+/// Maps `hash` to `0..n` using its high bits.
+#[inline]
+fn reduce(hash: u64, n: usize) -> usize {
+    ((u128::from(hash) * n as u128) >> 64) as usize
+}
+
+/// Returns the displacement group of a key with the given hash.
+#[inline]
+fn group_of(hash: u64, groups: usize) -> usize {
+    reduce(hash_integer(hash), groups)
+}
+
+/// Returns the slot of a key with the given hash, in a group with the given
+/// displacement.
+#[inline]
+fn slot_of(hash: u64, displacement: u32, slots: usize) -> usize {
+    let seed = u64::from(displacement).wrapping_add(1);
+    reduce(
+        hash_integer(hash ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15)),
+        slots,
+    )
+}
+
+/// A read-only hash map whose entries are placed by a perfect hash function.
+///
+/// The keys are split into small groups by their hash, and each group gets a
+/// displacement which sends its keys to slots no other key uses. A lookup
+/// hashes the key, reads the displacement of its group and compares the key
+/// with the single entry in the slot this gives, so it never probes more
+/// than one entry. The table has no empty slots and no control bytes: on top
+/// of the entries, it only stores one 32-bit displacement for every four
+/// keys.
+///
+/// Building the map takes longer than inserting the entries into a
+/// [`HashMap`], so it is meant for lookup tables which are built once and
+/// read many times. Use [`into_map`] to turn it back into a `HashMap`.
+///
+/// [`into_map`]: PerfectMap::into_map
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::{HashMap, PerfectMap};
+///
+/// let map: HashMap<&str, u16> = [("http", 80), ("https", 443), ("ssh", 22)].into();
+/// let ports: PerfectMap<_, _> = map.freeze_perfect().unwrap();
+/// assert_eq!(ports.get("https"), Some(&443));
+/// assert_eq!(ports.get("ftp"), None);
+/// ```
+pub struct PerfectMap<K, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    displacements: Box<[u32]>,
+    entries: Box<[(K, V)]>,
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Converts the map into a read-only [`PerfectMap`].
+    ///
+    /// The perfect hash function is built from the hashes of the keys, so it
+    /// cannot tell apart keys whose 64-bit hashes are equal. If the map
+    /// contains such keys, it is returned unchanged in the `Err` variant.
+    /// This does not happen with a good hasher, unless the keys were chosen
+    /// to collide.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<u32, u32> = (0..1000).map(|i| (i, i * i)).collect();
+    /// let perfect = map.freeze_perfect().unwrap();
+    /// assert_eq!(perfect.len(), 1000);
+    /// assert_eq!(perfect[&30], 900);
+    /// ```
+    pub fn freeze_perfect(self) -> Result<PerfectMap<K, V, S>, Self> {
+        let n = self.len();
+        let mut hashes: Vec<u64> = self
+            .keys()
+            .map(|k| make_hash::<K, S>(&self.hash_builder, k))
+            .collect();
+        hashes.sort_unstable();
+        if hashes.windows(2).any(|w| w[0] == w[1]) {
+            return Err(self);
+        }
+
+        let groups = (n + KEYS_PER_DISPLACEMENT - 1) / KEYS_PER_DISPLACEMENT;
+        let mut members: Vec<Vec<u64>> = (0..groups).map(|_| Vec::new()).collect();
+        for hash in hashes {
+            members[group_of(hash, groups)].push(hash);
+        }
+        // Place the largest groups first, while most slots are still free.
+        let mut order: Vec<usize> = (0..groups).collect();
+        order.sort_unstable_by_key(|&g| core::cmp::Reverse(members[g].len()));
+
+        let mut displacements = alloc::vec![0u32; groups];
+        let mut taken = alloc::vec![false; n];
+        let mut slots = Vec::new();
+        for g in order {
+            let group = &members[g];
+            if group.is_empty() {
+                break;
+            }
+            // Every displacement sends the keys to independent slots, so one
+            // which works for the group is found quickly unless the table is
+            // nearly full, and the last groups placed only have one key.
+            let mut displacement = 0u32;
+            loop {
+                slots.clear();
+                let fits = group.iter().all(|&hash| {
+                    let slot = slot_of(hash, displacement, n);
+                    let free = !taken[slot] && !slots.contains(&slot);
+                    slots.push(slot);
+                    free
+                });
+                if fits {
+                    break;
+                }
+                displacement = displacement
+                    .checked_add(1)
+                    .expect("no displacement places the group");
+            }
+            for &slot in &slots {
+                taken[slot] = true;
+            }
+            displacements[g] = displacement;
+        }
+
+        let HashMap {
+            hash_builder,
+            table,
+            ..
+        } = self;
+        let mut placed: Vec<(usize, (K, V))> = table
+            .into_iter()
+            .map(|entry| {
+                let hash = make_hash::<K, S>(&hash_builder, &entry.0);
+                let displacement = displacements[group_of(hash, groups)];
+                (slot_of(hash, displacement, n), entry)
+            })
+            .collect();
+        placed.sort_unstable_by_key(|&(slot, _)| slot);
+        Ok(PerfectMap {
+            hash_builder,
+            displacements: displacements.into_boxed_slice(),
+            entries: placed.into_iter().map(|(_, entry)| entry).collect(),
+        })
+    }
+}
+
+impl<K, V, S> PerfectMap<K, V, S> {
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+}
+
+impl<K, V, S> PerfectMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Converts the map back into a [`HashMap`] which can be modified.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    ///
+    /// let map: HashMap<_, _> = [(1, "a")].into();
+    /// let mut map = map.freeze_perfect().unwrap().into_map();
+    /// map.insert(2, "b");
+    /// assert_eq!(map.len(), 2);
+    /// ```
+    pub fn into_map(self) -> HashMap<K, V, S> {
+        let mut map = HashMap::with_capacity_and_hasher(self.entries.len(), self.hash_builder);
+        map.extend(Vec::from(self.entries));
+        map
+    }
+
+    /// Returns the entry of the key, if it is present.
+    #[inline]
+    fn find<Q>(&self, k: &Q) -> Option<&(K, V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let displacement = self.displacements[group_of(hash, self.displacements.len())];
+        let entry = &self.entries[slot_of(hash, displacement, self.entries.len())];
+        if k.equivalent(&entry.0) {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(k).map(|(_, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(k).map(|(k, v)| (k, v))
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.find(k).is_some()
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for PerfectMap<K, V, S> {
+    fn clone(&self) -> Self {
+        PerfectMap {
+            hash_builder: self.hash_builder.clone(),
+            displacements: self.displacements.clone(),
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for PerfectMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for PerfectMap<K, V, S>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `PerfectMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a PerfectMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `PerfectMap`.
+///
+/// This `struct` is created by the [`iter`] method on [`PerfectMap`].
+///
+/// [`iter`]: PerfectMap::iter
+pub struct Iter<'a, K, V> {
+    inner: slice::Iter<'a, (K, V)>,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.inner.next().map(|(k, v)| (k, v))
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_perfect_map {
+    use crate::hasher::IntegerHashBuilder;
+    use crate::HashMap;
+    use core::hash::BuildHasherDefault;
+    use std::string::{String, ToString};
+
+    #[test]
+    fn test_lookups() {
+        for n in [0u64, 1, 2, 5, 100, 10_000] {
+            let map: HashMap<u64, u64> = (0..n).map(|i| (i * 7, i)).collect();
+            let perfect = map.freeze_perfect().unwrap();
+            assert_eq!(perfect.len(), n as usize);
+            assert_eq!(perfect.iter().count(), n as usize);
+            for i in 0..n * 7 {
+                let expected = if i % 7 == 0 { Some(i / 7) } else { None };
+                assert_eq!(perfect.get(&i).copied(), expected);
+            }
+            assert_eq!(perfect.clone().into_map().len(), n as usize);
+        }
+    }
+
+    #[test]
+    fn test_weak_hasher() {
+        // Small integers only set the low bits of their hashes.
+        let map: HashMap<u64, u64, IntegerHashBuilder> = (0..1000).map(|i| (i, i)).collect();
+        let perfect = map.freeze_perfect().unwrap();
+        assert!((0..1000).all(|i| perfect[&i] == i));
+        assert!(!perfect.contains_key(&1000));
+    }
+
+    #[test]
+    fn test_colliding_hashes() {
+        #[derive(Default)]
+        struct ConstantHasher;
+        impl core::hash::Hasher for ConstantHasher {
+            fn finish(&self) -> u64 {
+                0
+            }
+            fn write(&mut self, _: &[u8]) {}
+        }
+        let map: HashMap<u32, u32, BuildHasherDefault<ConstantHasher>> =
+            (0..3).map(|i| (i, i)).collect();
+        let map = map.freeze_perfect().unwrap_err();
+        assert_eq!(map.len(), 3);
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let map: HashMap<String, usize> = ["a", "bb", "ccc"]
+            .iter()
+            .map(|s| (s.to_string(), s.len()))
+            .collect();
+        let perfect = map.freeze_perfect().unwrap();
+        assert_eq!(perfect.get("bb"), Some(&2));
+        assert_eq!(perfect.get_key_value("ccc"), Some((&"ccc".to_string(), &3)));
+        assert_eq!(perfect.get("d"), None);
+    }
+}
@@ -0,0 +1,375 @@
+use super::{hasher_check, invalid_data, Header, Pod};
+use crate::map::{make_hash, DefaultHashBuilder, Iter, Keys, Values};
+use crate::raw::{h2, Group, ProbeSeq, RawIter};
+use crate::Equivalent;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::ops::Index;
+use core::ptr::{self, NonNull};
+use core::slice;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+/// A read-only memory mapping of a whole file.
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+impl Mmap {
+    fn open(file: &File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| invalid_data("file is too large"))?;
+        if len == 0 {
+            return Err(invalid_data("not a stored hash table"));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: NonNull::new(ptr.cast()).unwrap(),
+            len,
+        })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+/// A read-only hash map backed by a memory-mapped file.
+///
+/// The file is written by [`HashMap::write_file`] and searched in place, so
+/// opening it does not rehash or copy any entry, and only the pages which are
+/// actually accessed are read from disk.
+///
+/// See the [`persist`] module for the requirements on the keys, values and
+/// hasher.
+///
+/// [`HashMap::write_file`]: crate::HashMap::write_file
+/// [`persist`]: crate::persist
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::persist::MappedHashMap;
+/// use hashbrown::HashMap;
+/// use std::collections::hash_map::DefaultHasher;
+/// use std::hash::BuildHasherDefault;
+///
+/// type Hasher = BuildHasherDefault<DefaultHasher>;
+///
+/// let map: HashMap<u32, u64, Hasher> = (0..100).map(|i| (i, u64::from(i) * 2)).collect();
+/// let path = std::env::temp_dir().join("hashbrown-mapped-hash-map-doctest");
+/// map.write_file(&path).unwrap();
+///
+/// // SAFETY: The file is not modified while it is mapped.
+/// let mapped: MappedHashMap<u32, u64, Hasher> = unsafe { MappedHashMap::open(&path) }.unwrap();
+/// assert_eq!(mapped.len(), 100);
+/// assert_eq!(mapped.get(&21), Some(&42));
+/// assert_eq!(mapped.get(&100), None);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MappedHashMap<K, V, S = DefaultHashBuilder> {
+    // Keeps the file mapped for as long as `ctrl` points into it.
+    _map: Mmap,
+    ctrl: NonNull<u8>,
+    bucket_mask: usize,
+    items: usize,
+    hash_builder: S,
+    marker: PhantomData<(K, V)>,
+}
+
+// The mapping is never written to, so it can be shared like a `&[(K, V)]`.
+unsafe impl<K: Sync, V: Sync, S: Send> Send for MappedHashMap<K, V, S> {}
+unsafe impl<K: Sync, V: Sync, S: Sync> Sync for MappedHashMap<K, V, S> {}
+
+impl<K: Pod, V: Pod, S: BuildHasher + Default> MappedHashMap<K, V, S> {
+    /// Maps the table stored in the file at `path`, using the default hasher
+    /// of `S`.
+    ///
+    /// The header and the control bytes of the table are validated, and an
+    /// error of kind [`InvalidData`] is returned if they do not describe a
+    /// table of `(K, V)` written with an equivalent hasher by a compatible
+    /// build.
+    ///
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this or any other process, for as
+    /// long as the map exists.
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::open_with_hasher(path, S::default())
+    }
+}
+
+impl<K: Pod, V: Pod, S: BuildHasher> MappedHashMap<K, V, S> {
+    /// Maps the table stored in the file at `path`, using `hash_builder` to
+    /// hash keys.
+    ///
+    /// See [`open`] for details.
+    ///
+    /// [`open`]: MappedHashMap::open
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this or any other process, for as
+    /// long as the map exists.
+    pub unsafe fn open_with_hasher<P: AsRef<Path>>(path: P, hash_builder: S) -> io::Result<Self> {
+        let map = Mmap::open(&File::open(path)?)?;
+        let bytes = map.as_slice();
+        let header = Header::decode::<K, V>(bytes, hasher_check(&hash_builder))?;
+        let buckets = header.buckets as usize;
+        let items = header.items as usize;
+        let ctrl = NonNull::new_unchecked(map.ptr.as_ptr().add(header.ctrl_offset as usize));
+        Ok(Self {
+            _map: map,
+            ctrl,
+            bucket_mask: buckets - 1,
+            items,
+            hash_builder,
+            marker: PhantomData,
+        })
+    }
+}
+
+impl<K, V, S> MappedHashMap<K, V, S> {
+    /// Returns a reference to the map's [`BuildHasher`].
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of elements in the map.
+    pub fn len(&self) -> usize {
+        self.items
+    }
+
+    /// Returns `true` if the map contains no elements.
+    pub fn is_empty(&self) -> bool {
+        self.items == 0
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        // SAFETY: The control bytes were validated when the file was opened,
+        // and are aligned to the group width within the mapping.
+        unsafe {
+            Iter {
+                inner: RawIter::new(
+                    self.ctrl.as_ptr(),
+                    self.ctrl.cast(),
+                    self.bucket_mask + 1,
+                    self.items,
+                ),
+                marker: PhantomData,
+            }
+        }
+    }
+
+    /// An iterator visiting all keys in arbitrary order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    /// An iterator visiting all values in arbitrary order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// Returns the entry in bucket `index`, which must be full.
+    unsafe fn bucket(&self, index: usize) -> &(K, V) {
+        &*self.ctrl.cast::<(K, V)>().as_ptr().sub(index + 1)
+    }
+}
+
+impl<K, V, S> MappedHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let h2_hash = h2(hash);
+        let mut probe_seq = ProbeSeq::new(hash, self.bucket_mask);
+
+        loop {
+            // SAFETY: The control bytes span `bucket_mask + 1 + Group::WIDTH`
+            // bytes, and every full bucket holds an initialized entry.
+            unsafe {
+                let group = Group::load(self.ctrl.as_ptr().add(probe_seq.pos));
+
+                for bit in group.match_byte(h2_hash) {
+                    let index = (probe_seq.pos + bit) & self.bucket_mask;
+                    let (key, value) = self.bucket(index);
+                    if k.equivalent(key) {
+                        return Some((key, value));
+                    }
+                }
+
+                if group.match_empty().any_bit_set() {
+                    return None;
+                }
+            }
+
+            probe_seq.move_next(self.bucket_mask);
+        }
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Equivalent<K>,
+    {
+        self.get_key_value(k).is_some()
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for MappedHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, Q, V, S> Index<&Q> for MappedHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    Q: ?Sized + Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found")
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a MappedHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod test_mmap {
+    use super::MappedHashMap;
+    use crate::HashMap;
+    use core::hash::BuildHasherDefault;
+    use std::collections::hash_map::DefaultHasher;
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+    use std::vec::Vec;
+
+    type Hasher = BuildHasherDefault<DefaultHasher>;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("hashbrown-test-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = temp_path("round-trip");
+        for len in [0, 1, 3, 100, 5000] {
+            let mut map: HashMap<u64, [u8; 3], Hasher> = HashMap::default();
+            for i in 0..len {
+                map.insert(i, [i as u8; 3]);
+            }
+            // Leave tombstones behind.
+            for i in (0..len).step_by(7) {
+                map.remove(&i);
+            }
+            map.write_file(&path).unwrap();
+
+            let mapped: MappedHashMap<u64, [u8; 3], Hasher> =
+                unsafe { MappedHashMap::open(&path) }.unwrap();
+            assert_eq!(mapped.len(), map.len());
+            for i in 0..len + 10 {
+                assert_eq!(mapped.get(&i), map.get(&i));
+            }
+            let mut entries: Vec<_> = mapped.iter().map(|(k, v)| (*k, *v)).collect();
+            entries.sort_unstable();
+            let mut expected: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+            expected.sort_unstable();
+            assert_eq!(entries, expected);
+            assert_eq!(mapped.keys().count(), map.len());
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_mismatch() {
+        let path = temp_path("mismatch");
+        let map: HashMap<u32, u32, Hasher> = (0..10).map(|i| (i, i)).collect();
+        map.write_file(&path).unwrap();
+
+        let err = unsafe { MappedHashMap::<u64, u32, Hasher>::open(&path) }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        #[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+        {
+            let hasher = crate::hash_map::DefaultHashBuilder::default();
+            let err = unsafe { MappedHashMap::<u32, u32, _>::open_with_hasher(&path, hasher) }
+                .unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData);
+        }
+
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] = 0x81;
+        fs::write(&path, &bytes).unwrap();
+        let err = unsafe { MappedHashMap::<u32, u32, Hasher>::open(&path) }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        bytes.pop();
+        fs::write(&path, &bytes).unwrap();
+        let err = unsafe { MappedHashMap::<u32, u32, Hasher>::open(&path) }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        fs::write(&path, b"").unwrap();
+        let err = unsafe { MappedHashMap::<u32, u32, Hasher>::open(&path) }.unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+    }
+}
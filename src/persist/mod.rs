@@ -0,0 +1,551 @@
+//! Writing tables to files and mapping them back as read-only maps.
+//!
+//! [`HashMap::write_to`] stores the table of a map exactly as it is laid out
+//! in memory: a header, the buckets, and the control bytes. On Unix,
+//! [`MappedHashMap`] maps such a file into memory and searches it in place,
+//! so opening even a very large table only costs validating its header and
+//! control bytes instead of re-inserting every entry. [`HashMap::to_bytes`]
+//! and [`HashMap::from_bytes`] do the same with a byte buffer, copying the
+//! stored table back into a map which can be modified.
+//!
+//! Only keys and values which are plain bytes, as described by [`Pod`], can
+//! be stored this way. The file records the layout of the buckets and the
+//! group width of the build which wrote it, and is rejected by builds which
+//! disagree on either, for example on a different target.
+//!
+//! Keys are not hashed again when a table is opened, so the map must be
+//! opened with a hasher which produces the same hashes as the one it was
+//! written with. Hashers with random keys, like the default one, produce
+//! different hashes in every process; use a deterministic hasher instead.
+//! The header records the hash of a fixed value, and a table opened with a
+//! hasher which hashes that value differently is rejected.
+//!
+//! [`HashMap::write_to`]: crate::HashMap::write_to
+//! [`HashMap::to_bytes`]: crate::HashMap::to_bytes
+//! [`HashMap::from_bytes`]: crate::HashMap::from_bytes
+
+use crate::map::{make_hash, HashMap};
+use crate::raw::{bucket_mask_to_capacity, is_full, Allocator, Global, Group, RawTable};
+use crate::raw::{DELETED, EMPTY, PROBE_SEQ_KIND};
+use alloc::vec::Vec;
+use core::cmp;
+use core::hash::BuildHasher;
+use core::mem::{self, MaybeUninit};
+use core::ptr;
+use core::slice;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+#[cfg(unix)]
+mod mmap;
+#[cfg(unix)]
+pub use self::mmap::MappedHashMap;
+
+/// Types which can be stored as their raw bytes.
+///
+/// Keys and values of a table written with [`HashMap::write_to`] must
+/// implement this trait.
+///
+/// [`HashMap::write_to`]: crate::HashMap::write_to
+///
+/// # Safety
+///
+/// Implementing types must not contain padding bytes, pointers or
+/// references, and every bit pattern of their size must be a valid value.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty)*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8 u16 u32 u64 u128 usize i8 i16 i32 i64 i128 isize f32 f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+const MAGIC: [u8; 8] = *b"HBTABLE\0";
+const VERSION: u64 = 1;
+const BYTE_ORDER: u64 = 0x0102_0304_0506_0708;
+
+/// Value hashed to check that a table is opened with the right hasher.
+const HASHER_CHECK_VALUE: u64 = 0x9e37_79b9_7f4a_7c15;
+
+/// Size of the header at the start of the file.
+const HEADER_LEN: usize = 128;
+
+/// Mappings are only guaranteed to be aligned to this.
+const MAX_ALIGN: usize = 4096;
+
+/// The header of a stored table.
+///
+/// The header is followed by zero padding, the buckets in the order they have
+/// in memory, and the control bytes, which start at `ctrl_offset`.
+struct Header {
+    version: u64,
+    byte_order: u64,
+    group_width: u64,
+    bucket_size: u64,
+    bucket_align: u64,
+    key_offset: u64,
+    key_size: u64,
+    value_offset: u64,
+    value_size: u64,
+    buckets: u64,
+    items: u64,
+    ctrl_offset: u64,
+    hasher_check: u64,
+    probe_seq: u64,
+}
+
+impl Header {
+    /// Describes a table of `(K, V)` with the given number of buckets and
+    /// items, as laid out by this build.
+    fn new<K, V>(buckets: usize, items: usize, hasher_check: u64) -> Option<Self> {
+        let (key_offset, value_offset) = field_offsets::<K, V>();
+        Some(Self {
+            version: VERSION,
+            byte_order: BYTE_ORDER,
+            group_width: Group::WIDTH as u64,
+            bucket_size: mem::size_of::<(K, V)>() as u64,
+            bucket_align: mem::align_of::<(K, V)>() as u64,
+            key_offset: key_offset as u64,
+            key_size: mem::size_of::<K>() as u64,
+            value_offset: value_offset as u64,
+            value_size: mem::size_of::<V>() as u64,
+            buckets: buckets as u64,
+            items: items as u64,
+            ctrl_offset: ctrl_offset::<K, V>(buckets)? as u64,
+            hasher_check,
+            probe_seq: PROBE_SEQ_KIND,
+        })
+    }
+
+    fn fields(&self) -> [u64; 14] {
+        [
+            self.version,
+            self.byte_order,
+            self.group_width,
+            self.bucket_size,
+            self.bucket_align,
+            self.key_offset,
+            self.key_size,
+            self.value_offset,
+            self.value_size,
+            self.buckets,
+            self.items,
+            self.ctrl_offset,
+            self.hasher_check,
+            self.probe_seq,
+        ]
+    }
+
+    fn encode(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..MAGIC.len()].copy_from_slice(&MAGIC);
+        let fields = bytes[MAGIC.len()..].chunks_exact_mut(8);
+        for (chunk, field) in fields.zip(self.fields()) {
+            chunk.copy_from_slice(&field.to_ne_bytes());
+        }
+        bytes
+    }
+
+    /// Reads the header at the start of `bytes` and checks that `bytes` are a
+    /// valid table of `(K, V)` hashed like `hasher_check`.
+    ///
+    /// This guarantees that searching or iterating the table never accesses
+    /// memory outside of it.
+    fn decode<K, V>(bytes: &[u8], hasher_check: u64) -> io::Result<Self> {
+        if bytes.len() < HEADER_LEN || bytes[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a stored hash table"));
+        }
+        let mut fields = bytes[MAGIC.len()..HEADER_LEN].chunks_exact(8).map(|chunk| {
+            let mut field = [0; 8];
+            field.copy_from_slice(chunk);
+            u64::from_ne_bytes(field)
+        });
+        let mut next = || fields.next().unwrap();
+        let header = Self {
+            version: next(),
+            byte_order: next(),
+            group_width: next(),
+            bucket_size: next(),
+            bucket_align: next(),
+            key_offset: next(),
+            key_size: next(),
+            value_offset: next(),
+            value_size: next(),
+            buckets: next(),
+            items: next(),
+            ctrl_offset: next(),
+            hasher_check: next(),
+            probe_seq: next(),
+        };
+
+        if header.version != VERSION {
+            return Err(invalid_data("unsupported stored table version"));
+        }
+        if header.byte_order != BYTE_ORDER {
+            return Err(invalid_data("stored table has a different byte order"));
+        }
+        let buckets = usize::try_from(header.buckets)
+            .ok()
+            .filter(|buckets| buckets.is_power_of_two())
+            .ok_or_else(|| invalid_data("invalid number of buckets"))?;
+        let expected = Self::new::<K, V>(buckets, 0, hasher_check)
+            .ok_or_else(|| invalid_data("invalid number of buckets"))?;
+        if header.group_width != expected.group_width {
+            return Err(invalid_data("stored table has a different group width"));
+        }
+        if header.fields()[3..9] != expected.fields()[3..9] {
+            return Err(invalid_data("stored table has a different bucket layout"));
+        }
+        if mem::align_of::<(K, V)>() > MAX_ALIGN {
+            return Err(invalid_data("bucket alignment is too large"));
+        }
+        if header.probe_seq != expected.probe_seq {
+            return Err(invalid_data(
+                "stored table was written with a different probe sequence",
+            ));
+        }
+        if header.hasher_check != hasher_check {
+            return Err(invalid_data(
+                "stored table was written with a different hasher",
+            ));
+        }
+        if header.ctrl_offset != expected.ctrl_offset
+            || header
+                .ctrl_offset
+                .checked_add(header.buckets + Group::WIDTH as u64)
+                != Some(bytes.len() as u64)
+        {
+            return Err(invalid_data("stored table has the wrong size"));
+        }
+        let ctrl = &bytes[header.ctrl_offset as usize..];
+        check_ctrl(ctrl, buckets, header.items)?;
+        Ok(header)
+    }
+}
+
+/// Checks that `ctrl` are the control bytes of a table with `buckets` buckets
+/// of which `items` are full.
+fn check_ctrl(ctrl: &[u8], buckets: usize, items: u64) -> io::Result<()> {
+    debug_assert_eq!(ctrl.len(), buckets + Group::WIDTH);
+    let (table, tail) = ctrl.split_at(buckets);
+    let mut full = 0;
+    let mut deleted = 0;
+    for &byte in table {
+        if is_full(byte) {
+            full += 1;
+        } else if byte == DELETED {
+            deleted += 1;
+        } else if byte != EMPTY {
+            return Err(invalid_data("invalid control byte"));
+        }
+    }
+    if full != items {
+        return Err(invalid_data("control bytes do not match the header"));
+    }
+    // Like in any table, tombstones count against the capacity. This also
+    // leaves an empty bucket for lookups to stop at.
+    if full + deleted > bucket_mask_to_capacity(buckets - 1) as u64 {
+        return Err(invalid_data("stored table has too many items"));
+    }
+
+    // The tail repeats the first group. In tables smaller than a group it is
+    // preceded by empty control bytes instead, which iteration relies on.
+    let mirrored = if buckets < Group::WIDTH {
+        let (padding, mirrored) = tail.split_at(Group::WIDTH - buckets);
+        if padding.iter().any(|&byte| byte != EMPTY) {
+            return Err(invalid_data("control bytes do not match the header"));
+        }
+        mirrored
+    } else {
+        tail
+    };
+    if mirrored != &table[..mirrored.len()] {
+        return Err(invalid_data("control bytes do not match the header"));
+    }
+    Ok(())
+}
+
+/// Returns the hash which is recorded in the header to identify the hasher.
+fn hasher_check<S: BuildHasher>(hash_builder: &S) -> u64 {
+    make_hash::<u64, S>(hash_builder, &HASHER_CHECK_VALUE)
+}
+
+/// Returns the offset of the control bytes in a stored table of `(K, V)`
+/// with the given number of buckets.
+///
+/// The buckets end right before the control bytes, which are aligned to
+/// the group width as well as to the buckets.
+fn ctrl_offset<K, V>(buckets: usize) -> Option<usize> {
+    let align = cmp::max(mem::align_of::<(K, V)>(), Group::WIDTH);
+    let end = buckets
+        .checked_mul(mem::size_of::<(K, V)>())?
+        .checked_add(HEADER_LEN + align - 1)?;
+    Some(end & !(align - 1))
+}
+
+/// Returns the offsets of the key and the value inside a `(K, V)`.
+fn field_offsets<K, V>() -> (usize, usize) {
+    let pair = MaybeUninit::<(K, V)>::uninit();
+    let base = pair.as_ptr();
+    unsafe {
+        (
+            ptr::addr_of!((*base).0) as usize - base as usize,
+            ptr::addr_of!((*base).1) as usize - base as usize,
+        )
+    }
+}
+
+fn as_bytes<T: Pod>(value: &T) -> &[u8] {
+    // SAFETY: `Pod` types have no padding, so all their bytes are initialized.
+    unsafe { slice::from_raw_parts((value as *const T).cast::<u8>(), mem::size_of::<T>()) }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Pod,
+    V: Pod,
+    S: BuildHasher,
+{
+    /// Creates a map from a table in the format written by [`write_to`],
+    /// without hashing any key.
+    ///
+    /// The buckets and control bytes are copied into a new table of the same
+    /// size, so entries keep their positions. An error of kind
+    /// [`InvalidData`] is returned if `bytes` are not a table of `(K, V)`
+    /// written with an equivalent hasher by a compatible build.
+    ///
+    /// See the [`persist`] module for the requirements on the hasher.
+    ///
+    /// [`write_to`]: HashMap::write_to
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    /// [`persist`]: crate::persist
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    ///
+    /// type Hasher = BuildHasherDefault<DefaultHasher>;
+    ///
+    /// let mut map: HashMap<u32, u64, Hasher> = HashMap::default();
+    /// map.insert(1, 10);
+    /// let bytes = map.to_bytes();
+    ///
+    /// let mut restored: HashMap<u32, u64, Hasher> = HashMap::from_bytes(&bytes, Hasher::default()).unwrap();
+    /// assert_eq!(restored[&1], 10);
+    /// restored.insert(2, 20);
+    ///
+    /// assert!(HashMap::<u32, u32, Hasher>::from_bytes(&bytes, Hasher::default()).is_err());
+    /// ```
+    pub fn from_bytes(bytes: &[u8], hash_builder: S) -> io::Result<Self> {
+        Self::from_bytes_in(bytes, hash_builder, Global)
+    }
+}
+
+impl<K, V, S, A> HashMap<K, V, S, A>
+where
+    K: Pod,
+    V: Pod,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    /// Writes the table of the map to `writer`, in a format which can be
+    /// opened again without rehashing any key.
+    ///
+    /// The buckets and control bytes are written as they are laid out in
+    /// memory. Unused buckets and the padding inside of buckets are written
+    /// as zeros. Writing many small pieces is slow on an unbuffered writer,
+    /// so consider wrapping it in a [`BufWriter`], or use [`write_file`].
+    ///
+    /// See the [`persist`] module for the requirements on the hasher.
+    ///
+    /// [`write_file`]: HashMap::write_file
+    /// [`persist`]: crate::persist
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    ///
+    /// let mut map: HashMap<u32, u64, BuildHasherDefault<DefaultHasher>> = HashMap::default();
+    /// map.insert(1, 10);
+    /// map.insert(2, 20);
+    ///
+    /// let mut bytes = Vec::new();
+    /// map.write_to(&mut bytes).unwrap();
+    /// assert!(bytes.len() > 2 * std::mem::size_of::<(u32, u64)>());
+    /// ```
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let ctrl = self.table.ctrl_bytes();
+        // The unallocated empty table is written as a table with a single
+        // empty bucket.
+        let buckets = if ctrl.is_empty() {
+            1
+        } else {
+            self.table.buckets()
+        };
+        let header = Header::new::<K, V>(buckets, self.len(), hasher_check(&self.hash_builder))
+            .ok_or_else(|| invalid_data("table is too large"))?;
+        writer.write_all(&header.encode())?;
+
+        let bucket_size = mem::size_of::<(K, V)>();
+        let data_offset = header.ctrl_offset as usize - buckets * bucket_size;
+        writer.write_all(&alloc::vec![0; data_offset - HEADER_LEN])?;
+
+        let (key_offset, value_offset) = field_offsets::<K, V>();
+        let mut bucket = alloc::vec![0; bucket_size];
+        for index in (0..buckets).rev() {
+            bucket.fill(0);
+            if !ctrl.is_empty() && is_full(ctrl[index]) {
+                let (key, value) = unsafe { self.table.bucket(index).as_ref() };
+                bucket[key_offset..key_offset + mem::size_of::<K>()].copy_from_slice(as_bytes(key));
+                bucket[value_offset..value_offset + mem::size_of::<V>()]
+                    .copy_from_slice(as_bytes(value));
+            }
+            writer.write_all(&bucket)?;
+        }
+
+        if ctrl.is_empty() {
+            writer.write_all(&[EMPTY; 1 + Group::WIDTH])
+        } else {
+            writer.write_all(ctrl)
+        }
+    }
+
+    /// Returns the table of the map in the format written by [`write_to`].
+    ///
+    /// [`write_to`]: HashMap::write_to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashMap;
+    /// use std::collections::hash_map::DefaultHasher;
+    /// use std::hash::BuildHasherDefault;
+    ///
+    /// type Hasher = BuildHasherDefault<DefaultHasher>;
+    ///
+    /// let map: HashMap<u32, u64, Hasher> = (0..100).map(|i| (i, u64::from(i) * 2)).collect();
+    /// let bytes = map.to_bytes();
+    ///
+    /// let restored: HashMap<u32, u64, Hasher> = HashMap::from_bytes(&bytes, Hasher::default()).unwrap();
+    /// assert_eq!(restored, map);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_to(&mut bytes).expect("table is too large");
+        bytes
+    }
+
+    /// Creates a map from a table in the format written by [`write_to`],
+    /// using `alloc` for the table.
+    ///
+    /// See [`from_bytes`] for details.
+    ///
+    /// [`write_to`]: HashMap::write_to
+    /// [`from_bytes`]: HashMap::from_bytes
+    pub fn from_bytes_in(bytes: &[u8], hash_builder: S, alloc: A) -> io::Result<Self> {
+        let header = Header::decode::<K, V>(bytes, hasher_check(&hash_builder))?;
+        let buckets = header.buckets as usize;
+        // A single bucket is how the unallocated empty table is stored.
+        if buckets == 1 {
+            return Ok(Self::with_hasher_in(hash_builder, alloc));
+        }
+
+        let ctrl_offset = header.ctrl_offset as usize;
+        let data = &bytes[ctrl_offset - buckets * mem::size_of::<(K, V)>()..ctrl_offset];
+        // SAFETY: The header and control bytes were validated, and any bytes
+        // are a valid `(K, V)` since both are `Pod`.
+        let table = unsafe { RawTable::from_bytes_in(alloc, buckets, data, &bytes[ctrl_offset..]) };
+        Ok(Self {
+            hash_builder,
+            table,
+        })
+    }
+
+    /// Writes the table of the map to a new file at `path`, replacing any
+    /// existing file.
+    ///
+    /// See [`write_to`] for details.
+    ///
+    /// [`write_to`]: HashMap::write_to
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test_persist {
+    use crate::HashMap;
+    use core::hash::BuildHasherDefault;
+    use std::collections::hash_map::DefaultHasher;
+    use std::io::ErrorKind;
+
+    type Hasher = BuildHasherDefault<DefaultHasher>;
+
+    #[test]
+    fn test_bytes_round_trip() {
+        for len in [0, 1, 3, 100, 5000] {
+            let mut map: HashMap<u64, [u16; 3], Hasher> = HashMap::default();
+            for i in 0..len {
+                map.insert(i, [i as u16; 3]);
+            }
+            // Leave tombstones behind.
+            for i in (0..len).step_by(7) {
+                map.remove(&i);
+            }
+
+            let bytes = map.to_bytes();
+            let mut restored: HashMap<u64, [u16; 3], Hasher> =
+                HashMap::from_bytes(&bytes, Hasher::default()).unwrap();
+            assert_eq!(restored, map);
+            assert_eq!(restored.capacity(), map.capacity());
+            assert_eq!(restored.to_bytes(), bytes);
+
+            // The restored table must account for its tombstones when growing.
+            for i in len..len * 3 {
+                restored.insert(i, [0; 3]);
+            }
+            for (i, &value) in map.iter() {
+                assert_eq!(restored[i], value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_invalid() {
+        let map: HashMap<u32, u32, Hasher> = (0..10).map(|i| (i, i)).collect();
+        let mut bytes = map.to_bytes();
+
+        let err = HashMap::<u32, u64, Hasher>::from_bytes(&bytes, Hasher::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        let err =
+            HashMap::<u32, u32, Hasher>::from_bytes(&bytes[1..], Hasher::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+        // Mark every empty bucket as deleted, which no table can have.
+        let ctrl = bytes.len() - map.table.buckets() - crate::raw::Group::WIDTH;
+        for byte in &mut bytes[ctrl..] {
+            if *byte == crate::raw::EMPTY {
+                *byte = crate::raw::DELETED;
+            }
+        }
+        let err = HashMap::<u32, u32, Hasher>::from_bytes(&bytes, Hasher::default()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,612 @@
+//! A hash map whose clones share their memory until they are modified.
+//!
+//! [`PersistentHashMap`] splits its entries over a fixed number of segments,
+//! each a [`RawTable`] behind its own reference count, with the list of
+//! segments behind another one. Cloning the map only increments the count of
+//! the list, and modifying a clone copies the list and the one segment which
+//! the modified key lives in, leaving the other segments shared. Versions of
+//! a map which differ in a few keys therefore take little more memory than a
+//! single version.
+
+use crate::map::{
+    equivalent_key, make_hash, make_hasher, make_insert_hash, shard_index, DefaultHashBuilder,
+};
+use crate::raw::{RawIter, RawTable};
+use crate::Equivalent;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Index;
+
+/// The default number of segments of a [`PersistentHashMap`].
+const DEFAULT_SEGMENTS: usize = 64;
+
+/// A hash map with constant-time clones, which copies only the part of the
+/// map a modification touches.
+///
+/// The entries are split over a fixed number of segments by bits of their
+/// hash. A clone shares all segments with the original, and the first
+/// modification of a key in a shared segment copies that segment, along with
+/// the list of segments, before changing it. Reads never copy anything. A
+/// map with `n` entries and `s` segments therefore copies about `n / s`
+/// entries and `s` pointers on the first write to each segment after a
+/// clone, instead of all `n` entries.
+///
+/// This makes it cheap to keep many versions of a large map, for example one
+/// per transaction in an MVCC catalog or one per step of an undo history.
+/// Since copies are made on demand, modifying a map needs its keys and values
+/// to be [`Clone`].
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::PersistentHashMap;
+///
+/// let mut v1 = PersistentHashMap::new();
+/// for i in 0..1000 {
+///     v1.insert(i, i * 10);
+/// }
+///
+/// // Taking a snapshot does not copy any entries.
+/// let mut v2 = v1.clone();
+/// v2.insert(5, 0);
+/// v2.remove(&6);
+///
+/// assert_eq!(v1.get(&5), Some(&50));
+/// assert_eq!(v1.get(&6), Some(&60));
+/// assert_eq!(v2.get(&5), Some(&0));
+/// assert_eq!(v2.get(&6), None);
+/// assert_eq!((v1.len(), v2.len()), (1000, 999));
+/// ```
+pub struct PersistentHashMap<K, V, S = DefaultHashBuilder> {
+    hash_builder: S,
+    segments: Arc<Vec<Segment<K, V>>>,
+    // Log2 of the number of segments, see `shard_index`.
+    segment_bits: u32,
+    len: usize,
+}
+
+/// A single, possibly shared, table of a [`PersistentHashMap`].
+type Segment<K, V> = Arc<RawTable<(K, V)>>;
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> PersistentHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `PersistentHashMap` with a default number of
+    /// segments.
+    ///
+    /// Only the list of segments is allocated until entries are inserted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    /// let map: PersistentHashMap<&str, i32> = PersistentHashMap::new();
+    /// assert!(map.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `PersistentHashMap` with at least the given number of
+    /// segments. The number of segments is rounded up to a power of two.
+    ///
+    /// More segments make the first write to a segment after a clone copy
+    /// fewer entries, at the cost of copying a longer list of segments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    /// let map: PersistentHashMap<&str, i32> = PersistentHashMap::with_segments(100);
+    /// assert_eq!(map.segment_amount(), 128);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_segments(segments: usize) -> Self {
+        Self::with_segments_and_hasher(segments, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> PersistentHashMap<K, V, S> {
+    /// Creates an empty `PersistentHashMap` with a default number of segments
+    /// which will use the given hash builder to hash keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_segments_and_hasher(DEFAULT_SEGMENTS, hash_builder)
+    }
+
+    /// Creates an empty `PersistentHashMap` with at least the given number of
+    /// segments which will use the given hash builder to hash keys. The
+    /// number of segments is rounded up to a power of two.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of segments, rounded up to a power of two, does
+    /// not fit in a `usize` or needs more than 32 hash bits to index.
+    pub fn with_segments_and_hasher(segments: usize, hash_builder: S) -> Self {
+        let segments = segments.max(1).next_power_of_two();
+        let segment_bits = segments.trailing_zeros();
+        assert!(segment_bits <= 32, "too many segments");
+        Self {
+            hash_builder,
+            segments: Arc::new((0..segments).map(|_| Arc::new(RawTable::new())).collect()),
+            segment_bits,
+            len: 0,
+        }
+    }
+
+    /// Returns a reference to the map's [`BuildHasher`].
+    ///
+    /// [`BuildHasher`]: https://doc.rust-lang.org/std/hash/trait.BuildHasher.html
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Returns the number of segments of the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn segment_amount(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns the number of segments which this map shares with `other`,
+    /// because neither has modified them since one was cloned from the other.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    ///
+    /// let mut v1: PersistentHashMap<i32, i32> = PersistentHashMap::with_segments(16);
+    /// v1.extend((0..100).map(|i| (i, i)));
+    /// let mut v2 = v1.clone();
+    /// assert_eq!(v1.shared_segments(&v2), 16);
+    /// v2.insert(1, 2);
+    /// assert_eq!(v1.shared_segments(&v2), 15);
+    /// ```
+    pub fn shared_segments(&self, other: &Self) -> usize {
+        if Arc::ptr_eq(&self.segments, &other.segments) {
+            return self.segments.len();
+        }
+        self.segments
+            .iter()
+            .zip(other.segments.iter())
+            .filter(|(a, b)| Arc::ptr_eq(a, b))
+            .count()
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Clears the map, removing all key-value pairs. Segments shared with
+    /// other versions of the map are left to them.
+    pub fn clear(&mut self) {
+        let segments = self.segments.len();
+        self.segments = Arc::new((0..segments).map(|_| Arc::new(RawTable::new())).collect());
+        self.len = 0;
+    }
+
+    /// An iterator visiting all key-value pairs in arbitrary order.
+    /// The iterator element type is `(&'a K, &'a V)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    ///
+    /// let mut map = PersistentHashMap::new();
+    /// map.insert("a", 1);
+    /// map.insert("b", 2);
+    ///
+    /// let mut vec: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    /// vec.sort_unstable();
+    /// assert_eq!(vec, [("a", 1), ("b", 2)]);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            segments: self.segments.iter(),
+            current: None,
+            remaining: self.len,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S> PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    #[inline]
+    fn segment_index(&self, hash: u64) -> usize {
+        shard_index(hash, self.segment_bits)
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let segment = &self.segments[self.segment_index(hash)];
+        segment
+            .get(hash, equivalent_key(k))
+            .map(|(key, value)| (key, value))
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    ///
+    /// Lookups never copy any part of the map.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(k).is_some()
+    }
+}
+
+impl<K, V, S> PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    /// Returns the segment at `index` for modification, copying it and the
+    /// list of segments first if they are shared with another version.
+    #[inline]
+    fn segment_mut(&mut self, index: usize) -> &mut RawTable<(K, V)> {
+        Arc::make_mut(&mut Arc::make_mut(&mut self.segments)[index])
+    }
+
+    /// Inserts a key-value pair into the map.
+    ///
+    /// If the map did not have this key present, [`None`] is returned.
+    /// Otherwise the value is updated and the old value is returned.
+    ///
+    /// If the key's segment is shared with another version of the map, it
+    /// is copied first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    ///
+    /// let mut map = PersistentHashMap::new();
+    /// assert_eq!(map.insert(37, "a"), None);
+    /// assert_eq!(map.insert(37, "b"), Some("a"));
+    /// assert_eq!(map[&37], "b");
+    /// ```
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_insert_hash::<K, S>(&self.hash_builder, &k);
+        let index = self.segment_index(hash);
+        // Borrow the fields separately to hash with `hash_builder` while the
+        // segment is borrowed mutably.
+        let hash_builder = &self.hash_builder;
+        let table = Arc::make_mut(&mut Arc::make_mut(&mut self.segments)[index]);
+        match table.get_mut(hash, equivalent_key(&k)) {
+            Some((_, item)) => Some(mem::replace(item, v)),
+            None => {
+                table.insert(hash, (k, v), make_hasher::<K, V, S>(hash_builder));
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key.
+    ///
+    /// If the key is present and its segment is shared with another version
+    /// of the map, the segment is copied first. Nothing is copied for a key
+    /// which is not present.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    ///
+    /// let mut v1 = PersistentHashMap::new();
+    /// v1.insert(1, 10);
+    /// let mut v2 = v1.clone();
+    /// *v2.get_mut(&1).unwrap() += 1;
+    /// assert_eq!((v1[&1], v2[&1]), (10, 11));
+    /// ```
+    pub fn get_mut<Q>(&mut self, k: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let index = self.segment_index(hash);
+        self.segments[index].find(hash, equivalent_key(k))?;
+        self.segment_mut(index)
+            .get_mut(hash, equivalent_key(k))
+            .map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the
+    /// key was previously in the map.
+    ///
+    /// Nothing is copied for a key which is not present.
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash::<Q, S>(&self.hash_builder, k);
+        let index = self.segment_index(hash);
+        self.segments[index].find(hash, equivalent_key(k))?;
+        let entry = self
+            .segment_mut(index)
+            .remove_entry(hash, equivalent_key(k));
+        if entry.is_some() {
+            self.len -= 1;
+        }
+        entry
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key
+    /// was previously in the map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::PersistentHashMap;
+    ///
+    /// let mut v1 = PersistentHashMap::new();
+    /// v1.insert(1, "a");
+    /// let mut v2 = v1.clone();
+    /// assert_eq!(v2.remove(&1), Some("a"));
+    /// assert_eq!(v2.remove(&1), None);
+    /// assert_eq!(v1.get(&1), Some(&"a"));
+    /// ```
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(k).map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S: Clone> Clone for PersistentHashMap<K, V, S> {
+    /// Returns a version of the map which shares all of its memory with this
+    /// one, in constant time.
+    fn clone(&self) -> Self {
+        PersistentHashMap {
+            hash_builder: self.hash_builder.clone(),
+            segments: Arc::clone(&self.segments),
+            segment_bits: self.segment_bits,
+            len: self.len,
+        }
+    }
+}
+
+impl<K, V, S> PartialEq for PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        if self.len != other.len {
+            return false;
+        }
+        if Arc::ptr_eq(&self.segments, &other.segments) {
+            return true;
+        }
+        self.iter()
+            .all(|(key, value)| other.get(key).map_or(false, |v| *value == *v))
+    }
+}
+
+impl<K, V, S> Eq for PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K: Debug, V: Debug, S> Debug for PersistentHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S: Default> Default for PersistentHashMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    Q: Hash + Equivalent<K>,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found")
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for PersistentHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    S: BuildHasher + Default,
+{
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a PersistentHashMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of a `PersistentHashMap` in arbitrary order.
+///
+/// This `struct` is created by the [`iter`] method on [`PersistentHashMap`].
+///
+/// [`iter`]: PersistentHashMap::iter
+pub struct Iter<'a, K, V> {
+    segments: core::slice::Iter<'a, Segment<K, V>>,
+    current: Option<RawIter<(K, V)>>,
+    remaining: usize,
+    marker: PhantomData<(&'a K, &'a V)>,
+}
+
+impl<K, V> Clone for Iter<'_, K, V> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        Iter {
+            segments: self.segments.clone(),
+            current: self.current.clone(),
+            remaining: self.remaining,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            if let Some(ref mut current) = self.current {
+                if let Some(bucket) = current.next() {
+                    self.remaining -= 1;
+                    // SAFETY: The segments outlive the iterator.
+                    let (k, v) = unsafe { bucket.as_ref() };
+                    return Some((k, v));
+                }
+            }
+            // SAFETY: The segments outlive the iterator.
+            self.current = Some(unsafe { self.segments.next()?.iter() });
+        }
+    }
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+impl<K: Debug, V: Debug> Debug for Iter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test_persistent_map {
+    use super::PersistentHashMap;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_versions_share_segments() {
+        let mut versions: Vec<PersistentHashMap<u32, u32>> = Vec::new();
+        let mut map = PersistentHashMap::with_segments(32);
+        map.extend((0..10_000).map(|i| (i, 0)));
+        for version in 1..=20 {
+            versions.push(map.clone());
+            map.insert(version, version);
+            map.remove(&(10_000 - version));
+            *map.get_mut(&(5000 + version)).unwrap() = version;
+            // Nothing is copied for absent keys.
+            assert_eq!(map.remove(&u32::MAX), None);
+            assert!(map.get_mut(&u32::MAX).is_none());
+        }
+        for (i, old) in versions.iter().enumerate() {
+            let v = i as u32;
+            assert_eq!(old.len(), 10_000 - v as usize);
+            assert_eq!(old.iter().len(), old.len());
+            assert_eq!(old.iter().count(), old.len());
+            assert_eq!(old.get(&(v + 1)), Some(&0));
+            assert!(old.contains_key(&(10_000 - v - 1)));
+            assert!(old.shared_segments(&map) < 32);
+            if v > 0 {
+                assert_eq!(old.get(&v), Some(&v));
+                assert!(!old.contains_key(&(10_000 - v)));
+            }
+        }
+        // Every version shares the segments which no later write touched.
+        let last = versions.last().unwrap();
+        assert!(last.shared_segments(&map) >= 32 - 3);
+        assert_ne!(*last, map);
+        assert_eq!(map.clone(), map);
+
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.iter().count(), 0);
+        assert_eq!(versions[0].len(), 10_000);
+    }
+
+    #[test]
+    fn test_single_segment() {
+        let mut map: PersistentHashMap<i32, i32> = PersistentHashMap::with_segments(0);
+        assert_eq!(map.segment_amount(), 1);
+        map.extend((0..100).map(|i| (i, i)));
+        let snapshot = map.clone();
+        map.insert(0, 1);
+        assert_eq!(snapshot.shared_segments(&map), 0);
+        assert_eq!(snapshot[&0], 0);
+        assert_eq!(map[&0], 1);
+    }
+}
@@ -26,6 +26,36 @@ mod inner {
         #[inline]
         unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
     }
+
+    #[cfg(feature = "huge-pages")]
+    unsafe impl Allocator for crate::huge_pages::HugePageAlloc {
+        #[inline]
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+            match self.allocate_huge(layout) {
+                Some((ptr, size)) => Ok(NonNull::slice_from_raw_parts(ptr, size)),
+                None => Err(core::alloc::AllocError),
+            }
+        }
+        #[inline]
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocate_huge(ptr, layout);
+        }
+    }
+
+    #[cfg(feature = "numa")]
+    unsafe impl Allocator for crate::numa::NumaAlloc {
+        #[inline]
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, core::alloc::AllocError> {
+            match self.allocate_on_node(layout) {
+                Some(ptr) => Ok(NonNull::slice_from_raw_parts(ptr, layout.size())),
+                None => Err(core::alloc::AllocError),
+            }
+        }
+        #[inline]
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocate_on_node(ptr, layout);
+        }
+    }
 }
 
 #[cfg(not(feature = "nightly"))]
@@ -70,4 +100,28 @@ mod inner {
         }
         unsafe fn deallocate(&self, _ptr: NonNull<u8>, _layout: Layout) {}
     }
+
+    #[cfg(feature = "huge-pages")]
+    unsafe impl Allocator for crate::huge_pages::HugePageAlloc {
+        #[inline]
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, ()> {
+            self.allocate_huge(layout).map(|(ptr, _)| ptr).ok_or(())
+        }
+        #[inline]
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocate_huge(ptr, layout);
+        }
+    }
+
+    #[cfg(feature = "numa")]
+    unsafe impl Allocator for crate::numa::NumaAlloc {
+        #[inline]
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, ()> {
+            self.allocate_on_node(layout).ok_or(())
+        }
+        #[inline]
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocate_on_node(ptr, layout);
+        }
+    }
 }
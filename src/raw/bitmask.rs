@@ -1,6 +1,8 @@
 use super::imp::{BitMaskWord, BITMASK_MASK, BITMASK_STRIDE};
+use super::Group;
 #[cfg(feature = "nightly")]
 use core::intrinsics;
+use core::iter::FusedIterator;
 
 /// A bit mask which contains the result of a `Match` operation on a `Group` and
 /// allows iterating through them.
@@ -13,8 +15,33 @@ use core::intrinsics;
 /// case, `BITMASK_STRIDE` will be 8 to indicate a divide-by-8 should be
 /// performed on counts/indices to normalize this difference. `BITMASK_MASK` is
 /// similarly a mask of all the actually-used bits.
-#[derive(Copy, Clone)]
-pub struct BitMask(pub BitMaskWord);
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "raw")]
+/// # fn test() {
+/// use hashbrown::raw::{Group, DELETED, EMPTY};
+///
+/// let mut ctrl = [EMPTY; 64];
+/// ctrl[1] = 0x12;
+/// ctrl[3] = 0x12;
+/// ctrl[4] = DELETED;
+///
+/// // SAFETY: `ctrl` holds more than `Group::WIDTH` bytes.
+/// let group = unsafe { Group::load(ctrl.as_ptr()) };
+/// let matches: Vec<usize> = group.match_byte(0x12).into_iter().collect();
+/// assert_eq!(matches, [1, 3]);
+/// assert_eq!(group.match_full().lowest_set_bit(), Some(1));
+/// assert_eq!(group.match_empty().into_iter().count(), Group::WIDTH - 3);
+/// # }
+/// # fn main() {
+/// #     #[cfg(feature = "raw")]
+/// #     test()
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BitMask(pub(crate) BitMaskWord);
 
 #[allow(clippy::use_self)]
 impl BitMask {
@@ -28,6 +55,10 @@ impl BitMask {
     /// Flip the bit in the mask for the entry at the given index.
     ///
     /// Returns the bit's previous state.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be less than `Group::WIDTH`.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)]
     #[cfg(feature = "raw")]
@@ -39,12 +70,102 @@ impl BitMask {
         self.0 & mask == 0
     }
 
+    /// Splits the mask into the bits of the entries before `index` and those
+    /// of the entries at or after it.
+    #[inline]
+    pub fn split_at(self, index: usize) -> (Self, Self) {
+        let below = ((1 as BitMaskWord) << (index * BITMASK_STRIDE)).wrapping_sub(1);
+        (BitMask(self.0 & below), BitMask(self.0 & !below))
+    }
+
     /// Returns a new `BitMask` with the lowest bit removed.
     #[inline]
     #[must_use]
     pub fn remove_lowest_bit(self) -> Self {
         BitMask(self.0 & (self.0 - 1))
     }
+
+    /// Returns the entries which are set in both `self` and `other`.
+    #[inline]
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        BitMask(self.0 & other.0)
+    }
+
+    /// Returns the entries which are set in `self`, `other` or both.
+    #[inline]
+    #[must_use]
+    #[cfg(feature = "raw")]
+    pub fn or(self, other: Self) -> Self {
+        BitMask(self.0 | other.0)
+    }
+
+    /// Returns the entries which are set in exactly one of `self` and
+    /// `other`.
+    #[inline]
+    #[must_use]
+    #[cfg(feature = "raw")]
+    pub fn xor(self, other: Self) -> Self {
+        BitMask(self.0 ^ other.0)
+    }
+
+    /// Returns the number of set entries.
+    #[inline]
+    pub fn count_ones(self) -> usize {
+        // Only one bit of every stride is ever set.
+        self.0.count_ones() as usize
+    }
+
+    /// Returns the index of the `n`th set entry, counting from zero, if
+    /// there are more than `n` set entries.
+    #[inline]
+    #[cfg(feature = "raw")]
+    pub fn nth_set_bit(self, n: usize) -> Option<usize> {
+        let mut mask = self;
+        for _ in 0..n {
+            if !mask.any_bit_set() {
+                return None;
+            }
+            mask = mask.remove_lowest_bit();
+        }
+        mask.lowest_set_bit()
+    }
+
+    /// Returns the mask packed into a `u16` whose bit `i` is set if entry `i`
+    /// is set, independently of how the group implementation lays out its
+    /// bits.
+    #[inline]
+    #[cfg(feature = "raw")]
+    #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)]
+    pub fn to_u16(self) -> u16 {
+        if BITMASK_STRIDE == 1 {
+            self.0 as u16
+        } else {
+            let mut bits = 0;
+            for index in self {
+                bits |= 1 << index;
+            }
+            bits
+        }
+    }
+
+    /// Returns the mask whose entry `i` is set if bit `i` of `bits` is set,
+    /// for the `i` below `Group::WIDTH`. Higher bits are ignored.
+    #[inline]
+    pub fn from_u16(bits: u16) -> Self {
+        if BITMASK_STRIDE == 1 {
+            BitMask(BitMaskWord::from(bits) & BITMASK_MASK)
+        } else {
+            let mut word = 0;
+            for index in 0..Group::WIDTH {
+                if bits & (1 << index) != 0 {
+                    word |= 1 << (index * BITMASK_STRIDE + BITMASK_STRIDE - 1);
+                }
+            }
+            BitMask(word)
+        }
+    }
+
     /// Returns whether the `BitMask` has at least one set bit.
     #[inline]
     pub fn any_bit_set(self) -> bool {
@@ -63,11 +184,22 @@ impl BitMask {
 
     /// Returns the first set bit in the `BitMask`, if there is one. The
     /// bitmask must not be empty.
+    ///
+    /// # Safety
+    ///
+    /// At least one bit must be set.
     #[inline]
     #[cfg(feature = "nightly")]
     pub unsafe fn lowest_set_bit_nonzero(self) -> usize {
         intrinsics::cttz_nonzero(self.0) as usize / BITMASK_STRIDE
     }
+
+    /// Returns the first set bit in the `BitMask`, if there is one. The
+    /// bitmask must not be empty.
+    ///
+    /// # Safety
+    ///
+    /// At least one bit must be set.
     #[inline]
     #[cfg(not(feature = "nightly"))]
     pub unsafe fn lowest_set_bit_nonzero(self) -> usize {
@@ -108,6 +240,7 @@ impl IntoIterator for BitMask {
 
 /// Iterator over the contents of a `BitMask`, returning the indices of set
 /// bits.
+#[derive(Clone, Debug)]
 pub struct BitMaskIter(BitMask);
 
 impl Iterator for BitMaskIter {
@@ -119,4 +252,58 @@ impl Iterator for BitMaskIter {
         self.0 = self.0.remove_lowest_bit();
         Some(bit)
     }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.count_ones();
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for BitMaskIter {}
+impl FusedIterator for BitMaskIter {}
+
+#[cfg(test)]
+mod test_bitmask {
+    use super::BitMask;
+    use crate::raw::{Group, DELETED, EMPTY};
+
+    fn group(tags: &[u8]) -> Group {
+        let mut ctrl = [EMPTY; 16];
+        ctrl[..tags.len()].copy_from_slice(tags);
+        // SAFETY: `ctrl` holds at least `Group::WIDTH` bytes.
+        unsafe { Group::load(ctrl.as_ptr()) }
+    }
+
+    #[test]
+    fn test_count_and_combine() {
+        let group = group(&[1, DELETED, 1, 2]);
+        let ones = group.match_byte(1);
+        let full = group.match_full();
+        assert_eq!(ones.count_ones(), 2);
+        assert_eq!(full.count_ones(), 3);
+        let mut iter = full.into_iter();
+        iter.next();
+        assert_eq!(iter.len(), 2);
+        assert_eq!(
+            ones.and(full).into_iter().collect::<std::vec::Vec<_>>(),
+            [0, 2]
+        );
+        assert_eq!(full.and(BitMask::from_u16(0b1100)).count_ones(), 2);
+        assert_eq!(BitMask::from_u16(u16::MAX).count_ones(), Group::WIDTH);
+    }
+
+    #[test]
+    #[cfg(feature = "raw")]
+    fn test_raw_combinators() {
+        let group = group(&[1, DELETED, 1, 2]);
+        let ones = group.match_byte(1);
+        let twos = group.match_byte(2);
+        assert_eq!(ones.or(twos).to_u16(), 0b1101);
+        assert_eq!(ones.xor(group.match_full()).to_u16(), 0b1000);
+        assert_eq!(ones.nth_set_bit(0), Some(0));
+        assert_eq!(ones.nth_set_bit(1), Some(2));
+        assert_eq!(ones.nth_set_bit(2), None);
+        assert_eq!(BitMask::from_u16(0b1010).to_u16(), 0b1010);
+    }
 }
@@ -0,0 +1,442 @@
+//! A C interface to a type-erased raw table.
+//!
+//! The table stores fixed-size blobs of bytes whose size and alignment are
+//! given when it is created. The table never interprets the blobs: the caller
+//! passes the hash of every value it inserts or looks up, supplies a hash
+//! callback at creation which the table uses to rehash values when it grows,
+//! and supplies an equality callback to each lookup. Values are copied into
+//! the table with `memcpy` and are never dropped, so they must be plain data
+//! from the table's point of view.
+//!
+//! The functions correspond to these C declarations:
+//!
+//! ```c
+//! typedef struct hashbrown_table hashbrown_table;
+//!
+//! typedef uint64_t (*hashbrown_hash_fn)(const void *value, void *ctx);
+//! typedef bool (*hashbrown_eq_fn)(const void *value, const void *key, void *ctx);
+//! typedef bool (*hashbrown_visit_fn)(void *value, void *ctx);
+//!
+//! hashbrown_table *hashbrown_table_new(size_t value_size, size_t value_align,
+//!                                      hashbrown_hash_fn hash, void *hash_ctx);
+//! void hashbrown_table_free(hashbrown_table *table);
+//! size_t hashbrown_table_len(const hashbrown_table *table);
+//! bool hashbrown_table_reserve(hashbrown_table *table, size_t additional);
+//! void *hashbrown_table_insert(hashbrown_table *table, uint64_t hash, const void *value);
+//! void *hashbrown_table_find(const hashbrown_table *table, uint64_t hash, const void *key,
+//!                            hashbrown_eq_fn eq, void *eq_ctx);
+//! bool hashbrown_table_erase(hashbrown_table *table, uint64_t hash, const void *key,
+//!                            hashbrown_eq_fn eq, void *eq_ctx, void *out);
+//! void hashbrown_table_clear(hashbrown_table *table);
+//! size_t hashbrown_table_iterate(const hashbrown_table *table, hashbrown_visit_fn visit,
+//!                                void *ctx);
+//! ```
+//!
+//! Pointers to values returned by the table stay valid until the table is next
+//! modified by `insert`, `reserve`, `erase`, `clear` or `free`.
+//!
+//! This module is only available if the `ffi` feature is enabled.
+
+use super::{special_is_empty, Fallibility, Global, Group, RawTableInner, TableLayout};
+use crate::alloc::boxed::Box;
+use core::ffi::c_void;
+use core::ptr;
+
+/// Hashes the value stored at `value`.
+///
+/// The table calls this with the context pointer given to
+/// [`hashbrown_table_new`] whenever it has to move values to a larger table.
+pub type HashFn = unsafe extern "C" fn(value: *const c_void, ctx: *mut c_void) -> u64;
+
+/// Returns `true` if the value stored at `value` matches the key passed to a
+/// lookup.
+pub type EqFn =
+    unsafe extern "C" fn(value: *const c_void, key: *const c_void, ctx: *mut c_void) -> bool;
+
+/// Visits a value during [`hashbrown_table_iterate`], returning `false` to stop
+/// the iteration.
+pub type VisitFn = unsafe extern "C" fn(value: *mut c_void, ctx: *mut c_void) -> bool;
+
+/// A table of fixed-size values, created by [`hashbrown_table_new`].
+///
+/// The type is opaque to C code, which only ever handles pointers to it.
+pub struct Table {
+    table: RawTableInner<Global>,
+    layout: TableLayout,
+    hash: HashFn,
+    hash_ctx: *mut c_void,
+}
+
+impl Table {
+    /// Returns a pointer to the value in the bucket at `index`.
+    #[inline]
+    unsafe fn value(&self, index: usize) -> *mut c_void {
+        self.table.bucket_ptr(index, self.layout.size).cast()
+    }
+
+    /// Makes room for `additional` more values, returning `false` if the
+    /// allocation fails.
+    fn reserve(&mut self, additional: usize) -> bool {
+        if additional <= self.table.growth_left {
+            return true;
+        }
+        let (size, hash, hash_ctx) = (self.layout.size, self.hash, self.hash_ctx);
+        unsafe {
+            self.table
+                .reserve_rehash_inner(
+                    additional,
+                    &|table, index| hash(table.bucket_ptr(index, size).cast(), hash_ctx),
+                    Fallibility::Fallible,
+                    self.layout,
+                    None,
+                    None,
+                )
+                .is_ok()
+        }
+    }
+
+    /// Returns the index of the bucket holding a value matching `key`.
+    unsafe fn find(
+        &self,
+        hash: u64,
+        key: *const c_void,
+        eq: EqFn,
+        ctx: *mut c_void,
+    ) -> Option<usize> {
+        self.table
+            .find_inner(hash, &mut |index| eq(self.value(index), key, ctx))
+    }
+}
+
+impl Drop for Table {
+    fn drop(&mut self) {
+        if !self.table.is_empty_singleton() {
+            unsafe {
+                self.table.free_buckets(self.layout);
+            }
+        }
+    }
+}
+
+/// Creates an empty table for values of `value_size` bytes aligned to
+/// `value_align` bytes, which are hashed by `hash` when the table grows.
+///
+/// Returns a null pointer if `value_align` is not a power of two or
+/// `value_size` is not a multiple of it, as for any C type. The table must be
+/// freed with [`hashbrown_table_free`].
+#[no_mangle]
+pub extern "C" fn hashbrown_table_new(
+    value_size: usize,
+    value_align: usize,
+    hash: HashFn,
+    hash_ctx: *mut c_void,
+) -> *mut Table {
+    if !value_align.is_power_of_two() || value_size % value_align != 0 {
+        return ptr::null_mut();
+    }
+    let table = Table {
+        table: RawTableInner::new_in(Global),
+        layout: TableLayout {
+            size: value_size,
+            ctrl_align: usize::max(value_align, Group::WIDTH),
+        },
+        hash,
+        hash_ctx,
+    };
+    Box::into_raw(Box::new(table))
+}
+
+/// Frees a table created by [`hashbrown_table_new`]. Does nothing if `table`
+/// is null.
+///
+/// # Safety
+///
+/// `table` must be null or a table which has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_free(table: *mut Table) {
+    if !table.is_null() {
+        drop(Box::from_raw(table));
+    }
+}
+
+/// Returns the number of values in the table.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_len(table: *const Table) -> usize {
+    (*table).table.items
+}
+
+/// Makes room for at least `additional` more values, returning `false` if the
+/// memory could not be allocated.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_reserve(table: *mut Table, additional: usize) -> bool {
+    (*table).reserve(additional)
+}
+
+/// Copies the value at `value` into the table under `hash` and returns a
+/// pointer to the copy, or a null pointer if the table could not grow.
+///
+/// This does not check whether an equal value is already in the table.
+///
+/// # Safety
+///
+/// `table` must be a live table and `value` must point to `value_size`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_insert(
+    table: *mut Table,
+    hash: u64,
+    value: *const c_void,
+) -> *mut c_void {
+    let table = &mut *table;
+    let mut index = table.table.find_insert_slot(hash);
+
+    // Like `RawTable::insert`, replacing a tombstone doesn't need room to grow.
+    let old_ctrl = *table.table.ctrl(index);
+    if table.table.growth_left == 0 && special_is_empty(old_ctrl) {
+        if !table.reserve(1) {
+            return ptr::null_mut();
+        }
+        index = table.table.find_insert_slot(hash);
+    }
+
+    let old_ctrl = *table.table.ctrl(index);
+    table.table.record_item_insert_at(index, old_ctrl, hash);
+    let slot = table.value(index);
+    ptr::copy_nonoverlapping(value.cast::<u8>(), slot.cast::<u8>(), table.layout.size);
+    slot
+}
+
+/// Returns a pointer to a value stored under `hash` for which `eq` returns
+/// `true` when called with `key`, or a null pointer if there is none.
+///
+/// # Safety
+///
+/// `table` must be a live table, and `eq` must be safe to call with `key` and
+/// `eq_ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_find(
+    table: *const Table,
+    hash: u64,
+    key: *const c_void,
+    eq: EqFn,
+    eq_ctx: *mut c_void,
+) -> *mut c_void {
+    let table = &*table;
+    match table.find(hash, key, eq, eq_ctx) {
+        Some(index) => table.value(index),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Removes a value stored under `hash` for which `eq` returns `true` when
+/// called with `key`, copying it to `out` unless `out` is null.
+///
+/// Returns `false` if there was no such value.
+///
+/// # Safety
+///
+/// `table` must be a live table, `eq` must be safe to call with `key` and
+/// `eq_ctx`, and `out` must be null or point to `value_size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_erase(
+    table: *mut Table,
+    hash: u64,
+    key: *const c_void,
+    eq: EqFn,
+    eq_ctx: *mut c_void,
+    out: *mut c_void,
+) -> bool {
+    let table = &mut *table;
+    match table.find(hash, key, eq, eq_ctx) {
+        Some(index) => {
+            if !out.is_null() {
+                let size = table.layout.size;
+                ptr::copy_nonoverlapping(table.value(index).cast::<u8>(), out.cast::<u8>(), size);
+            }
+            table.table.erase(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Removes all values from the table, keeping its memory.
+///
+/// # Safety
+///
+/// `table` must be a live table.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_clear(table: *mut Table) {
+    (*table).table.clear_no_drop();
+}
+
+/// Calls `visit` with a pointer to each value in the table, in no particular
+/// order, until it returns `false`.
+///
+/// Returns the number of values visited. `visit` may modify the values it is
+/// given, but must not change their hashes or modify the table.
+///
+/// # Safety
+///
+/// `table` must be a live table, and `visit` must be safe to call with `ctx`.
+#[no_mangle]
+pub unsafe extern "C" fn hashbrown_table_iterate(
+    table: *const Table,
+    visit: VisitFn,
+    ctx: *mut c_void,
+) -> usize {
+    let table = &*table;
+    let mut visited = 0;
+    if table.table.items == 0 {
+        return visited;
+    }
+    for index in 0..table.table.buckets() {
+        if table.table.is_bucket_full(index) {
+            visited += 1;
+            if !visit(table.value(index), ctx) {
+                break;
+            }
+        }
+    }
+    visited
+}
+
+#[cfg(test)]
+mod test_ffi {
+    use super::*;
+    use crate::hasher::hash_integer;
+    use core::mem;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Record {
+        key: u64,
+        value: u32,
+    }
+
+    unsafe extern "C" fn hash_record(value: *const c_void, _ctx: *mut c_void) -> u64 {
+        hash_integer((*value.cast::<Record>()).key)
+    }
+
+    unsafe extern "C" fn record_has_key(
+        value: *const c_void,
+        key: *const c_void,
+        _ctx: *mut c_void,
+    ) -> bool {
+        (*value.cast::<Record>()).key == *key.cast::<u64>()
+    }
+
+    unsafe extern "C" fn sum_values(value: *mut c_void, ctx: *mut c_void) -> bool {
+        *ctx.cast::<u64>() += u64::from((*value.cast::<Record>()).value);
+        true
+    }
+
+    unsafe extern "C" fn stop(_value: *mut c_void, _ctx: *mut c_void) -> bool {
+        false
+    }
+
+    fn new_table() -> *mut Table {
+        hashbrown_table_new(
+            mem::size_of::<Record>(),
+            mem::align_of::<Record>(),
+            hash_record,
+            ptr::null_mut(),
+        )
+    }
+
+    unsafe fn find(table: *const Table, key: u64) -> Option<Record> {
+        let key: *const u64 = &key;
+        let value = hashbrown_table_find(
+            table,
+            hash_integer(*key),
+            key.cast(),
+            record_has_key,
+            ptr::null_mut(),
+        );
+        if value.is_null() {
+            None
+        } else {
+            Some(*value.cast::<Record>())
+        }
+    }
+
+    #[test]
+    fn test_insert_find_erase() {
+        unsafe {
+            let table = new_table();
+            for key in 0..1000 {
+                let record = Record {
+                    key,
+                    value: key as u32 * 2,
+                };
+                let slot = hashbrown_table_insert(
+                    table,
+                    hash_integer(key),
+                    (&record as *const Record).cast(),
+                );
+                assert_eq!(*slot.cast::<Record>(), record);
+            }
+            assert_eq!(hashbrown_table_len(table), 1000);
+            for key in 0..1000 {
+                assert_eq!(
+                    find(table, key),
+                    Some(Record {
+                        key,
+                        value: key as u32 * 2
+                    })
+                );
+            }
+            assert_eq!(find(table, 1000), None);
+
+            let mut out = Record { key: 0, value: 0 };
+            let key = 7u64;
+            let erased = hashbrown_table_erase(
+                table,
+                hash_integer(key),
+                (&key as *const u64).cast(),
+                record_has_key,
+                ptr::null_mut(),
+                (&mut out as *mut Record).cast(),
+            );
+            assert!(erased);
+            assert_eq!(out, Record { key: 7, value: 14 });
+            assert_eq!(find(table, 7), None);
+            assert_eq!(hashbrown_table_len(table), 999);
+
+            let mut sum = 0u64;
+            let visited = hashbrown_table_iterate(table, sum_values, (&mut sum as *mut u64).cast());
+            assert_eq!(visited, 999);
+            assert_eq!(sum, 999 * 1000 - 14);
+            assert_eq!(hashbrown_table_iterate(table, stop, ptr::null_mut()), 1);
+
+            hashbrown_table_clear(table);
+            assert_eq!(hashbrown_table_len(table), 0);
+            assert_eq!(find(table, 1), None);
+            hashbrown_table_free(table);
+        }
+    }
+
+    #[test]
+    fn test_empty_and_invalid() {
+        unsafe {
+            assert!(hashbrown_table_new(4, 3, hash_record, ptr::null_mut()).is_null());
+            assert!(hashbrown_table_new(6, 4, hash_record, ptr::null_mut()).is_null());
+
+            let table = new_table();
+            assert_eq!(find(table, 1), None);
+            assert_eq!(hashbrown_table_iterate(table, stop, ptr::null_mut()), 0);
+            assert!(hashbrown_table_reserve(table, 100));
+            assert_eq!(hashbrown_table_len(table), 0);
+            hashbrown_table_free(table);
+            hashbrown_table_free(ptr::null_mut());
+        }
+    }
+}
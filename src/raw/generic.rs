@@ -4,12 +4,14 @@ use core::{mem, ptr};
 
 // Use the native word size as the group size. Using a 64-bit group size on
 // a 32-bit architecture will just end up being more expensive because
-// shifts and multiplies will need to be emulated.
+// shifts and multiplies will need to be emulated. The `deterministic` feature
+// pays that cost to place elements the same way on every platform.
 #[cfg(any(
     target_pointer_width = "64",
     target_arch = "aarch64",
     target_arch = "x86_64",
     target_arch = "wasm32",
+    feature = "deterministic",
 ))]
 type GroupWord = u64;
 #[cfg(all(
@@ -17,6 +19,7 @@ type GroupWord = u64;
     not(target_arch = "aarch64"),
     not(target_arch = "x86_64"),
     not(target_arch = "wasm32"),
+    not(feature = "deterministic"),
 ))]
 type GroupWord = u32;
 
@@ -26,6 +29,13 @@ pub const BITMASK_STRIDE: usize = 8;
 #[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)]
 pub const BITMASK_MASK: BitMaskWord = 0x8080_8080_8080_8080_u64 as GroupWord;
 
+/// Does nothing, since this implementation is always scalar. Returns
+/// `true`, as if it had been forced before.
+#[cfg(feature = "force-generic")]
+pub fn force_generic(_force: bool) -> bool {
+    true
+}
+
 /// Helper function to replicate a byte across a `GroupWord`.
 #[inline]
 fn repeat(byte: u8) -> GroupWord {
@@ -36,6 +46,10 @@ fn repeat(byte: u8) -> GroupWord {
 /// parallel.
 ///
 /// This implementation uses a word-sized integer.
+///
+/// The implementation, and with it `WIDTH` and the layout of the returned
+/// [`BitMask`]s, is chosen at compile time for the target. Code using groups
+/// must not assume a particular width.
 #[derive(Copy, Clone)]
 pub struct Group(GroupWord);
 
@@ -67,6 +81,11 @@ impl Group {
     }
 
     /// Loads a group of bytes starting at the given address.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of `WIDTH` bytes, which need not be
+    /// aligned.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)] // unaligned load
     pub unsafe fn load(ptr: *const u8) -> Self {
@@ -75,6 +94,11 @@ impl Group {
 
     /// Loads a group of bytes starting at the given address, which must be
     /// aligned to `mem::align_of::<Group>()`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of `WIDTH` bytes and aligned to
+    /// `mem::align_of::<Group>()`.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)]
     pub unsafe fn load_aligned(ptr: *const u8) -> Self {
@@ -85,6 +109,11 @@ impl Group {
 
     /// Stores the group of bytes to the given address, which must be
     /// aligned to `mem::align_of::<Group>()`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `WIDTH` bytes and aligned to
+    /// `mem::align_of::<Group>()`.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)]
     pub unsafe fn store_aligned(self, ptr: *mut u8) {
@@ -1,5 +1,5 @@
 use crate::alloc::alloc::{handle_alloc_error, Layout};
-use crate::scopeguard::{guard, ScopeGuard};
+use crate::scopeguard::{guard, AbortOnUnwind, ScopeGuard};
 use crate::TryReserveError;
 use core::iter::FusedIterator;
 use core::marker::PhantomData;
@@ -7,7 +7,10 @@ use core::mem;
 use core::mem::ManuallyDrop;
 use core::mem::MaybeUninit;
 use core::ptr::NonNull;
-use core::{hint, ptr};
+use core::{fmt, hint, ptr};
+
+#[cfg(feature = "metrics")]
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 cfg_if! {
     // Use the SSE2 implementation if possible: it allows us to scan 16 buckets
@@ -18,10 +21,21 @@ cfg_if! {
     // I attempted an implementation on ARM using NEON instructions, but it
     // turns out that most NEON instructions have multi-cycle latency, which in
     // the end outweighs any gains over the generic implementation.
+    //
+    // The `deterministic` feature always uses the generic implementation with
+    // 64-bit groups, since the group width decides where elements are placed
+    // and so the iteration order.
+    //
+    // The choice is made purely from the target features enabled at compile
+    // time and never needs `std`, so `no_std` targets whose specification
+    // leaves SSE2 disabled get the SIMD implementation by building with
+    // `-C target-feature=+sse2`. The generic implementation only needs
+    // integer arithmetic and works on every target.
     if #[cfg(all(
         target_feature = "sse2",
         any(target_arch = "x86", target_arch = "x86_64"),
-        not(miri)
+        not(miri),
+        not(feature = "deterministic")
     ))] {
         mod sse2;
         use sse2 as imp;
@@ -37,8 +51,46 @@ pub(crate) use self::alloc::{do_alloc, Allocator, Global};
 
 mod bitmask;
 
-use self::bitmask::{BitMask, BitMaskIter};
-use self::imp::Group;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+// With the `raw` feature these are public, so that other probing schemes and
+// filters over control bytes can reuse the matching code.
+pub use self::bitmask::{BitMask, BitMaskIter};
+
+/// Makes `Group` compute every match with scalar code at runtime, even when
+/// the target uses a SIMD implementation, or switches back to it. Returns
+/// whether scalar code was forced before.
+///
+/// The scalar code keeps the width and mask layout of the SIMD implementation,
+/// so tables built either way can be used either way. This lets a test suite
+/// run each case through both implementations in the same process and compare
+/// the results. The setting is global and checked on every operation, so this
+/// is meant for testing only.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::raw::{force_generic, RawTable};
+///
+/// let run = || {
+///     let mut table = RawTable::new();
+///     for i in 0..100_u64 {
+///         table.insert(i, i, |&x| x);
+///     }
+///     (0..200).filter(|&i| table.get(i, |&x| x == i).is_some()).count()
+/// };
+/// let simd = run();
+/// let previous = force_generic(true);
+/// let scalar = run();
+/// force_generic(previous);
+/// assert_eq!(simd, scalar);
+/// ```
+#[cfg(feature = "force-generic")]
+pub fn force_generic(force: bool) -> bool {
+    imp::force_generic(force)
+}
+pub use self::imp::Group;
 
 // Branch prediction hint. This is currently only available on nightly but it
 // consistently improves performance by 10-15%.
@@ -102,14 +154,17 @@ impl Fallibility {
 }
 
 /// Control byte value for an empty bucket.
-const EMPTY: u8 = 0b1111_1111;
+///
+/// The control byte of a full bucket holds the top 7 bits of the hash of its
+/// element, so its high bit is clear, while both special values have it set.
+pub const EMPTY: u8 = 0b1111_1111;
 
-/// Control byte value for a deleted bucket.
-const DELETED: u8 = 0b1000_0000;
+/// Control byte value for a deleted bucket, also called a tombstone.
+pub const DELETED: u8 = 0b1000_0000;
 
 /// Checks whether a control byte represents a full bucket (top bit is clear).
 #[inline]
-fn is_full(ctrl: u8) -> bool {
+pub(crate) fn is_full(ctrl: u8) -> bool {
     ctrl & 0x80 == 0
 }
 
@@ -121,7 +176,7 @@ fn is_special(ctrl: u8) -> bool {
 
 /// Checks whether a special control value is EMPTY (just check 1 bit).
 #[inline]
-fn special_is_empty(ctrl: u8) -> bool {
+pub(crate) fn special_is_empty(ctrl: u8) -> bool {
     debug_assert!(is_special(ctrl));
     ctrl & 0x01 != 0
 }
@@ -129,13 +184,19 @@ fn special_is_empty(ctrl: u8) -> bool {
 /// Primary hash function, used to select the initial bucket to probe from.
 #[inline]
 #[allow(clippy::cast_possible_truncation)]
-fn h1(hash: u64) -> usize {
+pub(crate) fn h1(hash: u64) -> usize {
     // On 32-bit platforms we simply ignore the higher hash bits.
     hash as usize
 }
 
 // Constant for h2 function that grabing the top 7 bits of the hash.
-const MIN_HASH_LEN: usize = if mem::size_of::<usize>() < mem::size_of::<u64>() {
+//
+// The `deterministic` feature always takes the top 7 bits of the 64-bit hash,
+// so that elements get the same tags, and so the same buckets, on every
+// platform.
+const MIN_HASH_LEN: usize = if cfg!(feature = "deterministic") {
+    mem::size_of::<u64>()
+} else if mem::size_of::<usize>() < mem::size_of::<u64>() {
     mem::size_of::<usize>()
 } else {
     mem::size_of::<u64>()
@@ -144,7 +205,7 @@ const MIN_HASH_LEN: usize = if mem::size_of::<usize>() < mem::size_of::<u64>() {
 /// Secondary hash function, saved in the low 7 bits of the control byte.
 #[inline]
 #[allow(clippy::cast_possible_truncation)]
-fn h2(hash: u64) -> u8 {
+pub fn h2(hash: u64) -> u8 {
     // Grab the top 7 bits of the hash. While the hash is normally a full 64-bit
     // value, some hash functions (such as FxHash) produce a usize result
     // instead, which means that the top 32 bits are 0 on 32-bit platforms.
@@ -153,23 +214,143 @@ fn h2(hash: u64) -> u8 {
     (top7 & 0x7f) as u8 // truncation
 }
 
-/// Probe sequence based on triangular numbers, which is guaranteed (since our
-/// table size is a power of two) to visit every group of elements exactly once.
+/// Folds a 128-bit hash into the 64-bit hash used by the table, such that `h2`
+/// comes from the high half of the hash and `h1` from the low half.
 ///
-/// A triangular probe has us jump by 1 more group every time. So first we
-/// jump by 1 group (meaning we just continue our linear scan), then 2 groups
-/// (skipping over 1 group), then 3 groups (skipping over 2 groups), and so on.
+/// The bits which `h2` reads are taken from the top of the high half, all
+/// other bits from the low half. Since `h1` only uses those other bits for any
+/// table which fits in memory, the bucket index and the tag of a key are then
+/// independent of each other.
+#[inline]
+pub(crate) fn fold_hash128(hash: u128) -> u64 {
+    compose_hash(hash as u64, (hash >> 121) as u8)
+}
+
+/// Builds a hash for which `h1` returns the bits of `index` and `h2` returns
+/// `tag`, which must be less than 128.
+///
+/// The bits of `index` which `h2` would read are replaced by `tag`, so only
+/// the others are used for the bucket index. These are at least the low 25
+/// bits, and on 64-bit platforms the low 57 bits.
+#[inline]
+pub(crate) fn compose_hash(index: u64, tag: u8) -> u64 {
+    debug_assert!(tag < 0x80);
+    let shift = MIN_HASH_LEN * 8 - 7;
+    let tag_mask = 0x7f << shift;
+    (index & !tag_mask) | (u64::from(tag) << shift)
+}
+
+/// Sequence of groups which lookups and insertions of a hash visit.
+///
+/// Every strategy starts at `h1(hash)` and moves by multiples of the group
+/// width, so the groups it visits are the same windows of `Group::WIDTH`
+/// buckets, only in a different order, and it must visit every one of them
+/// exactly once before repeating. Lookups stop at the first group with an
+/// empty bucket and insertions use the first empty or deleted bucket along
+/// the sequence, so `find`, `insert` and rehashing agree as long as they all
+/// probe through this type, and `is_in_same_group` holds for any strategy.
+///
+/// By default the probe is based on triangular numbers, which is guaranteed
+/// (since our table size is a power of two) to visit every group of elements
+/// exactly once. A triangular probe has us jump by 1 more group every time.
+/// So first we jump by 1 group (meaning we just continue our linear scan),
+/// then 2 groups (skipping over 1 group), then 3 groups (skipping over 2
+/// groups), and so on.
 ///
 /// Proof that the probe will visit every group in the table:
 /// <https://fgiesen.wordpress.com/2015/02/22/triangular-numbers-mod-2n/>
-struct ProbeSeq {
-    pos: usize,
+///
+/// With the `linear-probing` feature, the probe always moves on to the next
+/// group, which reads memory sequentially at the cost of longer clusters.
+///
+/// With the `double-hashing` feature, which takes precedence, every hash
+/// moves by its own odd number of groups, taken from bits 32 and up of the
+/// hash. Since the number of groups is a power of two, an odd step visits all
+/// of them. Hashers which only produce 32 bits get the step of linear probing.
+///
+/// With the `raw` feature this type is public, so that tools can replay the
+/// exact order in which a table probes its buckets for a hash, or implement
+/// other lookups on top of it.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "raw")]
+/// # fn test() {
+/// use hashbrown::raw::{Group, RawTable};
+///
+/// let mut table = RawTable::with_capacity(100);
+/// for i in 0..50_u64 {
+///     table.insert(i, i, |&x| x);
+/// }
+///
+/// // Count the groups a lookup of a missing hash reads before giving up,
+/// // which is when it reaches a group with a bucket that was never used.
+/// let bucket_mask = table.buckets() - 1;
+/// let group_full = |pos: usize| {
+///     (0..Group::WIDTH).all(|i| unsafe { table.is_bucket_full((pos + i) & bucket_mask) })
+/// };
+/// let mut probe_seq = table.probe_seq(1000);
+/// let mut groups = 1;
+/// while group_full(probe_seq.pos()) {
+///     probe_seq.move_next(bucket_mask);
+///     groups += 1;
+/// }
+/// assert!(groups <= table.buckets() / Group::WIDTH);
+/// # }
+/// # fn main() {
+/// #     #[cfg(feature = "raw")]
+/// #     test()
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ProbeSeq {
+    pub(crate) pos: usize,
+    // The group width times the number of moves so far.
     stride: usize,
+    #[cfg(feature = "double-hashing")]
+    step: usize,
 }
 
+/// Identifies the probe sequence of this build, so that `persist` does not
+/// open a table laid out for another one.
+#[cfg(feature = "persist")]
+pub(crate) const PROBE_SEQ_KIND: u64 = if cfg!(feature = "double-hashing") {
+    2
+} else if cfg!(feature = "linear-probing") {
+    1
+} else {
+    0
+};
+
 impl ProbeSeq {
+    /// Starts the probe sequence of `hash` in a table with `bucket_mask + 1`
+    /// buckets.
+    #[inline]
+    pub fn new(hash: u64, bucket_mask: usize) -> Self {
+        Self {
+            pos: h1(hash) & bucket_mask,
+            stride: 0,
+            #[cfg(feature = "double-hashing")]
+            step: ((hash >> 32) as usize | 1).wrapping_mul(Group::WIDTH),
+        }
+    }
+
+    /// Returns the index of the first bucket of the current group. The group
+    /// may wrap around the end of the table into the mirrored control bytes.
+    #[inline]
+    #[cfg(feature = "raw")]
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Moves on to the next group of the sequence.
+    ///
+    /// # Panics
+    ///
+    /// With debug assertions, panics once every group has been visited.
     #[inline]
-    fn move_next(&mut self, bucket_mask: usize) {
+    pub fn move_next(&mut self, bucket_mask: usize) {
         // We should have found an empty bucket by now and ended the probe.
         debug_assert!(
             self.stride <= bucket_mask,
@@ -177,7 +358,18 @@ impl ProbeSeq {
         );
 
         self.stride += Group::WIDTH;
-        self.pos += self.stride;
+        #[cfg(feature = "double-hashing")]
+        {
+            self.pos = self.pos.wrapping_add(self.step);
+        }
+        #[cfg(all(feature = "linear-probing", not(feature = "double-hashing")))]
+        {
+            self.pos += Group::WIDTH;
+        }
+        #[cfg(not(any(feature = "linear-probing", feature = "double-hashing")))]
+        {
+            self.pos += self.stride;
+        }
         self.pos &= bucket_mask;
     }
 }
@@ -189,7 +381,7 @@ impl ProbeSeq {
 // Workaround for emscripten bug emscripten-core/emscripten-fastcomp#258
 #[cfg_attr(target_os = "emscripten", inline(never))]
 #[cfg_attr(not(target_os = "emscripten"), inline)]
-fn capacity_to_buckets(cap: usize) -> Option<usize> {
+pub(crate) fn capacity_to_buckets(cap: usize) -> Option<usize> {
     debug_assert_ne!(cap, 0);
 
     // For small tables we require at least 1 empty bucket so that lookups are
@@ -216,7 +408,7 @@ fn capacity_to_buckets(cap: usize) -> Option<usize> {
 /// Returns the maximum effective capacity for the given bucket mask, taking
 /// the maximum load factor into account.
 #[inline]
-fn bucket_mask_to_capacity(bucket_mask: usize) -> usize {
+pub(crate) const fn bucket_mask_to_capacity(bucket_mask: usize) -> usize {
     if bucket_mask < 8 {
         // For tables with 1/2/4/8 buckets, we always reserve one empty slot.
         // Keep in mind that the bucket mask is one less than the bucket count.
@@ -283,6 +475,10 @@ pub struct Bucket<T> {
     // keeping direct pointer to element introduces difficulty.
     // Using `NonNull` for variance and niche layout
     ptr: NonNull<T>,
+    // Generation of the table when the bucket was handed out, to detect
+    // buckets which are used after the table was resized or cleared.
+    #[cfg(debug_assertions)]
+    generation: usize,
 }
 
 // This Send impl is needed for rayon support. This is safe since Bucket is
@@ -292,7 +488,11 @@ unsafe impl<T> Send for Bucket<T> {}
 impl<T> Clone for Bucket<T> {
     #[inline]
     fn clone(&self) -> Self {
-        Self { ptr: self.ptr }
+        Self {
+            ptr: self.ptr,
+            #[cfg(debug_assertions)]
+            generation: self.generation,
+        }
     }
 }
 
@@ -309,7 +509,21 @@ impl<T> Bucket<T> {
         };
         Self {
             ptr: NonNull::new_unchecked(ptr),
+            #[cfg(debug_assertions)]
+            generation: 0,
+        }
+    }
+    /// Records the current generation of `table`, which the bucket belongs
+    /// to, so that using the bucket after the table was resized or cleared
+    /// panics with debug assertions.
+    #[inline]
+    #[cfg_attr(not(debug_assertions), allow(unused_mut, unused_variables))]
+    fn stamped<A>(mut self, table: &RawTableInner<A>) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            self.generation = table.generation;
         }
+        self
     }
     #[inline]
     unsafe fn to_base_index(&self, base: NonNull<T>) -> usize {
@@ -337,6 +551,8 @@ impl<T> Bucket<T> {
         };
         Self {
             ptr: NonNull::new_unchecked(ptr),
+            #[cfg(debug_assertions)]
+            generation: self.generation,
         }
     }
     #[cfg_attr(feature = "inline-more", inline)]
@@ -366,6 +582,135 @@ impl<T> Bucket<T> {
     }
 }
 
+/// Statistics about the layout of a table, returned by [`RawTable::stats`].
+///
+/// Probe lengths are counted in groups: an element in the first group which
+/// a lookup for it probes has a probe length of 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct TableStats {
+    /// The number of buckets in the table.
+    pub buckets: usize,
+    /// The number of buckets holding an element.
+    pub full: usize,
+    /// The number of buckets holding a tombstone, which is left behind by a
+    /// removed element and is only reclaimed when the table is rehashed or
+    /// resized.
+    pub deleted: usize,
+    /// The number of empty buckets.
+    pub empty: usize,
+    /// The longest probe length of any element, or 0 if the table is empty.
+    pub max_probe_length: usize,
+    /// The mean probe length of the elements, or 0 if the table is empty.
+    pub mean_probe_length: f64,
+    /// The share of the buckets which hold an element.
+    pub load_factor: f64,
+}
+
+/// Counters of the work a table has done, returned by
+/// [`RawTable::metrics`].
+///
+/// Unlike [`TableStats`], which describes the layout of the table at one
+/// point, these add up over the life of the table until they are reset, so
+/// that they can be exported as monotonic counters.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableMetrics {
+    /// The number of times the elements were moved to a new allocation,
+    /// either to grow the table or to shrink it.
+    pub resizes: usize,
+    /// The number of times the table was rehashed in place to reclaim the
+    /// buckets of its tombstones.
+    pub rehashes: usize,
+    /// The number of tombstones left behind by removed elements.
+    pub tombstones: usize,
+    /// The number of groups of control bytes read while searching for
+    /// elements or for free buckets.
+    pub probe_steps: usize,
+    /// The number of buckets whose control byte matched the 7-bit tag of the
+    /// hash being searched for, but whose element was not the one searched
+    /// for.
+    pub false_matches: usize,
+    /// The number of groups of control bytes loaded by the operations of the
+    /// table: lookups, insertions, removals and rehashes, but not iteration.
+    /// Only counted with the `instrument` feature.
+    #[cfg(feature = "instrument")]
+    pub group_loads: usize,
+    /// The number of buckets whose control byte matched the 7-bit tag of the
+    /// hash being searched for, including those holding the element searched
+    /// for. Only counted with the `instrument` feature.
+    #[cfg(feature = "instrument")]
+    pub tag_matches: usize,
+}
+
+#[cfg(feature = "metrics")]
+impl TableMetrics {
+    /// Returns the average number of false tag matches per probe step, or 0
+    /// if no probe steps were counted.
+    ///
+    /// Every false match costs a comparison of keys. With a good hasher this
+    /// is close to the group width divided by 128, the chance that an
+    /// unrelated control byte has the same tag.
+    pub fn false_match_rate(&self) -> f64 {
+        if self.probe_steps == 0 {
+            0.0
+        } else {
+            self.false_matches as f64 / self.probe_steps as f64
+        }
+    }
+
+    /// Returns the share of the tag matches which were false positives, for
+    /// which a key was compared in vain, or 0 if no tag matched.
+    ///
+    /// This measures how well the tag tells elements apart, independently
+    /// of how long the probe sequences are.
+    #[cfg(feature = "instrument")]
+    pub fn false_positive_rate(&self) -> f64 {
+        if self.tag_matches == 0 {
+            0.0
+        } else {
+            self.false_matches as f64 / self.tag_matches as f64
+        }
+    }
+}
+
+/// Counters behind [`TableMetrics`].
+///
+/// Lookups only have shared access to the table, so they count their probe
+/// steps in atomics, with atomic additions so that no lookup running
+/// concurrently on another thread is missed.
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct Counters {
+    resizes: usize,
+    rehashes: usize,
+    tombstones: usize,
+    probe_steps: AtomicUsize,
+    false_matches: AtomicUsize,
+    #[cfg(feature = "instrument")]
+    group_loads: AtomicUsize,
+    #[cfg(feature = "instrument")]
+    tag_matches: AtomicUsize,
+}
+
+#[cfg(feature = "metrics")]
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            resizes: 0,
+            rehashes: 0,
+            tombstones: 0,
+            probe_steps: AtomicUsize::new(0),
+            false_matches: AtomicUsize::new(0),
+            #[cfg(feature = "instrument")]
+            group_loads: AtomicUsize::new(0),
+            #[cfg(feature = "instrument")]
+            tag_matches: AtomicUsize::new(0),
+        }
+    }
+}
+
 /// A raw hash table with an unsafe API.
 pub struct RawTable<T, A: Allocator + Clone = Global> {
     table: RawTableInner<A>,
@@ -390,6 +735,15 @@ struct RawTableInner<A> {
     // Number of elements in the table, only really used by len()
     items: usize,
 
+    // Incremented whenever the elements move to other buckets or are all
+    // removed at once, which invalidates buckets and iterators handed out
+    // before.
+    #[cfg(debug_assertions)]
+    generation: usize,
+
+    #[cfg(feature = "metrics")]
+    counters: Counters,
+
     alloc: A,
 }
 
@@ -496,6 +850,37 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         }
     }
 
+    /// Creates a table with `buckets` buckets from the bytes of its elements
+    /// and its control bytes, as they are laid out in memory.
+    ///
+    /// # Safety
+    ///
+    /// `buckets` must be a power of two larger than 1. `data` must hold the
+    /// bytes of `buckets` elements, starting with the last bucket, and every
+    /// bucket which `ctrl` marks as full must hold a valid `T`. `ctrl` must be
+    /// the control bytes of a table with that many buckets, whose full and
+    /// deleted buckets do not exceed its capacity.
+    #[cfg(feature = "persist")]
+    pub(crate) unsafe fn from_bytes_in(alloc: A, buckets: usize, data: &[u8], ctrl: &[u8]) -> Self {
+        debug_assert_eq!(data.len(), buckets * mem::size_of::<T>());
+        debug_assert_eq!(ctrl.len(), buckets + Group::WIDTH);
+
+        let mut table = match Self::new_uninitialized(alloc, buckets, Fallibility::Infallible) {
+            Ok(table) => table,
+            Err(_) => hint::unreachable_unchecked(),
+        };
+        let data_start = table.data_end().as_ptr().sub(buckets).cast::<u8>();
+        ptr::copy_nonoverlapping(data.as_ptr(), data_start, data.len());
+        ptr::copy_nonoverlapping(ctrl.as_ptr(), table.table.ctrl.as_ptr(), ctrl.len());
+
+        let ctrl = &ctrl[..buckets];
+        let items = ctrl.iter().filter(|&&byte| is_full(byte)).count();
+        let deleted = ctrl.iter().filter(|&&byte| byte == DELETED).count();
+        table.table.items = items;
+        table.table.growth_left -= items + deleted;
+        table
+    }
+
     /// Returns a reference to the underlying allocator.
     #[inline]
     pub fn allocator(&self) -> &A {
@@ -529,7 +914,6 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     ///
     /// This function might be useful for memory profiling.
     #[inline]
-    #[cfg(feature = "raw")]
     pub fn allocation_info(&self) -> (NonNull<u8>, Layout) {
         self.table.allocation_info_or_zero(Self::TABLE_LAYOUT)
     }
@@ -537,6 +921,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     /// Returns the index of a bucket from a `Bucket`.
     #[inline]
     pub unsafe fn bucket_index(&self, bucket: &Bucket<T>) -> usize {
+        self.table.check_generation(bucket);
         bucket.to_base_index(self.data_end())
     }
 
@@ -545,7 +930,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     pub unsafe fn bucket(&self, index: usize) -> Bucket<T> {
         debug_assert_ne!(self.table.bucket_mask, 0);
         debug_assert!(index < self.buckets());
-        Bucket::from_base_index(self.data_end(), index)
+        Bucket::from_base_index(self.data_end(), index).stamped(&self.table)
     }
 
     /// Erases an element from the table without dropping it.
@@ -614,6 +999,30 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         }
     }
 
+    /// Removes all elements from the table and shrinks it to fit `min_size`
+    /// elements, freeing its memory if `min_size` is 0.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_and_shrink_to(&mut self, min_size: usize) {
+        self.clear();
+        // The table is empty, so shrinking it never rehashes an element.
+        self.shrink_to(min_size, |_| unreachable!());
+    }
+
+    /// Replaces the table with the empty table `new`, which counts as a new
+    /// generation of it.
+    #[inline]
+    fn replace_table(&mut self, new: Self) {
+        debug_assert_eq!(self.table.items, 0);
+        #[cfg(debug_assertions)]
+        let generation = self.table.generation;
+        *self = new;
+        #[cfg(debug_assertions)]
+        {
+            self.table.generation = generation;
+        }
+        self.table.bump_generation();
+    }
+
     unsafe fn drop_elements(&mut self) {
         if Self::DATA_NEEDS_DROP && !self.is_empty() {
             for item in self.iter() {
@@ -625,11 +1034,37 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     /// Shrinks the table to fit `max(self.len(), min_size)` elements.
     #[cfg_attr(feature = "inline-more", inline)]
     pub fn shrink_to(&mut self, min_size: usize, hasher: impl Fn(&T) -> u64) {
+        self.shrink_to_inner(min_size, hasher, None);
+    }
+
+    /// Shrinks the table to fit `max(self.len(), min_size)` elements, calling
+    /// `relocate(old_index, new_index)` for every element moved to another
+    /// bucket.
+    ///
+    /// See [`reserve_with_relocation`] for how to apply the moves.
+    ///
+    /// [`reserve_with_relocation`]: RawTable::reserve_with_relocation
+    pub fn shrink_to_with_relocation(
+        &mut self,
+        min_size: usize,
+        hasher: impl Fn(&T) -> u64,
+        mut relocate: impl FnMut(usize, usize),
+    ) {
+        self.shrink_to_inner(min_size, hasher, Some(&mut relocate));
+    }
+
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn shrink_to_inner(
+        &mut self,
+        min_size: usize,
+        hasher: impl Fn(&T) -> u64,
+        relocate: Option<&mut dyn FnMut(usize, usize)>,
+    ) {
         // Calculate the minimal number of elements that we need to reserve
         // space for.
         let min_size = usize::max(self.table.items, min_size);
         if min_size == 0 {
-            *self = Self::new_in(self.table.alloc.clone());
+            self.replace_table(Self::new_in(self.table.alloc.clone()));
             return;
         }
 
@@ -646,11 +1081,11 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         if min_buckets < self.buckets() {
             // Fast path if the table is empty
             if self.table.items == 0 {
-                *self = Self::with_capacity_in(min_size, self.table.alloc.clone());
+                self.replace_table(Self::with_capacity_in(min_size, self.table.alloc.clone()));
             } else {
                 // Avoid `Result::unwrap_or_else` because it bloats LLVM IR.
                 if self
-                    .resize(min_size, hasher, Fallibility::Infallible)
+                    .resize(min_size, hasher, Fallibility::Infallible, relocate)
                     .is_err()
                 {
                     unsafe { hint::unreachable_unchecked() }
@@ -666,7 +1101,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         if additional > self.table.growth_left {
             // Avoid `Result::unwrap_or_else` because it bloats LLVM IR.
             if self
-                .reserve_rehash(additional, hasher, Fallibility::Infallible)
+                .reserve_rehash(additional, hasher, Fallibility::Infallible, None)
                 .is_err()
             {
                 unsafe { hint::unreachable_unchecked() }
@@ -683,12 +1118,107 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         hasher: impl Fn(&T) -> u64,
     ) -> Result<(), TryReserveError> {
         if additional > self.table.growth_left {
-            self.reserve_rehash(additional, hasher, Fallibility::Fallible)
+            self.reserve_rehash(additional, hasher, Fallibility::Fallible, None)
         } else {
             Ok(())
         }
     }
 
+    /// Ensures that at least `additional` items can be inserted into the table
+    /// without reallocation, calling `relocate(old_index, new_index)` for
+    /// every element moved to another bucket on the way.
+    ///
+    /// Elements move when the table grows, and when it is rehashed in place
+    /// to reclaim the buckets of removed elements. The indices are those
+    /// returned by [`bucket_index`] before and after the move, so that
+    /// structures which remember the buckets of elements can follow them
+    /// instead of being rebuilt. The moves are reported one at a time while
+    /// elements may still be swapped, so `new_index` can be the `old_index`
+    /// of an element reported later: apply them to a copy of such a
+    /// structure rather than in place.
+    ///
+    /// `relocate` is not called if the table already has room.
+    ///
+    /// [`bucket_index`]: RawTable::bucket_index
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve_with_relocation(
+        &mut self,
+        additional: usize,
+        hasher: impl Fn(&T) -> u64,
+        mut relocate: impl FnMut(usize, usize),
+    ) {
+        if additional > self.table.growth_left {
+            // Avoid `Result::unwrap_or_else` because it bloats LLVM IR.
+            if self
+                .reserve_rehash(
+                    additional,
+                    hasher,
+                    Fallibility::Infallible,
+                    Some(&mut relocate),
+                )
+                .is_err()
+            {
+                unsafe { hint::unreachable_unchecked() }
+            }
+        }
+    }
+
+    /// Ensures that at least `additional` items can be inserted into the table
+    /// without reallocation, growing to the smallest table which holds them.
+    ///
+    /// Unlike `reserve`, this does not round up to at least double the
+    /// current capacity when it has to reallocate.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve_exact(&mut self, additional: usize, hasher: impl Fn(&T) -> u64) {
+        if additional > self.table.growth_left {
+            // Avoid `Result::unwrap_or_else` because it bloats LLVM IR.
+            if self
+                .reserve_exact_rehash(additional, hasher, Fallibility::Infallible)
+                .is_err()
+            {
+                unsafe { hint::unreachable_unchecked() }
+            }
+        }
+    }
+
+    /// Tries to ensure that at least `additional` items can be inserted into
+    /// the table without reallocation, growing to the smallest table which
+    /// holds them.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn try_reserve_exact(
+        &mut self,
+        additional: usize,
+        hasher: impl Fn(&T) -> u64,
+    ) -> Result<(), TryReserveError> {
+        if additional > self.table.growth_left {
+            self.reserve_exact_rehash(additional, hasher, Fallibility::Fallible)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Out-of-line slow path for `reserve_exact` and `try_reserve_exact`.
+    #[cold]
+    #[inline(never)]
+    fn reserve_exact_rehash(
+        &mut self,
+        additional: usize,
+        hasher: impl Fn(&T) -> u64,
+        fallibility: Fallibility,
+    ) -> Result<(), TryReserveError> {
+        let new_items = match self.table.items.checked_add(additional) {
+            Some(new_items) => new_items,
+            None => return Err(fallibility.capacity_overflow()),
+        };
+        if new_items <= bucket_mask_to_capacity(self.table.bucket_mask) / 2 {
+            // Tombstones are using up the space; `reserve_rehash` reclaims it
+            // in place without changing the number of buckets.
+            self.reserve_rehash(additional, hasher, fallibility, None)
+        } else {
+            self.resize(new_items, hasher, fallibility, None)
+        }
+    }
+
     /// Out-of-line slow path for `reserve` and `try_reserve`.
     #[cold]
     #[inline(never)]
@@ -697,6 +1227,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         additional: usize,
         hasher: impl Fn(&T) -> u64,
         fallibility: Fallibility,
+        relocate: Option<&mut dyn FnMut(usize, usize)>,
     ) -> Result<(), TryReserveError> {
         unsafe {
             self.table.reserve_rehash_inner(
@@ -709,6 +1240,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
                 } else {
                     None
                 },
+                relocate,
             )
         }
     }
@@ -720,6 +1252,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         capacity: usize,
         hasher: impl Fn(&T) -> u64,
         fallibility: Fallibility,
+        relocate: Option<&mut dyn FnMut(usize, usize)>,
     ) -> Result<(), TryReserveError> {
         unsafe {
             self.table.resize_inner(
@@ -727,6 +1260,7 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
                 &|table, index| hasher(table.bucket::<T>(index).as_ref()),
                 fallibility,
                 Self::TABLE_LAYOUT,
+                relocate,
             )
         }
     }
@@ -791,7 +1325,6 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     ///
     /// This does not check if the given element already exists in the table.
     #[cfg_attr(feature = "inline-more", inline)]
-    #[cfg(any(feature = "raw", feature = "rustc-internal-api"))]
     pub unsafe fn insert_no_grow(&mut self, hash: u64, value: T) -> Bucket<T> {
         let (index, old_ctrl) = self.table.prepare_insert_slot(hash);
         let bucket = self.table.bucket(index);
@@ -805,6 +1338,29 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         bucket
     }
 
+    /// Moves all elements of `other` into this table.
+    ///
+    /// The table is grown at most once, up front, to make room for all the
+    /// elements of `other`. Every element is hashed again with `hasher`, since
+    /// `other` doesn't keep the hashes it placed them by. Since `other` is
+    /// consumed, its allocator type may differ from this table's.
+    ///
+    /// This does not check if any of the elements already exist in the table.
+    pub fn absorb<A2: Allocator + Clone>(
+        &mut self,
+        other: RawTable<T, A2>,
+        hasher: impl Fn(&T) -> u64,
+    ) {
+        self.reserve(other.len(), &hasher);
+        for item in other {
+            let hash = hasher(&item);
+            // SAFETY: We reserved room for all elements of `other` above.
+            unsafe {
+                self.insert_no_grow(hash, item);
+            }
+        }
+    }
+
     /// Temporary removes a bucket, applying the given function to the removed
     /// element and optionally put back the returned value in the same bucket.
     ///
@@ -846,6 +1402,110 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         }
     }
 
+    /// Hints to the CPU that the first group of control bytes probed for
+    /// `hash` is about to be read, so that it can be loaded into the cache
+    /// while other work is done.
+    ///
+    /// This only has an effect on x86 targets with SSE; elsewhere it does
+    /// nothing.
+    #[inline]
+    pub(crate) fn prefetch(&self, hash: u64) {
+        #[cfg(all(
+            target_feature = "sse",
+            any(target_arch = "x86", target_arch = "x86_64"),
+            not(miri)
+        ))]
+        {
+            #[cfg(target_arch = "x86")]
+            use core::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+            #[cfg(target_arch = "x86_64")]
+            use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+            let pos = h1(hash) & self.table.bucket_mask;
+            // SAFETY: `pos` is a valid bucket index, and prefetching never
+            // faults anyway.
+            unsafe {
+                _mm_prefetch(self.table.ctrl(pos).cast::<i8>(), _MM_HINT_T0);
+            }
+        }
+        #[cfg(not(all(
+            target_feature = "sse",
+            any(target_arch = "x86", target_arch = "x86_64"),
+            not(miri)
+        )))]
+        let _ = hash;
+    }
+
+    /// Searches for an element in the table, and for a slot to insert it into
+    /// if it is missing, probing the table only once.
+    ///
+    /// Returns `Ok` with the bucket of the element, or `Err` with a slot to
+    /// pass to [`insert_in_slot`]. The table is grown beforehand if it is full,
+    /// even if the element turns out to be present, so that inserting into the
+    /// slot never needs to grow it.
+    ///
+    /// [`insert_in_slot`]: RawTable::insert_in_slot
+    #[inline]
+    pub(crate) fn find_or_find_insert_slot(
+        &mut self,
+        hash: u64,
+        eq: impl FnMut(&T) -> bool,
+        hasher: impl Fn(&T) -> u64,
+    ) -> Result<Bucket<T>, usize> {
+        self.reserve(1, hasher);
+        unsafe { self.find_or_find_insert_slot_no_grow(hash, eq) }
+    }
+
+    /// Searches for an element in the table, and for a slot to insert it into
+    /// if it is missing, like [`find_or_find_insert_slot`] but without
+    /// growing the table.
+    ///
+    /// # Safety
+    ///
+    /// There must be room in the table for at least one more element.
+    ///
+    /// [`find_or_find_insert_slot`]: RawTable::find_or_find_insert_slot
+    #[inline]
+    pub(crate) unsafe fn find_or_find_insert_slot_no_grow(
+        &self,
+        hash: u64,
+        mut eq: impl FnMut(&T) -> bool,
+    ) -> Result<Bucket<T>, usize> {
+        debug_assert!(self.table.growth_left > 0);
+        match self
+            .table
+            .find_or_find_insert_slot_inner(hash, &mut |index| eq(self.bucket(index).as_ref()))
+        {
+            Ok(index) => Ok(self.bucket(index)),
+            Err(slot) => Err(slot),
+        }
+    }
+
+    /// Returns the number of elements which can be inserted into empty
+    /// buckets before the table must grow.
+    #[inline]
+    pub(crate) fn growth_left(&self) -> usize {
+        self.table.growth_left
+    }
+
+    /// Inserts an element into a slot returned by
+    /// [`find_or_find_insert_slot`], and returns its bucket.
+    ///
+    /// # Safety
+    ///
+    /// `slot` must have been returned by `find_or_find_insert_slot` for the
+    /// same `hash`, and the table must not have been modified since.
+    ///
+    /// [`find_or_find_insert_slot`]: RawTable::find_or_find_insert_slot
+    #[inline]
+    pub(crate) unsafe fn insert_in_slot(&mut self, hash: u64, slot: usize, value: T) -> Bucket<T> {
+        let old_ctrl = *self.table.ctrl(slot);
+        self.table.record_item_insert_at(slot, old_ctrl, hash);
+        let bucket = self.bucket(slot);
+        bucket.write(value);
+        bucket
+    }
+
     /// Gets a reference to an element in the table.
     #[inline]
     pub fn get(&self, hash: u64, eq: impl FnMut(&T) -> bool) -> Option<&T> {
@@ -944,12 +1604,225 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         self.len() == 0
     }
 
+    /// Returns the control bytes of the table, including the copy of the
+    /// first group which follows the last bucket.
+    ///
+    /// Returns an empty slice for the unallocated empty table, since its
+    /// control bytes are a shared static group.
+    #[cfg(feature = "persist")]
+    #[inline]
+    pub(crate) fn ctrl_bytes(&self) -> &[u8] {
+        if self.table.is_empty_singleton() {
+            return &[];
+        }
+        unsafe {
+            core::slice::from_raw_parts(self.table.ctrl.as_ptr(), self.table.num_ctrl_bytes())
+        }
+    }
+
+    /// Returns the number of groups which a search for an element with the
+    /// given hash probes before it reaches the bucket at `index`, including
+    /// the group containing it.
+    #[inline]
+    pub(crate) fn probe_length(&self, hash: u64, index: usize) -> usize {
+        let bucket_mask = self.table.bucket_mask;
+        let mut probe_seq = self.table.probe_seq(hash);
+        let mut groups = 1;
+        while index.wrapping_sub(probe_seq.pos) & bucket_mask >= Group::WIDTH {
+            probe_seq.move_next(bucket_mask);
+            groups += 1;
+        }
+        groups
+    }
+
     /// Returns the number of buckets in the table.
     #[inline]
     pub fn buckets(&self) -> usize {
         self.table.bucket_mask + 1
     }
 
+    /// Returns the number of buckets which the table has allocated, which is
+    /// zero for a table that has not allocated yet.
+    #[inline]
+    pub(crate) fn allocated_buckets(&self) -> usize {
+        if self.table.is_empty_singleton() {
+            0
+        } else {
+            self.buckets()
+        }
+    }
+
+    /// Returns statistics about the layout of the table, such as the number
+    /// of tombstones and the probe lengths of the elements.
+    ///
+    /// This scans the control bytes of all buckets and hashes every element
+    /// with `hasher`, so it takes time linear in the number of buckets.
+    pub fn stats(&self, hasher: impl Fn(&T) -> u64) -> TableStats {
+        let buckets = self.buckets();
+        let mut stats = TableStats {
+            buckets,
+            full: 0,
+            deleted: 0,
+            empty: 0,
+            max_probe_length: 0,
+            mean_probe_length: 0.0,
+            load_factor: 0.0,
+        };
+        let mut total_probe_length = 0;
+        // Tables with fewer buckets than a group only have one group, whose
+        // bytes after the last bucket are padding.
+        for start in (0..buckets).step_by(Group::WIDTH) {
+            unsafe {
+                let group = Group::load_aligned(self.table.ctrl(start));
+                for bit in group.match_full() {
+                    let index = start + bit;
+                    if index < buckets {
+                        let hash = hasher(self.bucket(index).as_ref());
+                        let probe_length = self.probe_length(hash, index);
+                        stats.full += 1;
+                        stats.max_probe_length = stats.max_probe_length.max(probe_length);
+                        total_probe_length += probe_length;
+                    }
+                }
+                let in_table = BitMask::from_u16(((1_u32 << (buckets - start).min(16)) - 1) as u16);
+                stats.deleted += group.match_byte(DELETED).and(in_table).count_ones();
+            }
+        }
+        stats.empty = buckets - stats.full - stats.deleted;
+        if stats.full > 0 {
+            stats.mean_probe_length = total_probe_length as f64 / stats.full as f64;
+        }
+        stats.load_factor = stats.full as f64 / buckets as f64;
+        stats
+    }
+
+    /// Returns the counters of the work the table has done since it was
+    /// created or [`reset_metrics`] was last called.
+    ///
+    /// A clone of the table starts with all counters at zero.
+    ///
+    /// [`reset_metrics`]: RawTable::reset_metrics
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> TableMetrics {
+        let counters = &self.table.counters;
+        TableMetrics {
+            resizes: counters.resizes,
+            rehashes: counters.rehashes,
+            tombstones: counters.tombstones,
+            probe_steps: counters.probe_steps.load(Ordering::Relaxed),
+            false_matches: counters.false_matches.load(Ordering::Relaxed),
+            #[cfg(feature = "instrument")]
+            group_loads: counters.group_loads.load(Ordering::Relaxed),
+            #[cfg(feature = "instrument")]
+            tag_matches: counters.tag_matches.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Sets all counters returned by [`metrics`] back to zero.
+    ///
+    /// [`metrics`]: RawTable::metrics
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&mut self) {
+        self.table.counters = Counters::default();
+    }
+
+    /// Checks the invariants of the table, panicking with a description of
+    /// the first one which is broken.
+    ///
+    /// `hasher` must return the hash which the element was inserted with.
+    /// The checks are that:
+    /// - every control byte is either full, empty or deleted,
+    /// - the tag of every full bucket is the tag of its element's hash,
+    /// - a lookup for every element reaches it, without stopping early at a
+    ///   group with an empty bucket,
+    /// - the trailing control bytes mirror the first group,
+    /// - the number of items and the growth left match a recount.
+    ///
+    /// This is meant for debugging, for example after changing how the table
+    /// is implemented. It scans the whole table and hashes every element, so
+    /// it should not be called on hot paths.
+    pub fn verify(&self, hasher: impl Fn(&T) -> u64) {
+        if self.table.is_empty_singleton() {
+            assert_eq!(self.table.items, 0, "the empty singleton has items");
+            assert_eq!(
+                self.table.growth_left, 0,
+                "the empty singleton has growth left"
+            );
+            return;
+        }
+
+        let buckets = self.buckets();
+        let ctrl = unsafe {
+            core::slice::from_raw_parts(self.table.ctrl.as_ptr(), self.table.num_ctrl_bytes())
+        };
+        let (table, tail) = ctrl.split_at(buckets);
+
+        let mut full = 0;
+        let mut deleted = 0;
+        for (index, &byte) in table.iter().enumerate() {
+            if byte == DELETED {
+                deleted += 1;
+                continue;
+            }
+            if byte == EMPTY {
+                continue;
+            }
+            assert!(
+                is_full(byte),
+                "invalid control byte {:#04x} at {}",
+                byte,
+                index
+            );
+            full += 1;
+
+            let hash = hasher(unsafe { self.bucket(index).as_ref() });
+            assert_eq!(byte, h2(hash), "wrong tag in the control byte at {}", index);
+            let mut probe_seq = self.table.probe_seq(hash);
+            while index.wrapping_sub(probe_seq.pos) & self.table.bucket_mask >= Group::WIDTH {
+                let group = unsafe { Group::load(self.table.ctrl(probe_seq.pos)) };
+                assert!(
+                    !group.match_empty().any_bit_set(),
+                    "lookups stop before reaching the element at {}",
+                    index
+                );
+                probe_seq.move_next(self.table.bucket_mask);
+            }
+        }
+
+        // Tables smaller than a group pad the first group with empty control
+        // bytes, and only mirror their buckets after that.
+        let mirrored = if buckets < Group::WIDTH {
+            let (padding, mirrored) = tail.split_at(Group::WIDTH - buckets);
+            assert!(
+                padding.iter().all(|&byte| byte == EMPTY),
+                "the control bytes after the last bucket are not empty"
+            );
+            mirrored
+        } else {
+            tail
+        };
+        assert_eq!(
+            mirrored,
+            &table[..mirrored.len()],
+            "the trailing control bytes do not mirror the first group"
+        );
+
+        assert_eq!(self.table.items, full, "the item count does not match");
+        assert_eq!(
+            self.table.growth_left,
+            bucket_mask_to_capacity(self.table.bucket_mask) - full - deleted,
+            "the growth left does not match"
+        );
+    }
+
+    /// Returns the sequence of groups which lookups and insertions of `hash`
+    /// visit in this table, for as long as it is not resized.
+    #[inline]
+    #[cfg(feature = "raw")]
+    pub fn probe_seq(&self, hash: u64) -> ProbeSeq {
+        self.table.probe_seq(hash)
+    }
+
     /// Checks whether the bucket at `index` is full.
     ///
     /// # Safety
@@ -960,17 +1833,140 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         self.table.is_bucket_full(index)
     }
 
+    /// Checks whether the bucket at `index` is empty, which ends lookups that
+    /// probe it.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure `index` is less than the number of buckets.
+    #[inline]
+    #[cfg(feature = "raw")]
+    pub unsafe fn is_bucket_empty(&self, index: usize) -> bool {
+        self.ctrl_byte(index) == EMPTY
+    }
+
+    /// Checks whether the bucket at `index` is deleted, which lookups probe
+    /// past but insertions may reuse.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure `index` is less than the number of buckets.
+    #[inline]
+    #[cfg(feature = "raw")]
+    pub unsafe fn is_bucket_deleted(&self, index: usize) -> bool {
+        self.ctrl_byte(index) == DELETED
+    }
+
+    /// Returns the control byte of the bucket at `index`: `EMPTY`, `DELETED`,
+    /// or for a full bucket the `h2` tag of its element's hash, which is
+    /// below `0x80`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure `index` is less than the number of buckets.
+    #[inline]
+    pub unsafe fn ctrl_byte(&self, index: usize) -> u8 {
+        debug_assert!(index < self.buckets());
+        *self.table.ctrl(index)
+    }
+
     /// Returns an iterator over every element in the table. It is up to
     /// the caller to ensure that the `RawTable` outlives the `RawIter`.
     /// Because we cannot make the `next` method unsafe on the `RawIter`
     /// struct, we have to make the `iter` method unsafe.
+    ///
+    /// With the `random-order` feature, every iterator starts at a random
+    /// bucket and wraps around at the end of the table.
     #[inline]
     pub unsafe fn iter(&self) -> RawIter<T> {
-        let data = Bucket::from_base_index(self.data_end(), 0);
-        RawIter {
-            iter: RawIterRange::new(self.table.ctrl.as_ptr(), data, self.table.buckets()),
-            items: self.table.items,
+        #[cfg_attr(not(feature = "random-order"), allow(unused_mut))]
+        let mut iter = self.iter_in_order();
+        #[cfg(feature = "random-order")]
+        iter.start_at(random_start(
+            self.table.ctrl.as_ptr(),
+            self.table.bucket_mask,
+        ));
+        iter
+    }
+
+    /// Returns an iterator over every element in the table, in the order of
+    /// the buckets, even with the `random-order` feature.
+    #[inline]
+    pub(crate) unsafe fn iter_in_order(&self) -> RawIter<T> {
+        let mut iter = RawIter::new(
+            self.table.ctrl.as_ptr(),
+            self.data_end(),
+            self.table.buckets(),
+            self.table.items,
+        );
+        iter.iter.data = iter.iter.data.stamped(&self.table);
+        #[cfg(feature = "random-order")]
+        {
+            iter.wrap.data = iter.wrap.data.clone().stamped(&self.table);
         }
+        iter
+    }
+
+    /// Returns an iterator over the full buckets from the bucket at `index`
+    /// to the end of the table, in the order of the buckets.
+    ///
+    /// Passing one more than the index of the last bucket yielded by an
+    /// earlier scan resumes that scan, so that a huge table can be visited in
+    /// slices without holding an iterator in between. The order does not
+    /// change with the `random-order` feature. If the table was resized in
+    /// between, the indices refer to other elements and the scan may skip or
+    /// repeat some.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be at most the number of buckets, and the caller must
+    /// uphold the same rules as for [`RawTable::iter`].
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub unsafe fn iter_from(&self, index: usize) -> RawIterRange<T> {
+        debug_assert!(index <= self.buckets());
+        let buckets = self.table.buckets();
+        let aligned = index.min(buckets - 1) & !(Group::WIDTH - 1);
+        let data = Bucket::from_base_index(self.data_end(), 0).stamped(&self.table);
+        let mut iter = RawIterRange::new(
+            self.table.ctrl(aligned),
+            data.next_n(aligned),
+            buckets - aligned,
+        );
+        iter.current_group = if index - aligned < Group::WIDTH {
+            iter.current_group.split_at(index - aligned).1
+        } else {
+            BitMask::from_u16(0)
+        };
+        iter
+    }
+
+    /// Returns an iterator over the full buckets of the `chunk`th of `chunks`
+    /// disjoint ranges of buckets which together cover the table.
+    ///
+    /// The ranges are made of whole groups and are as even as possible, so
+    /// each can be driven by another thread to scan the table in parallel.
+    /// When there are fewer groups than ranges, the last ranges are empty.
+    ///
+    /// # Safety
+    ///
+    /// `chunk` must be less than `chunks`, and the caller must uphold the same
+    /// rules as for [`RawTable::iter`].
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub unsafe fn iter_chunk(&self, chunk: usize, chunks: usize) -> RawIterRange<T> {
+        debug_assert!(chunk < chunks);
+        let buckets = self.table.buckets();
+        let groups = (buckets + Group::WIDTH - 1) / Group::WIDTH;
+        // The first `groups % chunks` ranges get one group more than the others.
+        let (base, longer) = (groups / chunks, groups % chunks);
+        let start_group = chunk * base + usize::min(chunk, longer);
+        let end_group = start_group + base + usize::from(chunk < longer);
+        if start_group == end_group {
+            return self.iter_from(buckets);
+        }
+        let start = start_group * Group::WIDTH;
+        let end = usize::min(end_group * Group::WIDTH, buckets);
+        let data = Bucket::from_base_index(self.data_end(), 0).stamped(&self.table);
+        RawIterRange::new(self.table.ctrl(start), data.next_n(start), end - start)
     }
 
     /// Returns an iterator over occupied buckets that could match a given hash.
@@ -983,7 +1979,6 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
     /// `RawIterHash`. Because we cannot make the `next` method unsafe on the
     /// `RawIterHash` struct, we have to make the `iter_hash` method unsafe.
     #[cfg_attr(feature = "inline-more", inline)]
-    #[cfg(feature = "raw")]
     pub unsafe fn iter_hash(&self, hash: u64) -> RawIterHash<'_, T, A> {
         RawIterHash::new(self, hash)
     }
@@ -1056,6 +2051,104 @@ impl<T, A: Allocator + Clone> RawTable<T, A> {
         mem::forget(self);
         alloc
     }
+
+    /// Converts every element of the table with `f`, writing the results into
+    /// the buckets the elements were taken from, and returns the table of the
+    /// results. The allocation and control bytes are reused as they are, so
+    /// the results must hash the same way as the elements they replace.
+    ///
+    /// If `f` panics, the elements which have not been converted yet and the
+    /// results so far are dropped, and the allocation is freed.
+    ///
+    /// # Safety
+    ///
+    /// `U` must have the same size and alignment as `T`.
+    pub(crate) unsafe fn map_in_place<U>(self, mut f: impl FnMut(T) -> U) -> RawTable<U, A> {
+        debug_assert!(Layout::new::<T>() == Layout::new::<U>());
+        let this = ManuallyDrop::new(self);
+        let mut guard = guard((0, ptr::read(&this.table)), |(index, table)| {
+            for i in 0..table.buckets() {
+                if table.is_bucket_full(i) {
+                    match i.cmp(index) {
+                        core::cmp::Ordering::Less => table.bucket::<U>(i).drop(),
+                        core::cmp::Ordering::Greater => table.bucket::<T>(i).drop(),
+                        // The element was taken out when `f` panicked.
+                        core::cmp::Ordering::Equal => {}
+                    }
+                }
+            }
+            if !table.is_empty_singleton() {
+                table.free_buckets(Self::TABLE_LAYOUT);
+            }
+        });
+
+        let (index, table) = &mut *guard;
+        for i in 0..table.buckets() {
+            if table.is_bucket_full(i) {
+                *index = i;
+                let value = f(table.bucket::<T>(i).read());
+                table.bucket::<U>(i).write(value);
+            }
+        }
+
+        let (_, table) = ScopeGuard::into_inner(guard);
+        RawTable {
+            table,
+            marker: PhantomData,
+        }
+    }
+
+    /// Converts every element of the table with `f` and returns the table of
+    /// the results, in which each result is in the bucket its element was
+    /// taken from. The results must hash the same way as the elements they
+    /// replace.
+    ///
+    /// No element is hashed: if `U` has the same size and alignment as `T`,
+    /// the table is converted in place, and otherwise a table with the same
+    /// number of buckets is allocated and the control bytes are copied to it.
+    pub(crate) fn map_into<U>(self, mut f: impl FnMut(T) -> U) -> RawTable<U, A> {
+        if Layout::new::<T>() == Layout::new::<U>() {
+            // SAFETY: The layouts were just checked.
+            return unsafe { self.map_in_place(f) };
+        }
+        if self.table.is_empty_singleton() {
+            return RawTable::new_in(self.table.alloc.clone());
+        }
+
+        unsafe {
+            let buckets = self.table.buckets();
+            let mut new = match RawTable::new_uninitialized(
+                self.table.alloc.clone(),
+                buckets,
+                Fallibility::Infallible,
+            ) {
+                Ok(table) => table,
+                Err(_) => hint::unreachable_unchecked(),
+            };
+            new.table
+                .ctrl(0)
+                .write_bytes(EMPTY, new.table.num_ctrl_bytes());
+
+            // The control bytes are only copied once every element has been
+            // converted, so that if `f` panics, `new` only drops the results
+            // and `items` drops the elements which are left.
+            let growth_left = self.table.growth_left;
+            let data_end = self.data_end();
+            let ctrl = self.table.ctrl(0);
+            let iter = self.iter_in_order();
+            let mut items = self.into_iter_from(iter);
+            for bucket in items.iter.by_ref() {
+                let index = bucket.to_base_index(data_end);
+                let value = f(bucket.read());
+                new.bucket(index).write(value);
+                new.table.set_ctrl(index, *ctrl.add(index));
+                new.table.items += 1;
+            }
+            ctrl.copy_to_nonoverlapping(new.table.ctrl(0), new.table.num_ctrl_bytes());
+            new.table.growth_left = growth_left;
+            new
+        }
+    }
 }
 
 unsafe impl<T, A: Allocator + Clone> Send for RawTable<T, A>
@@ -1080,6 +2173,10 @@ impl<A> RawTableInner<A> {
             bucket_mask: 0,
             items: 0,
             growth_left: 0,
+            #[cfg(debug_assertions)]
+            generation: 0,
+            #[cfg(feature = "metrics")]
+            counters: Counters::new(),
             alloc,
         }
     }
@@ -1112,6 +2209,10 @@ impl<A: Allocator + Clone> RawTableInner<A> {
             bucket_mask: buckets - 1,
             items: 0,
             growth_left: bucket_mask_to_capacity(buckets - 1),
+            #[cfg(debug_assertions)]
+            generation: 0,
+            #[cfg(feature = "metrics")]
+            counters: Counters::new(),
             alloc,
         })
     }
@@ -1158,31 +2259,84 @@ impl<A: Allocator + Clone> RawTableInner<A> {
     fn find_insert_slot(&self, hash: u64) -> usize {
         let mut probe_seq = self.probe_seq(hash);
         loop {
+            self.count_probe_step();
             unsafe {
-                let group = Group::load(self.ctrl(probe_seq.pos));
+                let group = self.load_group(probe_seq.pos);
                 if let Some(bit) = group.match_empty_or_deleted().lowest_set_bit() {
                     let result = (probe_seq.pos + bit) & self.bucket_mask;
+                    return self.fix_insert_slot(result);
+                }
+            }
+            probe_seq.move_next(self.bucket_mask);
+        }
+    }
 
-                    // In tables smaller than the group width, trailing control
-                    // bytes outside the range of the table are filled with
-                    // EMPTY entries. These will unfortunately trigger a
-                    // match, but once masked may point to a full bucket that
-                    // is already occupied. We detect this situation here and
-                    // perform a second scan starting at the beginning of the
-                    // table. This second scan is guaranteed to find an empty
-                    // slot (due to the load factor) before hitting the trailing
-                    // control bytes (containing EMPTY).
-                    if unlikely(self.is_bucket_full(result)) {
-                        debug_assert!(self.bucket_mask < Group::WIDTH);
-                        debug_assert_ne!(probe_seq.pos, 0);
-                        return Group::load_aligned(self.ctrl(0))
-                            .match_empty_or_deleted()
-                            .lowest_set_bit_nonzero();
-                    }
+    /// Corrects an insertion slot found in the first group of the probe
+    /// sequence of a small table.
+    ///
+    /// In tables smaller than the group width, trailing control bytes outside
+    /// the range of the table are filled with EMPTY entries. These will
+    /// unfortunately trigger a match, but once masked may point to a full
+    /// bucket that is already occupied. We detect this situation here and
+    /// perform a second scan starting at the beginning of the table. This
+    /// second scan is guaranteed to find an empty slot (due to the load factor)
+    /// before hitting the trailing control bytes (containing EMPTY).
+    #[inline]
+    unsafe fn fix_insert_slot(&self, index: usize) -> usize {
+        if unlikely(self.is_bucket_full(index)) {
+            debug_assert!(self.bucket_mask < Group::WIDTH);
+            return self
+                .load_group(0)
+                .match_empty_or_deleted()
+                .lowest_set_bit_nonzero();
+        }
+        index
+    }
+
+    /// Searches for an element in the table, and for a bucket suitable for
+    /// inserting it if it is missing, in a single pass over the probe sequence.
+    ///
+    /// Returns `Ok` with the index of the element, or `Err` with the index of
+    /// the first empty or deleted bucket of the probe sequence. There must be
+    /// at least 1 empty bucket in the table.
+    #[inline(always)]
+    fn find_or_find_insert_slot_inner(
+        &self,
+        hash: u64,
+        eq: &mut dyn FnMut(usize) -> bool,
+    ) -> Result<usize, usize> {
+        let h2_hash = h2(hash);
+        let mut probe_seq = self.probe_seq(hash);
+        let mut insert_slot = None;
 
-                    return result;
+        loop {
+            self.count_probe_step();
+            let group = unsafe { self.load_group(probe_seq.pos) };
+
+            for bit in group.match_byte(h2_hash) {
+                self.count_tag_match();
+                let index = (probe_seq.pos + bit) & self.bucket_mask;
+
+                if likely(eq(index)) {
+                    return Ok(index);
                 }
+                self.count_false_match();
             }
+
+            if likely(insert_slot.is_none()) {
+                insert_slot = group
+                    .match_empty_or_deleted()
+                    .lowest_set_bit()
+                    .map(|bit| (probe_seq.pos + bit) & self.bucket_mask);
+            }
+
+            if likely(group.match_empty().any_bit_set()) {
+                // The group has an empty bucket, so `insert_slot` was set at
+                // the latest while scanning it.
+                let index = insert_slot.unwrap_or_else(|| unsafe { hint::unreachable_unchecked() });
+                return Err(unsafe { self.fix_insert_slot(index) });
+            }
+
             probe_seq.move_next(self.bucket_mask);
         }
     }
@@ -1195,14 +2349,17 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         let mut probe_seq = self.probe_seq(hash);
 
         loop {
-            let group = unsafe { Group::load(self.ctrl(probe_seq.pos)) };
+            self.count_probe_step();
+            let group = unsafe { self.load_group(probe_seq.pos) };
 
             for bit in group.match_byte(h2_hash) {
+                self.count_tag_match();
                 let index = (probe_seq.pos + bit) & self.bucket_mask;
 
                 if likely(eq(index)) {
                     return Some(index);
                 }
+                self.count_false_match();
             }
 
             if likely(group.match_empty().any_bit_set()) {
@@ -1220,6 +2377,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         // control bytes to EMPTY. This effectively frees up all buckets
         // containing a DELETED entry.
         for i in (0..self.buckets()).step_by(Group::WIDTH) {
+            self.count_group_load();
             let group = Group::load_aligned(self.ctrl(i));
             let group = group.convert_special_to_empty_and_full_to_deleted();
             group.store_aligned(self.ctrl(i));
@@ -1240,7 +2398,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
     unsafe fn bucket<T>(&self, index: usize) -> Bucket<T> {
         debug_assert_ne!(self.bucket_mask, 0);
         debug_assert!(index < self.buckets());
-        Bucket::from_base_index(self.data_end(), index)
+        Bucket::from_base_index(self.data_end(), index).stamped(self)
     }
 
     #[inline]
@@ -1263,12 +2421,64 @@ impl<A: Allocator + Clone> RawTableInner<A> {
     /// reaching a group containing an empty bucket.
     #[inline]
     fn probe_seq(&self, hash: u64) -> ProbeSeq {
-        ProbeSeq {
-            pos: h1(hash) & self.bucket_mask,
-            stride: 0,
+        ProbeSeq::new(hash, self.bucket_mask)
+    }
+
+    /// Counts a group read by a probe sequence in the table's metrics.
+    #[inline(always)]
+    fn count_probe_step(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            let steps = &self.counters.probe_steps;
+            steps.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a tag match on a bucket holding another element in the table's
+    /// metrics.
+    #[inline(always)]
+    fn count_false_match(&self) {
+        #[cfg(feature = "metrics")]
+        {
+            let matches = &self.counters.false_matches;
+            matches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a tag match in the table's metrics, with the `instrument`
+    /// feature.
+    #[inline(always)]
+    fn count_tag_match(&self) {
+        #[cfg(feature = "instrument")]
+        {
+            let matches = &self.counters.tag_matches;
+            matches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a load of a group of control bytes in the table's metrics,
+    /// with the `instrument` feature.
+    #[inline(always)]
+    fn count_group_load(&self) {
+        #[cfg(feature = "instrument")]
+        {
+            let loads = &self.counters.group_loads;
+            loads.fetch_add(1, Ordering::Relaxed);
         }
     }
 
+    /// Loads the group of control bytes starting at `index`, and counts the
+    /// load.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be at most the number of buckets.
+    #[inline(always)]
+    unsafe fn load_group(&self, index: usize) -> Group {
+        self.count_group_load();
+        Group::load(self.ctrl(index))
+    }
+
     /// Returns the index of a bucket for which a value must be inserted if there is enough rooom
     /// in the table, otherwise returns error
     #[cfg(feature = "raw")]
@@ -1420,6 +2630,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         fallibility: Fallibility,
         layout: TableLayout,
         drop: Option<fn(*mut u8)>,
+        relocate: Option<&mut dyn FnMut(usize, usize)>,
     ) -> Result<(), TryReserveError> {
         // Avoid `Option::ok_or_else` because it bloats LLVM IR.
         let new_items = match self.items.checked_add(additional) {
@@ -1430,7 +2641,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         if new_items <= full_capacity / 2 {
             // Rehash in-place without re-allocating if we have plenty of spare
             // capacity that is locked up due to DELETED entries.
-            self.rehash_in_place(hasher, layout.size, drop);
+            self.rehash_in_place(hasher, layout.size, drop, relocate);
             Ok(())
         } else {
             // Otherwise, conservatively resize to at least the next size up
@@ -1440,6 +2651,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
                 hasher,
                 fallibility,
                 layout,
+                relocate,
             )
         }
     }
@@ -1457,8 +2669,10 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         hasher: &dyn Fn(&mut Self, usize) -> u64,
         fallibility: Fallibility,
         layout: TableLayout,
+        mut relocate: Option<&mut dyn FnMut(usize, usize)>,
     ) -> Result<(), TryReserveError> {
         let mut new_table = self.prepare_resize(layout, capacity, fallibility)?;
+        let abort = AbortOnUnwind::arm();
 
         // Copy all elements to the new table.
         for i in 0..self.buckets() {
@@ -1480,13 +2694,28 @@ impl<A: Allocator + Clone> RawTableInner<A> {
                 new_table.bucket_ptr(index, layout.size),
                 layout.size,
             );
+            if let Some(relocate) = relocate.as_mut() {
+                relocate(i, index);
+            }
         }
 
+        abort.disarm();
+
         // We successfully copied all elements without panicking. Now replace
         // self with the new table. The old table will have its memory freed but
         // the items will not be dropped (since they have been moved into the
         // new table).
         mem::swap(self, &mut new_table);
+        #[cfg(debug_assertions)]
+        {
+            self.generation = new_table.generation;
+        }
+        #[cfg(feature = "metrics")]
+        {
+            mem::swap(&mut self.counters, &mut new_table.counters);
+            self.counters.resizes = self.counters.resizes.wrapping_add(1);
+        }
+        self.bump_generation();
 
         Ok(())
     }
@@ -1506,6 +2735,7 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         hasher: &dyn Fn(&mut Self, usize) -> u64,
         size_of: usize,
         drop: Option<fn(*mut u8)>,
+        mut relocate: Option<&mut dyn FnMut(usize, usize)>,
     ) {
         // If the hash function panics then properly clean up any elements
         // that we haven't rehashed yet. We unfortunately can't preserve the
@@ -1513,18 +2743,21 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         // without risking another panic.
         self.prepare_rehash_in_place();
 
+        // The elements which don't need dropping must be removed as well, or
+        // `items` would count elements which can no longer be found.
         let mut guard = guard(self, move |self_| {
-            if let Some(drop) = drop {
-                for i in 0..self_.buckets() {
-                    if *self_.ctrl(i) == DELETED {
-                        self_.set_ctrl(i, EMPTY);
+            for i in 0..self_.buckets() {
+                if *self_.ctrl(i) == DELETED {
+                    self_.set_ctrl(i, EMPTY);
+                    self_.items -= 1;
+                    if let Some(drop) = drop {
                         drop(self_.bucket_ptr(i, size_of));
-                        self_.items -= 1;
                     }
                 }
             }
             self_.growth_left = bucket_mask_to_capacity(self_.bucket_mask) - self_.items;
         });
+        let abort = AbortOnUnwind::arm();
 
         // At this point, DELETED elements are elements that we haven't
         // rehashed yet. Find them and re-insert them at their ideal
@@ -1535,6 +2768,9 @@ impl<A: Allocator + Clone> RawTableInner<A> {
             }
 
             let i_p = guard.bucket_ptr(i, size_of);
+            // The bucket the element in bucket `i` was in before the rehash,
+            // which changes when another element is swapped into `i`.
+            let mut origin = i;
 
             'inner: loop {
                 // Hash the current item
@@ -1550,6 +2786,11 @@ impl<A: Allocator + Clone> RawTableInner<A> {
                 // it and we can just continue to the next item.
                 if likely(guard.is_in_same_group(i, new_i, hash)) {
                     guard.set_ctrl_h2(i, hash);
+                    if origin != i {
+                        if let Some(relocate) = relocate.as_mut() {
+                            relocate(origin, i);
+                        }
+                    }
                     continue 'outer;
                 }
 
@@ -1558,6 +2799,9 @@ impl<A: Allocator + Clone> RawTableInner<A> {
                 // We are moving the current item to a new position. Write
                 // our H2 to the control byte of the new position.
                 let prev_ctrl = guard.replace_ctrl_h2(new_i, hash);
+                if let Some(relocate) = relocate.as_mut() {
+                    relocate(origin, new_i);
+                }
                 if prev_ctrl == EMPTY {
                     guard.set_ctrl(i, EMPTY);
                     // If the target slot is empty, simply move the current
@@ -1571,12 +2815,19 @@ impl<A: Allocator + Clone> RawTableInner<A> {
                     // swapped into the old slot.
                     debug_assert_eq!(prev_ctrl, DELETED);
                     ptr::swap_nonoverlapping(i_p, new_i_p, size_of);
+                    origin = new_i;
                     continue 'inner;
                 }
             }
         }
 
+        abort.disarm();
         guard.growth_left = bucket_mask_to_capacity(guard.bucket_mask) - guard.items;
+        guard.bump_generation();
+        #[cfg(feature = "metrics")]
+        {
+            guard.counters.rehashes = guard.counters.rehashes.wrapping_add(1);
+        }
 
         mem::forget(guard);
     }
@@ -1605,7 +2856,6 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         )
     }
 
-    #[cfg(feature = "raw")]
     fn allocation_info_or_zero(&self, table_layout: TableLayout) -> (NonNull<u8>, Layout) {
         if self.is_empty_singleton() {
             (NonNull::dangling(), Layout::new::<()>())
@@ -1624,14 +2874,36 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         }
         self.items = 0;
         self.growth_left = bucket_mask_to_capacity(self.bucket_mask);
+        self.bump_generation();
+    }
+
+    /// Invalidates all buckets and iterators handed out so far.
+    #[inline]
+    fn bump_generation(&mut self) {
+        #[cfg(debug_assertions)]
+        {
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+
+    /// Panics if `bucket` was handed out before the table was last resized,
+    /// rehashed or cleared, when debug assertions are enabled.
+    #[inline]
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    fn check_generation<T>(&self, bucket: &Bucket<T>) {
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            bucket.generation, self.generation,
+            "bucket used after the table was resized, rehashed or cleared"
+        );
     }
 
     #[inline]
     unsafe fn erase(&mut self, index: usize) {
         debug_assert!(self.is_bucket_full(index));
         let index_before = index.wrapping_sub(Group::WIDTH) & self.bucket_mask;
-        let empty_before = Group::load(self.ctrl(index_before)).match_empty();
-        let empty_after = Group::load(self.ctrl(index)).match_empty();
+        let empty_before = self.load_group(index_before).match_empty();
+        let empty_after = self.load_group(index).match_empty();
 
         // If we are inside a continuous block of Group::WIDTH full or deleted
         // cells then a probe window may have seen a full block when trying to
@@ -1642,6 +2914,10 @@ impl<A: Allocator + Clone> RawTableInner<A> {
         // end of a group, while `trailing_zeros` refers to the bytes at the
         // beginning of a group.
         let ctrl = if empty_before.leading_zeros() + empty_after.trailing_zeros() >= Group::WIDTH {
+            #[cfg(feature = "metrics")]
+            {
+                self.counters.tombstones = self.counters.tombstones.wrapping_add(1);
+            }
             DELETED
         } else {
             self.growth_left += 1;
@@ -1774,27 +3050,32 @@ impl<T: Clone, A: Allocator + Clone> RawTable<T, A> {
 
         // The cloning of elements may panic, in which case we need
         // to make sure we drop only the elements that have been
-        // cloned so far.
+        // cloned so far, which are those in the full buckets before the
+        // index in the guard. `items` can't tell whether there are any, as
+        // it is either 0 or stale here.
         let mut guard = guard((0, &mut *self), |(index, self_)| {
-            if Self::DATA_NEEDS_DROP && !self_.is_empty() {
-                for i in 0..=*index {
+            if Self::DATA_NEEDS_DROP {
+                for i in 0..*index {
                     if self_.is_bucket_full(i) {
                         self_.bucket(i).drop();
                     }
                 }
             }
         });
+        let abort = AbortOnUnwind::arm();
 
-        for from in source.iter() {
+        // Clone in the order of the buckets, whatever the order of `iter`.
+        for from in source.iter_in_order() {
             let index = source.bucket_index(&from);
             let to = guard.1.bucket(index);
             to.write(from.as_ref().clone());
 
             // Update the index in case we need to unwind.
-            guard.0 = index;
+            guard.0 = index + 1;
         }
 
         // Successfully cloned all items, no need to clean up.
+        abort.disarm();
         mem::forget(guard);
 
         self.table.items = source.table.items;
@@ -1813,13 +3094,14 @@ impl<T: Clone, A: Allocator + Clone> RawTable<T, A> {
         {
             self.clear();
 
-            let guard_self = guard(&mut *self, |self_| {
+            let mut guard_self = guard(&mut *self, |self_| {
                 // Clear the partially copied table if a panic occurs, otherwise
                 // items and growth_left will be out of sync with the contents
                 // of the table.
                 self_.clear();
             });
 
+            let abort = AbortOnUnwind::arm();
             unsafe {
                 for item in source.iter() {
                     // This may panic.
@@ -1832,14 +3114,17 @@ impl<T: Clone, A: Allocator + Clone> RawTable<T, A> {
                     // - all elements are unique.
                     let (index, _) = guard_self.table.prepare_insert_slot(hash);
                     guard_self.bucket(index).write(item);
+
+                    // Count the item right away, so that the guard drops it
+                    // if a later one panics.
+                    guard_self.table.items += 1;
+                    guard_self.table.growth_left -= 1;
                 }
             }
 
             // Successfully cloned all items, no need to clean up.
+            abort.disarm();
             mem::forget(guard_self);
-
-            self.table.items = source.table.items;
-            self.table.growth_left -= source.table.items;
         } else {
             self.clone_from(source);
         }
@@ -1893,7 +3178,7 @@ impl<T, A: Allocator + Clone> IntoIterator for RawTable<T, A> {
 
 /// Iterator over a sub-range of a table. Unlike `RawIter` this iterator does
 /// not track an item count.
-pub(crate) struct RawIterRange<T> {
+pub struct RawIterRange<T> {
     // Mask of full buckets in the current group. Bits are cleared from this
     // mask as each element is processed.
     current_group: BitMask,
@@ -2046,6 +3331,12 @@ impl<T> Iterator for RawIterRange<T> {
 
 impl<T> FusedIterator for RawIterRange<T> {}
 
+impl<T> fmt::Debug for RawIterRange<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawIterRange").finish_non_exhaustive()
+    }
+}
+
 /// Iterator which returns a raw pointer to every full bucket in the table.
 ///
 /// For maximum flexibility this iterator is not bound by a lifetime, but you
@@ -2061,11 +3352,152 @@ impl<T> FusedIterator for RawIterRange<T> {}
 pub struct RawIter<T> {
     pub(crate) iter: RawIterRange<T>,
     items: usize,
+    #[cfg(feature = "random-order")]
+    wrap: Wrap<T>,
+}
+
+/// Returns the bucket which an iterator over a table starts from with the
+/// `random-order` feature.
+///
+/// This only has to differ between iterators, not be unpredictable, so the
+/// address of the table is mixed with a counter of the iterators created.
+#[cfg(feature = "random-order")]
+fn random_start(ctrl: *const u8, bucket_mask: usize) -> usize {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static ITERATORS: AtomicUsize = AtomicUsize::new(0);
+    // A racy increment is fine: it only makes iterators more likely to start
+    // at the same bucket.
+    let count = ITERATORS.load(Ordering::Relaxed);
+    ITERATORS.store(count.wrapping_add(1), Ordering::Relaxed);
+    let seed = (ctrl as usize as u64) ^ (count as u64).rotate_left(32);
+    crate::hasher::hash_integer(seed) as usize & bucket_mask
+}
+
+/// The buckets which a `RawIter` visits after those of its current range.
+///
+/// An iterator starting at the bucket `start` visits the table in three
+/// ranges: the buckets from `start` to the end of the table, the groups
+/// before the one containing `start`, and the buckets of that group before
+/// `start`. The later ranges are only created when they are reached, so that
+/// their control bytes are read as late as without the `random-order`
+/// feature.
+#[cfg(feature = "random-order")]
+struct Wrap<T> {
+    ctrl: *const u8,
+    // The bucket at index 0.
+    data: Bucket<T>,
+    buckets: usize,
+    start: usize,
+    // The index of the current range.
+    range: usize,
+}
+
+#[cfg(feature = "random-order")]
+impl<T> Wrap<T> {
+    /// Returns the index of the range which the bucket at `index` belongs to.
+    fn range_of(&self, index: usize) -> usize {
+        if index >= self.start {
+            0
+        } else if index < self.start & !(Group::WIDTH - 1) {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Moves on to the next range with any buckets, if there is one.
+    unsafe fn next_range(&mut self) -> Option<RawIterRange<T>> {
+        let aligned = self.start & !(Group::WIDTH - 1);
+        loop {
+            self.range += 1;
+            match self.range {
+                1 if aligned > 0 => {
+                    return Some(RawIterRange::new(self.ctrl, self.data.clone(), aligned));
+                }
+                2 if self.start > aligned => {
+                    let len = usize::min(Group::WIDTH, self.buckets);
+                    let mut iter =
+                        RawIterRange::new(self.ctrl.add(aligned), self.data.next_n(aligned), len);
+                    iter.current_group = iter.current_group.split_at(self.start - aligned).0;
+                    return Some(iter);
+                }
+                1 | 2 => {}
+                _ => {
+                    self.range = 3;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "random-order")]
+impl<T> Clone for Wrap<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ctrl: self.ctrl,
+            data: self.data.clone(),
+            buckets: self.buckets,
+            start: self.start,
+            range: self.range,
+        }
+    }
 }
 
+// Like `RawIterRange`, the iterators using `Wrap` determine the real Send/Sync
+// bounds.
+#[cfg(feature = "random-order")]
+unsafe impl<T> Send for Wrap<T> {}
+#[cfg(feature = "random-order")]
+unsafe impl<T> Sync for Wrap<T> {}
+
 impl<T> RawIter<T> {
     const DATA_NEEDS_DROP: bool = mem::needs_drop::<T>();
 
+    /// Creates an iterator over `buckets` buckets laid out like those of a
+    /// `RawTable`: the control bytes start at `ctrl` and the bucket at index
+    /// `i` is stored just before `data_end.sub(i)`.
+    ///
+    /// The control byte address must be aligned to the group size and
+    /// `items` must be the number of full buckets.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub(crate) unsafe fn new(
+        ctrl: *const u8,
+        data_end: NonNull<T>,
+        buckets: usize,
+        items: usize,
+    ) -> Self {
+        let data = Bucket::from_base_index(data_end, 0);
+        RawIter {
+            #[cfg(feature = "random-order")]
+            wrap: Wrap {
+                ctrl,
+                data: data.clone(),
+                buckets,
+                start: 0,
+                range: 0,
+            },
+            iter: RawIterRange::new(ctrl, data, buckets),
+            items,
+        }
+    }
+
+    /// Makes a new iterator start at the bucket at `start` instead of the
+    /// first one, wrapping around at the end of the table.
+    #[cfg(feature = "random-order")]
+    unsafe fn start_at(&mut self, start: usize) {
+        debug_assert!(start < self.wrap.buckets);
+        let aligned = start & !(Group::WIDTH - 1);
+        self.iter = RawIterRange::new(
+            self.wrap.ctrl.add(aligned),
+            self.wrap.data.next_n(aligned),
+            self.wrap.buckets - aligned,
+        );
+        self.iter.current_group = self.iter.current_group.split_at(start - aligned).1;
+        self.wrap.start = start;
+    }
+
     /// Refresh the iterator so that it reflects a removal from the given bucket.
     ///
     /// For the iterator to remain valid, this method must be called once
@@ -2096,6 +3528,26 @@ impl<T> RawIter<T> {
     #[cfg(feature = "raw")]
     fn reflect_toggle_full(&mut self, b: &Bucket<T>, is_insert: bool) {
         unsafe {
+            #[cfg(feature = "random-order")]
+            {
+                // The logic below only applies to buckets of the current
+                // range. Later ranges read their control bytes when they are
+                // reached, so only the item count needs to be adjusted for
+                // their buckets.
+                let range = self.wrap.range_of(b.to_base_index(self.wrap.data.ptr));
+                if range < self.wrap.range {
+                    return;
+                }
+                if range > self.wrap.range {
+                    if is_insert {
+                        self.items += 1;
+                    } else {
+                        self.items -= 1;
+                    }
+                    return;
+                }
+            }
+
             if b.as_ptr() > self.iter.data.as_ptr() {
                 // The iterator has already passed the bucket's group.
                 // So the toggle isn't relevant to this iterator.
@@ -2197,6 +3649,8 @@ impl<T> Clone for RawIter<T> {
         Self {
             iter: self.iter.clone(),
             items: self.items,
+            #[cfg(feature = "random-order")]
+            wrap: self.wrap.clone(),
         }
     }
 }
@@ -2212,11 +3666,27 @@ impl<T> Iterator for RawIter<T> {
             return None;
         }
 
+        #[cfg(not(feature = "random-order"))]
         let nxt = unsafe {
             // SAFETY: We check number of items to yield using `items` field.
             self.iter.next_impl::<false>()
         };
 
+        // The ranges of an iterator starting at a random bucket do not reach
+        // the end of the table, so they must be checked.
+        #[cfg(feature = "random-order")]
+        let nxt = unsafe {
+            loop {
+                match self.iter.next_impl::<true>() {
+                    Some(bucket) => break Some(bucket),
+                    None => match self.wrap.next_range() {
+                        Some(iter) => self.iter = iter,
+                        None => break None,
+                    },
+                }
+            }
+        };
+
         if nxt.is_some() {
             self.items -= 1;
         }
@@ -2233,6 +3703,15 @@ impl<T> Iterator for RawIter<T> {
 impl<T> ExactSizeIterator for RawIter<T> {}
 impl<T> FusedIterator for RawIter<T> {}
 
+impl<T> fmt::Debug for RawIter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The buckets may not be valid anymore, so only the count is shown.
+        f.debug_struct("RawIter")
+            .field("items", &self.items)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Iterator which consumes a table and returns elements.
 pub struct RawIntoIter<T, A: Allocator + Clone = Global> {
     iter: RawIter<T>,
@@ -2309,6 +3788,14 @@ impl<T, A: Allocator + Clone> Iterator for RawIntoIter<T, A> {
 impl<T, A: Allocator + Clone> ExactSizeIterator for RawIntoIter<T, A> {}
 impl<T, A: Allocator + Clone> FusedIterator for RawIntoIter<T, A> {}
 
+impl<T: fmt::Debug, A: Allocator + Clone> fmt::Debug for RawIntoIter<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // SAFETY: The iterator owns the elements it hasn't yielded yet.
+        let entries = self.iter.clone().map(|bucket| unsafe { bucket.as_ref() });
+        f.debug_list().entries(entries).finish()
+    }
+}
+
 /// Iterator which consumes elements without freeing the table storage.
 pub struct RawDrain<'a, T, A: Allocator + Clone = Global> {
     iter: RawIter<T>,
@@ -2383,6 +3870,14 @@ impl<T, A: Allocator + Clone> Iterator for RawDrain<'_, T, A> {
 impl<T, A: Allocator + Clone> ExactSizeIterator for RawDrain<'_, T, A> {}
 impl<T, A: Allocator + Clone> FusedIterator for RawDrain<'_, T, A> {}
 
+impl<T: fmt::Debug, A: Allocator + Clone> fmt::Debug for RawDrain<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // SAFETY: The drain owns the elements it hasn't yielded yet.
+        let entries = self.iter.clone().map(|bucket| unsafe { bucket.as_ref() });
+        f.debug_list().entries(entries).finish()
+    }
+}
+
 /// Iterator over occupied buckets that could match a given hash.
 ///
 /// `RawTable` only stores 7 bits of the hash value, so this iterator may return
@@ -2393,6 +3888,7 @@ pub struct RawIterHash<'a, T, A: Allocator + Clone = Global> {
     _marker: PhantomData<T>,
 }
 
+#[derive(Clone)]
 struct RawIterHashInner<'a, A: Allocator + Clone> {
     table: &'a RawTableInner<A>,
 
@@ -2410,7 +3906,6 @@ struct RawIterHashInner<'a, A: Allocator + Clone> {
 
 impl<'a, T, A: Allocator + Clone> RawIterHash<'a, T, A> {
     #[cfg_attr(feature = "inline-more", inline)]
-    #[cfg(feature = "raw")]
     fn new(table: &'a RawTable<T, A>, hash: u64) -> Self {
         RawIterHash {
             inner: RawIterHashInner::new(&table.table, hash),
@@ -2420,12 +3915,11 @@ impl<'a, T, A: Allocator + Clone> RawIterHash<'a, T, A> {
 }
 impl<'a, A: Allocator + Clone> RawIterHashInner<'a, A> {
     #[cfg_attr(feature = "inline-more", inline)]
-    #[cfg(feature = "raw")]
     fn new(table: &'a RawTableInner<A>, hash: u64) -> Self {
         unsafe {
             let h2_hash = h2(hash);
             let probe_seq = table.probe_seq(hash);
-            let group = Group::load(table.ctrl(probe_seq.pos));
+            let group = table.load_group(probe_seq.pos);
             let bitmask = group.match_byte(h2_hash).into_iter();
 
             RawIterHashInner {
@@ -2452,6 +3946,25 @@ impl<'a, T, A: Allocator + Clone> Iterator for RawIterHash<'a, T, A> {
     }
 }
 
+impl<T, A: Allocator + Clone> Clone for RawIterHash<'_, T, A> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn clone(&self) -> Self {
+        RawIterHash {
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Once the probe sequence reaches a group with an empty bucket, it stays there.
+impl<T, A: Allocator + Clone> FusedIterator for RawIterHash<'_, T, A> {}
+
+impl<T, A: Allocator + Clone> fmt::Debug for RawIterHash<'_, T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RawIterHash").finish_non_exhaustive()
+    }
+}
+
 impl<'a, A: Allocator + Clone> Iterator for RawIterHashInner<'a, A> {
     type Item = usize;
 
@@ -2459,6 +3972,7 @@ impl<'a, A: Allocator + Clone> Iterator for RawIterHashInner<'a, A> {
         unsafe {
             loop {
                 if let Some(bit) = self.bitmask.next() {
+                    self.table.count_tag_match();
                     let index = (self.probe_seq.pos + bit) & self.table.bucket_mask;
                     return Some(index);
                 }
@@ -2466,7 +3980,7 @@ impl<'a, A: Allocator + Clone> Iterator for RawIterHashInner<'a, A> {
                     return None;
                 }
                 self.probe_seq.move_next(self.table.bucket_mask);
-                self.group = Group::load(self.table.ctrl(self.probe_seq.pos));
+                self.group = self.table.load_group(self.probe_seq.pos);
                 self.bitmask = self.group.match_byte(self.h2_hash).into_iter();
             }
         }
@@ -2477,7 +3991,7 @@ impl<'a, A: Allocator + Clone> Iterator for RawIterHashInner<'a, A> {
 mod test_map {
     use super::*;
 
-    fn rehash_in_place<T>(table: &mut RawTable<T>, hasher: impl Fn(&T) -> u64) {
+    fn rehash_in_place<T>(table: &mut RawTable<T, Global>, hasher: impl Fn(&T) -> u64) {
         unsafe {
             table.table.rehash_in_place(
                 &|table, index| hasher(table.bucket::<T>(index).as_ref()),
@@ -2487,6 +4001,7 @@ mod test_map {
                 } else {
                     None
                 },
+                None,
             );
         }
     }
@@ -2515,4 +4030,456 @@ mod test_map {
             assert!(table.find(i + 100, |x| *x == i + 100).is_none());
         }
     }
+
+    #[test]
+    fn absorb() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::new();
+        let mut other = RawTable::new();
+        for i in 0..50 {
+            table.insert(i, i, hasher);
+            other.insert(i + 50, i + 50, hasher);
+        }
+
+        table.absorb(other, hasher);
+        assert_eq!(table.len(), 100);
+        for i in 0..100 {
+            assert!(table.find(i, |x| *x == i).is_some());
+        }
+    }
+
+    #[test]
+    fn verify() {
+        let hasher = |i: &u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut table = RawTable::new();
+        table.verify(hasher);
+        for i in 0..3 {
+            table.insert(hasher(&i), i, hasher);
+        }
+        table.verify(hasher);
+        for i in 3..1000 {
+            table.insert(hasher(&i), i, hasher);
+        }
+        for i in (0..1000).step_by(3) {
+            table.remove_entry(hasher(&i), |x| *x == i);
+        }
+        table.verify(hasher);
+        rehash_in_place(&mut table, hasher);
+        table.verify(hasher);
+    }
+
+    #[test]
+    #[should_panic = "wrong tag"]
+    fn verify_wrong_hash() {
+        let hasher = |i: &u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut table = RawTable::new();
+        for i in 0..100 {
+            table.insert(hasher(&i), i, hasher);
+        }
+        table.verify(|i| hasher(&(i + 1)));
+    }
+
+    #[test]
+    #[cfg(feature = "force-generic")]
+    fn force_generic_matches() {
+        let mut ctrl = [EMPTY; 64];
+        for (i, c) in ctrl.iter_mut().enumerate() {
+            *c = match i % 5 {
+                0 => EMPTY,
+                1 => DELETED,
+                _ => (i as u8).wrapping_mul(37) & 0x7f,
+            };
+        }
+        let run = || {
+            let mut out = std::vec::Vec::new();
+            for start in 0..64 - Group::WIDTH {
+                let group = unsafe { Group::load(ctrl.as_ptr().add(start)) };
+                out.push(group.match_empty().to_u16());
+                out.push(group.match_empty_or_deleted().to_u16());
+                out.push(group.match_full().to_u16());
+                for byte in [0, 5, 0x25, 0x7f] {
+                    out.push(group.match_byte(byte).to_u16());
+                }
+                let mut buf = [EMPTY; 64];
+                let at = buf.as_ptr().align_offset(Group::WIDTH);
+                unsafe {
+                    group
+                        .convert_special_to_empty_and_full_to_deleted()
+                        .store_aligned(buf.as_mut_ptr().add(at));
+                }
+                out.extend(buf[at..at + Group::WIDTH].iter().map(|&b| u16::from(b)));
+            }
+            out
+        };
+        let simd = run();
+        let previous = force_generic(true);
+        let scalar = run();
+        force_generic(previous);
+        assert_eq!(simd, scalar);
+    }
+
+    #[test]
+    #[cfg(feature = "raw")]
+    fn bucket_states() {
+        let hasher = |i: &u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        let mut table = RawTable::new();
+        for i in 0..20 {
+            table.insert(hasher(&i), i, hasher);
+        }
+        let removed = table.find(hasher(&3), |x| *x == 3).unwrap();
+        let index = unsafe { table.bucket_index(&removed) };
+        unsafe { table.erase(removed) };
+        let (mut full, mut empty, mut deleted) = (0, 0, 0);
+        for i in 0..table.buckets() {
+            unsafe {
+                let ctrl = table.ctrl_byte(i);
+                match (
+                    table.is_bucket_full(i),
+                    table.is_bucket_empty(i),
+                    table.is_bucket_deleted(i),
+                ) {
+                    (true, false, false) => {
+                        let value = *table.bucket(i).as_ref();
+                        assert_eq!(ctrl, h2(hasher(&value)));
+                        full += 1;
+                    }
+                    (false, true, false) => empty += 1,
+                    (false, false, true) => deleted += 1,
+                    states => panic!("bucket {} is {:?}", i, states),
+                }
+            }
+        }
+        assert_eq!(full, 19);
+        assert_eq!(full + empty + deleted, table.buckets());
+        let ctrl = unsafe { table.ctrl_byte(index) };
+        assert!(ctrl == EMPTY || ctrl == DELETED);
+    }
+
+    #[test]
+    fn iter_from() {
+        let hasher = |i: &u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        for n in [0, 3, 100] {
+            let mut table = RawTable::new();
+            for i in 0..n {
+                table.insert(hasher(&i), i, hasher);
+            }
+            unsafe {
+                let all: std::vec::Vec<u64> = table.iter_from(0).map(|b| *b.as_ref()).collect();
+                assert_eq!(all.len(), n as usize);
+                assert_eq!(table.iter_from(table.buckets()).count(), 0);
+
+                // Resume a scan three elements at a time.
+                let mut resumed = std::vec::Vec::new();
+                let mut position = 0;
+                loop {
+                    let slice: std::vec::Vec<_> = table.iter_from(position).take(3).collect();
+                    if slice.is_empty() {
+                        break;
+                    }
+                    position = table.bucket_index(slice.last().unwrap()) + 1;
+                    resumed.extend(slice.iter().map(|b| *b.as_ref()));
+                }
+                assert_eq!(resumed, all);
+            }
+        }
+    }
+
+    #[test]
+    fn iter_chunk() {
+        let hasher = |i: &u64| i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        for n in [0, 3, 100, 1000] {
+            let mut table = RawTable::new();
+            for i in 0..n {
+                table.insert(hasher(&i), i, hasher);
+            }
+            for chunks in [1, 2, 3, 7, 64] {
+                let mut seen = std::vec![false; n as usize];
+                for chunk in 0..chunks {
+                    for bucket in unsafe { table.iter_chunk(chunk, chunks) } {
+                        let i = unsafe { *bucket.as_ref() } as usize;
+                        assert!(!seen[i], "{} yielded twice", i);
+                        seen[i] = true;
+                    }
+                }
+                assert!(seen.iter().all(|&s| s));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "item count"]
+    fn verify_wrong_items() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::new();
+        for i in 0..100 {
+            table.insert(i, i, hasher);
+        }
+        table.table.items += 1;
+        table.verify(hasher);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "bucket used after the table was resized"]
+    fn stale_bucket_after_resize() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::new();
+        let bucket = table.insert(0, 0, hasher);
+        for i in 1..100 {
+            table.insert(i, i, hasher);
+        }
+        unsafe { table.erase(bucket) };
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic = "bucket used after the table was resized"]
+    fn stale_iterator_after_clear() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::new();
+        for i in 0..10 {
+            table.insert(i, i, hasher);
+        }
+        let mut iter = unsafe { table.iter() };
+        table.clear();
+        table.insert(0, 0, hasher);
+        let bucket = iter.next().unwrap();
+        unsafe { table.remove(bucket) };
+    }
+
+    #[test]
+    fn buckets_survive_insert_and_erase() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::with_capacity(100);
+        let bucket = table.insert(0, 0, hasher);
+        for i in 1..50 {
+            table.insert(i, i, hasher);
+        }
+        table.remove_entry(1, |x| *x == 1);
+        unsafe {
+            for item in table.iter() {
+                if *item.as_ref() % 2 == 1 {
+                    table.erase(item);
+                }
+            }
+            assert_eq!(table.remove(bucket), 0);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "random-order")]
+    fn random_order_yields_every_item() {
+        let hasher = |i: &u64| *i;
+        for n in [0, 1, 3, 7, 8, 15, 16, 17, 100, 1000] {
+            let mut table = RawTable::new();
+            for i in 0..n {
+                table.insert(i, i, hasher);
+            }
+            for _ in 0..20 {
+                let mut seen = std::vec![false; n as usize];
+                let mut iter = unsafe { table.iter() };
+                assert_eq!(iter.len(), n as usize);
+                for item in &mut iter {
+                    let i = unsafe { *item.as_ref() } as usize;
+                    assert!(!seen[i], "{} yielded twice", i);
+                    seen[i] = true;
+                }
+                assert!(seen.iter().all(|&s| s));
+                assert!(iter.next().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn probe_seq_visits_every_group() {
+        for buckets in [Group::WIDTH, Group::WIDTH * 4, Group::WIDTH * 64] {
+            let bucket_mask = buckets - 1;
+            for hash in [0, 1, 0x1234_5678_9abc_def0, u64::MAX] {
+                let mut probe_seq = ProbeSeq::new(hash, bucket_mask);
+                let start = probe_seq.pos;
+                let mut seen = std::vec![false; buckets / Group::WIDTH];
+                for _ in 0..seen.len() {
+                    let group = (probe_seq.pos.wrapping_sub(start) & bucket_mask) / Group::WIDTH;
+                    assert!(!seen[group], "group {} probed twice", group);
+                    seen[group] = true;
+                    if probe_seq.stride < bucket_mask {
+                        probe_seq.move_next(bucket_mask);
+                    }
+                }
+                assert!(seen.iter().all(|&s| s));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "random-order")]
+    fn random_order_varies_start() {
+        let hasher = |i: &u64| *i;
+        let mut table = RawTable::new();
+        for i in 0..100 {
+            table.insert(i, i, hasher);
+        }
+        let first = |table: &RawTable<u64>| unsafe { *table.iter().next().unwrap().as_ref() };
+        let start = first(&table);
+        assert!((0..100).any(|_| first(&table) != start));
+    }
+
+    #[test]
+    #[cfg(all(feature = "random-order", feature = "raw"))]
+    fn random_order_reflect_remove() {
+        let hasher = |i: &u64| *i;
+        for n in [5, 16, 50, 300] {
+            let mut table = RawTable::new();
+            for i in 0..n {
+                table.insert(i, i, hasher);
+            }
+            unsafe {
+                let mut iter = table.iter();
+                let mut seen = std::vec![false; n as usize];
+                let mut yielded = 0;
+                let mut removed = 0;
+                while let Some(item) = iter.next() {
+                    let i = *item.as_ref();
+                    assert!(!seen[i as usize], "{} yielded twice", i);
+                    seen[i as usize] = true;
+                    yielded += 1;
+                    // Remove the item's successor if it has not been yielded
+                    // yet, whichever range it is in.
+                    let next = (i + 1) % n;
+                    if seen[next as usize] {
+                        continue;
+                    }
+                    if let Some(bucket) = table.find(next, |x| *x == next) {
+                        iter.reflect_remove(&bucket);
+                        table.erase(bucket);
+                        removed += 1;
+                    }
+                }
+                assert_eq!(yielded + removed, n);
+                assert_eq!(table.len() as u64, n - removed);
+            }
+        }
+    }
+
+    // With the `abort-on-unwind` feature, these panics abort the tests.
+    #[cfg(not(feature = "abort-on-unwind"))]
+    mod panic_safety {
+        use super::*;
+        use core::cell::Cell;
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        /// Counts the live clones of a value, and panics when cloned while the
+        /// shared budget of clones is used up.
+        struct Counted<'a> {
+            value: u64,
+            live: &'a Cell<usize>,
+            clones: &'a Cell<usize>,
+        }
+
+        impl Clone for Counted<'_> {
+            fn clone(&self) -> Self {
+                if self.clones.get() == 0 {
+                    panic!("clone");
+                }
+                self.clones.set(self.clones.get() - 1);
+                self.live.set(self.live.get() + 1);
+                Counted { ..*self }
+            }
+        }
+
+        impl Drop for Counted<'_> {
+            fn drop(&mut self) {
+                self.live.set(self.live.get() - 1);
+            }
+        }
+
+        fn counted_table<'a>(
+            live: &'a Cell<usize>,
+            clones: &'a Cell<usize>,
+        ) -> RawTable<Counted<'a>> {
+            let mut table = RawTable::new();
+            for value in 0..100 {
+                live.set(live.get() + 1);
+                let item = Counted {
+                    value,
+                    live,
+                    clones,
+                };
+                table.insert(value, item, |x| x.value);
+            }
+            table
+        }
+
+        #[test]
+        fn clone_panic() {
+            let live = Cell::new(0);
+            let clones = Cell::new(0);
+            let source = counted_table(&live, &clones);
+
+            // The first clone, and then one in the middle, panics. Only the
+            // elements cloned before are dropped, once.
+            for budget in [0, 50] {
+                clones.set(budget);
+                assert!(catch_unwind(AssertUnwindSafe(|| source.clone())).is_err());
+                assert_eq!(live.get(), 100);
+
+                // The elements of the target are dropped before cloning.
+                let mut target = counted_table(&live, &clones);
+                clones.set(budget);
+                let result = catch_unwind(AssertUnwindSafe(|| target.clone_from(&source)));
+                assert!(result.is_err());
+                assert!(target.is_empty());
+                target.verify(|x| x.value);
+                assert_eq!(live.get(), 100);
+            }
+        }
+
+        #[test]
+        #[cfg(feature = "raw")]
+        fn clone_from_with_hasher_panic() {
+            let live = Cell::new(0);
+            let clones = Cell::new(0);
+            let source = counted_table(&live, &clones);
+
+            let mut target = RawTable::with_capacity(1000);
+            clones.set(50);
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                target.clone_from_with_hasher(&source, |x| x.value);
+            }));
+            assert!(result.is_err());
+            assert!(target.is_empty());
+            target.verify(|x| x.value);
+            assert_eq!(live.get(), 100);
+        }
+
+        #[test]
+        fn rehash_in_place_panic() {
+            let mut table = RawTable::new();
+            for i in 0..100_u64 {
+                table.insert(i, i, |x| *x);
+            }
+
+            // The hasher panics half way through: the elements which weren't
+            // rehashed yet are gone, even though `u64` needs no drop, and the
+            // table is still usable.
+            let calls = Cell::new(0);
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                rehash_in_place(&mut table, |x| {
+                    calls.set(calls.get() + 1);
+                    if calls.get() == 50 {
+                        panic!("hash");
+                    }
+                    *x
+                });
+            }));
+            assert!(result.is_err());
+            assert!(table.len() < 100);
+            assert_eq!(unsafe { table.iter().count() }, table.len());
+            table.verify(|x| *x);
+            for i in 100..200 {
+                table.insert(i, i, |x| *x);
+            }
+            table.verify(|x| *x);
+        }
+    }
 }
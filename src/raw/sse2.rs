@@ -1,6 +1,10 @@
 use super::bitmask::BitMask;
+#[cfg(feature = "force-generic")]
+use super::DELETED;
 use super::EMPTY;
 use core::mem;
+#[cfg(feature = "force-generic")]
+use core::sync::atomic::{AtomicBool, Ordering};
 
 #[cfg(target_arch = "x86")]
 use core::arch::x86;
@@ -11,10 +15,32 @@ pub type BitMaskWord = u16;
 pub const BITMASK_STRIDE: usize = 1;
 pub const BITMASK_MASK: BitMaskWord = 0xffff;
 
+// Set by `force_generic`, makes every operation run the scalar code below
+// instead of the SSE2 instructions.
+#[cfg(feature = "force-generic")]
+static FORCE_GENERIC: AtomicBool = AtomicBool::new(false);
+
+/// Makes every `Group` operation use scalar code instead of SSE2, or switches
+/// back, returning the previous setting.
+#[cfg(feature = "force-generic")]
+pub fn force_generic(force: bool) -> bool {
+    FORCE_GENERIC.swap(force, Ordering::Relaxed)
+}
+
+#[cfg(feature = "force-generic")]
+#[inline]
+fn forced() -> bool {
+    FORCE_GENERIC.load(Ordering::Relaxed)
+}
+
 /// Abstraction over a group of control bytes which can be scanned in
 /// parallel.
 ///
 /// This implementation uses a 128-bit SSE value.
+///
+/// The implementation, and with it `WIDTH` and the layout of the returned
+/// [`BitMask`]s, is chosen at compile time for the target. Code using groups
+/// must not assume a particular width.
 #[derive(Copy, Clone)]
 pub struct Group(x86::__m128i);
 
@@ -44,29 +70,57 @@ impl Group {
     }
 
     /// Loads a group of bytes starting at the given address.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of `WIDTH` bytes, which need not be
+    /// aligned.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)] // unaligned load
     pub unsafe fn load(ptr: *const u8) -> Self {
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            return Group::from_bytes(core::ptr::read_unaligned(ptr.cast()));
+        }
         Group(x86::_mm_loadu_si128(ptr.cast()))
     }
 
     /// Loads a group of bytes starting at the given address, which must be
     /// aligned to `mem::align_of::<Group>()`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for reads of `WIDTH` bytes and aligned to
+    /// `mem::align_of::<Group>()`.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)]
     pub unsafe fn load_aligned(ptr: *const u8) -> Self {
         // FIXME: use align_offset once it stabilizes
         debug_assert_eq!(ptr as usize & (mem::align_of::<Self>() - 1), 0);
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            return Group::from_bytes(*ptr.cast());
+        }
         Group(x86::_mm_load_si128(ptr.cast()))
     }
 
     /// Stores the group of bytes to the given address, which must be
     /// aligned to `mem::align_of::<Group>()`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `WIDTH` bytes and aligned to
+    /// `mem::align_of::<Group>()`.
     #[inline]
     #[allow(clippy::cast_ptr_alignment)]
     pub unsafe fn store_aligned(self, ptr: *mut u8) {
         // FIXME: use align_offset once it stabilizes
         debug_assert_eq!(ptr as usize & (mem::align_of::<Self>() - 1), 0);
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            *ptr.cast() = self.to_bytes();
+            return;
+        }
         x86::_mm_store_si128(ptr.cast(), self.0);
     }
 
@@ -74,6 +128,10 @@ impl Group {
     /// the given value.
     #[inline]
     pub fn match_byte(self, byte: u8) -> BitMask {
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            return self.match_scalar(|b| b == byte);
+        }
         #[allow(
             clippy::cast_possible_wrap, // byte: u8 as i8
             // byte: i32 as u16
@@ -99,6 +157,10 @@ impl Group {
     /// `EMPTY` or `DELETED`.
     #[inline]
     pub fn match_empty_or_deleted(self) -> BitMask {
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            return self.match_scalar(|b| b & 0x80 != 0);
+        }
         #[allow(
             // byte: i32 as u16
             //   note: _mm_movemask_epi8 returns a 16-bit mask in a i32, the
@@ -114,7 +176,7 @@ impl Group {
 
     /// Returns a `BitMask` indicating all bytes in the group which are full.
     #[inline]
-    pub fn match_full(&self) -> BitMask {
+    pub fn match_full(self) -> BitMask {
         self.match_empty_or_deleted().invert()
     }
 
@@ -131,6 +193,14 @@ impl Group {
         //   let special = 0 > byte = 1111_1111 (true) or 0000_0000 (false)
         //   1111_1111 | 1000_0000 = 1111_1111
         //   0000_0000 | 1000_0000 = 1000_0000
+        #[cfg(feature = "force-generic")]
+        if forced() {
+            let mut bytes = self.to_bytes();
+            for b in &mut bytes {
+                *b = if *b & 0x80 != 0 { EMPTY } else { DELETED };
+            }
+            return Group::from_bytes(bytes);
+        }
         #[allow(
             clippy::cast_possible_wrap, // byte: 0x80_u8 as i8
         )]
@@ -143,4 +213,32 @@ impl Group {
             ))
         }
     }
+
+    #[cfg(feature = "force-generic")]
+    #[inline]
+    fn from_bytes(bytes: [u8; Group::WIDTH]) -> Self {
+        // SAFETY: `__m128i` is 16 bytes without invalid bit patterns.
+        Group(unsafe { mem::transmute::<[u8; Group::WIDTH], x86::__m128i>(bytes) })
+    }
+
+    #[cfg(feature = "force-generic")]
+    #[inline]
+    fn to_bytes(self) -> [u8; Group::WIDTH] {
+        // SAFETY: `__m128i` is 16 bytes without invalid bit patterns.
+        unsafe { mem::transmute::<x86::__m128i, [u8; Group::WIDTH]>(self.0) }
+    }
+
+    /// Returns a `BitMask` of the bytes for which `f` holds, computed one byte
+    /// at a time.
+    #[cfg(feature = "force-generic")]
+    #[inline]
+    fn match_scalar(self, f: impl Fn(u8) -> bool) -> BitMask {
+        let mut mask = 0;
+        for (i, &b) in self.to_bytes().iter().enumerate() {
+            if f(b) {
+                mask |= 1 << i;
+            }
+        }
+        BitMask(mask)
+    }
 }
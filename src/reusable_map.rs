@@ -0,0 +1,254 @@
+use crate::map::HashMap;
+use crate::raw::{Allocator, Global};
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FromIterator;
+use core::ops::{Deref, DerefMut};
+
+/// What [`ReusableHashMap::clear`] does with the memory of the table.
+///
+/// A scratch map which is cleared and refilled many times wants to keep its
+/// memory, while a map which is filled once and then cleared wants to give
+/// it back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClearPolicy {
+    /// Keep all the allocated memory for reuse, like [`HashMap::clear`].
+    Keep,
+    /// Shrink the table so that it can hold at most about the given number
+    /// of elements without reallocating, keeping smaller tables as they are,
+    /// like [`HashMap::clear_and_shrink_to`].
+    ShrinkTo(usize),
+    /// Free the memory of the table, like [`HashMap::clear_and_shrink`].
+    Free,
+}
+
+impl Default for ClearPolicy {
+    fn default() -> Self {
+        Self::Keep
+    }
+}
+
+/// A [`HashMap`] whose [`clear`] follows a [`ClearPolicy`].
+///
+/// Code which owns a map can call [`HashMap::clear_and_shrink_to`] itself.
+/// This wrapper is for maps handed out for reuse, such as scratch maps kept
+/// in a pool, whose users call `clear` and where the owner decides what
+/// happens to the memory.
+///
+/// The map dereferences to a [`HashMap`] for all other operations. Only
+/// `clear` called on the `ReusableHashMap` itself follows the policy, not
+/// `clear` called through a `&mut HashMap` borrowed from it.
+///
+/// [`clear`]: ReusableHashMap::clear
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::{ClearPolicy, ReusableHashMap};
+///
+/// // A scratch map which shouldn't keep the memory of an unusually large
+/// // batch.
+/// let mut scratch = ReusableHashMap::with_capacity(16);
+/// scratch.set_clear_policy(ClearPolicy::ShrinkTo(16));
+///
+/// scratch.extend((0..1000).map(|i| (i, i)));
+/// assert_eq!(scratch[&10], 10);
+/// scratch.clear();
+/// assert!(scratch.capacity() >= 16);
+/// assert!(scratch.capacity() < 1000);
+/// ```
+pub struct ReusableHashMap<K, V, S = crate::DefaultHashBuilder, A: Allocator + Clone = Global> {
+    map: HashMap<K, V, S, A>,
+    clear_policy: ClearPolicy,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> ReusableHashMap<K, V> {
+    /// Creates an empty `ReusableHashMap` which keeps its memory when
+    /// cleared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::{ClearPolicy, ReusableHashMap};
+    ///
+    /// let map: ReusableHashMap<i32, i32> = ReusableHashMap::new();
+    /// assert!(map.is_empty());
+    /// assert_eq!(map.clear_policy(), ClearPolicy::Keep);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(Default::default())
+    }
+
+    /// Creates an empty `ReusableHashMap` which can hold at least `capacity`
+    /// elements without reallocating, and keeps its memory when cleared.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, Default::default())
+    }
+}
+
+impl<K, V, S> ReusableHashMap<K, V, S> {
+    /// Creates an empty `ReusableHashMap` which uses `hash_builder` to hash
+    /// keys.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::from(HashMap::with_hasher(hash_builder))
+    }
+
+    /// Creates an empty `ReusableHashMap` which uses `hash_builder` to hash
+    /// keys and can hold at least `capacity` elements without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::from(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> ReusableHashMap<K, V, S, A> {
+    /// Returns what [`clear`] does with the memory of the map.
+    ///
+    /// [`clear`]: ReusableHashMap::clear
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_policy(&self) -> ClearPolicy {
+        self.clear_policy
+    }
+
+    /// Sets what [`clear`] does with the memory of the map.
+    ///
+    /// [`clear`]: ReusableHashMap::clear
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn set_clear_policy(&mut self, policy: ClearPolicy) {
+        self.clear_policy = policy;
+    }
+
+    /// Clears the map, removing all key-value pairs, and keeps, shrinks or
+    /// frees the memory of the table as the [`ClearPolicy`] of the map says.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        match self.clear_policy {
+            ClearPolicy::Keep => self.map.clear(),
+            ClearPolicy::ShrinkTo(min_capacity) => self.map.clear_and_shrink_to(min_capacity),
+            ClearPolicy::Free => self.map.clear_and_shrink(),
+        }
+    }
+
+    /// Returns the underlying `HashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_inner(self) -> HashMap<K, V, S, A> {
+        self.map
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> From<HashMap<K, V, S, A>> for ReusableHashMap<K, V, S, A> {
+    /// Wraps a `HashMap`, with [`ClearPolicy::Keep`].
+    fn from(map: HashMap<K, V, S, A>) -> Self {
+        Self {
+            map,
+            clear_policy: ClearPolicy::Keep,
+        }
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> Deref for ReusableHashMap<K, V, S, A> {
+    type Target = HashMap<K, V, S, A>;
+
+    #[inline]
+    fn deref(&self) -> &HashMap<K, V, S, A> {
+        &self.map
+    }
+}
+
+impl<K, V, S, A: Allocator + Clone> DerefMut for ReusableHashMap<K, V, S, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut HashMap<K, V, S, A> {
+        &mut self.map
+    }
+}
+
+impl<K, V, S, A> Extend<(K, V)> for ReusableHashMap<K, V, S, A>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    A: Allocator + Clone,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        self.map.extend(iter);
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for ReusableHashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
+        Self::from(HashMap::from_iter(iter))
+    }
+}
+
+impl<K, V, S: Default> Default for ReusableHashMap<K, V, S> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, A: Allocator + Clone> Clone for ReusableHashMap<K, V, S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            clear_policy: self.clear_policy,
+        }
+    }
+}
+
+impl<K: Debug, V: Debug, S, A: Allocator + Clone> Debug for ReusableHashMap<K, V, S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_reusable {
+    use super::{ClearPolicy, ReusableHashMap};
+    use crate::HashMap;
+
+    #[test]
+    fn test_clear_policy() {
+        let mut m: ReusableHashMap<i32, i32> = ReusableHashMap::new();
+        assert_eq!(m.clear_policy(), ClearPolicy::Keep);
+
+        m.extend((0..100).map(|i| (i, i)));
+        let capacity = m.capacity();
+        m.clear();
+        assert!(m.is_empty());
+        assert_eq!(m.capacity(), capacity);
+
+        m.set_clear_policy(ClearPolicy::ShrinkTo(10));
+        m.extend((0..100).map(|i| (i, i)));
+        m.clear();
+        assert!(m.is_empty());
+        assert!(m.capacity() >= 10);
+        assert!(m.capacity() < capacity);
+
+        // Clones inherit the policy.
+        m.set_clear_policy(ClearPolicy::Free);
+        m.extend((0..100).map(|i| (i, i)));
+        let mut m2 = m.clone();
+        assert_eq!(m2.clear_policy(), ClearPolicy::Free);
+        m2.clear();
+        assert_eq!(m2.capacity(), 0);
+        m2.insert(1, 1);
+        assert_eq!(m2[&1], 1);
+
+        // The policy doesn't cost the maps which don't use it anything.
+        assert_eq!(
+            core::mem::size_of::<HashMap<i32, i32>>(),
+            core::mem::size_of::<crate::raw::RawTable<(i32, i32)>>()
+                + core::mem::size_of::<crate::DefaultHashBuilder>()
+        );
+    }
+}
@@ -70,3 +70,34 @@ where
         (self.dropfn)(&mut self.value);
     }
 }
+
+/// Aborts the process if it is dropped, which only happens while unwinding
+/// out of the code it guards, unless that code called [`disarm`] when done.
+///
+/// This only does anything with the `abort-on-unwind` feature, which makes a
+/// panic in user code during a resize, rehash or clone of a table abort the
+/// process instead of leaving the table truncated.
+///
+/// [`disarm`]: AbortOnUnwind::disarm
+pub struct AbortOnUnwind(());
+
+impl AbortOnUnwind {
+    #[inline]
+    pub fn arm() -> Self {
+        AbortOnUnwind(())
+    }
+
+    #[inline]
+    pub fn disarm(self) {
+        let _ = ManuallyDrop::new(self);
+    }
+}
+
+#[cfg(feature = "abort-on-unwind")]
+impl Drop for AbortOnUnwind {
+    #[cold]
+    fn drop(&mut self) {
+        // Panicking while already unwinding aborts, with or without `std`.
+        panic!("panic while resizing, rehashing or cloning a table, aborting");
+    }
+}
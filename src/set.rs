@@ -2,6 +2,7 @@
 use crate::raw::RawTable;
 use crate::{Equivalent, TryReserveError};
 use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
 use core::fmt;
 use core::hash::{BuildHasher, Hash};
 use core::iter::{Chain, FromIterator, FusedIterator};
@@ -9,7 +10,9 @@ use core::mem;
 use core::ops::{BitAnd, BitOr, BitXor, Sub};
 
 use super::map::{self, ConsumeAllOnDrop, DefaultHashBuilder, DrainFilterInner, HashMap, Keys};
-use crate::raw::{Allocator, Global};
+#[cfg(feature = "metrics")]
+use crate::raw::TableMetrics;
+use crate::raw::{Allocator, Global, TableStats};
 
 // Future Optimization (FIXME!)
 // =============================
@@ -129,7 +132,7 @@ impl<T: Clone, S: Clone, A: Allocator + Clone> Clone for HashSet<T, S, A> {
     }
 }
 
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<T> HashSet<T, DefaultHashBuilder> {
     /// Creates an empty `HashSet`.
     ///
@@ -193,7 +196,7 @@ impl<T> HashSet<T, DefaultHashBuilder> {
     }
 }
 
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<T: Hash + Eq, A: Allocator + Clone> HashSet<T, DefaultHashBuilder, A> {
     /// Creates an empty `HashSet`.
     ///
@@ -272,6 +275,26 @@ impl<T, S, A: Allocator + Clone> HashSet<T, S, A> {
         self.map.capacity()
     }
 
+    /// Returns the number of buckets the set has allocated.
+    ///
+    /// See [`HashMap::bucket_count`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut set: HashSet<i32> = HashSet::new();
+    /// assert_eq!(set.bucket_count(), 0);
+    ///
+    /// set.reserve_exact(100);
+    /// assert_eq!(set.bucket_count(), 128);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn bucket_count(&self) -> usize {
+        self.map.bucket_count()
+    }
+
     /// An iterator visiting all elements in arbitrary order.
     /// The iterator element type is `&'a T`.
     ///
@@ -429,6 +452,58 @@ impl<T, S, A: Allocator + Clone> HashSet<T, S, A> {
     pub fn clear(&mut self) {
         self.map.clear();
     }
+
+    /// Clears the set, removing all values, and frees the allocated memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut v: HashSet<i32> = (0..100).collect();
+    /// v.clear_and_shrink();
+    /// assert!(v.is_empty());
+    /// assert_eq!(v.capacity(), 0);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_and_shrink(&mut self) {
+        self.map.clear_and_shrink();
+    }
+
+    /// Clears the set, removing all values, and shrinks its table so that it
+    /// holds at most about `min_capacity` values without reallocating, as
+    /// [`HashMap::clear_and_shrink_to`] does for maps.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear_and_shrink_to(&mut self, min_capacity: usize) {
+        self.map.clear_and_shrink_to(min_capacity);
+    }
+
+    /// Removes an arbitrary value from the set and returns it, or returns
+    /// `None` if the set is empty.
+    ///
+    /// The value is found by scanning the control bytes a group at a time and
+    /// removed without hashing it, as with [`HashMap::pop`]. Which value is
+    /// returned is unspecified.
+    ///
+    /// [`HashMap::pop`]: crate::HashMap::pop
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut set: HashSet<_> = [1, 2, 3].into();
+    /// let mut sum = 0;
+    /// while let Some(value) = set.take_any() {
+    ///     sum += value;
+    /// }
+    /// assert_eq!(sum, 6);
+    /// assert!(set.is_empty());
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn take_any(&mut self) -> Option<T> {
+        self.map.pop().map(|(value, ())| value)
+    }
 }
 
 impl<T, S> HashSet<T, S, Global> {
@@ -668,6 +743,51 @@ where
         self.map.try_reserve(additional)
     }
 
+    /// Reserves capacity for at least `additional` more elements, growing to
+    /// the smallest table which holds them if the set has to reallocate.
+    ///
+    /// See [`HashMap::reserve_exact`] for how this differs from
+    /// [`reserve`](HashSet::reserve).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the new allocation size overflows `usize`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    /// let mut set: HashSet<i32> = HashSet::new();
+    /// set.reserve_exact(10);
+    /// assert_eq!(set.bucket_count(), 16);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.map.reserve_exact(additional);
+    }
+
+    /// Tries to reserve capacity for at least `additional` more elements,
+    /// growing to the smallest table which holds them if the set has to
+    /// reallocate.
+    ///
+    /// # Errors
+    ///
+    /// If the capacity overflows, or the allocator reports a failure, then an error
+    /// is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    /// let mut set: HashSet<i32> = HashSet::new();
+    /// set.try_reserve_exact(10).expect("why is the test harness OOMing on 10 bytes?");
+    /// assert_eq!(set.bucket_count(), 16);
+    /// ```
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.map.try_reserve_exact(additional)
+    }
+
     /// Shrinks the capacity of the set as much as possible. It will drop
     /// down as much as possible while maintaining the internal rules
     /// and possibly leaving some space in accordance with the resize policy.
@@ -715,6 +835,54 @@ where
         self.map.shrink_to(min_capacity);
     }
 
+    /// Returns statistics about the layout of the set's table.
+    ///
+    /// See [`HashMap::stats`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let set: HashSet<i32> = (0..100).collect();
+    /// assert_eq!(set.stats().full, 100);
+    /// ```
+    pub fn stats(&self) -> TableStats {
+        self.map.stats()
+    }
+
+    /// Returns counters of the work the set's table has done.
+    ///
+    /// See [`HashMap::metrics`] for details.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> TableMetrics {
+        self.map.metrics()
+    }
+
+    /// Sets all counters returned by [`metrics`] back to zero.
+    ///
+    /// [`metrics`]: HashSet::metrics
+    #[cfg(feature = "metrics")]
+    pub fn reset_metrics(&mut self) {
+        self.map.reset_metrics();
+    }
+
+    /// Checks the invariants of the set's table, panicking if any is broken.
+    ///
+    /// See [`HashMap::verify`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let set: HashSet<i32> = (0..100).collect();
+    /// set.verify();
+    /// ```
+    pub fn verify(&self) {
+        self.map.verify();
+    }
+
     /// Visits the values representing the difference,
     /// i.e., the values that are in `self` but not in `other`.
     ///
@@ -837,6 +1005,249 @@ where
         }
     }
 
+    /// Returns the values which are in both `self` and `other` as a new set.
+    ///
+    /// Unlike collecting [`intersection`], this walks the table of the smaller
+    /// set a group at a time and hashes each of its values once, with the
+    /// hasher of the larger set, which the result uses as well: the hash used
+    /// to look a value up is also used to insert it. The result is allocated
+    /// once, with room for exactly the values in it.
+    ///
+    /// [`intersection`]: HashSet::intersection
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let a: HashSet<_> = [1, 2, 3].into_iter().collect();
+    /// let b: HashSet<_> = [4, 2, 3, 4].into_iter().collect();
+    ///
+    /// let intersection = a.intersection_set(&b);
+    /// assert_eq!(intersection, [2, 3].into_iter().collect());
+    /// assert_eq!(intersection.capacity(), HashSet::<i32>::with_capacity(2).capacity());
+    /// ```
+    pub fn intersection_set(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        S: Clone,
+    {
+        let (smaller, larger) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let common = smaller.hashed_with(larger, true);
+
+        let mut set = Self::with_capacity_and_hasher_in(
+            common.len(),
+            larger.map.hash_builder.clone(),
+            larger.map.table.allocator().clone(),
+        );
+        for (hash, value) in common {
+            // SAFETY: The set was allocated with room for all the values.
+            unsafe {
+                set.map.table.insert_no_grow(hash, (value.clone(), ()));
+            }
+        }
+        set
+    }
+
+    /// Returns the values which are in `self` or `other`, without duplicates,
+    /// as a new set.
+    ///
+    /// Unlike collecting [`union`], this clones the table of the larger set
+    /// without hashing its values, and only hashes the values of the smaller
+    /// set, once each, to find those missing from the larger one. The result
+    /// grows at most once, to exactly the size it needs, in which case the
+    /// values of the larger set are hashed once to place them in the new
+    /// table.
+    ///
+    /// [`union`]: HashSet::union
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let a: HashSet<_> = [1, 2, 3].into_iter().collect();
+    /// let b: HashSet<_> = [4, 2, 3, 4].into_iter().collect();
+    ///
+    /// let union = a.union_set(&b);
+    /// assert_eq!(union, [1, 2, 3, 4].into_iter().collect());
+    /// ```
+    pub fn union_set(&self, other: &Self) -> Self
+    where
+        T: Clone,
+        S: Clone,
+    {
+        let (smaller, larger) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let missing = smaller.hashed_with(larger, false);
+
+        let mut set = if missing.len() <= larger.capacity() - larger.len() {
+            larger.clone()
+        } else {
+            let mut set = Self::with_capacity_and_hasher_in(
+                larger.len() + missing.len(),
+                larger.map.hash_builder.clone(),
+                larger.map.table.allocator().clone(),
+            );
+            for value in larger {
+                let hash = map::make_hash::<T, S>(&set.map.hash_builder, value);
+                // SAFETY: The set was allocated with room for all the values.
+                unsafe {
+                    set.map.table.insert_no_grow(hash, (value.clone(), ()));
+                }
+            }
+            set
+        };
+        for (hash, value) in missing {
+            // SAFETY: There is room for all the missing values, either in the
+            // spare capacity of the clone or in the new set.
+            unsafe {
+                set.map.table.insert_no_grow(hash, (value.clone(), ()));
+            }
+        }
+        set
+    }
+
+    /// Hashes every value of `self` with the hasher of `other`, and returns
+    /// the hashes and values of those which are in `other` if `present` is
+    /// `true`, or of those which are not if it is `false`.
+    fn hashed_with<'a>(&'a self, other: &Self, present: bool) -> Vec<(u64, &'a T)> {
+        // SAFETY: The buckets outlive the iterator since `self` is borrowed.
+        unsafe { self.map.table.iter() }
+            .filter_map(|bucket| {
+                let (value, ()) = unsafe { bucket.as_ref() };
+                let hash = map::make_hash::<T, S>(&other.map.hash_builder, value);
+                let found = other
+                    .map
+                    .table
+                    .find(hash, map::equivalent_key(value))
+                    .is_some();
+                (found == present).then(|| (hash, value))
+            })
+            .collect()
+    }
+
+    /// Removes the values which are in `other` from `self`.
+    ///
+    /// Unlike collecting [`difference`] into a new set, this erases the
+    /// values from the table of `self` in place and never reallocates it. If
+    /// `other` is the smaller set, its values are looked up in `self`,
+    /// otherwise the table of `self` is scanned a group at a time and each
+    /// of its values is looked up in `other`.
+    ///
+    /// [`difference`]: HashSet::difference
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut a: HashSet<_> = [1, 2, 3].into_iter().collect();
+    /// let b: HashSet<_> = [4, 2, 3, 4].into_iter().collect();
+    ///
+    /// a.difference_in_place(&b);
+    /// assert_eq!(a, [1].into_iter().collect());
+    /// ```
+    pub fn difference_in_place(&mut self, other: &Self) {
+        if other.len() < self.len() {
+            for value in other {
+                let hash = map::make_hash::<T, S>(&self.map.hash_builder, value);
+                self.map
+                    .table
+                    .remove_entry(hash, map::equivalent_key(value));
+            }
+        } else {
+            self.retain(|value| !other.contains(value));
+        }
+    }
+
+    /// Removes the values which are not in `other` from `self`, leaving only
+    /// the values which are in both.
+    ///
+    /// Unlike collecting [`intersection`] into a new set, this scans the
+    /// table of `self` a group at a time, erasing the values missing from
+    /// `other` in place, and never reallocates it.
+    ///
+    /// [`intersection`]: HashSet::intersection
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut a: HashSet<_> = [1, 2, 3].into_iter().collect();
+    /// let b: HashSet<_> = [4, 2, 3, 4].into_iter().collect();
+    ///
+    /// a.intersect_in_place(&b);
+    /// assert_eq!(a, [2, 3].into_iter().collect());
+    /// ```
+    pub fn intersect_in_place(&mut self, other: &Self) {
+        if other.is_empty() {
+            self.clear();
+        } else {
+            self.retain(|value| other.contains(value));
+        }
+    }
+
+    /// Replaces `self` with the values which are in `self` or in `other`,
+    /// but not in both.
+    ///
+    /// The values of `other` are looked up in `self` once each: those found
+    /// are erased in place, and clones of the others are inserted after
+    /// growing the table at most once, to exactly the size it needs. Unlike
+    /// collecting [`symmetric_difference`] into a new set, the values of
+    /// `self` are never moved unless the table has to grow.
+    ///
+    /// [`symmetric_difference`]: HashSet::symmetric_difference
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let mut a: HashSet<_> = [1, 2, 3].into_iter().collect();
+    /// let b: HashSet<_> = [4, 2, 3, 4].into_iter().collect();
+    ///
+    /// a.symmetric_difference_in_place(&b);
+    /// assert_eq!(a, [1, 4].into_iter().collect());
+    /// ```
+    pub fn symmetric_difference_in_place(&mut self, other: &Self)
+    where
+        T: Clone,
+    {
+        let mut missing = Vec::new();
+        for value in other {
+            let hash = map::make_hash::<T, S>(&self.map.hash_builder, value);
+            if self
+                .map
+                .table
+                .remove_entry(hash, map::equivalent_key(value))
+                .is_none()
+            {
+                missing.push((hash, value));
+            }
+        }
+
+        self.map.table.reserve(
+            missing.len(),
+            map::make_hasher::<T, (), S>(&self.map.hash_builder),
+        );
+        for (hash, value) in missing {
+            // SAFETY: The table was reserved with room for all the missing
+            // values, none of which is in it.
+            unsafe {
+                self.map.table.insert_no_grow(hash, (value.clone(), ()));
+            }
+        }
+    }
+
     /// Returns `true` if the set contains a value.
     ///
     /// The value may be any borrowed form of the set's value type, but
@@ -1027,6 +1438,9 @@ where
     /// Returns `true` if `self` has no elements in common with `other`.
     /// This is equivalent to checking for an empty intersection.
     ///
+    /// Only the elements of the smaller set are looked up in the larger one,
+    /// and the check stops at the first element the sets have in common.
+    ///
     /// # Examples
     ///
     /// ```
@@ -1042,12 +1456,24 @@ where
     /// assert_eq!(a.is_disjoint(&b), false);
     /// ```
     pub fn is_disjoint(&self, other: &Self) -> bool {
-        self.iter().all(|v| !other.contains(v))
+        if core::ptr::eq(self, other) {
+            return self.is_empty();
+        }
+        let (smaller, larger) = if self.len() <= other.len() {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        smaller.iter().all(|v| !larger.contains(v))
     }
 
     /// Returns `true` if the set is a subset of another,
     /// i.e., `other` contains at least all the values in `self`.
     ///
+    /// No element is looked up if `self` is larger than `other` or is the
+    /// same set, and the check stops at the first element missing from
+    /// `other`.
+    ///
     /// # Examples
     ///
     /// ```
@@ -1063,6 +1489,9 @@ where
     /// assert_eq!(set.is_subset(&sup), false);
     /// ```
     pub fn is_subset(&self, other: &Self) -> bool {
+        if core::ptr::eq(self, other) {
+            return true;
+        }
         self.len() <= other.len() && self.iter().all(|v| other.contains(v))
     }
 
@@ -1254,7 +1683,7 @@ where
             return false;
         }
 
-        self.iter().all(|key| other.contains(key))
+        self.map == other.map
     }
 }
 
@@ -1266,6 +1695,39 @@ where
 {
 }
 
+/// Hashes the set like a map with unit values: the length of the set and the
+/// wrapping sum of the hashes of its values, computed with a fixed seed, so
+/// that equal sets hash the same whatever the order of their tables.
+///
+/// Only available with the `unordered-hash` feature.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "unordered-hash")]
+/// # fn test() {
+/// use hashbrown::{HashMap, HashSet};
+///
+/// let mut groups: HashMap<HashSet<i32>, &str> = HashMap::new();
+/// groups.insert([1, 2, 3].into(), "small");
+/// assert_eq!(groups.get(&HashSet::from([3, 2, 1])), Some(&"small"));
+/// # }
+/// # fn main() {
+/// #     #[cfg(feature = "unordered-hash")]
+/// #     test()
+/// # }
+/// ```
+#[cfg(feature = "unordered-hash")]
+impl<T, S, A> Hash for HashSet<T, S, A>
+where
+    T: Hash,
+    A: Allocator + Clone,
+{
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.map.hash(state);
+    }
+}
+
 impl<T, S, A> fmt::Debug for HashSet<T, S, A>
 where
     T: fmt::Debug,
@@ -1293,14 +1755,14 @@ where
 {
     #[cfg_attr(feature = "inline-more", inline)]
     fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
-        let mut set = Self::with_hasher_in(Default::default(), Default::default());
+        let mut set = Self::with_hasher_in(S::default(), A::default());
         set.extend(iter);
         set
     }
 }
 
 // The default hasher is used to match the std implementation signature
-#[cfg(feature = "ahash")]
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
 impl<T, A, const N: usize> From<[T; N]> for HashSet<T, DefaultHashBuilder, A>
 where
     T: Eq + Hash,
@@ -1315,8 +1777,21 @@ where
     /// let set2: HashSet<_> = [1, 2, 3, 4].into();
     /// assert_eq!(set1, set2);
     /// ```
+    ///
+    /// The table is allocated once, with room for exactly `N` elements:
+    ///
+    /// ```
+    /// use hashbrown::HashSet;
+    ///
+    /// let set: HashSet<_> = HashSet::from([1, 2, 3]);
+    /// assert_eq!(set.capacity(), HashSet::<i32>::with_capacity(3).capacity());
+    /// ```
     fn from(arr: [T; N]) -> Self {
-        arr.into_iter().collect()
+        let mut set = Self::with_capacity_and_hasher_in(N, Default::default(), A::default());
+        for value in arr {
+            set.insert(value);
+        }
+        set
     }
 }
 
@@ -1786,6 +2261,15 @@ impl<K, F, A: Allocator + Clone> FusedIterator for DrainFilter<'_, K, F, A> wher
 {
 }
 
+impl<K, F, A: Allocator + Clone> fmt::Debug for DrainFilter<'_, K, F, A>
+where
+    F: FnMut(&K) -> bool,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrainFilter").finish_non_exhaustive()
+    }
+}
+
 impl<T, S, A: Allocator + Clone> Clone for Intersection<'_, T, S, A> {
     #[cfg_attr(feature = "inline-more", inline)]
     fn clone(&self) -> Self {
@@ -2654,6 +3138,136 @@ mod test_set {
         assert!(a.contains(&4));
     }
 
+    #[test]
+    fn test_no_space_for_values() {
+        use crate::raw::RawTable;
+        use core::alloc::Layout;
+
+        // The unit values of a set take no space and add no padding, so the
+        // buckets are exactly as large as the elements.
+        fn check<T: Eq + core::hash::Hash + From<u8>>() {
+            assert_eq!(Layout::new::<(T, ())>(), Layout::new::<T>());
+            let set: HashSet<T> = (0..100).map(T::from).collect();
+            let table: RawTable<T> = RawTable::with_capacity(set.capacity());
+            assert_eq!(set.map.table.buckets(), table.buckets());
+            assert_eq!(set.map.table.allocation_info().1, table.allocation_info().1);
+        }
+        check::<u8>();
+        check::<u16>();
+        check::<u64>();
+        check::<u128>();
+    }
+
+    #[test]
+    fn test_subset_disjoint_shortcuts() {
+        let small: HashSet<i32> = [5, 500].into_iter().collect();
+        let large: HashSet<i32> = (0..100).collect();
+        assert!(!small.is_disjoint(&large));
+        assert!(!large.is_disjoint(&small));
+        assert!(!small.is_subset(&large));
+        assert!(!large.is_superset(&small));
+
+        let outside: HashSet<i32> = [-1, 100].into_iter().collect();
+        assert!(outside.is_disjoint(&large));
+        assert!(large.is_disjoint(&outside));
+
+        assert!(large.is_subset(&large));
+        assert!(large.is_superset(&large));
+        assert!(!large.is_disjoint(&large));
+        let empty: HashSet<i32> = HashSet::new();
+        assert!(empty.is_disjoint(&empty));
+        assert!(empty.is_disjoint(&large));
+        assert!(large.is_disjoint(&empty));
+        assert!(empty.is_subset(&large));
+    }
+
+    #[test]
+    fn test_intersection_set() {
+        let a: HashSet<i32> = (0..100).collect();
+        let b: HashSet<i32> = (50..1000).step_by(2).collect();
+        let expected: HashSet<i32> = a.intersection(&b).copied().collect();
+        for set in [a.intersection_set(&b), b.intersection_set(&a)] {
+            assert_eq!(set, expected);
+            assert_eq!(set.capacity(), HashSet::<i32>::with_capacity(25).capacity());
+        }
+        assert!(a.intersection_set(&HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_union_set() {
+        let a: HashSet<i32> = (0..100).collect();
+        let expected: HashSet<i32> = (0..1000).filter(|i| i % 2 == 0 || *i < 100).collect();
+
+        // The larger set has to grow.
+        let b: HashSet<i32> = (50..1000).step_by(2).collect();
+        assert_eq!(a.union_set(&b), expected);
+        assert_eq!(b.union_set(&a), expected);
+
+        // The larger set has room for the missing values.
+        let mut b = HashSet::with_capacity(1000);
+        b.extend((50..1000).step_by(2));
+        let union = a.union_set(&b);
+        assert_eq!(union, expected);
+        assert_eq!(union.capacity(), b.capacity());
+
+        assert_eq!(a.union_set(&HashSet::new()), a);
+    }
+
+    #[test]
+    #[cfg(feature = "unordered-hash")]
+    fn test_unordered_hash() {
+        let mut sets: HashSet<HashSet<i32>> = HashSet::new();
+        assert!(sets.insert((0..100).collect()));
+        assert!(!sets.insert((0..100).rev().collect()));
+        assert!(sets.insert((1..100).collect()));
+        assert!(sets.contains(&(0..100).collect::<HashSet<_>>()));
+        assert_eq!(sets.len(), 2);
+    }
+
+    #[test]
+    fn test_set_ops_in_place() {
+        let a: HashSet<i32> = (0..100).collect();
+        let b: HashSet<i32> = (50..1000).step_by(2).collect();
+
+        for (x, y) in [(&a, &b), (&b, &a)] {
+            let mut set = x.clone();
+            let buckets = set.map.table.buckets();
+            set.difference_in_place(y);
+            assert_eq!(set, x.difference(y).copied().collect());
+            assert_eq!(set.map.table.buckets(), buckets);
+
+            let mut set = x.clone();
+            set.intersect_in_place(y);
+            assert_eq!(set, x.intersection(y).copied().collect());
+            assert_eq!(set.map.table.buckets(), buckets);
+
+            let mut set = x.clone();
+            set.symmetric_difference_in_place(y);
+            assert_eq!(set, x.symmetric_difference(y).copied().collect());
+        }
+
+        let mut set = a.clone();
+        set.intersect_in_place(&HashSet::new());
+        assert!(set.is_empty());
+        set.symmetric_difference_in_place(&a);
+        assert_eq!(set, a);
+        set.difference_in_place(&a.clone());
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn test_take_any() {
+        let mut set: HashSet<i32> = (0..50).collect();
+        let mut taken = Vec::new();
+        while let Some(value) = set.take_any() {
+            assert!(!set.contains(&value));
+            taken.push(value);
+        }
+        taken.sort_unstable();
+        assert_eq!(taken, (0..50).collect::<Vec<_>>());
+        assert_eq!(set.take_any(), None);
+    }
+
     #[test]
     fn test_from_iter() {
         let xs = [1, 2, 2, 3, 4, 5, 6, 7, 8, 9];
@@ -2665,6 +3279,10 @@ mod test_set {
         }
 
         assert_eq!(set.iter().len(), xs.len() - 1);
+        assert_eq!(
+            set.capacity(),
+            HashSet::<i32>::with_capacity(xs.len()).capacity()
+        );
     }
 
     #[test]
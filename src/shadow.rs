@@ -0,0 +1,381 @@
+//! A map which checks itself against the standard library's `HashMap`.
+//!
+//! [`ShadowHashMap`] applies every operation both to a [`HashMap`] of this
+//! crate and to a [`std::collections::HashMap`], and panics as soon as the two
+//! disagree. It is meant for integration tests of code which uses this crate,
+//! to catch bugs in the table implementation under realistic workloads.
+//!
+//! This module is only available if the `shadow` feature is enabled.
+
+use crate::map::{DefaultHashBuilder, HashMap};
+use core::borrow::Borrow;
+use core::fmt::{self, Debug};
+use core::hash::{BuildHasher, Hash};
+use std::collections::HashMap as StdHashMap;
+
+/// A [`HashMap`] which mirrors every operation into a
+/// [`std::collections::HashMap`] and asserts that both give the same results.
+///
+/// Every method panics if the result of the operation, or the length of the
+/// map after it, differs between the two maps. Methods which touch the whole
+/// table, like [`retain`] or [`shrink_to_fit`], also compare the full
+/// contents of the maps afterwards, as does [`check`].
+///
+/// Keys and values are cloned into the shadow map, so this is only meant for
+/// tests.
+///
+/// [`retain`]: ShadowHashMap::retain
+/// [`shrink_to_fit`]: ShadowHashMap::shrink_to_fit
+/// [`check`]: ShadowHashMap::check
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::shadow::ShadowHashMap;
+///
+/// let mut map = ShadowHashMap::new();
+/// assert_eq!(map.insert(1, "a"), None);
+/// assert_eq!(map.insert(1, "b"), Some("a"));
+/// assert_eq!(map.get(&1), Some(&"b"));
+/// map.retain(|_, v| *v == "b");
+/// assert_eq!(map.len(), 1);
+/// map.check();
+/// ```
+pub struct ShadowHashMap<K, V, S = DefaultHashBuilder> {
+    map: HashMap<K, V, S>,
+    shadow: StdHashMap<K, V>,
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> ShadowHashMap<K, V, DefaultHashBuilder> {
+    /// Creates an empty `ShadowHashMap`.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn new() -> Self {
+        Self::with_hasher(DefaultHashBuilder::default())
+    }
+
+    /// Creates an empty `ShadowHashMap` with at least the specified capacity.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, DefaultHashBuilder::default())
+    }
+}
+
+impl<K, V, S> ShadowHashMap<K, V, S> {
+    /// Creates an empty `ShadowHashMap` whose checked map uses the given hash
+    /// builder.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_hasher(hash_builder),
+            shadow: StdHashMap::new(),
+        }
+    }
+
+    /// Creates an empty `ShadowHashMap` with at least the specified capacity,
+    /// whose checked map uses the given hash builder.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+            shadow: StdHashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Returns a reference to the checked map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn inner(&self) -> &HashMap<K, V, S> {
+        &self.map
+    }
+
+    /// Returns the checked map, dropping the shadow map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn into_inner(self) -> HashMap<K, V, S> {
+        self.map
+    }
+
+    /// Returns the number of elements in the map.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn len(&self) -> usize {
+        self.check_len();
+        self.map.len()
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of elements the map can hold without reallocating.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
+
+    /// Clears the map, removing all key-value pairs.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.shadow.clear();
+        self.check_len();
+    }
+
+    fn check_len(&self) {
+        assert_eq!(
+            self.map.len(),
+            self.shadow.len(),
+            "the map and its shadow have different lengths"
+        );
+    }
+}
+
+impl<K, V, S> ShadowHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Clone + PartialEq + Debug,
+    S: BuildHasher,
+{
+    /// Compares the full contents of the map with those of its shadow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any key is missing from one of the maps, or maps to a
+    /// different value.
+    pub fn check(&self) {
+        self.check_len();
+        let mut count = 0;
+        for (k, v) in self.map.iter() {
+            assert_eq!(
+                Some(v),
+                self.shadow.get(k),
+                "the map and its shadow disagree on the value of {:?}",
+                k
+            );
+            count += 1;
+        }
+        assert_eq!(
+            count,
+            self.map.len(),
+            "iteration yields a wrong number of items"
+        );
+        for k in self.shadow.keys() {
+            assert!(self.map.contains_key(k), "the map lost {:?}", k);
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        let value = self.map.get(k);
+        assert_eq!(
+            value,
+            self.shadow.get(k),
+            "the map and its shadow disagree on the value of {:?}",
+            k
+        );
+        value
+    }
+
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        let entry = self.map.get_key_value(k);
+        assert_eq!(
+            entry,
+            self.shadow.get_key_value(k),
+            "the map and its shadow disagree on the entry of {:?}",
+            k
+        );
+        entry
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        self.get(k).is_some()
+    }
+
+    /// Inserts a key-value pair into the map, returning the old value of the
+    /// key, if any.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let expected = self.shadow.insert(k.clone(), v.clone());
+        let old = self.map.insert(k, v);
+        assert_eq!(
+            old, expected,
+            "the map and its shadow replaced different values"
+        );
+        self.check_len();
+        old
+    }
+
+    /// Removes a key from the map, returning its value, if any.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        self.remove_entry(k).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value, if
+    /// any.
+    #[cfg_attr(feature = "inline-more", inline)]
+    pub fn remove_entry<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+    {
+        let expected = self.shadow.remove_entry(k);
+        let entry = self.map.remove_entry(k);
+        assert_eq!(
+            entry, expected,
+            "the map and its shadow removed different entries for {:?}",
+            k
+        );
+        self.check_len();
+        entry
+    }
+
+    /// Calls `f` on the value of the key in both maps, returning whether the
+    /// key was present.
+    ///
+    /// `f` must make the same change to both values; it is called on the
+    /// shadow's value first.
+    pub fn update<Q, F>(&mut self, k: &Q, mut f: F) -> bool
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Eq + Debug,
+        F: FnMut(&mut V),
+    {
+        let expected = self.shadow.get_mut(k).map(&mut f);
+        let value = self.map.get_mut(k).map(&mut f);
+        assert_eq!(
+            value.is_some(),
+            expected.is_some(),
+            "the map and its shadow disagree on the presence of {:?}",
+            k
+        );
+        self.get(k);
+        value.is_some()
+    }
+
+    /// Retains only the elements specified by the predicate, then compares
+    /// the full contents of both maps.
+    ///
+    /// `f` is called once for each element of each map, so it should only
+    /// depend on its arguments.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.shadow.retain(|k, v| f(k, v));
+        self.map.retain(|k, v| f(k, v));
+        self.check();
+    }
+
+    /// Reserves capacity for at least `additional` more elements, then
+    /// compares the full contents of both maps.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+        self.shadow.reserve(additional);
+        self.check();
+    }
+
+    /// Shrinks the capacity of the map as much as possible, then compares the
+    /// full contents of both maps.
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+        self.shadow.shrink_to_fit();
+        self.check();
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for ShadowHashMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Debug,
+    V: Clone + PartialEq + Debug,
+    S: BuildHasher,
+{
+    fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+#[cfg(any(feature = "ahash", feature = "siphash", feature = "deterministic"))]
+impl<K, V> Default for ShadowHashMap<K, V, DefaultHashBuilder> {
+    #[cfg_attr(feature = "inline-more", inline)]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Debug, V: Debug, S> Debug for ShadowHashMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.map.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_shadow {
+    use super::ShadowHashMap;
+    use std::string::{String, ToString};
+
+    #[test]
+    fn test_mixed_operations() {
+        let mut map = ShadowHashMap::new();
+        for i in 0..1000u32 {
+            map.insert(i % 300, i);
+            if i % 5 == 0 {
+                map.remove(&(i / 3));
+            }
+            if i % 7 == 0 {
+                map.update(&(i % 100), |v| *v += 1);
+            }
+        }
+        map.check();
+        map.retain(|k, _| k % 2 == 0);
+        map.shrink_to_fit();
+        map.reserve(1000);
+        map.extend((0..50).map(|i| (i, i)));
+        assert!(map.contains_key(&0));
+        map.clear();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_borrowed_keys() {
+        let mut map: ShadowHashMap<String, usize> = ShadowHashMap::new();
+        map.insert("a".to_string(), 1);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get_key_value("a"), Some((&"a".to_string(), &1)));
+        assert_eq!(map.remove_entry("a"), Some(("a".to_string(), 1)));
+        assert_eq!(map.get("a"), None);
+    }
+
+    #[test]
+    #[should_panic = "disagree on the value"]
+    fn test_detects_divergence() {
+        let mut map = ShadowHashMap::new();
+        map.insert(1, 1);
+        // Change a value behind the shadow's back.
+        map.map.insert(1, 2);
+        map.get(&1);
+    }
+}
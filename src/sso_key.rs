@@ -0,0 +1,267 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt::{self, Debug, Display};
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+use core::str;
+
+/// Longest string which [`SsoKey`] stores inline.
+const INLINE_CAP: usize = 22;
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, buf: [u8; INLINE_CAP] },
+    Heap(Box<str>),
+}
+
+/// A string key which stores short strings inline.
+///
+/// Strings of up to 22 bytes are kept in the key itself, so a
+/// `HashMap<SsoKey, V>` stores them directly in the bucket and comparing a
+/// candidate key during a lookup does not have to follow a pointer to the
+/// heap. Longer strings are stored in a `Box<str>`. On 64-bit targets an
+/// `SsoKey` is the same size as a `String`.
+///
+/// `SsoKey` hashes and compares like `str` and implements `Borrow<str>`, so
+/// maps keyed by it can be queried with plain `&str` keys.
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::{HashMap, SsoKey};
+///
+/// let mut map = HashMap::new();
+/// map.insert(SsoKey::from("short"), 1);
+/// map.insert(SsoKey::from("a key which is too long to be stored inline"), 2);
+///
+/// assert!(SsoKey::from("short").is_inline());
+/// assert_eq!(map.get("short"), Some(&1));
+/// assert_eq!(map.get("a key which is too long to be stored inline"), Some(&2));
+/// ```
+#[derive(Clone)]
+pub struct SsoKey {
+    repr: Repr,
+}
+
+impl SsoKey {
+    /// Creates a key from a string slice, copying it inline if it is short
+    /// enough and to the heap otherwise.
+    #[inline]
+    pub fn new(s: &str) -> Self {
+        if s.len() <= INLINE_CAP {
+            let mut buf = [0; INLINE_CAP];
+            buf[..s.len()].copy_from_slice(s.as_bytes());
+            Self {
+                repr: Repr::Inline {
+                    len: s.len() as u8,
+                    buf,
+                },
+            }
+        } else {
+            Self {
+                repr: Repr::Heap(Box::from(s)),
+            }
+        }
+    }
+
+    /// Returns the key as a string slice.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self.repr {
+            Repr::Inline { len, ref buf } => {
+                // SAFETY: the buffer was copied from a `str` of length `len`.
+                unsafe { str::from_utf8_unchecked(&buf[..len as usize]) }
+            }
+            Repr::Heap(ref s) => s,
+        }
+    }
+
+    /// Returns `true` if the string is stored inline rather than on the heap.
+    #[inline]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.repr, Repr::Inline { .. })
+    }
+
+    /// Converts the key into a `String`.
+    #[inline]
+    pub fn into_string(self) -> String {
+        match self.repr {
+            Repr::Inline { .. } => String::from(self.as_str()),
+            Repr::Heap(s) => s.into_string(),
+        }
+    }
+}
+
+impl Deref for SsoKey {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for SsoKey {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for SsoKey {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for SsoKey {
+    #[inline]
+    fn from(s: &str) -> Self {
+        Self::new(s)
+    }
+}
+
+impl From<String> for SsoKey {
+    #[inline]
+    fn from(s: String) -> Self {
+        if s.len() <= INLINE_CAP {
+            Self::new(&s)
+        } else {
+            Self {
+                repr: Repr::Heap(s.into_boxed_str()),
+            }
+        }
+    }
+}
+
+impl From<SsoKey> for String {
+    #[inline]
+    fn from(key: SsoKey) -> Self {
+        key.into_string()
+    }
+}
+
+impl Default for SsoKey {
+    #[inline]
+    fn default() -> Self {
+        Self::new("")
+    }
+}
+
+// `Hash`, `Eq` and `Ord` must agree with `str` for the `Borrow<str>`
+// implementation to be sound for hash map lookups.
+impl Hash for SsoKey {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialEq for SsoKey {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for SsoKey {}
+
+impl PartialEq<str> for SsoKey {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for SsoKey {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for SsoKey {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SsoKey {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Debug for SsoKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for SsoKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod test_sso_key {
+    use super::{SsoKey, INLINE_CAP};
+    use crate::map::make_hash;
+    use crate::HashMap;
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn test_inline_boundary() {
+        let short = "x".repeat(INLINE_CAP);
+        let long = "x".repeat(INLINE_CAP + 1);
+        assert!(SsoKey::from(short.as_str()).is_inline());
+        assert!(!SsoKey::from(long.as_str()).is_inline());
+        assert!(SsoKey::from(short.clone()).is_inline());
+        assert_eq!(SsoKey::from(long.clone()).as_str(), long);
+        assert_eq!(String::from(SsoKey::from(short.as_str())), short);
+        assert!(SsoKey::default().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_size() {
+        assert_eq!(
+            core::mem::size_of::<SsoKey>(),
+            core::mem::size_of::<String>()
+        );
+    }
+
+    #[test]
+    fn test_hash_matches_str() {
+        let map: HashMap<SsoKey, usize> = HashMap::new();
+        let hasher = map.hasher();
+        for s in ["", "abc", "a string longer than the inline capacity"] {
+            let key = SsoKey::from(s);
+            assert_eq!(make_hash(hasher, &key), make_hash(hasher, s));
+        }
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            let key = if i % 2 == 0 {
+                i.to_string()
+            } else {
+                "long key number ".repeat(2) + &i.to_string()
+            };
+            map.insert(SsoKey::from(key), i);
+        }
+        assert_eq!(map.get("42"), Some(&42));
+        assert_eq!(map.get("long key number long key number 43"), Some(&43));
+        assert_eq!(map.get("43"), None);
+        assert_eq!(map.remove("0"), Some(0));
+        assert_eq!(map.len(), 99);
+    }
+}
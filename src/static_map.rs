@@ -0,0 +1,478 @@
+//! Maps laid out at compile time.
+//!
+//! A [`StaticMap`] is built by a `const fn` from a literal list of entries,
+//! usually through the [`static_map!`] macro, so that its control bytes are
+//! computed by the compiler and the whole map can live in a `static`. At
+//! runtime, lookups probe the control bytes a group at a time with the same
+//! SIMD matching as [`HashMap`].
+//!
+//! Since the keys have to be hashed at compile time, only the key types
+//! implementing [`StaticKey`] are supported: `&'static str`,
+//! `&'static [u8]` and `u64`.
+//!
+//! [`HashMap`]: crate::HashMap
+//! [`static_map!`]: crate::static_map!
+
+use crate::hasher::hash_integer;
+use crate::raw::{Group, EMPTY};
+use core::fmt::{self, Debug};
+
+/// Seed of the compile-time hash functions.
+const SEED: u64 = 0x243f_6a88_85a3_08d3;
+
+/// Hashes a byte string at compile time or at runtime.
+const fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hash = hash_integer(SEED ^ bytes.len() as u64);
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut chunk = 0u64;
+        let mut j = 0;
+        while j < 8 && i + j < bytes.len() {
+            chunk |= (bytes[i + j] as u64) << (j * 8);
+            j += 1;
+        }
+        hash = hash_integer(hash ^ chunk);
+        i += 8;
+    }
+    hash
+}
+
+/// Hashes an integer at compile time or at runtime.
+const fn hash_u64(value: u64) -> u64 {
+    hash_integer(hash_integer(SEED ^ value))
+}
+
+/// Returns the tag stored in the control byte of a key with the given hash.
+const fn tag(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Returns the number of buckets which [`static_map!`] uses for `len`
+/// entries: the smallest power of two which keeps the table at most 7/8 full
+/// and has room for at least two groups.
+///
+/// [`static_map!`]: crate::static_map!
+pub const fn buckets_for(len: usize) -> usize {
+    let min = len + len / 7 + 1;
+    let mut buckets = 2 * Group::WIDTH;
+    while buckets < min {
+        buckets *= 2;
+    }
+    buckets
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for &'static str {}
+    impl Sealed for &'static [u8] {}
+    impl Sealed for u64 {}
+}
+
+/// A key type which can be hashed at compile time, for use in a
+/// [`StaticMap`].
+///
+/// This trait is sealed: it is implemented for `&'static str`,
+/// `&'static [u8]` and `u64`, each of which has its own `const` constructor
+/// on `StaticMap`.
+pub trait StaticKey: private::Sealed {
+    /// The type of the keys passed to lookups.
+    type Borrowed: ?Sized;
+
+    /// Hashes a key the same way as the `const` constructor of `StaticMap`.
+    fn static_hash(key: &Self::Borrowed) -> u64;
+
+    /// Returns `true` if the stored key is equal to `key`.
+    fn matches(&self, key: &Self::Borrowed) -> bool;
+}
+
+impl StaticKey for &'static str {
+    type Borrowed = str;
+
+    #[inline]
+    fn static_hash(key: &str) -> u64 {
+        hash_bytes(key.as_bytes())
+    }
+
+    #[inline]
+    fn matches(&self, key: &str) -> bool {
+        *self == key
+    }
+}
+
+impl StaticKey for &'static [u8] {
+    type Borrowed = [u8];
+
+    #[inline]
+    fn static_hash(key: &[u8]) -> u64 {
+        hash_bytes(key)
+    }
+
+    #[inline]
+    fn matches(&self, key: &[u8]) -> bool {
+        *self == key
+    }
+}
+
+impl StaticKey for u64 {
+    type Borrowed = u64;
+
+    #[inline]
+    fn static_hash(key: &u64) -> u64 {
+        hash_u64(*key)
+    }
+
+    #[inline]
+    fn matches(&self, key: &u64) -> bool {
+        self == key
+    }
+}
+
+/// A read-only hash map whose table is computed at compile time.
+///
+/// `N` is the number of entries and `B` the number of buckets, which must be
+/// a power of two larger than `N` and at least twice the group width;
+/// [`buckets_for`] returns a suitable value, and the [`static_map!`] macro
+/// fills in both parameters.
+///
+/// The entries are kept in the order they were given in. The table stores a
+/// control byte and the index of an entry for each bucket, and a key is
+/// looked up by probing the groups of control bytes for its tag, like in a
+/// [`HashMap`], and comparing it with the entries whose tags match. Groups
+/// are always loaded at multiples of the group width, so the table does not
+/// need the trailing control bytes of a `HashMap`. Since the group width
+/// depends on the target, the layout does too, but it is computed for the
+/// target the map is compiled for.
+///
+/// Building a map with two equal keys, or with keys whose 64-bit hashes are
+/// equal, fails at compile time.
+///
+/// Each supported key type has its own `new` constructor, so calls to it need
+/// the full type of the map; a type alias keeps them short.
+///
+/// [`static_map!`]: crate::static_map!
+/// [`HashMap`]: crate::HashMap
+///
+/// # Examples
+///
+/// ```
+/// use hashbrown::static_map::{buckets_for, StaticMap};
+///
+/// type Ports = StaticMap<&'static str, u16, 3, { buckets_for(3) }>;
+///
+/// static PORTS: Ports = Ports::new([("http", 80), ("https", 443), ("ssh", 22)]);
+///
+/// assert_eq!(PORTS.get("https"), Some(&443));
+/// assert_eq!(PORTS.get("ftp"), None);
+/// ```
+pub struct StaticMap<K: 'static, V: 'static, const N: usize, const B: usize> {
+    entries: [(K, V); N],
+    ctrl: [u8; B],
+    slots: [u32; B],
+}
+
+/// The control bytes and entry indices of a `StaticMap` with `B` buckets.
+struct Layout<const B: usize> {
+    ctrl: [u8; B],
+    slots: [u32; B],
+}
+
+/// Computes the layout of a table holding keys with the given hashes.
+const fn layout<const N: usize, const B: usize>(hashes: &[u64; N]) -> Layout<B> {
+    assert!(
+        B.is_power_of_two() && B >= 2 * Group::WIDTH,
+        "the number of buckets must be a power of two of at least twice the group width"
+    );
+    assert!(N < B, "the table must have more buckets than entries");
+    assert!(N <= u32::MAX as usize, "too many entries");
+    let groups = B / Group::WIDTH;
+    let mut ctrl = [EMPTY; B];
+    let mut slots = [0u32; B];
+    let mut i = 0;
+    while i < N {
+        let hash = hashes[i];
+        let mut group = hash as usize & (groups - 1);
+        let mut stride = 0;
+        let mut placed = false;
+        while !placed {
+            let mut bit = 0;
+            while bit < Group::WIDTH {
+                let pos = group * Group::WIDTH + bit;
+                if ctrl[pos] == EMPTY {
+                    ctrl[pos] = tag(hash);
+                    slots[pos] = i as u32;
+                    placed = true;
+                    break;
+                }
+                assert!(
+                    hashes[slots[pos] as usize] != hash,
+                    "duplicate key in static map"
+                );
+                bit += 1;
+            }
+            stride += 1;
+            group = (group + stride) & (groups - 1);
+        }
+        i += 1;
+    }
+    Layout { ctrl, slots }
+}
+
+impl<V, const N: usize, const B: usize> StaticMap<&'static str, V, N, B> {
+    /// Builds a map with string keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics, or fails to compile in a `const` context, if two keys are
+    /// equal or `B` is not a valid number of buckets for `N` entries.
+    pub const fn new(entries: [(&'static str, V); N]) -> Self {
+        let mut hashes = [0u64; N];
+        let mut i = 0;
+        while i < N {
+            hashes[i] = hash_bytes(entries[i].0.as_bytes());
+            i += 1;
+        }
+        let Layout { ctrl, slots } = layout::<N, B>(&hashes);
+        Self {
+            entries,
+            ctrl,
+            slots,
+        }
+    }
+}
+
+impl<V, const N: usize, const B: usize> StaticMap<&'static [u8], V, N, B> {
+    /// Builds a map with byte string keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics, or fails to compile in a `const` context, if two keys are
+    /// equal or `B` is not a valid number of buckets for `N` entries.
+    pub const fn new(entries: [(&'static [u8], V); N]) -> Self {
+        let mut hashes = [0u64; N];
+        let mut i = 0;
+        while i < N {
+            hashes[i] = hash_bytes(entries[i].0);
+            i += 1;
+        }
+        let Layout { ctrl, slots } = layout::<N, B>(&hashes);
+        Self {
+            entries,
+            ctrl,
+            slots,
+        }
+    }
+}
+
+impl<V, const N: usize, const B: usize> StaticMap<u64, V, N, B> {
+    /// Builds a map with integer keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics, or fails to compile in a `const` context, if two keys are
+    /// equal or `B` is not a valid number of buckets for `N` entries.
+    pub const fn new(entries: [(u64, V); N]) -> Self {
+        let mut hashes = [0u64; N];
+        let mut i = 0;
+        while i < N {
+            hashes[i] = hash_u64(entries[i].0);
+            i += 1;
+        }
+        let Layout { ctrl, slots } = layout::<N, B>(&hashes);
+        Self {
+            entries,
+            ctrl,
+            slots,
+        }
+    }
+}
+
+impl<K, V, const N: usize, const B: usize> StaticMap<K, V, N, B> {
+    /// Returns the number of elements in the map.
+    #[inline]
+    pub const fn len(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if the map contains no elements.
+    #[inline]
+    pub const fn is_empty(&self) -> bool {
+        N == 0
+    }
+
+    /// Returns the entries of the map, in the order they were given in.
+    #[inline]
+    pub const fn entries(&self) -> &[(K, V); N] {
+        &self.entries
+    }
+}
+
+impl<K: StaticKey, V, const N: usize, const B: usize> StaticMap<K, V, N, B> {
+    /// Returns the key-value pair corresponding to the supplied key.
+    #[inline]
+    pub fn get_key_value(&self, key: &K::Borrowed) -> Option<(&K, &V)> {
+        let hash = K::static_hash(key);
+        let tag = tag(hash);
+        let groups = B / Group::WIDTH;
+        let mut group = hash as usize & (groups - 1);
+        let mut stride = 0;
+        loop {
+            let start = group * Group::WIDTH;
+            // SAFETY: The group lies within the control bytes since `B` is a
+            // multiple of the group width.
+            let ctrl = unsafe { Group::load(self.ctrl.as_ptr().add(start)) };
+            for bit in ctrl.match_byte(tag) {
+                let (k, v) = &self.entries[self.slots[start + bit] as usize];
+                if k.matches(key) {
+                    return Some((k, v));
+                }
+            }
+            // There is an empty bucket since `N < B`, so this terminates.
+            if ctrl.match_empty().any_bit_set() {
+                return None;
+            }
+            stride += 1;
+            group = (group + stride) & (groups - 1);
+        }
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    #[inline]
+    pub fn get(&self, key: &K::Borrowed) -> Option<&V> {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns `true` if the map contains a value for the specified key.
+    #[inline]
+    pub fn contains_key(&self, key: &K::Borrowed) -> bool {
+        self.get_key_value(key).is_some()
+    }
+}
+
+impl<K: Debug, V: Debug, const N: usize, const B: usize> Debug for StaticMap<K, V, N, B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+/// Declares a `static` [`StaticMap`] whose table is computed at compile time.
+///
+/// The number of entries and of buckets are filled in from the list of
+/// entries. Keys can be string literals, byte string literals or `u64`
+/// values, and must be distinct.
+///
+/// [`StaticMap`]: crate::static_map::StaticMap
+///
+/// # Examples
+///
+/// ```
+/// hashbrown::static_map! {
+///     static KEYWORDS: &'static str => u8 = {
+///         "fn" => 1,
+///         "let" => 2,
+///         "match" => 3,
+///     };
+/// }
+///
+/// assert_eq!(KEYWORDS.get("let"), Some(&2));
+/// assert_eq!(KEYWORDS.get("loop"), None);
+/// assert_eq!(KEYWORDS.len(), 3);
+/// ```
+#[macro_export]
+macro_rules! static_map {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $k:ty => $v:ty = {
+        $($key:expr => $value:expr),* $(,)?
+    };) => {
+        $(#[$attr])*
+        $vis static $name: $crate::static_map!(@type $k, $v, $($key)*) =
+            <$crate::static_map!(@type $k, $v, $($key)*)>::new([$(($key, $value)),*]);
+    };
+    (@type $k:ty, $v:ty, $($key:expr)*) => {
+        $crate::static_map::StaticMap<
+            $k,
+            $v,
+            { $crate::static_map!(@count $($key)*) },
+            { $crate::static_map::buckets_for($crate::static_map!(@count $($key)*)) },
+        >
+    };
+    (@count $($key:expr)*) => {
+        <[()]>::len(&[$($crate::static_map!(@unit $key)),*])
+    };
+    (@unit $key:expr) => {
+        ()
+    };
+}
+
+#[cfg(test)]
+mod test_static_map {
+    use super::{buckets_for, StaticMap};
+
+    static_map! {
+        static NUMBERS: u64 => &'static str = {
+            1 => "one",
+            2 => "two",
+            3 => "three",
+        };
+    }
+
+    static_map! {
+        static EMPTY_MAP: &'static str => u8 = {};
+    }
+
+    #[test]
+    fn test_macro() {
+        assert_eq!(NUMBERS.get(&2), Some(&"two"));
+        assert_eq!(NUMBERS.get(&4), None);
+        assert_eq!(NUMBERS.entries()[0], (1, "one"));
+        assert!(EMPTY_MAP.is_empty());
+        assert_eq!(EMPTY_MAP.get("a"), None);
+    }
+
+    // Enough keys to fill several groups, with many tags in common.
+    const KEYS: [u64; 200] = {
+        let mut keys = [0; 200];
+        let mut i = 0;
+        while i < 200 {
+            keys[i] = (i as u64) << 40;
+            i += 1;
+        }
+        keys
+    };
+
+    #[test]
+    fn test_full_table() {
+        const LEN: usize = 200;
+        type Map = StaticMap<u64, usize, LEN, { buckets_for(LEN) }>;
+        const MAP: Map = {
+            let mut entries = [(0, 0); LEN];
+            let mut i = 0;
+            while i < LEN {
+                entries[i] = (KEYS[i], i);
+                i += 1;
+            }
+            Map::new(entries)
+        };
+        assert_eq!(buckets_for(LEN), 256);
+        for (i, key) in KEYS.iter().enumerate() {
+            assert_eq!(MAP.get(key), Some(&i));
+            assert_eq!(MAP.get(&(key + 1)), None);
+        }
+    }
+
+    #[test]
+    fn test_byte_keys() {
+        let map =
+            StaticMap::<&[u8], u8, 3, 32>::new([(b"", 0), (b"12345678", 8), (b"123456789", 9)]);
+        assert_eq!(map.get(b"12345678"), Some(&8));
+        assert_eq!(map.get(b"123456789"), Some(&9));
+        assert_eq!(map.get(b""), Some(&0));
+        assert_eq!(map.get(b"1234567"), None);
+    }
+
+    #[test]
+    #[should_panic = "duplicate key"]
+    fn test_duplicate_keys() {
+        let _ = StaticMap::<&str, u8, 2, 32>::new([("a", 1), ("a", 2)]);
+    }
+}
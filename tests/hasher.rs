@@ -32,6 +32,42 @@ fn random_state() {
     check::<std::collections::hash_map::RandomState>();
 }
 
+/// The `siphash` feature makes std's hasher the default one.
+#[cfg(feature = "siphash")]
+#[test]
+fn siphash_default() {
+    let map = hashbrown::HashMap::<i32, i32>::new();
+    let _: &std::collections::hash_map::RandomState = map.hasher();
+}
+
+/// The `deterministic` feature fixes the iteration order of the default hasher
+/// on every run and platform.
+#[cfg(all(feature = "deterministic", not(feature = "random-order")))]
+#[test]
+fn deterministic_order() {
+    let mut map = hashbrown::HashMap::new();
+    for i in 0..20u32 {
+        map.insert(i, ());
+    }
+    map.remove(&7);
+    map.insert(100, ());
+    let keys: Vec<u32> = map.keys().copied().collect();
+    assert_eq!(
+        keys,
+        [19, 10, 5, 0, 13, 8, 17, 4, 2, 1, 9, 14, 6, 18, 11, 12, 100, 3, 15, 16]
+    );
+
+    let set: hashbrown::HashSet<&str> = ["a", "b", "c", "d", "e", "f"].into_iter().collect();
+    let keys: Vec<&str> = set.into_iter().collect();
+    assert_eq!(keys, ["b", "d", "f", "a", "c", "e"]);
+}
+
+/// Use hashbrown's integer hasher.
+#[test]
+fn integer() {
+    check::<hashbrown::hash_map::IntegerHashBuilder>();
+}
+
 /// Use a constant 0 hash.
 #[test]
 fn zero() {
@@ -63,3 +99,33 @@ fn max() {
 
     check::<BuildHasherDefault<MaxHasher>>();
 }
+
+/// Use hashbrown's seedable hasher.
+#[cfg(feature = "ahash")]
+#[test]
+fn seeded_random_state() {
+    use hashbrown::hash_map::RandomState;
+    use std::hash::Hash;
+
+    check::<RandomState>();
+
+    fn hash(state: &RandomState, value: i32) -> u64 {
+        let mut hasher = state.build_hasher();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    assert_eq!(
+        hash(&RandomState::with_seed(1), 1),
+        hash(&RandomState::with_seed(1), 1)
+    );
+    assert_eq!(
+        hash(&RandomState::with_seeds(1, 2, 3, 4), 1),
+        hash(&RandomState::with_seeds(1, 2, 3, 4), 1)
+    );
+    assert_ne!(
+        hash(&RandomState::with_seed(1), 1),
+        hash(&RandomState::with_seed(2), 1)
+    );
+    assert_ne!(hash(&RandomState::new(), 1), hash(&RandomState::new(), 1));
+}
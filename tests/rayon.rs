@@ -4,6 +4,10 @@
 extern crate lazy_static;
 
 use hashbrown::{HashMap, HashSet};
+// With the `random-order` feature, sequential iterators start at a random
+// bucket while parallel ones don't, so their orders are only compared without
+// it.
+
 use rayon::iter::{
     IntoParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelExtend,
     ParallelIterator,
@@ -44,6 +48,7 @@ fn map_seq_par_equivalence_iter() {
     let mut vec_seq = MAP.iter().collect::<Vec<_>>();
     let mut vec_par = MAP.par_iter().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -77,6 +82,7 @@ fn map_seq_par_equivalence_keys() {
     let mut vec_seq = MAP.keys().collect::<Vec<_>>();
     let mut vec_par = MAP.par_keys().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -103,6 +109,7 @@ fn map_seq_par_equivalence_values() {
     let mut vec_seq = MAP.values().collect::<Vec<_>>();
     let mut vec_par = MAP.par_values().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -133,6 +140,7 @@ fn map_seq_par_equivalence_iter_mut() {
     let mut vec_seq = map1.iter_mut().collect::<Vec<_>>();
     let mut vec_par = map2.par_iter_mut().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -172,6 +180,7 @@ fn map_seq_par_equivalence_values_mut() {
     let mut vec_seq = map1.values_mut().collect::<Vec<_>>();
     let mut vec_par = map2.par_values_mut().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -196,6 +205,7 @@ fn map_seq_par_equivalence_into_iter() {
     let mut vec_seq = MAP.clone().into_iter().collect::<Vec<_>>();
     let mut vec_par = MAP.clone().into_par_iter().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -340,6 +350,7 @@ fn set_seq_par_equivalence_iter() {
     let mut vec_seq = SET.iter().collect::<Vec<_>>();
     let mut vec_par = SET.par_iter().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -364,6 +375,7 @@ fn set_seq_par_equivalence_into_iter() {
     let mut vec_seq = SET.clone().into_iter().collect::<Vec<_>>();
     let mut vec_par = SET.clone().into_par_iter().collect::<Vec<_>>();
 
+    #[cfg(not(feature = "random-order"))]
     assert_eq!(vec_seq, vec_par);
 
     // Do not depend on the exact order of values
@@ -531,3 +543,55 @@ fn set_seq_par_equivalence_union() {
 
     assert_eq3!(union_ab_seq, union_ab_par, *SET_UNION_AB);
 }
+
+#[test]
+fn map_seq_par_equivalence_retain() {
+    let mut map_seq = MAP.clone();
+    let mut map_par = MAP.clone();
+
+    map_seq.retain(|&k, v| {
+        *v += 1;
+        k < 'd'
+    });
+    map_par.par_retain(|&k, v| {
+        *v += 1;
+        k < 'd'
+    });
+
+    assert_eq!(map_seq, map_par);
+    assert_eq!(map_par.len(), 3);
+}
+
+#[test]
+fn map_seq_par_equivalence_drain_filter() {
+    let mut map_seq = MAP.clone();
+    let mut map_par = MAP.clone();
+
+    let drained_seq = map_seq
+        .drain_filter(|&k, _| k > 'b')
+        .collect::<HashMap<_, _>>();
+    let drained_par = map_par
+        .par_drain_filter(|&k, _| k > 'b')
+        .collect::<HashMap<_, _>>();
+
+    assert_eq!(map_seq, map_par);
+    assert_eq!(drained_seq, drained_par);
+    assert_eq!(drained_par.len(), 4);
+}
+
+#[test]
+fn set_seq_par_equivalence_retain_drain_filter() {
+    let mut set_seq = SET.clone();
+    let mut set_par = SET.clone();
+
+    set_seq.retain(|&c| c != 'a');
+    set_par.par_retain(|&c| c != 'a');
+    assert_eq!(set_seq, set_par);
+
+    let drained_seq = set_seq.drain_filter(|&c| c < 'd').collect::<HashSet<_>>();
+    let drained_par = set_par
+        .par_drain_filter(|&c| c < 'd')
+        .collect::<HashSet<_>>();
+    assert_eq!(set_seq, set_par);
+    assert_eq!(drained_seq, drained_par);
+}
@@ -63,3 +63,73 @@ fn set_serde_tokens() {
         ],
     );
 }
+
+#[test]
+fn map_deserialize_in_place() {
+    use serde::de::value::{Error, MapDeserializer};
+    use serde::Deserialize;
+
+    let mut map = FnvHashMap::<u32, u32>::default();
+    map.extend((0..100).map(|i| (i, i)));
+    let capacity = map.capacity();
+
+    let entries = (0..50).map(|i| (i, i * 2));
+    let deserializer = MapDeserializer::<_, Error>::new(entries);
+    FnvHashMap::deserialize_in_place(deserializer, &mut map).unwrap();
+
+    assert_eq!(map.len(), 50);
+    assert_eq!(map.capacity(), capacity);
+    assert!(map.iter().all(|(k, v)| *v == k * 2));
+}
+
+#[test]
+fn set_deserialize_in_place() {
+    use serde::de::value::{Error, SeqDeserializer};
+    use serde::Deserialize;
+
+    let mut set: FnvHashSet<u32> = (0..100).collect();
+    let capacity = set.capacity();
+
+    let deserializer = SeqDeserializer::<_, Error>::new(200..250u32);
+    FnvHashSet::deserialize_in_place(deserializer, &mut set).unwrap();
+
+    assert_eq!(set.len(), 50);
+    assert_eq!(set.capacity(), capacity);
+    assert!(set.iter().all(|x| (200..250).contains(x)));
+}
+
+#[test]
+fn map_serde_sorted() {
+    use hashbrown::serde_sorted::Sorted;
+    use serde_test::assert_ser_tokens;
+
+    // Maps with random hashers and different insertion orders.
+    let a: HashMap<u32, u32> = (0..50).map(|i| (i, i * 2)).collect();
+    let b: HashMap<u32, u32> = (0..50).rev().map(|i| (i, i * 2)).collect();
+
+    let mut tokens = vec![Token::Map { len: Some(50) }];
+    for i in 0..50 {
+        tokens.extend([Token::U32(i), Token::U32(i * 2)]);
+    }
+    tokens.push(Token::MapEnd);
+
+    assert_ser_tokens(&Sorted(&a), &tokens);
+    assert_ser_tokens(&Sorted(&b), &tokens);
+    assert_ser_tokens(
+        &Sorted(&HashMap::<u32, u32>::new()),
+        &[Token::Map { len: Some(0) }, Token::MapEnd],
+    );
+}
+
+#[test]
+fn set_serde_sorted() {
+    use hashbrown::serde_sorted::Sorted;
+    use serde_test::assert_ser_tokens;
+
+    let set: HashSet<char> = "hashbrown".chars().collect();
+    let mut tokens = vec![Token::Seq { len: Some(8) }];
+    tokens.extend("abhnorsw".chars().map(Token::Char));
+    tokens.push(Token::SeqEnd);
+
+    assert_ser_tokens(&Sorted(&set), &tokens);
+}